@@ -5,7 +5,13 @@ pub fn android_main() {
 
     std::env::set_current_dir(&resource_dir).unwrap();
     
-    let options = doukutsu_rs::game::LaunchOptions { server_mode: false, editor: false };
+    let options = doukutsu_rs::game::LaunchOptions {
+        server_mode: false,
+        editor: false,
+        remote_control: None,
+        dump_constants: false,
+        extract_locale_keys: None,
+    };
 
     doukutsu_rs::game::init(options).unwrap();
 }
@@ -8,6 +8,7 @@ use crate::game::frame::Frame;
 use crate::game::scripting::tsc::text_script::{ConfirmSelection, TextScriptExecutionState, TextScriptLine};
 use crate::game::shared_game_state::SharedGameState;
 use crate::graphics::font::{Font, Symbols};
+use crate::graphics::text_layout;
 
 pub struct TextBoxes {
     pub slide_in: u8,
@@ -208,6 +209,7 @@ impl GameEntity<()> for TextBoxes {
         }
 
         let text_offset = if state.textscript_vm.face == 0 { 0.0 } else { 56.0 };
+        let text_width = 216.0 - text_offset;
 
         let y_offset = if let TextScriptExecutionState::MsgNewLine(_, _, _, _, counter) = state.textscript_vm.state {
             16.0 - counter as f32 * 4.0
@@ -216,6 +218,8 @@ impl GameEntity<()> for TextBoxes {
         };
 
         let lines = [&state.textscript_vm.line_1, &state.textscript_vm.line_2, &state.textscript_vm.line_3];
+        // right-to-left messages are aligned to the right edge of the box
+        let is_rtl = text_layout::is_rtl_text(lines.iter().flat_map(|line| line.iter().copied()));
 
         let clip_rect = Rect::new_size(
             0,
@@ -229,13 +233,18 @@ impl GameEntity<()> for TextBoxes {
             if !line.is_empty() {
                 let symbols = Symbols { symbols: &state.textscript_vm.substitution_rect_map, texture: "TextBox" };
 
-                state
+                let mut builder = state
                     .font
                     .builder()
                     .position(left_pos + text_offset + 14.0, top_pos + 10.0 + idx as f32 * 16.0 - y_offset)
                     .shadow(state.constants.textscript.text_shadow)
-                    .with_symbols(Some(symbols))
-                    .draw_iter(line.iter().copied(), ctx, &state.constants, &mut state.texture_set)?;
+                    .with_symbols(Some(symbols));
+
+                if is_rtl {
+                    builder = builder.right_align(text_width);
+                }
+
+                builder.draw_iter(line.iter().copied(), ctx, &state.constants, &mut state.texture_set)?;
             }
         }
         graphics::set_clip_rect(ctx, None)?;
@@ -258,6 +267,7 @@ impl GameEntity<()> for TextBoxes {
                         (builder.compute_width_iter(state.textscript_vm.line_3.iter().copied()), top_pos + 10.0 + 32.0)
                     }
                 };
+                x = if is_rtl { text_width - x - 5.0 } else { x };
                 x += left_pos + text_offset + 14.0;
 
                 graphics::draw_rect(
@@ -35,6 +35,8 @@ impl ExeResourceDirectory {
 pub struct ExeParser<'a> {
     pub resources: Resources<'a>,
     pub section_headers: Box<&'a SectionHeaders>,
+    pub image_base: u32,
+    pub data: &'a [u8],
 }
 
 impl<'a> ExeParser<'a> {
@@ -50,8 +52,14 @@ impl<'a> ExeParser<'a> {
                 }
 
                 let section_headers = pe.section_headers();
+                let image_base = pe.optional_header().ImageBase;
 
-                Ok(Self { resources: resources.unwrap(), section_headers: Box::new(section_headers) })
+                Ok(Self {
+                    resources: resources.unwrap(),
+                    section_headers: Box::new(section_headers),
+                    image_base,
+                    data: file,
+                })
             }
             Err(_) => Err(ParseError("Failed to parse PE file".to_string())),
         };
@@ -95,6 +103,54 @@ impl<'a> ExeParser<'a> {
         };
     }
 
+    /// Translates a virtual address (as referenced by the game code) to an offset in the executable file.
+    pub fn va_to_file_offset(&self, va: u32) -> Option<u32> {
+        let rva = va.checked_sub(self.image_base)?;
+        let section_header = self.section_headers.by_rva(rva)?;
+
+        let offset = rva - section_header.virtual_range().start;
+        if offset >= section_header.SizeOfRawData {
+            return None;
+        }
+
+        Some(section_header.file_range().start + offset)
+    }
+
+    /// Returns the bytes at specified virtual address.
+    pub fn bytes_at(&self, va: u32, len: usize) -> GameResult<&'a [u8]> {
+        let start = match self.va_to_file_offset(va) {
+            Some(offset) => offset as usize,
+            None => return Err(ParseError(format!("Address {:#x} is not mapped in the executable.", va))),
+        };
+
+        match self.data.get(start..start + len) {
+            Some(bytes) => Ok(bytes),
+            None => Err(ParseError(format!("Address {:#x} is out of the executable bounds.", va))),
+        }
+    }
+
+    pub fn get_named_section_bytes(&self, name: String) -> GameResult<Option<&'a [u8]>> {
+        let range = match self.get_named_section_byte_range(name.clone())? {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+
+        match self.data.get(range.start as usize..range.end as usize) {
+            Some(bytes) => Ok(Some(bytes)),
+            None => Err(ParseError(format!("Section {} is out of the executable bounds.", name))),
+        }
+    }
+
+    pub fn section_names(&self) -> Vec<String> {
+        self.section_headers
+            .iter()
+            .map(|section_header| {
+                let name = section_header.Name.split(|b| *b == 0).next().unwrap_or_default();
+                String::from_utf8_lossy(name).into_owned()
+            })
+            .collect()
+    }
+
     fn read_dir(&self, directory: Directory, dir_data: &mut ExeResourceDirectory, last_dir_name: String) {
         for dir in directory.entries() {
             let raw_entry = dir.entry();
@@ -125,3 +181,89 @@ impl<'a> ExeParser<'a> {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use byteorder::{ByteOrder, LE};
+
+    use super::*;
+
+    pub(crate) const TEXT_VA: u32 = 0x401000;
+
+    /// Builds a minimal PE32 image from `sections`, the first one being the code section, followed by
+    /// a .rsrc section holding an empty resource directory.
+    pub(crate) fn build_exe(sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let empty_resources = [0u8; 0x10];
+        let sections: Vec<(&str, &[u8])> = sections.iter().copied().chain([(".rsrc", &empty_resources[..])]).collect();
+        let rsrc_rva = 0x1000 * sections.len() as u32;
+
+        let mut exe = vec![0u8; 0x400];
+        exe[0x00..0x02].copy_from_slice(b"MZ");
+        LE::write_u32(&mut exe[0x3C..], 0x40);
+
+        // file header
+        exe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        LE::write_u16(&mut exe[0x44..], 0x14C); // i386
+        LE::write_u16(&mut exe[0x46..], sections.len() as u16); // number of sections
+        LE::write_u16(&mut exe[0x54..], 0xE0); // size of optional header
+        LE::write_u16(&mut exe[0x56..], 0x102); // executable, 32-bit
+
+        // optional header
+        let opt = &mut exe[0x58..0x138];
+        LE::write_u16(&mut opt[0..], 0x10B);
+        LE::write_u32(&mut opt[16..], 0x1000); // entry point
+        LE::write_u32(&mut opt[20..], 0x1000); // base of code
+        LE::write_u32(&mut opt[24..], 0x2000); // base of data
+        LE::write_u32(&mut opt[28..], 0x400000); // image base
+        LE::write_u32(&mut opt[32..], 0x1000); // section alignment
+        LE::write_u32(&mut opt[36..], 0x200); // file alignment
+        LE::write_u16(&mut opt[40..], 4); // OS version
+        LE::write_u16(&mut opt[48..], 4); // subsystem version
+        LE::write_u32(&mut opt[56..], rsrc_rva + 0x1000); // size of image
+        LE::write_u32(&mut opt[60..], 0x400); // size of headers
+        LE::write_u16(&mut opt[68..], 2); // GUI subsystem
+        LE::write_u32(&mut opt[92..], 16); // number of data directories
+        LE::write_u32(&mut opt[96 + 2 * 8..], rsrc_rva); // resource directory
+        LE::write_u32(&mut opt[96 + 2 * 8 + 4..], empty_resources.len() as u32);
+
+        // section headers and contents
+        for (i, (name, data)) in sections.iter().enumerate() {
+            assert!(data.len() <= 0x1000);
+            let size = (data.len() + 0x1ff) & !0x1ff;
+            let offset = exe.len();
+            let characteristics = if i == 0 { 0x60000020 } else { 0xC0000040 };
+
+            let header = &mut exe[0x138 + i * 40..0x138 + (i + 1) * 40];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            LE::write_u32(&mut header[8..], size as u32);
+            LE::write_u32(&mut header[12..], 0x1000 * (i as u32 + 1));
+            LE::write_u32(&mut header[16..], size as u32);
+            LE::write_u32(&mut header[20..], offset as u32);
+            LE::write_u32(&mut header[36..], characteristics);
+
+            exe.extend_from_slice(data);
+            exe.resize(offset + size, 0);
+        }
+
+        exe
+    }
+
+    #[test]
+    fn test_exe_parser_reads_sections() {
+        let text = [0x90, 0x90, 0xC7, 0x45, 0xD0, 0x2C, 0x03, 0x00, 0x00, 0xC3];
+        let exe = build_exe(&[(".text", &text[..]), (".csmap", &b"Credits\0"[..])]);
+        let parser = ExeParser::from(&exe).unwrap();
+
+        assert_eq!(parser.section_names(), vec![".text".to_owned(), ".csmap".to_owned(), ".rsrc".to_owned()]);
+        assert_eq!(parser.va_to_file_offset(TEXT_VA + 0x02), Some(0x402));
+        assert_eq!(parser.va_to_file_offset(0x402000), Some(0x600));
+        assert_eq!(parser.va_to_file_offset(0x300000), None);
+        assert_eq!(parser.bytes_at(TEXT_VA + 0x02, 3).unwrap(), &[0xC7, 0x45, 0xD0]);
+        assert!(parser.bytes_at(0x405000, 1).is_err());
+
+        let code = parser.get_named_section_bytes(".text".to_string()).unwrap().unwrap();
+        assert_eq!(&code[..text.len()], &text[..]);
+        assert_eq!(&parser.get_named_section_bytes(".csmap".to_string()).unwrap().unwrap()[..8], b"Credits\0");
+        assert!(parser.get_named_section_bytes(".data".to_string()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,596 @@
+//! Detection of well-known binary hacks applied to Doukutsu.exe by mods.
+//!
+//! Recognised hacks are translated to overrides of `EngineConstants` in the constants.json format,
+//! hacks which can't be recognised precisely (e.g. injected code) are only reported.
+//!
+//! Currently recognised are modified player physics, intro and new game start points, window titles, extended
+//! flag arrays and stage tables relocated by Booster's Lab. Map names are a part of the stage table, which is
+//! extracted to stage.sect whether it has been relocated or edited in place. TSC commands added by injected code
+//! are reported by name, as the engine can't run their implementation.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::str::FromStr;
+
+use byteorder::{ByteOrder, LE};
+use serde_json::{Map, Value};
+
+use crate::data::exe_parser::ExeParser;
+use crate::game::scripting::tsc::opcodes::TSCOpCode;
+use crate::util::encoding::read_cur_shift_jis;
+
+/// Sections present in the unmodified executable.
+const VANILLA_SECTIONS: [&str; 4] = [".text", ".rdata", ".data", ".rsrc"];
+
+/// Order in which physics constants are assigned in ActMyChar_Normal.
+const PHYSICS_FIELDS: [&str; 8] =
+    ["max_dash", "max_move", "gravity_ground", "gravity_air", "jump", "dash_ground", "dash_air", "resist"];
+const VANILLA_WATER_PHYSICS: [i32; 8] = [0x196, 0x2ff, 0x28, 0x10, 0x280, 0x2a, 0x10, 0x19];
+const VANILLA_AIR_PHYSICS: [i32; 8] = [0x32c, 0x5ff, 0x50, 0x20, 0x500, 0x55, 0x20, 0x33];
+
+/// (stage, event, x, y) arguments of TransferStage calls made by ModeOpening and InitializeGame.
+const VANILLA_INTRO_POINT: [u32; 4] = [72, 100, 3, 3];
+const VANILLA_NEW_GAME_POINT: [u32; 4] = [13, 200, 10, 8];
+
+/// Window titles of the original release and the Aeon Genesis translation.
+const VANILLA_WINDOW_TITLES: [&str; 2] = ["洞窟物語", "Cave Story ~ Doukutsu Monogatari"];
+const MAX_WINDOW_TITLE_LEN: usize = 256;
+
+/// Size of the flag array in bytes, each byte holds 8 flags.
+const VANILLA_FLAG_BYTES: u32 = 1000;
+const MAX_FLAG_BYTES: u32 = 0x10000;
+
+/// Maximum distance between comparisons of consecutive characters of a TSC command.
+const MAX_COMMAND_CMP_GAP: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct ExePatch {
+    pub name: String,
+    /// Whether the behaviour of the hack is reproduced by the engine.
+    pub applied: bool,
+}
+
+#[derive(Default)]
+pub struct ExePatchSet {
+    pub patches: Vec<ExePatch>,
+    /// Overrides of engine constants in the constants.json format.
+    pub constants: Map<String, Value>,
+}
+
+impl ExePatchSet {
+    pub fn detect(parser: &ExeParser) -> ExePatchSet {
+        let mut set = ExePatchSet::default();
+
+        set.detect_sections(parser);
+
+        match parser.get_named_section_bytes(".text".to_string()) {
+            Ok(Some(text)) => {
+                set.detect_player_physics(text);
+                set.detect_start_points(text);
+                set.detect_window_title(parser, text);
+                set.detect_flag_count(text);
+            }
+            _ => log::warn!("Failed to find the code section, skipping detection of code patches."),
+        }
+
+        set
+    }
+
+    pub fn log_report(&self) {
+        if self.patches.is_empty() {
+            log::info!("No executable hacks have been detected.");
+            return;
+        }
+
+        for patch in self.patches.iter() {
+            if patch.applied {
+                log::info!("Recognised executable hack: {}", patch.name);
+            } else {
+                log::warn!("Ignored executable hack: {}", patch.name);
+            }
+        }
+    }
+
+    fn add(&mut self, name: impl Into<String>, applied: bool) {
+        self.patches.push(ExePatch { name: name.into(), applied });
+    }
+
+    fn set_constant(&mut self, section: &str, key: &str, value: Value) {
+        let section = self.constants.entry(section.to_owned()).or_insert_with(|| Value::Object(Map::new()));
+
+        if let Value::Object(section) = section {
+            section.insert(key.to_owned(), value);
+        }
+    }
+
+    /// Hack tools add their own sections for relocated tables and injected code.
+    fn detect_sections(&mut self, parser: &ExeParser) {
+        for name in parser.section_names() {
+            if VANILLA_SECTIONS.contains(&name.as_str()) {
+                continue;
+            }
+
+            match name.as_str() {
+                // stage table relocated by Booster's Lab, extracted into stage.sect
+                ".csmap" => self.add(format!("relocated stage table ({})", name), true),
+                _ => {
+                    self.add(format!("additional section {} (injected code is not supported)", name), false);
+
+                    if let Ok(Some(code)) = parser.get_named_section_bytes(name.clone()) {
+                        self.detect_tsc_commands(&name, code);
+                    }
+                }
+            }
+        }
+    }
+
+    /// TSC parsers compare the command characters one by one, jumping to the check of the next command if any of
+    /// them doesn't match. Commands compared this way by injected code which the engine doesn't know are extensions.
+    fn detect_tsc_commands(&mut self, section: &str, code: &[u8]) {
+        let compares: Vec<CharCmp> = (0..code.len()).filter_map(|pos| read_char_cmp(code, pos)).collect();
+
+        let next_compare = |from: usize, prev: &CharCmp| {
+            compares[from..]
+                .iter()
+                .take_while(|cmp| cmp.pos <= prev.end + MAX_COMMAND_CMP_GAP)
+                .position(|cmp| cmp.pos >= prev.end && cmp.target == prev.target && is_command_char(cmp.chr))
+                .map(|idx| from + idx)
+        };
+
+        let mut commands: Vec<String> = Vec::new();
+
+        for (first, cmp) in compares.iter().enumerate() {
+            if !cmp.chr.is_ascii_uppercase() {
+                continue;
+            }
+
+            let second = match next_compare(first + 1, cmp) {
+                Some(second) => second,
+                None => continue,
+            };
+
+            let third = match next_compare(second + 1, &compares[second]) {
+                Some(third) => third,
+                None => continue,
+            };
+
+            let name: String = [cmp, &compares[second], &compares[third]].iter().map(|cmp| cmp.chr as char).collect();
+            if TSCOpCode::from_str(&name).is_err() && !commands.contains(&name) {
+                commands.push(name);
+            }
+        }
+
+        for name in commands {
+            self.add(format!("TSC command extension <{} in section {} (not supported)", name, section), false);
+        }
+    }
+
+    /// Finds the window title WinMain passes as both the class and window name to CreateWindowExA.
+    fn detect_window_title(&mut self, parser: &ExeParser, text: &[u8]) {
+        for insn in text.windows(14) {
+            // push title; push title; push 0; call dword ptr [CreateWindowExA]
+            if insn[0] != 0x68
+                || insn[5] != 0x68
+                || insn[1..5] != insn[6..10]
+                || insn[10..] != [0x6A, 0x00, 0xFF, 0x15]
+            {
+                continue;
+            }
+
+            let title = match read_c_string(parser, LE::read_u32(&insn[1..])) {
+                Some(title) => title,
+                None => continue,
+            };
+
+            if !VANILLA_WINDOW_TITLES.contains(&title.as_str()) {
+                self.add(format!("window title \"{}\"", title), true);
+                self.set_constant("game", "window_title", title.into());
+            }
+
+            return;
+        }
+
+        log::warn!("Failed to find the window title in the executable.");
+    }
+
+    /// Finds the size of the flag array, which InitFlags clears with memset and SaveProfile copies with memcpy.
+    /// The map flag array is cleared and copied the same way, but it's smaller.
+    fn detect_flag_count(&mut self, text: &[u8]) {
+        let mut cleared = HashSet::new();
+        let mut copied = HashSet::new();
+
+        for pos in 0..text.len() {
+            let (size, len) = match read_push(text, pos) {
+                Some(push) => push,
+                None => continue,
+            };
+
+            let rest = &text[pos + len..];
+
+            // push size; push 0; push array; call memset
+            if rest.starts_with(&[0x6A, 0x00, 0x68]) && rest.get(7) == Some(&0xE8) {
+                cleared.insert((size, LE::read_u32(&rest[3..])));
+            }
+
+            // push size; push array; push destination; call memcpy
+            if rest.len() >= 5 && rest[0] == 0x68 {
+                copied.insert((size, LE::read_u32(&rest[1..])));
+            }
+        }
+
+        match cleared.intersection(&copied).map(|(size, _)| *size).max() {
+            Some(VANILLA_FLAG_BYTES) => {}
+            Some(size) if size > VANILLA_FLAG_BYTES && size <= MAX_FLAG_BYTES => {
+                self.set_constant("game", "flag_count", (size as usize * 8).into());
+                self.add(format!("flag count extended to {}", size * 8), true);
+            }
+            _ => log::warn!("Failed to find the size of the flag array in the executable."),
+        }
+    }
+
+    /// Finds the physics constants assigned at the start of ActMyChar_Normal, the water block comes first
+    /// and is followed by a short jump over the air block assigning the same locals.
+    fn detect_player_physics(&mut self, text: &[u8]) {
+        let n = PHYSICS_FIELDS.len();
+
+        for pos in 0..text.len() {
+            let water = match read_local_movs(text, pos, n) {
+                Some(movs) => movs,
+                None => continue,
+            };
+
+            let jmp = pos + n * 7;
+            if text.get(jmp) != Some(&0xEB) {
+                continue;
+            }
+
+            let air = match read_local_movs(text, jmp + 2, n) {
+                Some(movs) => movs,
+                None => continue,
+            };
+
+            let mut locals: Vec<u8> = water.iter().map(|(disp, _)| *disp).collect();
+            let same_locals = water.iter().zip(air.iter()).all(|(a, b)| a.0 == b.0);
+            locals.sort_unstable();
+            locals.dedup();
+
+            let sane = water.iter().chain(air.iter()).all(|(_, value)| (0..=0xffff).contains(value));
+            if !same_locals || locals.len() != n || !sane {
+                continue;
+            }
+
+            let water: Vec<i32> = water.into_iter().map(|(_, value)| value).collect();
+            let air: Vec<i32> = air.into_iter().map(|(_, value)| value).collect();
+
+            if water != VANILLA_WATER_PHYSICS || air != VANILLA_AIR_PHYSICS {
+                let to_json = |values: Vec<i32>| {
+                    let fields =
+                        PHYSICS_FIELDS.iter().zip(values).map(|(name, value)| (name.to_string(), value.into()));
+                    Value::Object(fields.collect())
+                };
+
+                let mut physics = Map::new();
+                physics.insert("air_physics".to_owned(), to_json(air));
+                physics.insert("water_physics".to_owned(), to_json(water));
+                self.constants.insert("player".to_owned(), Value::Object(physics));
+
+                self.add("modified player physics", true);
+            }
+
+            return;
+        }
+
+        log::warn!("Failed to find player physics constants in the executable.");
+    }
+
+    /// Finds the immediate TransferStage calls which start the intro and a new game.
+    fn detect_start_points(&mut self, text: &[u8]) {
+        let mut calls: HashMap<usize, Vec<[u32; 4]>> = HashMap::new();
+
+        for pos in 0..text.len() {
+            // push y; push x; push event; push stage; call TransferStage
+            let mut args = [0u32; 4];
+            let mut cursor = pos;
+            let mut valid = true;
+
+            for arg in args.iter_mut().rev() {
+                match read_push(text, cursor) {
+                    Some((value, len)) => {
+                        *arg = value;
+                        cursor += len;
+                    }
+                    None => {
+                        valid = false;
+                        break;
+                    }
+                }
+            }
+
+            if !valid
+                || args.iter().any(|arg| *arg > 0xffff)
+                || text.get(cursor) != Some(&0xE8)
+                || cursor + 5 > text.len()
+            {
+                continue;
+            }
+
+            let target = (cursor as i64 + 5 + LE::read_i32(&text[cursor + 1..]) as i64) as usize;
+            calls.entry(target).or_default().push(args);
+        }
+
+        for args in calls.values() {
+            if args.len() != 2 {
+                continue;
+            }
+
+            let (intro, new_game) = if args[0] == VANILLA_INTRO_POINT || args[1] == VANILLA_NEW_GAME_POINT {
+                (args[0], args[1])
+            } else if args[1] == VANILLA_INTRO_POINT || args[0] == VANILLA_NEW_GAME_POINT {
+                (args[1], args[0])
+            } else {
+                continue;
+            };
+
+            if intro != VANILLA_INTRO_POINT {
+                self.set_constant("game", "intro_stage", intro[0].into());
+                self.set_constant("game", "intro_event", intro[1].into());
+                self.set_constant("game", "intro_player_pos", Value::from(vec![intro[2] as i16, intro[3] as i16]));
+                self.add(format!("intro starts at stage {} event {}", intro[0], intro[1]), true);
+            }
+
+            if new_game != VANILLA_NEW_GAME_POINT {
+                self.set_constant("game", "new_game_stage", new_game[0].into());
+                self.set_constant("game", "new_game_event", new_game[1].into());
+                self.set_constant(
+                    "game",
+                    "new_game_player_pos",
+                    Value::from(vec![new_game[2] as i16, new_game[3] as i16]),
+                );
+                self.add(format!("new game starts at stage {} event {}", new_game[0], new_game[1]), true);
+            }
+
+            return;
+        }
+
+        log::warn!("Failed to find the game start points in the executable.");
+    }
+}
+
+/// Reads `n` consecutive `mov dword ptr [ebp+disp8], imm32` instructions.
+fn read_local_movs(text: &[u8], pos: usize, n: usize) -> Option<Vec<(u8, i32)>> {
+    let mut movs = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let insn = text.get(pos + i * 7..pos + (i + 1) * 7)?;
+        if insn[0] != 0xC7 || insn[1] != 0x45 {
+            return None;
+        }
+
+        movs.push((insn[2], LE::read_i32(&insn[3..])));
+    }
+
+    Some(movs)
+}
+
+/// Reads a `push imm8` or `push imm32` instruction, returns the pushed value and instruction length.
+fn read_push(text: &[u8], pos: usize) -> Option<(u32, usize)> {
+    match *text.get(pos)? {
+        0x6A => Some((*text.get(pos + 1)? as i8 as i32 as u32, 2)),
+        0x68 => Some((LE::read_u32(text.get(pos + 1..pos + 5)?), 5)),
+        _ => None,
+    }
+}
+
+/// A comparison of a byte with an immediate character, followed by a `jne`.
+struct CharCmp {
+    pos: usize,
+    end: usize,
+    chr: u8,
+    target: usize,
+}
+
+fn read_char_cmp(code: &[u8], pos: usize) -> Option<CharCmp> {
+    let (chr, len) = match (*code.get(pos)?, *code.get(pos + 1)?) {
+        // cmp al, imm8
+        (0x3C, chr) => (chr, 2),
+        // cmp r32, imm8
+        (0x83, 0xF8..=0xFF) => (*code.get(pos + 2)?, 3),
+        // cmp r8, imm8
+        (0x80, 0xF8..=0xFF) => (*code.get(pos + 2)?, 3),
+        // cmp byte ptr [reg+disp8], imm8
+        (0x80, modrm @ 0x78..=0x7F) if modrm != 0x7C => (*code.get(pos + 3)?, 4),
+        _ => return None,
+    };
+
+    let jne = pos + len;
+    let (end, rel) = match *code.get(jne..jne + 2)? {
+        [0x75, rel] => (jne + 2, rel as i8 as i64),
+        [0x0F, 0x85] => (jne + 6, LE::read_i32(code.get(jne + 2..jne + 6)?) as i64),
+        _ => return None,
+    };
+
+    Some(CharCmp { pos, end, chr, target: (end as i64 + rel) as usize })
+}
+
+fn is_command_char(chr: u8) -> bool {
+    chr.is_ascii_uppercase() || chr.is_ascii_digit() || chr == b'+' || chr == b'-'
+}
+
+/// Reads a NUL-terminated Shift-JIS string at specified virtual address.
+fn read_c_string(parser: &ExeParser, va: u32) -> Option<String> {
+    let start = parser.va_to_file_offset(va)? as usize;
+    let bytes = parser.data.get(start..)?;
+    let len = bytes.iter().take(MAX_WINDOW_TITLE_LEN).position(|b| *b == 0)?;
+
+    let mut cursor = Cursor::new(&bytes[..len]);
+    let mut remaining = len as u32;
+    let mut string = String::with_capacity(len);
+
+    while remaining > 0 {
+        let (consumed, chr) = read_cur_shift_jis(&mut cursor, remaining);
+        remaining = remaining.saturating_sub(consumed.max(1));
+        string.push(chr);
+    }
+
+    Some(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::WriteBytesExt;
+
+    use super::*;
+    use crate::data::exe_parser::tests::{build_exe, TEXT_VA};
+
+    fn write_local_movs(text: &mut Vec<u8>, values: &[i32]) {
+        for (i, value) in values.iter().enumerate() {
+            text.extend([0xC7, 0x45, 0xD0 + i as u8 * 4]);
+            text.write_i32::<LE>(*value).unwrap();
+        }
+    }
+
+    fn write_transfer_stage(text: &mut Vec<u8>, target: usize, [stage, event, x, y]: [u32; 4]) {
+        for arg in [y, x, event, stage] {
+            if arg < 0x80 {
+                text.extend([0x6A, arg as u8]);
+            } else {
+                text.push(0x68);
+                text.write_u32::<LE>(arg).unwrap();
+            }
+        }
+
+        let rel = target as i32 - (text.len() as i32 + 5);
+        text.push(0xE8);
+        text.write_i32::<LE>(rel).unwrap();
+    }
+
+    fn build_text(water: &[i32], air: &[i32], intro: [u32; 4], new_game: [u32; 4]) -> Vec<u8> {
+        let mut text = vec![0xC3; 0x10];
+
+        write_local_movs(&mut text, water);
+        text.extend([0xEB, (air.len() * 7) as u8]);
+        write_local_movs(&mut text, air);
+
+        write_transfer_stage(&mut text, 0x08, intro);
+        write_transfer_stage(&mut text, 0x08, new_game);
+
+        text
+    }
+
+    fn write_window_title(text: &mut Vec<u8>, title: &[u8]) {
+        text.extend([0xEB, title.len() as u8 + 1]);
+        let va = TEXT_VA + text.len() as u32;
+        text.extend(title);
+        text.push(0);
+
+        for _ in 0..2 {
+            text.push(0x68);
+            text.write_u32::<LE>(va).unwrap();
+        }
+        text.extend([0x6A, 0x00, 0xFF, 0x15, 0x00, 0x20, 0x40, 0x00]);
+    }
+
+    fn write_flag_arrays(text: &mut Vec<u8>, flag_bytes: u32) {
+        for (size, array) in [(flag_bytes, 0x49DDA0), (0x80, 0x49E5B8)] {
+            // memset(array, 0, size)
+            text.push(0x68);
+            text.write_u32::<LE>(size).unwrap();
+            text.extend([0x6A, 0x00, 0x68]);
+            text.write_u32::<LE>(array).unwrap();
+            text.extend([0xE8, 0x00, 0x00, 0x00, 0x00]);
+
+            // memcpy(profile.flags, array, size)
+            text.push(0x68);
+            text.write_u32::<LE>(size).unwrap();
+            text.push(0x68);
+            text.write_u32::<LE>(array).unwrap();
+            text.extend([0x8D, 0x45, 0xF0, 0x50, 0xE8, 0x00, 0x00, 0x00, 0x00]);
+        }
+    }
+
+    /// Writes the comparisons a TSC parser does for `command`.
+    fn write_command_cmp(code: &mut Vec<u8>, command: &[u8; 3]) {
+        for (i, chr) in command.iter().enumerate() {
+            // movsx edx, byte ptr [ecx+eax+i]; cmp edx, chr; jne next_command
+            let next_command = (command.len() - i - 1) as u8 * 10;
+            code.extend([0x0F, 0xBE, 0x54, 0x01, i as u8 + 1, 0x83, 0xFA, *chr, 0x75, next_command]);
+        }
+    }
+
+    #[test]
+    fn test_detect_vanilla() {
+        let mut text =
+            build_text(&VANILLA_WATER_PHYSICS, &VANILLA_AIR_PHYSICS, VANILLA_INTRO_POINT, VANILLA_NEW_GAME_POINT);
+        write_window_title(&mut text, &[0x93, 0xB4, 0x8C, 0x41, 0x95, 0xA8, 0x8C, 0xEA]);
+        write_flag_arrays(&mut text, VANILLA_FLAG_BYTES);
+
+        let exe = build_exe(&[(".text", &text[..])]);
+        let patches = ExePatchSet::detect(&ExeParser::from(&exe).unwrap());
+
+        assert!(patches.patches.is_empty(), "{:?}", patches.patches);
+        assert!(patches.constants.is_empty());
+    }
+
+    #[test]
+    fn test_detect_patches() {
+        let mut water = VANILLA_WATER_PHYSICS;
+        water[4] = 0x300;
+
+        let mut text = build_text(&water, &VANILLA_AIR_PHYSICS, [40, 1000, 5, 6], VANILLA_NEW_GAME_POINT);
+        write_window_title(&mut text, b"Jenka's Nightmare");
+        write_flag_arrays(&mut text, 2000);
+
+        let mut injected = Vec::new();
+        write_command_cmp(&mut injected, b"MSG");
+        write_command_cmp(&mut injected, b"VAR");
+        write_command_cmp(&mut injected, b"PH+");
+
+        let exe = build_exe(&[(".text", &text[..]), (".csmap", &[0u8; 0x10][..]), (".patch", &injected[..])]);
+        let patches = ExePatchSet::detect(&ExeParser::from(&exe).unwrap());
+
+        let applied: Vec<_> = patches.patches.iter().map(|patch| (patch.name.as_str(), patch.applied)).collect();
+        assert_eq!(
+            applied,
+            vec![
+                ("relocated stage table (.csmap)", true),
+                ("additional section .patch (injected code is not supported)", false),
+                ("TSC command extension <VAR in section .patch (not supported)", false),
+                ("TSC command extension <PH+ in section .patch (not supported)", false),
+                ("modified player physics", true),
+                ("intro starts at stage 40 event 1000", true),
+                ("window title \"Jenka's Nightmare\"", true),
+                ("flag count extended to 16000", true),
+            ]
+        );
+
+        let constants = Value::Object(patches.constants);
+        assert_eq!(constants["player"]["water_physics"]["jump"], 0x300);
+        assert_eq!(constants["player"]["air_physics"]["jump"], 0x500);
+        assert_eq!(constants["game"]["intro_stage"], 40);
+        assert_eq!(constants["game"]["intro_event"], 1000);
+        assert_eq!(constants["game"]["intro_player_pos"], serde_json::json!([5, 6]));
+        assert!(constants["game"].get("new_game_stage").is_none());
+        assert_eq!(constants["game"]["window_title"], "Jenka's Nightmare");
+        assert_eq!(constants["game"]["flag_count"], 16000);
+    }
+
+    #[test]
+    fn test_read_local_movs() {
+        #[rustfmt::skip]
+        let text = [
+            0x90,
+            0xC7, 0x45, 0xF8, 0x2C, 0x03, 0x00, 0x00, // mov dword ptr [ebp-8], 0x32c
+            0xC7, 0x45, 0xFC, 0xFF, 0xFF, 0xFF, 0xFF, // mov dword ptr [ebp-4], -1
+            0xEB, 0x10,
+        ];
+
+        assert_eq!(read_local_movs(&text, 1, 2), Some(vec![(0xF8, 0x32C), (0xFC, -1)]));
+        assert_eq!(read_local_movs(&text, 8, 1), Some(vec![(0xFC, -1)]));
+        assert_eq!(read_local_movs(&text, 1, 0), Some(vec![]));
+
+        // not a mov, or truncated
+        assert_eq!(read_local_movs(&text, 0, 1), None);
+        assert_eq!(read_local_movs(&text, 1, 3), None);
+        assert_eq!(read_local_movs(&text, 12, 1), None);
+    }
+}
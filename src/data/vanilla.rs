@@ -1,32 +1,49 @@
 use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     env,
+    fmt::Write as _,
     io::{Read, Write},
     ops::Range,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use byteorder::{LE, WriteBytesExt};
+use byteorder::{ByteOrder, WriteBytesExt, LE};
 
 use crate::data::exe_parser::ExeParser;
-use crate::framework::{
-    context::Context,
-    error::{GameError::ParseError, GameResult},
-    filesystem,
-};
+use crate::data::exe_patches::ExePatchSet;
+use crate::framework::error::{GameError::ParseError, GameResult};
 
 pub struct VanillaExtractor {
     exe_buffer: Vec<u8>,
     data_base_dir: String,
     root: PathBuf,
+    /// Files written by the previous extraction, which may be overwritten if the executable has changed.
+    previous_files: HashSet<String>,
+    /// Files written by this extraction, relative to the data directory.
+    written_files: RefCell<Vec<String>>,
 }
 
+/// Name of the file in the data directory recording which executable the data has been extracted from.
+const EXTRACTION_RECORD_NAME: &str = "vanilla_extraction.txt";
+
 const VANILLA_STAGE_COUNT: u32 = 95;
 const VANILLA_STAGE_ENTRY_SIZE: u32 = 0xC8;
 const VANILLA_STAGE_OFFSET: u32 = 0x937B0;
 const VANILLA_STAGE_TABLE_SIZE: u32 = VANILLA_STAGE_COUNT * VANILLA_STAGE_ENTRY_SIZE;
 
+const VANILLA_BULLET_TABLE_ADDR: u32 = 0x48F048;
+const VANILLA_BULLET_COUNT: usize = 46;
+const VANILLA_BULLET_ENTRY_SIZE: usize = 0x2C;
+
+const VANILLA_ARMS_LEVEL_TABLE_ADDR: u32 = 0x493660;
+const VANILLA_ARMS_COUNT: usize = 14;
+
+/// Size of PIXTONEPARAMETER structure, including the padding of the trailing double alignment.
+const VANILLA_PIXTONE_PARAMETER_SIZE: usize = 0x70;
+
 impl VanillaExtractor {
-    pub fn from(ctx: &mut Context, exe_name: String, data_base_dir: String) -> Option<Self> {
+    pub fn from(exe_name: String, data_base_dir: String) -> Option<Self> {
         #[cfg(not(any(target_os = "android", target_os = "horizon")))]
         let mut vanilla_exe_path = env::current_dir().unwrap();
 
@@ -54,12 +71,7 @@ impl VanillaExtractor {
         let mut root = vanilla_exe_path.clone();
         root.pop();
 
-        log::info!("Found vanilla game executable, attempting to extract resources.");
-
-        if filesystem::exists(ctx, format!("{}/stage.sect", data_base_dir.clone())) {
-            log::info!("Vanilla resources are already extracted, not proceeding.");
-            return None;
-        }
+        log::info!("Found vanilla game executable, extracting missing resources.");
 
         let file = std::fs::File::open(vanilla_exe_path);
         if file.is_err() {
@@ -74,10 +86,51 @@ impl VanillaExtractor {
             return None;
         }
 
-        Some(Self { exe_buffer, data_base_dir, root })
+        Some(Self {
+            exe_buffer,
+            data_base_dir,
+            root,
+            previous_files: HashSet::new(),
+            written_files: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Extracts the data, unless it has already been extracted from the same executable. If the executable
+    /// has changed since, the files written by the previous extraction are replaced, while files placed
+    /// in the data directory by the user are left untouched.
+    pub fn extract_data(&mut self) -> GameResult {
+        let record_path = self.data_path()?.join(EXTRACTION_RECORD_NAME);
+        let exe_hash = format!("{:016x}", Self::hash_exe(&self.exe_buffer));
+
+        if let Ok(text) = std::fs::read_to_string(&record_path) {
+            let record = ExtractionRecord::parse(&text);
+            let data_path = self.data_path()?;
+
+            if record.exe_hash == exe_hash && record.files.iter().all(|file| data_path.join(file).exists()) {
+                log::info!("Vanilla data has already been extracted from this executable.");
+                return Ok(());
+            }
+
+            if record.exe_hash != exe_hash {
+                log::info!("Vanilla game executable has changed, extracting its data again.");
+                self.previous_files = record.files.into_iter().collect();
+            }
+        }
+
+        self.extract_all()?;
+
+        // remove the outputs the new executable doesn't provide anymore, eg. tables moved by a different hack
+        let written_files = self.written_files.take();
+        for file in self.previous_files.iter().filter(|file| !written_files.contains(file)) {
+            log::info!("Removing {}, as it's not provided by the executable anymore.", file);
+            let _ = std::fs::remove_file(self.data_path()?.join(file));
+        }
+
+        let record = ExtractionRecord { exe_hash, files: written_files };
+        self.write_data_file(record_path, record.to_text().as_bytes())
     }
 
-    pub fn extract_data(&self) -> GameResult {
+    fn extract_all(&self) -> GameResult {
         let parser = ExeParser::from(&self.exe_buffer);
         if parser.is_err() {
             return Err(ParseError("Failed to create vanilla parser.".to_string()));
@@ -89,6 +142,287 @@ impl VanillaExtractor {
         self.extract_bitmaps(&parser)?;
         self.extract_stage_table(&parser)?;
 
+        // The tables below are often modified by executable hacks, but they aren't essential
+        // as the engine has built-in defaults, so a failure is not fatal.
+        if let Err(e) = self.extract_pixtone_parameters(&parser) {
+            log::warn!("Failed to extract PixTone parameters: {}", e);
+        }
+
+        if let Err(e) = self.extract_bullet_table(&parser) {
+            log::warn!("Failed to extract bullet table: {}", e);
+        }
+
+        if let Err(e) = self.extract_arms_level_table(&parser) {
+            log::warn!("Failed to extract arms level table: {}", e);
+        }
+
+        if let Err(e) = self.extract_exe_patches(&parser) {
+            log::warn!("Failed to extract executable hacks: {}", e);
+        }
+
+        Ok(())
+    }
+
+    fn data_path(&self) -> GameResult<PathBuf> {
+        let mut data_path = self.root.clone();
+        data_path.push(self.data_base_dir.clone());
+
+        if self.deep_create_dir_if_not_exists(data_path.clone()).is_err() {
+            return Err(ParseError("Failed to create data directory structure.".to_string()));
+        }
+
+        Ok(data_path)
+    }
+
+    /// FNV-1a hash of the executable, identifying it in the extraction record.
+    fn hash_exe(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+    }
+
+    /// Returns the path of `path` relative to the data directory, as stored in the extraction record.
+    fn record_name(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(self.root.join(&self.data_base_dir)).ok()?;
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Returns whether the file at `path` can be extracted, which is if it doesn't exist yet or if it has been
+    /// written by the previous extraction. Each output is checked on its own, so files added by newer versions
+    /// of the extractor are extracted for existing installations as well.
+    fn should_write(&self, path: &Path) -> bool {
+        if path.exists() && !self.record_name(path).map_or(false, |name| self.previous_files.contains(&name)) {
+            log::debug!("{:?} already exists, not extracting it.", path);
+            return false;
+        }
+
+        true
+    }
+
+    /// Remembers that the file at `path` has been written by the extractor.
+    fn record_written(&self, path: &Path) {
+        if let Some(name) = self.record_name(path) {
+            self.written_files.borrow_mut().push(name);
+        }
+    }
+
+    fn write_data_file(&self, path: PathBuf, bytes: &[u8]) -> GameResult {
+        let mut file = match std::fs::File::create(&path) {
+            Ok(file) => file,
+            Err(_) => {
+                return Err(ParseError(format!("Failed to create {:?}.", path)));
+            }
+        };
+
+        if file.write_all(bytes).is_err() {
+            return Err(ParseError(format!("Failed to write {:?}.", path)));
+        }
+
+        self.record_written(&path);
+
+        Ok(())
+    }
+
+    /// Finds the `MakePixToneObject(&gPtpTable[i], num, no)` calls in LoadGenericData and converts
+    /// the referenced parameters to .pxt files.
+    fn extract_pixtone_parameters(&self, parser: &ExeParser) -> GameResult {
+        let text = match parser.get_named_section_bytes(".text".to_string())? {
+            Some(text) => text,
+            None => return Err(ParseError("Failed to find the code section.".to_string())),
+        };
+
+        // push no; push num (imm8); push ptp (imm32); call MakePixToneObject
+        let mut calls = Vec::new();
+        for i in 5..text.len().saturating_sub(12) {
+            if text[i] != 0x6A || text[i + 2] != 0x68 || text[i + 7] != 0xE8 {
+                continue;
+            }
+
+            let no = if text[i - 2] == 0x6A {
+                text[i - 1] as u32
+            } else if text[i - 5] == 0x68 {
+                LE::read_u32(&text[i - 4..i])
+            } else {
+                continue;
+            };
+
+            let num = text[i + 1] as usize;
+            let ptp = LE::read_u32(&text[i + 3..i + 7]);
+            let target = (i as i64 + 12 + LE::read_i32(&text[i + 8..i + 12]) as i64) as usize;
+
+            if no <= 0xff && (1..=4).contains(&num) {
+                calls.push((target, no as u8, num, ptp));
+            }
+        }
+
+        let mut call_count: HashMap<usize, usize> = HashMap::new();
+        for (target, ..) in calls.iter() {
+            *call_count.entry(*target).or_default() += 1;
+        }
+
+        let make_pixtone_object = match call_count.into_iter().max_by_key(|(_, count)| *count) {
+            Some((target, _)) => target,
+            None => return Err(ParseError("Failed to find the PixTone parameter table.".to_string())),
+        };
+
+        let mut pxt_path = self.data_path()?;
+        pxt_path.push("pxt/");
+
+        if self.deep_create_dir_if_not_exists(pxt_path.clone()).is_err() {
+            return Err(ParseError("Failed to create PixTone directory structure.".to_string()));
+        }
+
+        let mut sound_count = 0;
+        for (_, no, num, ptp) in calls.into_iter().filter(|(target, ..)| *target == make_pixtone_object) {
+            let path = pxt_path.join(format!("fx{:02x}.pxt", no));
+            if !self.should_write(&path) {
+                continue;
+            }
+
+            let bytes = match parser.bytes_at(ptp, num * VANILLA_PIXTONE_PARAMETER_SIZE) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    log::warn!("Skipping PixTone sound {}: {}", no, e);
+                    continue;
+                }
+            };
+
+            let pxt = match Self::pixtone_parameters_to_pxt(bytes, num) {
+                Some(pxt) => pxt,
+                None => {
+                    log::warn!("Skipping PixTone sound {}: parameters are invalid.", no);
+                    continue;
+                }
+            };
+
+            self.write_data_file(path, pxt.as_bytes())?;
+            sound_count += 1;
+        }
+
+        log::info!("Extracted {} PixTone sounds.", sound_count);
+
+        Ok(())
+    }
+
+    /// Converts `num` PIXTONEPARAMETER structures to the text format of .pxt files.
+    fn pixtone_parameters_to_pxt(bytes: &[u8], num: usize) -> Option<String> {
+        const WAVES: [(&str, usize); 3] = [("main", 0x08), ("pitch", 0x20), ("volume", 0x38)];
+        const ENVELOPE: [&str; 7] = ["initialY", "ax", "ay", "bx", "by", "cx", "cy"];
+
+        let mut pxt = String::new();
+        let disabled = [0u8; VANILLA_PIXTONE_PARAMETER_SIZE];
+
+        for channel in 0..4 {
+            let ptp = match bytes.chunks_exact(VANILLA_PIXTONE_PARAMETER_SIZE).take(num).nth(channel) {
+                Some(ptp) => ptp,
+                None => &disabled,
+            };
+
+            let enabled = LE::read_i32(&ptp[0x00..]);
+            let size = LE::read_i32(&ptp[0x04..]);
+            if !(0..=1).contains(&enabled) || !(0..=0x100000).contains(&size) {
+                return None;
+            }
+
+            let _ = writeln!(pxt, "use  :{}", enabled);
+            let _ = writeln!(pxt, "size :{}", size);
+
+            for (name, offset) in WAVES {
+                let model = LE::read_i32(&ptp[offset..]);
+                let freq = LE::read_f64(&ptp[offset + 0x08..]);
+                if !(0..6).contains(&model) || !freq.is_finite() {
+                    return None;
+                }
+
+                let _ = writeln!(pxt, "{:13}:{}", format!("{}_model", name), model);
+                let _ = writeln!(pxt, "{:13}:{:.6}", format!("{}_freq", name), freq);
+                let _ = writeln!(pxt, "{:13}:{}", format!("{}_top", name), LE::read_i32(&ptp[offset + 0x10..]));
+                let _ = writeln!(pxt, "{:13}:{}", format!("{}_offset", name), LE::read_i32(&ptp[offset + 0x14..]));
+            }
+
+            for (i, name) in ENVELOPE.iter().enumerate() {
+                let _ = writeln!(pxt, "{:8}:{}", name, LE::read_i32(&ptp[0x50 + i * 4..]));
+            }
+
+            pxt.push('\n');
+        }
+
+        Some(pxt)
+    }
+
+    /// Converts gBulTbl to the bullet.tbl format used by Cave Story+.
+    fn extract_bullet_table(&self, parser: &ExeParser) -> GameResult {
+        let path = self.data_path()?.join("bullet.tbl");
+        if !self.should_write(&path) {
+            return Ok(());
+        }
+
+        let bytes = parser.bytes_at(VANILLA_BULLET_TABLE_ADDR, VANILLA_BULLET_COUNT * VANILLA_BULLET_ENTRY_SIZE)?;
+
+        // the first entry is unused and always zeroed, anything else means the table has been moved
+        if bytes[..VANILLA_BULLET_ENTRY_SIZE].iter().any(|b| *b != 0) {
+            return Err(ParseError("Bullet table signature mismatch.".to_string()));
+        }
+
+        let mut table = Vec::with_capacity(VANILLA_BULLET_COUNT * 0x2A);
+        for entry in bytes.chunks_exact(VANILLA_BULLET_ENTRY_SIZE) {
+            // damage and life are signed chars followed by 2 bytes of padding
+            table.push(entry[0]);
+            table.push(entry[1]);
+
+            for i in 0..10 {
+                let value = LE::read_i32(&entry[4 + i * 4..]);
+                if !(0..=0xffff).contains(&value) {
+                    return Err(ParseError("Bullet table contains invalid values.".to_string()));
+                }
+
+                table.write_i32::<LE>(value)?;
+            }
+        }
+
+        self.write_data_file(path, &table)?;
+        log::info!("Extracted bullet table.");
+
+        Ok(())
+    }
+
+    /// Converts gArmsLevelTable to the arms_level.tbl format used by Cave Story+.
+    fn extract_arms_level_table(&self, parser: &ExeParser) -> GameResult {
+        let path = self.data_path()?.join("arms_level.tbl");
+        if !self.should_write(&path) {
+            return Ok(());
+        }
+
+        let bytes = parser.bytes_at(VANILLA_ARMS_LEVEL_TABLE_ADDR, VANILLA_ARMS_COUNT * 3 * 4)?;
+
+        if bytes.chunks_exact(4).any(|value| !(0..=0xffff).contains(&LE::read_i32(value))) {
+            return Err(ParseError("Arms level table contains invalid values.".to_string()));
+        }
+
+        self.write_data_file(path, bytes)?;
+        log::info!("Extracted arms level table.");
+
+        Ok(())
+    }
+
+    /// Detects binary hacks and writes the engine constants they modify to constants.json,
+    /// if one doesn't exist yet.
+    fn extract_exe_patches(&self, parser: &ExeParser) -> GameResult {
+        let patches = ExePatchSet::detect(parser);
+        patches.log_report();
+
+        if patches.constants.is_empty() {
+            return Ok(());
+        }
+
+        let constants_path = self.data_path()?.join("constants.json");
+        if !self.should_write(&constants_path) {
+            log::warn!("constants.json already exists, not overwriting it with constants of executable hacks.");
+            return Ok(());
+        }
+
+        let constants = serde_json::Value::Object(patches.constants);
+        self.write_data_file(constants_path, serde_json::to_string_pretty(&constants)?.as_bytes())?;
+        log::info!("Extracted engine constants modified by executable hacks.");
+
         Ok(())
     }
 
@@ -122,8 +456,11 @@ impl VanillaExtractor {
             }
 
             org_path.push(format!("{}.org", org.name));
+            if !self.should_write(&org_path) {
+                continue;
+            }
 
-            let mut org_file = match std::fs::File::create(org_path) {
+            let mut org_file = match std::fs::File::create(&org_path) {
                 Ok(file) => file,
                 Err(_) => {
                     return Err(ParseError("Failed to create organya file.".to_string()));
@@ -134,6 +471,7 @@ impl VanillaExtractor {
             if result.is_err() {
                 return Err(ParseError("Failed to write organya file.".to_string()));
             }
+            self.record_written(&org_path);
 
             log::info!("Extracted organya file: {}", org.name);
         }
@@ -157,8 +495,11 @@ impl VanillaExtractor {
             }
 
             data_path.push(format!("{}.pbm", bitmap.name));
+            if !self.should_write(&data_path) {
+                continue;
+            }
 
-            let file = std::fs::File::create(data_path);
+            let file = std::fs::File::create(&data_path);
             if file.is_err() {
                 return Err(ParseError("Failed to create bitmap file.".to_string()));
             }
@@ -175,6 +516,7 @@ impl VanillaExtractor {
             if result.is_err() {
                 return Err(ParseError("Failed to write bitmap file.".to_string()));
             }
+            self.record_written(&data_path);
 
             log::info!("Extracted bitmap file: {}", bitmap.name);
         }
@@ -183,6 +525,11 @@ impl VanillaExtractor {
     }
 
     fn extract_stage_table(&self, parser: &ExeParser) -> GameResult {
+        let stage_tbl_path = self.data_path()?.join("stage.sect");
+        if !self.should_write(&stage_tbl_path) {
+            return Ok(());
+        }
+
         let range = parser.get_named_section_byte_range(".csmap".to_string());
         if range.is_err() {
             return Err(ParseError("Failed to retrieve stage table from executable.".to_string()));
@@ -198,16 +545,7 @@ impl VanillaExtractor {
 
         let byte_slice = &self.exe_buffer[start..end];
 
-        let mut stage_tbl_path = self.root.clone();
-        stage_tbl_path.push(self.data_base_dir.clone());
-
-        if self.deep_create_dir_if_not_exists(stage_tbl_path.clone()).is_err() {
-            return Err(ParseError("Failed to create data directory structure.".to_string()));
-        }
-
-        stage_tbl_path.push("stage.sect");
-
-        let mut stage_tbl_file = match std::fs::File::create(stage_tbl_path) {
+        let mut stage_tbl_file = match std::fs::File::create(&stage_tbl_path) {
             Ok(file) => file,
             Err(_) => {
                 return Err(ParseError("Failed to create stage table file.".to_string()));
@@ -218,7 +556,159 @@ impl VanillaExtractor {
         if result.is_err() {
             return Err(ParseError("Failed to write to stage table file.".to_string()));
         }
+        self.record_written(&stage_tbl_path);
 
         Ok(())
     }
 }
+
+/// Hash of the executable data has been extracted from, followed by the extracted files, one per line.
+#[derive(Debug, Default, PartialEq)]
+struct ExtractionRecord {
+    exe_hash: String,
+    files: Vec<String>,
+}
+
+impl ExtractionRecord {
+    fn parse(text: &str) -> ExtractionRecord {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let exe_hash = lines.next().unwrap_or_default().to_owned();
+
+        ExtractionRecord { exe_hash, files: lines.map(str::to_owned).collect() }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = self.exe_hash.clone();
+        for file in self.files.iter() {
+            text.push('\n');
+            text.push_str(file);
+        }
+        text.push('\n');
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixtone_parameter(enabled: i32, model: i32, freq: f64) -> Vec<u8> {
+        let mut ptp = vec![0u8; VANILLA_PIXTONE_PARAMETER_SIZE];
+        LE::write_i32(&mut ptp[0x00..], enabled);
+        LE::write_i32(&mut ptp[0x04..], 1000);
+
+        // main wave
+        LE::write_i32(&mut ptp[0x08..], model);
+        LE::write_f64(&mut ptp[0x10..], freq);
+        LE::write_i32(&mut ptp[0x18..], 32);
+        LE::write_i32(&mut ptp[0x1C..], -4);
+
+        // envelope
+        for (i, value) in [63, 10, 50, 20, 30, 40, 0].iter().enumerate() {
+            LE::write_i32(&mut ptp[0x50 + i * 4..], *value);
+        }
+
+        ptp
+    }
+
+    #[test]
+    fn test_pixtone_parameters_to_pxt() {
+        let pxt = VanillaExtractor::pixtone_parameters_to_pxt(&pixtone_parameter(1, 2, 5.5), 1).unwrap();
+        let mut channels = pxt.split("\n\n");
+
+        assert_eq!(
+            channels.next().unwrap(),
+            "use  :1\n\
+             size :1000\n\
+             main_model   :2\n\
+             main_freq    :5.500000\n\
+             main_top     :32\n\
+             main_offset  :-4\n\
+             pitch_model  :0\n\
+             pitch_freq   :0.000000\n\
+             pitch_top    :0\n\
+             pitch_offset :0\n\
+             volume_model :0\n\
+             volume_freq  :0.000000\n\
+             volume_top   :0\n\
+             volume_offset:0\n\
+             initialY:63\n\
+             ax      :10\n\
+             ay      :50\n\
+             bx      :20\n\
+             by      :30\n\
+             cx      :40\n\
+             cy      :0"
+        );
+
+        // missing channels are written as disabled
+        for channel in channels.by_ref().take(3) {
+            assert!(channel.starts_with("use  :0\nsize :0\n"), "{}", channel);
+        }
+        assert_eq!(channels.next(), Some(""));
+    }
+
+    #[test]
+    fn test_pixtone_parameters_to_pxt_uses_num() {
+        let mut bytes = pixtone_parameter(1, 0, 1.0);
+        bytes.extend(pixtone_parameter(1, 1, 1.0));
+
+        let pxt = VanillaExtractor::pixtone_parameters_to_pxt(&bytes, 1).unwrap();
+        assert_eq!(pxt.matches("use  :1").count(), 1);
+
+        let pxt = VanillaExtractor::pixtone_parameters_to_pxt(&bytes, 2).unwrap();
+        assert_eq!(pxt.matches("use  :1").count(), 2);
+    }
+
+    #[test]
+    fn test_pixtone_parameters_to_pxt_rejects_garbage() {
+        assert!(VanillaExtractor::pixtone_parameters_to_pxt(&pixtone_parameter(2, 0, 1.0), 1).is_none());
+        assert!(VanillaExtractor::pixtone_parameters_to_pxt(&pixtone_parameter(1, 6, 1.0), 1).is_none());
+        assert!(VanillaExtractor::pixtone_parameters_to_pxt(&pixtone_parameter(1, 0, f64::NAN), 1).is_none());
+    }
+
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_extraction_record() {
+        let record = ExtractionRecord {
+            exe_hash: format!("{:016x}", VanillaExtractor::hash_exe(b"Doukutsu")),
+            files: vec!["stage.sect".to_owned(), "Org/ACCESS.org".to_owned()],
+        };
+
+        assert_eq!(ExtractionRecord::parse(&record.to_text()), record);
+        assert_eq!(ExtractionRecord::parse(""), ExtractionRecord::default());
+        assert_ne!(VanillaExtractor::hash_exe(b"Doukutsu"), VanillaExtractor::hash_exe(b"Doukutsv"));
+    }
+
+    #[test]
+    fn test_should_write_only_replaces_extracted_files() {
+        let dir = TempDir(std::env::temp_dir().join(format!("doukutsu-rs-vanilla-test-{}", std::process::id())));
+        let data_path = dir.0.join("data");
+        std::fs::create_dir_all(&data_path).unwrap();
+        std::fs::write(data_path.join("bullet.tbl"), b"").unwrap();
+        std::fs::write(data_path.join("Title.pbm"), b"").unwrap();
+
+        let extractor = VanillaExtractor {
+            exe_buffer: Vec::new(),
+            data_base_dir: "data".to_owned(),
+            root: dir.0.clone(),
+            previous_files: ["bullet.tbl".to_owned()].into_iter().collect(),
+            written_files: RefCell::new(Vec::new()),
+        };
+
+        assert!(extractor.should_write(&data_path.join("bullet.tbl")));
+        assert!(!extractor.should_write(&data_path.join("Title.pbm")));
+        assert!(extractor.should_write(&data_path.join("arms_level.tbl")));
+
+        extractor.record_written(&data_path.join("Org").join("ACCESS.org"));
+        assert_eq!(*extractor.written_files.borrow(), vec!["Org/ACCESS.org".to_owned()]);
+    }
+}
@@ -3,26 +3,32 @@ use std::io::{BufRead, BufReader, Cursor, Read};
 
 use byteorder::{ReadBytesExt, LE};
 use case_insensitive_hashmap::CaseInsensitiveHashMap;
+use serde_json::Value;
 use xmltree::Element;
 
 use crate::case_insensitive_hashmap;
 use crate::common::{BulletFlag, Color, Rect};
 use crate::engine_constants::npcs::NPCConsts;
 use crate::framework::context::Context;
-use crate::framework::error::GameResult;
+use crate::framework::error::{GameError, GameResult};
 use crate::framework::filesystem;
 use crate::framework::gamepad::{Axis, Button};
 use crate::game::player::ControlMode;
 use crate::game::scripting::tsc::text_script::TextScriptEncoding;
 use crate::game::settings::Settings;
 use crate::game::shared_game_state::{FontData, Season};
-use crate::i18n::Locale;
+use crate::i18n::{Locale, REFERENCE_LOCALE};
 use crate::sound::pixtone::{Channel, Envelope, PixToneParameters, Waveform};
 use crate::sound::SoundManager;
 
 mod npcs;
 
-#[derive(Debug, Copy, Clone)]
+/// Path of the file in user directory the current values of overridable constants are dumped to.
+pub const CONSTANTS_DUMP_PATH: &str = "/constants_dump.json";
+/// Path of the file in user directory the JSON Schema of `constants.json` files is written to.
+pub const CONSTANTS_SCHEMA_PATH: &str = "/constants.schema.json";
+
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PhysicsConsts {
     pub max_dash: i32,
     pub max_move: i32,
@@ -34,7 +40,7 @@ pub struct PhysicsConsts {
     pub jump: i32,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BoosterConsts {
     pub fuel: u32,
     pub b2_0_up: i32,
@@ -44,7 +50,7 @@ pub struct BoosterConsts {
     pub b2_0_right: i32,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PlayerConsts {
     pub life: u16,
     pub max_life: u16,
@@ -56,7 +62,7 @@ pub struct PlayerConsts {
     pub frames_bubble: [Rect<u16>; 2],
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GameConsts {
     pub intro_stage: u16,
     pub intro_event: u16,
@@ -65,9 +71,13 @@ pub struct GameConsts {
     pub new_game_event: u16,
     pub new_game_player_pos: (i16, i16),
     pub tile_offset_x: i32,
+    /// Title of the game window, the backend's default title is used if empty.
+    pub window_title: String,
+    /// Number of TSC flags, only the first 8000 are stored in `Profile.dat`.
+    pub flag_count: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct CaretConsts {
     pub offsets: [(i32, i32); 18],
     pub bubble_left_rects: Vec<Rect<u16>>,
@@ -125,7 +135,7 @@ impl Clone for CaretConsts {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BulletData {
     pub damage: u8,
     pub life: u8,
@@ -138,7 +148,7 @@ pub struct BulletData {
     pub display_bounds: Rect<u8>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BulletRects {
     pub b001_snake_l1: [Rect<u16>; 8],
     pub b002_003_snake_l2_3: [Rect<u16>; 3],
@@ -175,7 +185,7 @@ pub struct BulletRects {
     pub b042_spur_trail_l3: [Rect<u16>; 6],
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct WeaponConsts {
     pub bullet_table: Vec<BulletData>,
     pub bullet_rects: BulletRects,
@@ -192,7 +202,7 @@ impl Clone for WeaponConsts {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct WorldConsts {
     pub snack_rect: Rect<u16>,
     pub water_push_rect: Rect<u16>,
@@ -212,8 +222,9 @@ pub struct ExtraSoundtrack {
     pub available: bool,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TextScriptConsts {
+    /// Encoding of the scripts in game data, scripts provided by a translation use the encoding of its locale.
     pub encoding: TextScriptEncoding,
     pub encrypted: bool,
     pub reset_invicibility_on_any_script: bool,
@@ -245,7 +256,7 @@ pub struct TextScriptConsts {
     pub fade_ticks: i8,
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct TitleConsts {
     pub intro_text: String,
     pub logo_rect: Rect<u16>,
@@ -316,6 +327,10 @@ impl GamepadConsts {
 #[derive(Debug)]
 pub struct EngineConstants {
     pub base_paths: Vec<String>,
+    /// Base paths containing data of the active translation.
+    pub locale_paths: Vec<String>,
+    /// Encoding of the text scripts found in `locale_paths`.
+    pub locale_encoding: TextScriptEncoding,
     pub is_cs_plus: bool,
     pub is_switch: bool,
     pub is_demo: bool,
@@ -342,22 +357,29 @@ pub struct EngineConstants {
     pub player_skin_paths: Vec<String>,
     pub animated_face_table: Vec<AnimatedFace>,
     pub string_table: HashMap<String, String>,
+    /// Names and operand counts of TSC commands registered by scripts.
+    pub tsc_custom_commands: HashMap<String, u8>,
     pub missile_flags: Vec<u16>,
     pub locales: Vec<Locale>,
     pub gamepad: GamepadConsts,
+    /// Overridable sections as the built-in defaults and version specific patches left them,
+    /// restored on each reload before loading the tables and `constants.json` files of mods.
+    base_sections: Option<Value>,
 }
 
 impl Clone for EngineConstants {
     fn clone(&self) -> EngineConstants {
         EngineConstants {
             base_paths: self.base_paths.clone(),
+            locale_paths: self.locale_paths.clone(),
+            locale_encoding: self.locale_encoding,
             is_cs_plus: self.is_cs_plus,
             is_switch: self.is_switch,
             is_demo: self.is_demo,
             supports_og_textures: self.supports_og_textures,
             has_difficulty_menu: self.has_difficulty_menu,
             supports_two_player: self.supports_two_player,
-            game: self.game,
+            game: self.game.clone(),
             player: self.player,
             booster: self.booster,
             caret: self.caret.clone(),
@@ -377,9 +399,11 @@ impl Clone for EngineConstants {
             player_skin_paths: self.player_skin_paths.clone(),
             animated_face_table: self.animated_face_table.clone(),
             string_table: self.string_table.clone(),
+            tsc_custom_commands: self.tsc_custom_commands.clone(),
             missile_flags: self.missile_flags.clone(),
             locales: self.locales.clone(),
             gamepad: self.gamepad.clone(),
+            base_sections: self.base_sections.clone(),
         }
     }
 }
@@ -388,6 +412,8 @@ impl EngineConstants {
     pub fn defaults() -> Self {
         EngineConstants {
             base_paths: Vec::new(),
+            locale_paths: Vec::new(),
+            locale_encoding: TextScriptEncoding::UTF8,
             is_cs_plus: false,
             is_switch: false,
             is_demo: false,
@@ -402,6 +428,8 @@ impl EngineConstants {
                 new_game_event: 200,
                 new_game_player_pos: (10, 8),
                 tile_offset_x: 0,
+                window_title: String::new(),
+                flag_count: 8000,
             },
             player: PlayerConsts {
                 life: 3,
@@ -1677,6 +1705,7 @@ impl EngineConstants {
             player_skin_paths: vec!["MyChar".to_owned()],
             animated_face_table: vec![AnimatedFace { face_id: 0, anim_id: 0, anim_frames: vec![(0, 0)] }],
             string_table: HashMap::new(),
+            tsc_custom_commands: HashMap::new(),
             missile_flags: vec![200, 201, 202, 218, 550, 766, 880, 920, 1551],
             locales: Vec::new(),
             gamepad: GamepadConsts {
@@ -1705,6 +1734,7 @@ impl EngineConstants {
                     (Axis::TriggerRight, GamepadConsts::rects(Rect::new(32, 80, 64, 96))),
                 ]),
             },
+            base_sections: None,
         }
     }
 
@@ -1797,7 +1827,8 @@ impl EngineConstants {
         self.title.logo_splash_rect = Rect { left: 224, top: 0, right: 320, bottom: 48 };
     }
 
-    pub fn rebuild_path_list(&mut self, mod_path: Option<String>, season: Season, settings: &Settings) {
+    /// Rebuilds the list of data roots, `mod_paths` are layered on top in load order so later mods take precedence.
+    pub fn rebuild_path_list(&mut self, mod_paths: &[String], season: Season, settings: &Settings) {
         self.base_paths.clear();
         self.base_paths.push("/builtin/builtin_data/".to_owned());
         self.base_paths.push("/".to_owned());
@@ -1814,23 +1845,38 @@ impl EngineConstants {
                     _ => {}
                 }
             }
+        }
 
-            if settings.locale != "en".to_string() {
-                self.base_paths.insert(0, format!("/base/{}/", settings.locale));
-            }
-        } else {
-            if settings.locale != "en".to_string() {
-                self.base_paths.insert(0, format!("/{}/", settings.locale));
-            }
+        // translations are layered from the least specific locale, eg. `/pt/` below `/pt-BR/`
+        let mut locale_paths = Vec::new();
+        let locale_chain: Vec<String> = Locale::fallback_chain(&self.locales, &settings.locale)
+            .into_iter()
+            .filter(|code| code != REFERENCE_LOCALE)
+            .rev()
+            .collect();
+
+        for code in locale_chain.iter() {
+            let path = if self.is_cs_plus { format!("/base/{}/", code) } else { format!("/{}/", code) };
+            self.base_paths.insert(0, path.clone());
+            locale_paths.push(path);
         }
 
-        if let Some(mut mod_path) = mod_path {
+        for mod_path in mod_paths {
             self.base_paths.insert(0, mod_path.clone());
             if settings.original_textures {
-                mod_path.push_str("ogph/");
-                self.base_paths.insert(0, mod_path);
+                self.base_paths.insert(0, format!("{}ogph/", mod_path));
             }
 
+            for code in locale_chain.iter() {
+                let path = format!("{}{}/", mod_path, code);
+                self.base_paths.insert(0, path.clone());
+                locale_paths.push(path);
+            }
+        }
+
+        self.locale_paths = self.base_paths.iter().filter(|path| locale_paths.contains(path)).cloned().collect();
+
+        if !mod_paths.is_empty() {
             // Nicalis left a landmine of a file in the original graphics for the nemesis challenge
             // It has 17 colors defined for a 4-bit color depth bitmap
             if self.is_cs_plus && !self.is_switch {
@@ -1839,6 +1885,21 @@ impl EngineConstants {
         }
     }
 
+    /// Returns the encoding of the text script at `path`, the one found in a translation uses its locale encoding.
+    pub fn text_script_encoding(&self, ctx: &Context, path: &str) -> TextScriptEncoding {
+        for base_path in self.base_paths.iter() {
+            if filesystem::exists(ctx, format!("{}{}", base_path, path)) {
+                if self.locale_paths.contains(base_path) {
+                    return self.locale_encoding;
+                }
+
+                break;
+            }
+        }
+
+        self.textscript.encoding
+    }
+
     pub fn special_treatment_for_csplus_mods(&mut self, mod_path: Option<&String>) {
         if !self.is_cs_plus {
             return;
@@ -1914,7 +1975,204 @@ impl EngineConstants {
         Ok(())
     }
 
-    pub fn apply_constant_json_files(&mut self) {}
+    /// Returns the current values of the sections `constants.json` files can override, in the format of these files.
+    /// Other constants, such as paths, sound tables or locales, are not included.
+    pub fn overridable_json(&self) -> serde_json::Result<Value> {
+        Ok(serde_json::json!({
+            "game": self.game,
+            "player": self.player,
+            "booster": self.booster,
+            "caret": self.caret,
+            "world": self.world,
+            "npc": self.npc,
+            "weapon": self.weapon,
+            "textscript": self.textscript,
+            "title": self.title,
+        }))
+    }
+
+    fn set_overridable_json(&mut self, mut value: Value) -> Result<(), String> {
+        macro_rules! section {
+            ($name:ident) => {
+                self.$name = serde_path_to_error::deserialize(value[stringify!($name)].take()).map_err(
+                    |err: serde_path_to_error::Error<serde_json::Error>| match err.path().to_string().as_str() {
+                        "." => format!("{}: {}", stringify!($name), err.inner()),
+                        path => format!("{}.{}: {}", stringify!($name), path, err.inner()),
+                    },
+                )?;
+            };
+        }
+
+        section!(game);
+        section!(player);
+        section!(booster);
+        section!(caret);
+        section!(world);
+        section!(npc);
+        section!(weapon);
+        section!(textscript);
+        section!(title);
+
+        Ok(())
+    }
+
+    /// Merges `patch` into `target`. Objects are merged recursively and array elements can be patched by
+    /// using an object with indices as keys, other values are replaced. Returns the offending key if `patch`
+    /// contains keys or value types not present in `target`.
+    fn merge_json(target: &mut Value, patch: Value, path: &str) -> Result<(), String> {
+        let key_path = |key: &str| if path.is_empty() { key.to_owned() } else { format!("{}.{}", path, key) };
+
+        match (target, patch) {
+            (Value::Object(target), Value::Object(patch)) => {
+                for (key, value) in patch {
+                    match target.get_mut(&key) {
+                        Some(target) => EngineConstants::merge_json(target, value, &key_path(&key))?,
+                        None => return Err(format!("{}: unknown key", key_path(&key))),
+                    }
+                }
+            }
+            (Value::Array(target), Value::Object(patch)) => {
+                for (key, value) in patch {
+                    match key.parse::<usize>().ok().and_then(|index| target.get_mut(index)) {
+                        Some(target) => EngineConstants::merge_json(target, value, &key_path(&key))?,
+                        None => return Err(format!("{}: index out of bounds", key_path(&key))),
+                    }
+                }
+            }
+            (Value::Array(target), Value::Array(patch)) => *target = patch,
+            (target @ Value::Number(_), patch @ Value::Number(_))
+            | (target @ Value::String(_), patch @ Value::String(_))
+            | (target @ Value::Bool(_), patch @ Value::Bool(_)) => *target = patch,
+            (target, patch) => {
+                let type_name = |value: &Value| match value {
+                    Value::Null => "null",
+                    Value::Bool(_) => "a boolean",
+                    Value::Number(_) => "a number",
+                    Value::String(_) => "a string",
+                    Value::Array(_) => "an array",
+                    Value::Object(_) => "an object",
+                };
+
+                return Err(format!("{}: expected {}, got {}", path, type_name(&*target), type_name(&patch)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn json_schema_of(value: &Value) -> Value {
+        match value {
+            Value::Null => serde_json::json!({}),
+            Value::Bool(_) => serde_json::json!({ "type": "boolean" }),
+            Value::Number(n) if n.is_f64() => serde_json::json!({ "type": "number" }),
+            Value::Number(_) => serde_json::json!({ "type": "integer" }),
+            Value::String(_) => serde_json::json!({ "type": "string" }),
+            Value::Array(items) => {
+                let items = items.first().map(EngineConstants::json_schema_of).unwrap_or_else(|| serde_json::json!({}));
+
+                // arrays can be either replaced or patched by index
+                serde_json::json!({
+                    "anyOf": [
+                        { "type": "array", "items": items },
+                        {
+                            "type": "object",
+                            "patternProperties": { "^[0-9]+$": items },
+                            "additionalProperties": false,
+                        },
+                    ]
+                })
+            }
+            Value::Object(map) => {
+                let properties: serde_json::Map<String, Value> =
+                    map.iter().map(|(key, value)| (key.clone(), EngineConstants::json_schema_of(value))).collect();
+
+                serde_json::json!({ "type": "object", "properties": properties, "additionalProperties": false })
+            }
+        }
+    }
+
+    /// Generates a JSON Schema of `constants.json` files from the current constants, for use with editors.
+    pub fn json_schema(&self) -> GameResult<Value> {
+        let mut schema = EngineConstants::json_schema_of(&self.overridable_json()?);
+        schema["$schema"] = Value::from("http://json-schema.org/draft-07/schema#");
+        schema["title"] = Value::from("doukutsu-rs engine constants");
+        schema["description"] = Value::from(
+            "Sections of engine constants which constants.json files can override. Other constants, such as \
+             CS+ and Switch specific tables, are derived from game data and can't be changed this way.",
+        );
+        schema["properties"]["$schema"] = serde_json::json!({ "type": "string" });
+
+        Ok(schema)
+    }
+
+    /// Writes the current values of the overridable sections (see [`EngineConstants::overridable_json`]) and
+    /// their schema to [`CONSTANTS_DUMP_PATH`] and [`CONSTANTS_SCHEMA_PATH`] in the user directory.
+    pub fn dump_overridable_json(&self, ctx: &Context) -> GameResult {
+        let mut dump = self.overridable_json()?;
+        dump["$schema"] = Value::from(CONSTANTS_SCHEMA_PATH.trim_start_matches('/'));
+
+        serde_json::to_writer_pretty(filesystem::user_create(ctx, CONSTANTS_DUMP_PATH)?, &dump)?;
+        serde_json::to_writer_pretty(filesystem::user_create(ctx, CONSTANTS_SCHEMA_PATH)?, &self.json_schema()?)?;
+
+        log::info!("Dumped overridable engine constants to {} and {}.", CONSTANTS_DUMP_PATH, CONSTANTS_SCHEMA_PATH);
+
+        Ok(())
+    }
+
+    /// Restores the overridable sections to their state before any mod was loaded, so the tables and
+    /// `constants.json` files of a previous mod don't carry over. The first call takes the snapshot instead.
+    pub fn reset_overridable_sections(&mut self) -> GameResult {
+        match self.base_sections.clone() {
+            Some(base) => self.set_overridable_json(base).map_err(GameError::InvalidValue),
+            None => {
+                self.base_sections = Some(self.overridable_json()?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Applies partial overrides from `constants.json` in each of base paths on top of the current constants,
+    /// with the files from the paths earlier in the list (eg. mods) taking precedence. This is meant to be
+    /// the last step of loading constants, so the overrides win over the values from the tables.
+    ///
+    /// A file containing an error is skipped as a whole and the error naming the offending key is logged.
+    pub fn apply_constant_json_files(&mut self, ctx: &mut Context) -> GameResult {
+        let mut merged = self.overridable_json()?;
+
+        for base_path in self.base_paths.clone().iter().rev() {
+            let path = format!("{}constants.json", base_path);
+            let file = match filesystem::open(ctx, &path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            let mut patch: Value = match serde_json::from_reader(file) {
+                Ok(patch) => patch,
+                Err(err) => {
+                    log::error!("Failed to parse {}: {}", path, err);
+                    continue;
+                }
+            };
+
+            if let Some(patch) = patch.as_object_mut() {
+                patch.remove("$schema");
+            }
+
+            let mut result = merged.clone();
+            let res = EngineConstants::merge_json(&mut result, patch, "")
+                .and_then(|_| self.set_overridable_json(result.clone()));
+
+            match res {
+                Ok(()) => {
+                    log::info!("Applied constant overrides from {}.", path);
+                    merged = result;
+                }
+                Err(err) => log::error!("Failed to apply {}: {}", path, err),
+            }
+        }
+
+        self.set_overridable_json(merged).map_err(GameError::InvalidValue)
+    }
 
     pub fn load_texture_size_hints(&mut self, ctx: &mut Context) -> GameResult {
         if let Ok(file) = filesystem::open_find(ctx, &self.base_paths, "texture_sizes.json") {
@@ -2033,3 +2291,204 @@ impl EngineConstants {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_merge_json_nested_objects() {
+        let mut target = json!({ "game": { "intro_stage": 72, "pos": { "x": 3, "y": 4 } }, "title": { "text": "a" } });
+        let patch = json!({ "game": { "pos": { "y": 10 } } });
+
+        EngineConstants::merge_json(&mut target, patch, "").unwrap();
+        assert_eq!(
+            target,
+            json!({ "game": { "intro_stage": 72, "pos": { "x": 3, "y": 10 } }, "title": { "text": "a" } })
+        );
+
+        let err = EngineConstants::merge_json(&mut target, json!({ "game": { "pos": { "z": 1 } } }), "").unwrap_err();
+        assert_eq!(err, "game.pos.z: unknown key");
+    }
+
+    #[test]
+    fn test_merge_json_arrays() {
+        let mut target = json!({ "table": [[1, 2], [3, 4], [5, 6]] });
+
+        // arrays are replaced as a whole, even with a different length
+        EngineConstants::merge_json(&mut target, json!({ "table": [[7, 8]] }), "").unwrap();
+        assert_eq!(target, json!({ "table": [[7, 8]] }));
+
+        // while objects with indices as keys patch single elements
+        let mut target = json!({ "table": [[1, 2], [3, 4], [5, 6]] });
+        EngineConstants::merge_json(&mut target, json!({ "table": { "1": { "0": 9 } } }), "").unwrap();
+        assert_eq!(target, json!({ "table": [[1, 2], [9, 4], [5, 6]] }));
+
+        let err = EngineConstants::merge_json(&mut target, json!({ "table": { "3": [0, 0] } }), "").unwrap_err();
+        assert_eq!(err, "table.3: index out of bounds");
+    }
+
+    #[test]
+    fn test_merge_json_null_and_type_mismatch() {
+        let mut target = json!({ "life": 3, "name": "Quote" });
+
+        let err = EngineConstants::merge_json(&mut target, json!({ "life": null }), "").unwrap_err();
+        assert_eq!(err, "life: expected a number, got null");
+
+        let err = EngineConstants::merge_json(&mut target, json!({ "name": 5 }), "").unwrap_err();
+        assert_eq!(err, "name: expected a string, got a number");
+
+        assert_eq!(target, json!({ "life": 3, "name": "Quote" }));
+    }
+
+    #[test]
+    fn test_override_errors_name_key() {
+        let mut constants = EngineConstants::defaults();
+        let mut value = constants.overridable_json().unwrap();
+
+        EngineConstants::merge_json(&mut value, json!({ "game": { "intro_stage": 70000 } }), "").unwrap();
+        let err = constants.set_overridable_json(value).unwrap_err();
+        assert!(err.starts_with("game.intro_stage: "), "{}", err);
+    }
+
+    #[test]
+    fn test_json_schema_of() {
+        let schema = EngineConstants::json_schema_of(&json!({
+            "name": "Quote",
+            "enabled": true,
+            "speed": 1.5,
+            "rects": [{ "left": 0, "top": 8 }],
+            "empty": [],
+        }));
+
+        let rect = json!({
+            "type": "object",
+            "properties": { "left": { "type": "integer" }, "top": { "type": "integer" } },
+            "additionalProperties": false,
+        });
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "enabled": { "type": "boolean" },
+                    "speed": { "type": "number" },
+                    "rects": {
+                        "anyOf": [
+                            { "type": "array", "items": rect },
+                            {
+                                "type": "object",
+                                "patternProperties": { "^[0-9]+$": rect },
+                                "additionalProperties": false,
+                            },
+                        ]
+                    },
+                    "empty": {
+                        "anyOf": [
+                            { "type": "array", "items": {} },
+                            {
+                                "type": "object",
+                                "patternProperties": { "^[0-9]+$": {} },
+                                "additionalProperties": false,
+                            },
+                        ]
+                    },
+                },
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_schema_covers_overridable_sections() {
+        let constants = EngineConstants::defaults();
+        let schema = constants.json_schema().unwrap();
+
+        for section in ["game", "player", "booster", "caret", "world", "npc", "weapon", "textscript", "title"] {
+            assert_eq!(schema["properties"][section]["type"], "object", "{}", section);
+        }
+        assert_eq!(schema["properties"]["game"]["properties"]["intro_stage"]["type"], "integer");
+        assert_eq!(schema["properties"]["$schema"]["type"], "string");
+    }
+
+    fn pt_br_constants() -> (EngineConstants, Settings) {
+        let mut constants = EngineConstants::defaults();
+        for code in ["en", "pt", "pt-BR"] {
+            let mut locale = Locale::default();
+            locale.code = code.to_owned();
+            constants.locales.push(locale);
+        }
+
+        let settings = Settings { locale: "pt-BR".to_owned(), ..Settings::default() };
+
+        (constants, settings)
+    }
+
+    #[test]
+    fn test_rebuild_path_list_locale_layering() {
+        let (mut constants, settings) = pt_br_constants();
+        constants.rebuild_path_list(&["/a/".to_owned(), "/b/".to_owned()], Season::None, &settings);
+
+        // the last mod goes on top, the more specific locale on top of the less specific one in every layer
+        assert_eq!(
+            constants.base_paths,
+            vec![
+                "/b/pt-BR/",
+                "/b/pt/",
+                "/b/",
+                "/a/pt-BR/",
+                "/a/pt/",
+                "/a/",
+                "/pt-BR/",
+                "/pt/",
+                "/builtin/builtin_data/",
+                "/",
+            ]
+        );
+        assert_eq!(constants.locale_paths, vec!["/b/pt-BR/", "/b/pt/", "/a/pt-BR/", "/a/pt/", "/pt-BR/", "/pt/"]);
+
+        constants.is_cs_plus = true;
+        constants.rebuild_path_list(&[], Season::None, &settings);
+        assert_eq!(constants.locale_paths, vec!["/base/pt-BR/", "/base/pt/"]);
+        assert_eq!(constants.base_paths[..3], ["/base/pt-BR/", "/base/pt/", "/base/"]);
+    }
+
+    #[test]
+    fn test_text_script_encoding() {
+        let root = std::env::temp_dir().join(format!("doukutsu-rs-encoding-test-{}", std::process::id()));
+        for file in ["Head.tsc", "pt/Head.tsc", "ArmsItem.tsc", "pt/Credit.tsc", "a/Credit.tsc", "a/pt-BR/Stage.tsc"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+
+        let mut ctx = Context::new();
+        filesystem::mount_vfs(&mut ctx, Box::new(crate::framework::vfs::PhysicalFS::new(&root, true)));
+
+        let (mut constants, settings) = pt_br_constants();
+        constants.textscript.encoding = TextScriptEncoding::ShiftJIS;
+        constants.locale_encoding = TextScriptEncoding::UTF8;
+        constants.rebuild_path_list(&["/a/".to_owned()], Season::None, &settings);
+
+        let encodings: Vec<TextScriptEncoding> = ["Head.tsc", "ArmsItem.tsc", "Credit.tsc", "Stage.tsc", "Missing.tsc"]
+            .iter()
+            .map(|path| constants.text_script_encoding(&ctx, path))
+            .collect();
+        let _ = std::fs::remove_dir_all(&root);
+
+        // untranslated files of a mod take precedence over the translation of the base game
+        assert_eq!(
+            encodings,
+            vec![
+                TextScriptEncoding::UTF8,
+                TextScriptEncoding::ShiftJIS,
+                TextScriptEncoding::ShiftJIS,
+                TextScriptEncoding::UTF8,
+                TextScriptEncoding::ShiftJIS,
+            ]
+        );
+    }
+}
@@ -55,6 +55,8 @@ impl Backend for GlutinBackend {
     }
 }
 
+const DEFAULT_WINDOW_TITLE: &str = "doukutsu-rs";
+
 pub struct GlutinEventLoop {
     refs: Rc<UnsafeCell<Option<WindowedContext<PossiblyCurrent>>>>,
 }
@@ -83,7 +85,7 @@ impl GlutinEventLoop {
                 window = window.with_drag_and_drop(false);
             }
 
-            window = window.with_title("doukutsu-rs");
+            window = window.with_title(DEFAULT_WINDOW_TITLE);
             
             #[cfg(not(any(target_os = "windows", target_os = "android", target_os = "horizon")))]
             {
@@ -187,6 +189,9 @@ impl BackendEventLoop for GlutinEventLoop {
         let (game, ctx): (&'static mut Game, &'static mut Context) =
             unsafe { (std::mem::transmute(game), std::mem::transmute(ctx)) };
 
+        #[cfg(not(any(target_os = "android", target_os = "horizon")))]
+        let mut window_title = String::new();
+
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Wait;
 
@@ -331,6 +336,13 @@ impl BackendEventLoop for GlutinEventLoop {
                             window.window().set_fullscreen(fullscreen_type);
                             window.window().set_cursor_visible(cursor_visible);
                         }
+
+                        if state_ref.constants.game.window_title != window_title {
+                            window_title = state_ref.constants.game.window_title.clone();
+                            let shown = if window_title.is_empty() { DEFAULT_WINDOW_TITLE } else { &window_title };
+
+                            window.window().set_title(shown);
+                        }
                     }
 
                     game.update(ctx).unwrap();
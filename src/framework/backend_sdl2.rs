@@ -155,9 +155,12 @@ struct SDL2Context {
     gl_context: Option<sdl2::video::GLContext>,
     blend_mode: sdl2::render::BlendMode,
     fullscreen_type: sdl2::video::FullscreenType,
+    window_title: String,
     game_controller: GameControllerSubsystem,
 }
 
+const DEFAULT_WINDOW_TITLE: &str = "Cave Story (doukutsu-rs)";
+
 impl SDL2EventLoop {
     pub fn new(sdl: &Sdl, size_hint: (u16, u16), ctx: &Context) -> GameResult<Box<dyn BackendEventLoop>> {
         let event_pump = sdl.event_pump().map_err(GameError::WindowError)?;
@@ -172,7 +175,7 @@ impl SDL2EventLoop {
         gl_attr.set_context_profile(GLProfile::Compatibility);
         gl_attr.set_context_version(2, 1);
 
-        let mut win_builder = video.window("Cave Story (doukutsu-rs)", size_hint.0 as _, size_hint.1 as _);
+        let mut win_builder = video.window(DEFAULT_WINDOW_TITLE, size_hint.0 as _, size_hint.1 as _);
         win_builder.position_centered();
         win_builder.resizable();
 
@@ -203,6 +206,7 @@ impl SDL2EventLoop {
                 gl_context: None,
                 blend_mode: sdl2::render::BlendMode::Blend,
                 fullscreen_type: sdl2::video::FullscreenType::Off,
+                window_title: String::new(),
                 game_controller,
             })),
             opengl_available: RefCell::new(opengl_available),
@@ -407,6 +411,15 @@ impl BackendEventLoop for SDL2EventLoop {
 
                     refs.fullscreen_type = fullscreen_type;
                 }
+
+                if state.constants.game.window_title != self.refs.borrow().window_title {
+                    let mut refs = self.refs.borrow_mut();
+                    let title = state.constants.game.window_title.clone();
+                    let shown = if title.is_empty() { DEFAULT_WINDOW_TITLE } else { title.as_str() };
+
+                    let _ = refs.window.window_mut().set_title(shown);
+                    refs.window_title = title;
+                }
             }
 
             game.update(ctx).unwrap();
@@ -3,6 +3,7 @@ use std::io;
 use std::io::SeekFrom;
 use std::path;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 use crate::framework::context::Context;
 use crate::framework::error::{GameError, GameResult};
@@ -143,6 +144,16 @@ impl Filesystem {
         self.vfs.metadata(path.as_ref()).map(|m| m.is_file()).unwrap_or(false)
     }
 
+    /// Returns the time of the last modification of a file, if it's known.
+    pub(crate) fn user_modified<P: AsRef<path::Path>>(&self, path: P) -> Option<SystemTime> {
+        self.user_vfs.metadata(path.as_ref()).ok().and_then(|m| m.modified())
+    }
+
+    /// Returns the time of the last modification of a file, if it's known.
+    pub(crate) fn modified<P: AsRef<path::Path>>(&self, path: P) -> Option<SystemTime> {
+        self.vfs.metadata(path.as_ref()).ok().and_then(|m| m.modified())
+    }
+
     /// Check whether a path points at a directory.
     pub(crate) fn user_is_dir<P: AsRef<path::Path>>(&self, path: P) -> bool {
         self.user_vfs.metadata(path.as_ref()).map(|m| m.is_dir()).unwrap_or(false)
@@ -304,6 +315,11 @@ pub fn user_is_dir<P: AsRef<path::Path>>(ctx: &Context, path: P) -> bool {
     ctx.filesystem.user_is_dir(path)
 }
 
+/// Returns the time of the last modification of a file in the user directory, if it's known.
+pub fn user_modified<P: AsRef<path::Path>>(ctx: &Context, path: P) -> Option<SystemTime> {
+    ctx.filesystem.user_modified(path)
+}
+
 /// Returns a list of all files and directories in the user directory,
 /// in no particular order.
 ///
@@ -343,6 +359,11 @@ pub fn is_dir<P: AsRef<path::Path>>(ctx: &Context, path: P) -> bool {
     ctx.filesystem.is_dir(path)
 }
 
+/// Returns the time of the last modification of a file in the resource directory, if it's known.
+pub fn modified<P: AsRef<path::Path>>(ctx: &Context, path: P) -> Option<SystemTime> {
+    ctx.filesystem.modified(path)
+}
+
 /// Returns a list of all files and directories in the resource directory,
 /// in no particular order.
 ///
@@ -9,12 +9,14 @@
 //! as a trait object, and its path abstraction is not the most
 //! convenient.
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::ffi::OsStr;
 use std::fmt::{self, Debug};
 use std::fs;
-use std::io::{Read, Seek, Write};
+use std::io::{self, Read, Seek, Write};
 use std::path::{self, Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
 use crate::framework::error::{GameError, GameResult};
 
@@ -145,6 +147,10 @@ pub trait VMetadata {
     /// Returns the length of the thing.  If it is a directory,
     /// the result of this is undefined/platform dependent.
     fn len(&self) -> u64;
+    /// Returns the time of the last modification, if the filesystem keeps track of it.
+    fn modified(&self) -> Option<SystemTime> {
+        None
+    }
 }
 
 /// A VFS that points to a directory and uses it as the root of its
@@ -173,6 +179,9 @@ impl VMetadata for PhysicalMetadata {
     fn len(&self) -> u64 {
         self.0.len()
     }
+    fn modified(&self) -> Option<SystemTime> {
+        self.0.modified().ok()
+    }
 }
 
 /// This takes an absolute path and returns either a sanitized relative
@@ -412,6 +421,209 @@ impl VFS for PhysicalFS {
     }
 }
 
+/// A read-only VFS backed by a zip archive, with its contents appearing under given mount point.
+///
+/// Paths are looked up case insensitively, same as with `PhysicalFS::new_lowercase`.
+/// If all entries of the archive are inside a single top-level directory, it's used as the root.
+pub struct ZipFS {
+    source: String,
+    mount_point: PathBuf,
+    archive: Mutex<zip::ZipArchive<Box<dyn VFile>>>,
+    /// Lowercase paths relative to the mount point, mapped to indices of file entries.
+    /// Directories have no entry index.
+    index: HashMap<String, Option<usize>>,
+    /// Names of the directory contents, by lowercase path of the directory.
+    children: HashMap<String, Vec<String>>,
+}
+
+impl ZipFS {
+    /// Creates a new ZipFS reading the archive from given file, `source` is only used for debugging.
+    pub fn new(file: Box<dyn VFile>, source: &str, mount_point: &Path) -> GameResult<Self> {
+        let mut archive = zip::ZipArchive::new(file)
+            .map_err(|e| GameError::FilesystemError(format!("Failed to open zip archive {}: {}", source, e)))?;
+
+        let mut names = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let entry = archive
+                .by_index(i)
+                .map_err(|e| GameError::FilesystemError(format!("Failed to read zip archive {}: {}", source, e)))?;
+            let components: Vec<String> =
+                entry.name().split(|c| c == '/' || c == '\\').filter(|c| !c.is_empty()).map(str::to_owned).collect();
+
+            if !components.is_empty() {
+                names.push((i, components, entry.is_dir()));
+            }
+        }
+
+        // strip the top-level directory, zipped mod folders are usually packed that way
+        let top_level = names.first().map(|(_, components, _)| components[0].to_lowercase());
+        if let Some(top_level) = top_level {
+            let single_dir = names.iter().all(|(_, components, is_dir)| {
+                components[0].to_lowercase() == top_level && (components.len() > 1 || *is_dir)
+            });
+
+            if single_dir {
+                names = names
+                    .into_iter()
+                    .filter(|(_, c, _)| c.len() > 1)
+                    .map(|(i, c, d)| (i, c[1..].to_vec(), d))
+                    .collect();
+            }
+        }
+
+        let mut index = HashMap::new();
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        index.insert(String::new(), None);
+
+        for (i, components, is_dir) in names {
+            let mut parent = String::new();
+
+            for (depth, component) in components.iter().enumerate() {
+                let lowercase = component.to_lowercase();
+                let key = if parent.is_empty() { lowercase } else { format!("{}/{}", parent, lowercase) };
+                let is_file = depth == components.len() - 1 && !is_dir;
+
+                if !index.contains_key(&key) {
+                    index.insert(key.clone(), if is_file { Some(i) } else { None });
+                    children.entry(parent.clone()).or_default().push(component.clone());
+                }
+
+                parent = key;
+            }
+        }
+
+        Ok(Self {
+            source: source.to_owned(),
+            mount_point: mount_point.to_path_buf(),
+            archive: Mutex::new(archive),
+            index,
+            children,
+        })
+    }
+
+    /// Returns the index key of given path, if it's inside the mount point.
+    fn to_key(&self, path: &Path) -> Option<String> {
+        let path = sanitize_path(path)?;
+        let mount_point = sanitize_path(&self.mount_point)?;
+
+        let mut path_components = path.components();
+        for component in mount_point.components() {
+            let path_component = path_components.next()?;
+            if !path_component.as_os_str().eq_ignore_ascii_case(component.as_os_str()) {
+                return None;
+            }
+        }
+
+        let components: Vec<String> = path_components.map(|c| c.as_os_str().to_string_lossy().to_lowercase()).collect();
+
+        Some(components.join("/"))
+    }
+
+    fn get_entry(&self, path: &Path) -> GameResult<Option<usize>> {
+        match self.to_key(path).and_then(|key| self.index.get(&key)) {
+            Some(entry) => Ok(*entry),
+            None => Err(GameError::FilesystemError(format!("File not found in {}: {:?}", self.source, path))),
+        }
+    }
+}
+
+impl Debug for ZipFS {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "<ZipFS source: {} mount point: {}>", self.source, self.mount_point.display())
+    }
+}
+
+struct ZipMetadata {
+    is_dir: bool,
+    size: u64,
+}
+
+impl VMetadata for ZipMetadata {
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    fn len(&self) -> u64 {
+        self.size
+    }
+}
+
+impl VFS for ZipFS {
+    /// Open the file at this path with the given options, the file is decompressed to memory.
+    fn open_options(&self, path: &Path, open_options: OpenOptions) -> GameResult<Box<dyn VFile>> {
+        if open_options.write || open_options.create || open_options.append || open_options.truncate {
+            let msg = format!("Cannot alter file {:?} in root {:?}, filesystem read-only", path, self);
+            return Err(GameError::FilesystemError(msg));
+        }
+
+        let entry = match self.get_entry(path)? {
+            Some(entry) => entry,
+            None => return Err(GameError::FilesystemError(format!("{:?} is a directory.", path))),
+        };
+
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive.by_index(entry).map_err(|e| {
+            GameError::FilesystemError(format!("Failed to read {:?} from {}: {}", path, self.source, e))
+        })?;
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+
+        Ok(Box::new(io::Cursor::new(data)))
+    }
+
+    fn mkdir(&self, _path: &Path) -> GameResult {
+        Err(GameError::FilesystemError("Tried to make directory {} but FS is read-only".to_string()))
+    }
+
+    fn rm(&self, _path: &Path) -> GameResult {
+        Err(GameError::FilesystemError("Tried to remove file {} but FS is read-only".to_string()))
+    }
+
+    fn rmrf(&self, _path: &Path) -> GameResult {
+        Err(GameError::FilesystemError("Tried to remove file/dir {} but FS is read-only".to_string()))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.get_entry(path).is_ok()
+    }
+
+    fn metadata(&self, path: &Path) -> GameResult<Box<dyn VMetadata>> {
+        match self.get_entry(path)? {
+            Some(entry) => {
+                let mut archive = self.archive.lock().unwrap();
+                let size = archive.by_index(entry).map(|file| file.size()).unwrap_or(0);
+
+                Ok(Box::new(ZipMetadata { is_dir: false, size }))
+            }
+            None => Ok(Box::new(ZipMetadata { is_dir: true, size: 0 })),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> GameResult<Box<dyn Iterator<Item = GameResult<PathBuf>>>> {
+        if self.get_entry(path)?.is_some() {
+            return Err(GameError::FilesystemError(format!("Expected a directory, found a file: {:?}", path)));
+        }
+
+        let key = self.to_key(path).unwrap_or_default();
+        let entries: Vec<GameResult<PathBuf>> = self
+            .children
+            .get(&key)
+            .map(|names| names.iter().map(|name| Ok(path.join(name))).collect())
+            .unwrap_or_default();
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn to_path_buf(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
 /// A structure that joins several VFS's together in order.
 #[derive(Debug)]
 pub struct OverlayFS {
@@ -686,5 +898,36 @@ mod tests {
         assert!(!fs.exists(testdir));
     }
 
+    #[test]
+    fn headless_test_zip() {
+        let mut buf = io::Cursor::new(Vec::new());
+        {
+            let mut zip = zip::ZipWriter::new(&mut buf);
+            let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            zip.start_file("MyMod/mod.txt", options).unwrap();
+            zip.write_all(b"Test").unwrap();
+            zip.start_file("MyMod/Stage/Cave.pxm", options).unwrap();
+            zip.finish().unwrap();
+        }
+        buf.set_position(0);
+
+        let fs = ZipFS::new(Box::new(buf), "test.zip", Path::new("/mymod/")).unwrap();
+
+        // the top-level directory is stripped and lookups are case insensitive
+        assert!(fs.exists(Path::new("/mymod/mod.txt")));
+        assert!(fs.exists(Path::new("/MyMod/stage/cave.PXM")));
+        assert!(!fs.exists(Path::new("/mod.txt")));
+        assert!(!fs.exists(Path::new("/mymod/MyMod/mod.txt")));
+
+        let mut s = String::new();
+        fs.open(Path::new("/mymod/MOD.TXT")).unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(s, "Test");
+
+        assert!(fs.metadata(Path::new("/mymod/stage")).unwrap().is_dir());
+        assert_eq!(fs.metadata(Path::new("/mymod/mod.txt")).unwrap().len(), 4);
+        assert_eq!(fs.read_dir(Path::new("/mymod/")).unwrap().count(), 2);
+        assert!(fs.create(Path::new("/mymod/new.txt")).is_err());
+    }
+
     // BUGGO: TODO: Make sure all functions are tested for OverlayFS and ZipFS!!
 }
@@ -6,7 +6,7 @@ use crate::{
         context::Context,
         error::GameResult,
         filesystem::{mount_user_vfs, mount_vfs, unmount_user_vfs},
-        vfs::PhysicalFS,
+        vfs::{PhysicalFS, ZipFS},
     },
 };
 
@@ -72,6 +72,19 @@ impl FilesystemContainer {
         {
             mount_vfs(context, Box::new(PhysicalFS::new(&resource_dir, true)));
             self.game_path = resource_dir.clone();
+
+            // data.zip next to the data directory, files extracted to the directory take precedence
+            let archive_path = resource_dir.with_extension("zip");
+            if archive_path.is_file() {
+                let file = std::fs::File::open(&archive_path)?;
+                match ZipFS::new(Box::new(file), &archive_path.to_string_lossy(), &PathBuf::from("/")) {
+                    Ok(zip_fs) => {
+                        log::info!("Mounting data archive: {:?}", archive_path);
+                        mount_vfs(context, Box::new(zip_fs));
+                    }
+                    Err(e) => log::error!("Failed to mount data archive: {}", e),
+                }
+            }
         }
 
         #[cfg(not(any(target_os = "android", target_os = "horizon")))]
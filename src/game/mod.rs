@@ -3,10 +3,12 @@ use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
+use downcast::Downcast;
 use lazy_static::lazy_static;
 
 use scripting::tsc::text_script::ScriptMode;
 
+use crate::engine_constants::CONSTANTS_DUMP_PATH;
 use crate::framework::context::Context;
 use crate::framework::error::GameResult;
 use crate::framework::graphics;
@@ -15,6 +17,9 @@ use crate::framework::ui::UI;
 use crate::game::filesystem_container::FilesystemContainer;
 use crate::game::shared_game_state::{Fps, SharedGameState, TimingMode};
 use crate::graphics::texture_set::{G_MAG, I_MAG};
+use crate::live_debugger::remote_control::RemoteControl;
+pub use crate::live_debugger::remote_control::DEFAULT_REMOTE_CONTROL_PORT;
+use crate::scene::game_scene::GameScene;
 use crate::scene::loading_scene::LoadingScene;
 use crate::scene::Scene;
 
@@ -36,6 +41,12 @@ pub mod weapon;
 pub struct LaunchOptions {
     pub server_mode: bool,
     pub editor: bool,
+    /// Port of the local remote control endpoint, disabled if `None`.
+    pub remote_control: Option<u16>,
+    /// Write the engine constants and their JSON Schema to the user directory and exit.
+    pub dump_constants: bool,
+    /// Print a locale template with the translation keys used in the given source directory and exit.
+    pub extract_locale_keys: Option<PathBuf>,
 }
 
 lazy_static! {
@@ -119,6 +130,13 @@ impl Game {
                     scene.tick(state_ref, ctx)?;
                 }
             }
+
+            // ticked outside of scenes so tools can still talk to the game in menus
+            if let Some(mut remote_control) = state_ref.remote_control.take() {
+                let game_scene: Option<&mut GameScene> = scene.downcast_mut().ok();
+                remote_control.tick(state_ref, game_scene, ctx);
+                state_ref.remote_control = Some(remote_control);
+            }
         }
         Ok(())
     }
@@ -292,6 +310,11 @@ fn init_logger() -> GameResult {
 
 pub fn init(options: LaunchOptions) -> GameResult {
     let _ = init_logger();
+
+    if let Some(src_dir) = &options.extract_locale_keys {
+        println!("{}", crate::i18n::extract_template(src_dir)?);
+        return Ok(());
+    }
     
     let mut context = Box::pin(Context::new());
 
@@ -303,14 +326,45 @@ pub fn init(options: LaunchOptions) -> GameResult {
         context.headless = true;
     }
 
+    if options.dump_constants {
+        context.headless = true;
+    }
+
     let mut game = Box::pin(Game::new(&mut context)?);
     #[cfg(feature = "scripting-lua")]
     unsafe {
         (*game.state.get()).lua.update_refs(&mut *game.state.get(), &mut *context);
     }
+    #[cfg(feature = "scripting-wasm")]
+    unsafe {
+        (*game.state.get()).wasm.update_refs(&mut *game.state.get(), &mut *context);
+    }
+
+    if options.dump_constants {
+        let state = game.state.get_mut();
+        state.reload_resources(&mut context)?;
+        state.constants.dump_overridable_json(&context)?;
+
+        let sections = state.constants.overridable_json()?;
+        let sections: Vec<&str> = sections.as_object().map_or(Vec::new(), |s| s.keys().map(|k| k.as_str()).collect());
+        println!(
+            "Engine constants overridable by constants.json ({}) have been written to {:?}.",
+            sections.join(", "),
+            fs_container.user_path.join(CONSTANTS_DUMP_PATH.trim_start_matches('/'))
+        );
+        println!("Other constants, such as CS+ and Switch specific tables, can't be overridden and aren't included.");
+        return Ok(());
+    }
 
     game.state.get_mut().fs_container = Some(fs_container);
 
+    if let Some(port) = options.remote_control {
+        match RemoteControl::bind(port) {
+            Ok(remote_control) => game.state.get_mut().remote_control = Some(remote_control),
+            Err(err) => log::error!("Failed to start remote control on port {}: {}", port, err),
+        }
+    }
+
     #[cfg(feature = "discord-rpc")]
     if game.state.get_mut().settings.discord_rpc {
         game.state.get_mut().discord_rpc.enabled = true;
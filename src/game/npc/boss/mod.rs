@@ -86,17 +86,24 @@ impl GameEntity<([&mut Player; 2], &NPCList, &mut Stage, &BulletManager, &mut Fl
             return Ok(());
         }
 
-        match self.boss_type {
-            1 => self.tick_b01_omega(state, players, npc_list, bullet_manager, flash),
-            2 => self.tick_b02_balfrog(state, players, npc_list),
-            3 => self.tick_b03_monster_x(state, players, npc_list, flash),
-            4 => self.tick_b04_core(state, players, npc_list, stage),
-            5 => self.tick_b05_ironhead(state, players, npc_list),
-            6 => self.tick_b06_sisters(state, players, npc_list, flash),
-            7 => self.tick_b07_undead_core(state, npc_list, stage, flash),
-            8 => self.tick_b08_heavy_press(state, npc_list, stage),
-            9 => self.tick_b09_ballos(state, players, npc_list, flash),
-            _ => {}
+        #[cfg(feature = "scripting-lua")]
+        let boss_hook_ran = state.lua.try_run_boss_hook(self.boss_type, self.parts.len());
+        #[cfg(not(feature = "scripting-lua"))]
+        let boss_hook_ran = false;
+
+        if !boss_hook_ran {
+            match self.boss_type {
+                1 => self.tick_b01_omega(state, players, npc_list, bullet_manager, flash),
+                2 => self.tick_b02_balfrog(state, players, npc_list),
+                3 => self.tick_b03_monster_x(state, players, npc_list, flash),
+                4 => self.tick_b04_core(state, players, npc_list, stage),
+                5 => self.tick_b05_ironhead(state, players, npc_list),
+                6 => self.tick_b06_sisters(state, players, npc_list, flash),
+                7 => self.tick_b07_undead_core(state, npc_list, stage, flash),
+                8 => self.tick_b08_heavy_press(state, npc_list, stage),
+                9 => self.tick_b09_ballos(state, players, npc_list, flash),
+                _ => {}
+            }
         }
 
         for part in &mut self.parts {
@@ -1,4 +1,4 @@
-use std::cell::{Cell, UnsafeCell};
+use std::cell::{Cell, RefCell, UnsafeCell};
 use std::mem::{MaybeUninit, transmute};
 
 use crate::framework::error::{GameError, GameResult};
@@ -15,6 +15,8 @@ pub struct NPCList {
     npcs: Box<UnsafeCell<[NPC; NPC_LIST_MAX_CAP]>>,
     max_npc: Cell<u16>,
     seed: i32,
+    /// IDs of NPCs spawned since last call to `take_spawned`.
+    spawned: RefCell<Vec<u16>>,
 }
 
 #[allow(dead_code)]
@@ -33,6 +35,7 @@ impl NPCList {
             })),
             max_npc: Cell::new(0),
             seed: 0,
+            spawned: RefCell::new(Vec::new()),
         };
 
         unsafe {
@@ -74,6 +77,8 @@ impl NPCList {
                     self.max_npc.replace(id + 1);
                 }
 
+                self.spawned.borrow_mut().push(id);
+
                 return Ok(());
             }
         }
@@ -106,9 +111,16 @@ impl NPCList {
             self.max_npc.replace(id + 1);
         }
 
+        self.spawned.borrow_mut().push(id);
+
         Ok(())
     }
 
+    /// Returns IDs of NPCs spawned since the last call.
+    pub fn take_spawned(&self) -> Vec<u16> {
+        std::mem::take(&mut *self.spawned.borrow_mut())
+    }
+
     /// Returns a mutable reference to NPC from this list.
     pub fn get_npc<'a: 'b, 'b>(&'a self, id: usize) -> Option<&'b mut NPC> {
         unsafe { self.npcs_mut().get_mut(id) }
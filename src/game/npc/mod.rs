@@ -248,6 +248,10 @@ impl GameEntity<([&mut Player; 2], &NPCList, &mut Stage, &mut BulletManager, &mu
             {
                 npc_hook_ran = state.lua.try_run_npc_hook(self.id, self.npc_type);
             }
+        #[cfg(feature = "scripting-wasm")]
+        if !npc_hook_ran {
+            npc_hook_ran = state.wasm.try_run_npc_hook(self.id, self.npc_type);
+        }
 
         match self.npc_type {
             _ if npc_hook_ran => Ok(()),
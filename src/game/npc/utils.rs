@@ -8,6 +8,7 @@ use crate::game::map::NPCData;
 use crate::game::npc::{NPC, NPCFlag, NPCLayer, NPCTable};
 use crate::game::npc::list::NPCList;
 use crate::game::player::Player;
+use crate::game::scripting::events::ScriptEvent;
 use crate::game::shared_game_state::{SharedGameState, TileSize};
 use crate::game::weapon::bullet::Bullet;
 use crate::util::rng::{RNG, Xoroshiro32PlusPlus};
@@ -283,6 +284,8 @@ impl NPCList {
     /// Called once NPC is killed, creates smoke and drops.
     pub fn kill_npc(&self, id: usize, vanish: bool, can_drop_missile: bool, state: &mut SharedGameState) {
         if let Some(npc) = self.get_npc(id) {
+            state.script_events.push(ScriptEvent::NPCKilled { npc_id: npc.id, npc_type: npc.npc_type });
+
             if let Some(table_entry) = state.npc_table.get_entry(npc.npc_type) {
                 state.sound_manager.play_sfx(table_entry.death_sound);
             }
@@ -14,6 +14,7 @@ use crate::game::npc::list::NPCList;
 use crate::game::npc::NPC;
 use crate::game::player::skin::basic::BasicPlayerSkin;
 use crate::game::player::skin::{PlayerAnimationState, PlayerAppearanceState, PlayerSkin};
+use crate::game::scripting::events::ScriptEvent;
 use crate::game::shared_game_state::SharedGameState;
 use crate::input::dummy_player_controller::DummyPlayerController;
 use crate::input::player_controller::PlayerController;
@@ -22,7 +23,7 @@ use crate::util::rng::RNG;
 mod player_hit;
 pub mod skin;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, serde::Serialize, serde::Deserialize)]
 #[repr(u8)]
 pub enum ControlMode {
     Normal = 0,
@@ -122,6 +123,8 @@ pub struct Player {
     dog_stack: Vec<DogStack>,
     pub has_dog: bool,
     pub teleport_counter: u16,
+    /// Which player this is, reported to scripts.
+    pub target_player: TargetPlayer,
 }
 
 impl Player {
@@ -176,6 +179,7 @@ impl Player {
             dog_stack: Vec::new(),
             has_dog: false,
             teleport_counter: 0,
+            target_player: TargetPlayer::Player1,
         }
     }
 
@@ -896,7 +900,11 @@ impl Player {
         self.damage_popup.add_value(-(self.damage as i16));
         self.damage_popup.update_displayed_value();
 
+        let player_id = self.target_player.index() as u8;
+        state.script_events.push(ScriptEvent::PlayerDamaged { player_id, damage: final_hp as u16 });
+
         if self.life == 0 {
+            state.script_events.push(ScriptEvent::PlayerDied { player_id });
             state.sound_manager.play_sfx(17);
             self.cond.0 = 0;
             state.control_flags.set_tick_world(true);
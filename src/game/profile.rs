@@ -8,6 +8,7 @@ use crate::framework::context::Context;
 use crate::framework::error::GameError::ResourceLoadError;
 use crate::framework::error::GameResult;
 use crate::game::player::{ControlMode, TargetPlayer};
+use crate::game::scripting::storage::ScriptStorage;
 use crate::game::shared_game_state::{GameDifficulty, SharedGameState};
 use crate::game::weapon::{WeaponLevel, WeaponType};
 use crate::scene::game_scene::GameScene;
@@ -132,6 +133,10 @@ impl GameProfile {
             }
         }
 
+        if let Some(save_path) = state.get_save_filename(state.save_slot) {
+            state.script_storage = ScriptStorage::load(ctx, &save_path);
+        }
+
         state.textscript_vm.start_script(0);
 
         game_scene.player1.equip.0 = self.equipment as u16;
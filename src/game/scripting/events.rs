@@ -0,0 +1,80 @@
+/// Game events queued during a tick and dispatched to scripts at the end of it.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ScriptEvent {
+    StageEnter { stage_id: u16 },
+    StageLeave { stage_id: u16 },
+    EventStart { event_num: u16 },
+    EventEnd { event_num: u16 },
+    PlayerDamaged { player_id: u8, damage: u16 },
+    PlayerDied { player_id: u8 },
+    NPCSpawned { npc_id: u16, npc_type: u16 },
+    NPCKilled { npc_id: u16, npc_type: u16 },
+    ItemAcquired { item_id: u16 },
+    WeaponAcquired { weapon_id: u16, ammo: u16 },
+    BulletFired { player_id: u8, bullet_type: u16 },
+    BossDefeated { boss_type: u16 },
+    Save { slot: usize },
+    Load { slot: usize },
+}
+
+impl ScriptEvent {
+    /// Name of the event as used by `doukutsu.on`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptEvent::StageEnter { .. } => "stageEnter",
+            ScriptEvent::StageLeave { .. } => "stageLeave",
+            ScriptEvent::EventStart { .. } => "eventStart",
+            ScriptEvent::EventEnd { .. } => "eventEnd",
+            ScriptEvent::PlayerDamaged { .. } => "playerDamaged",
+            ScriptEvent::PlayerDied { .. } => "playerDied",
+            ScriptEvent::NPCSpawned { .. } => "npcSpawned",
+            ScriptEvent::NPCKilled { .. } => "npcKilled",
+            ScriptEvent::ItemAcquired { .. } => "itemAcquired",
+            ScriptEvent::WeaponAcquired { .. } => "weaponAcquired",
+            ScriptEvent::BulletFired { .. } => "bulletFired",
+            ScriptEvent::BossDefeated { .. } => "bossDefeated",
+            ScriptEvent::Save { .. } => "save",
+            ScriptEvent::Load { .. } => "load",
+        }
+    }
+
+    /// Arguments passed to the handlers, in order.
+    pub fn args(&self) -> Vec<i32> {
+        match *self {
+            ScriptEvent::StageEnter { stage_id } | ScriptEvent::StageLeave { stage_id } => vec![stage_id as i32],
+            ScriptEvent::EventStart { event_num } | ScriptEvent::EventEnd { event_num } => vec![event_num as i32],
+            ScriptEvent::PlayerDamaged { player_id, damage } => vec![player_id as i32, damage as i32],
+            ScriptEvent::PlayerDied { player_id } => vec![player_id as i32],
+            ScriptEvent::NPCSpawned { npc_id, npc_type } | ScriptEvent::NPCKilled { npc_id, npc_type } => {
+                vec![npc_id as i32, npc_type as i32]
+            }
+            ScriptEvent::ItemAcquired { item_id } => vec![item_id as i32],
+            ScriptEvent::WeaponAcquired { weapon_id, ammo } => vec![weapon_id as i32, ammo as i32],
+            ScriptEvent::BulletFired { player_id, bullet_type } => vec![player_id as i32, bullet_type as i32],
+            ScriptEvent::BossDefeated { boss_type } => vec![boss_type as i32],
+            ScriptEvent::Save { slot } | ScriptEvent::Load { slot } => vec![slot as i32],
+        }
+    }
+}
+
+/// Points of `GameScene::draw` at which scripts are given a chance to render.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DrawLayer {
+    /// After the background, behind all tiles.
+    Background,
+    /// After NPCs, bullets and players, behind foreground tiles.
+    Entities,
+    /// On top of the HUD and inventory.
+    HUD,
+}
+
+impl DrawLayer {
+    /// Name of the layer as passed to `draw` handlers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DrawLayer::Background => "background",
+            DrawLayer::Entities => "entities",
+            DrawLayer::HUD => "hud",
+        }
+    }
+}
@@ -1,40 +1,14 @@
--- sandboxing (still tbd)
---_ENV = {
---    ipairs = ipairs,
---    next = next,
---    pairs = pairs,
---    pcall = pcall,
---    tonumber = tonumber,
---    tostring = tostring,
---    type = type,
---    unpack = unpack,
---    coroutine = { create = coroutine.create, resume = coroutine.resume,
---                  running = coroutine.running, status = coroutine.status,
---                  wrap = coroutine.wrap },
---    string = { byte = string.byte, char = string.char, find = string.find,
---               format = string.format, gmatch = string.gmatch, gsub = string.gsub,
---               len = string.len, lower = string.lower, match = string.match,
---               rep = string.rep, reverse = string.reverse, sub = string.sub,
---               upper = string.upper },
---    table = { insert = table.insert, maxn = table.maxn, remove = table.remove,
---              sort = table.sort },
---    math = { abs = math.abs, acos = math.acos, asin = math.asin,
---             atan = math.atan, atan2 = math.atan2, ceil = math.ceil, cos = math.cos,
---             cosh = math.cosh, deg = math.deg, exp = math.exp, floor = math.floor,
---             fmod = math.fmod, frexp = math.frexp, huge = math.huge,
---             ldexp = math.ldexp, log = math.log, log10 = math.log10, max = math.max,
---             min = math.min, modf = math.modf, pi = math.pi, pow = math.pow,
---             rad = math.rad, random = math.random, sin = math.sin, sinh = math.sinh,
---             sqrt = math.sqrt, tan = math.tan, tanh = math.tanh },
---    os = { clock = os.clock, difftime = os.difftime, time = os.time },
---}
-
 -- __doukutsu_rs is an internal API used meant to be used solely by doukutsu-rs to implement higher-level,
 -- documented APIs and is a subject to change. Do NOT use it or your scripts will break.
 
+local previous_runtime = __doukutsu_rs_runtime_dont_touch
+
 __doukutsu_rs_runtime_dont_touch = {}
 doukutsu = {}
 
+-- environments of loaded scripts by path, kept across hot reloads so they can be passed to onReload
+__doukutsu_rs_runtime_dont_touch._scriptEnvs = previous_runtime ~= nil and previous_runtime._scriptEnvs or {}
+
 ModCS = {
     Flag = {},
     Game = {
@@ -66,29 +40,328 @@ __doukutsu_rs_runtime_dont_touch._known_settings = {
 
 __doukutsu_rs_runtime_dont_touch._requires = {}
 
+-- for compatibility with Lua 5.2+, copy-pasted from Lua mailing list
+-- http://lua-users.org/lists/lua-l/2010-06/msg00313.html
+__doukutsu_rs_runtime_dont_touch._setfenv = setfenv or function(f, t)
+    f = (type(f) == 'function' and f or debug.getinfo(f + 1, 'f').func)
+    local name
+    local up = 0
+    repeat
+        up = up + 1
+        name = debug.getupvalue(f, up)
+    until name == '_ENV' or name == nil
+    if name then
+
+        debug.upvaluejoin(f, up, function()
+            return name
+        end, 1)
+        debug.setupvalue(f, up, t)
+    end
+end
+
+-- count hooks used to enforce the instruction limit aren't called inside of traces compiled by LuaJIT,
+-- so it's turned off for sandboxed code only, including functions defined inside of it
+__doukutsu_rs_runtime_dont_touch._disableJit = function(chunk)
+    if jit ~= nil then
+        jit.off(chunk, true)
+    end
+end
+
+__doukutsu_rs_runtime_dont_touch._require = function(env, modname, sandboxed)
+    local requires = __doukutsu_rs_runtime_dont_touch._requires
+    if requires[env] == nil then
+        requires[env] = {}
+    end
+
+    if requires[env][modname] == nil then
+        local chunk = __doukutsu_rs:loadScript(modname)
+        __doukutsu_rs_runtime_dont_touch._setfenv(chunk, env)
+        if sandboxed then
+            __doukutsu_rs_runtime_dont_touch._disableJit(chunk)
+        end
+
+        requires[env][modname] = { mod = chunk() }
+    end
+
+    return requires[env][modname].mod
+end
+
 require = function(modname)
-    if __doukutsu_rs_runtime_dont_touch._requires[modname] == nil then
-        local mod = __doukutsu_rs:loadScript(modname)
+    return __doukutsu_rs_runtime_dont_touch._require(_G, modname)
+end
 
-        __doukutsu_rs_runtime_dont_touch._requires[modname] = { mod = mod, loaded = True }
-    else
-        return __doukutsu_rs_runtime_dont_touch._requires[modname].mod
+-- errors raised by the engine when a script exceeds its limits, these can't be caught by sandboxed code
+__doukutsu_rs_runtime_dont_touch._limitErrors = {
+    ["instruction limit exceeded"] = true,
+    ["memory limit exceeded"] = true,
+}
+
+__doukutsu_rs_runtime_dont_touch._rethrowLimitError = function(status, ...)
+    if not status and __doukutsu_rs_runtime_dont_touch._limitErrors[...] then
+        error(..., 0)
+    end
+
+    return status, ...
+end
+
+-- the count hook can't see allocations made by a single call, so functions creating large strings check them
+__doukutsu_rs_runtime_dont_touch._checkMemory = function(extra_bytes)
+    local limit = __doukutsu_rs:memoryLimit()
+    if collectgarbage("count") + (extra_bytes or 0) / 1024 > limit then
+        collectgarbage("collect")
+
+        if collectgarbage("count") + (extra_bytes or 0) / 1024 > limit then
+            error("memory limit exceeded", 0)
+        end
     end
 end
 
+__doukutsu_rs_runtime_dont_touch._checkedAllocation = function(...)
+    __doukutsu_rs_runtime_dont_touch._checkMemory()
+
+    return ...
+end
+
+-- string functions available to scripts, the ones which can create large strings check the memory limit first
+__doukutsu_rs_runtime_dont_touch._safeString = {
+    byte = string.byte, find = string.find, gmatch = string.gmatch,
+    len = string.len, lower = string.lower, match = string.match,
+    reverse = string.reverse, sub = string.sub, upper = string.upper,
+    char = function(...)
+        return __doukutsu_rs_runtime_dont_touch._checkedAllocation(string.char(...))
+    end,
+    format = function(...)
+        return __doukutsu_rs_runtime_dont_touch._checkedAllocation(string.format(...))
+    end,
+    gsub = function(...)
+        return __doukutsu_rs_runtime_dont_touch._checkedAllocation(string.gsub(...))
+    end,
+    rep = function(str, count, sep)
+        if type(str) == "string" and type(count) == "number" and count > 0 then
+            local sep_len = type(sep) == "string" and #sep or 0
+            __doukutsu_rs_runtime_dont_touch._checkMemory(#str * count + sep_len * (count - 1))
+        end
+
+        return string.rep(str, count, sep)
+    end,
+}
+
+-- methods of strings are looked up in the metatable shared by all strings, so they'd bypass the checks above
+-- and let scripts modify the string library of everyone else, hence it's pointed at the checked functions
+-- and hidden from getmetatable
+do
+    local string_mt = debug.getmetatable("")
+    string_mt.__index = __doukutsu_rs_runtime_dont_touch._safeString
+    string_mt.__metatable = false
+end
+
+-- proxy which forwards reads to specified table, only the keys set to true in `writable` can be assigned
+__doukutsu_rs_runtime_dont_touch._readOnly = function(tbl, writable)
+    writable = writable or {}
+    local proxies = {}
+
+    return setmetatable({}, {
+        __index = function(self, key)
+            local value = tbl[key]
+            -- tables with metatables are references to game objects, plain ones are namespaces
+            if type(value) == "table" and getmetatable(value) == nil then
+                if proxies[key] == nil then
+                    proxies[key] = __doukutsu_rs_runtime_dont_touch._readOnly(value, writable[key])
+                end
+
+                return proxies[key]
+            end
+
+            return value
+        end,
+        __newindex = function(self, key, value)
+            if writable[key] ~= true then
+                error("attempt to modify read-only field " .. tostring(key), 2)
+            end
+
+            tbl[key] = value
+        end,
+        __metatable = false,
+    })
+end
+
+-- environment of mod scripts, without access to io, os, ffi, debug or loading code from files
+__doukutsu_rs_runtime_dont_touch._createSandbox = function()
+    local rethrow = __doukutsu_rs_runtime_dont_touch._rethrowLimitError
+    local checked = __doukutsu_rs_runtime_dont_touch._checkedAllocation
+
+    -- a copy, so changes made by one script don't affect the others
+    local string_lib = {}
+    for name, func in pairs(__doukutsu_rs_runtime_dont_touch._safeString) do
+        string_lib[name] = func
+    end
+
+    local env = {
+        assert = assert,
+        error = error,
+        getmetatable = function(value)
+            if type(value) == "string" then
+                return nil
+            end
+
+            return getmetatable(value)
+        end,
+        ipairs = ipairs,
+        next = next,
+        pairs = pairs,
+        pcall = function(f, ...)
+            return rethrow(pcall(f, ...))
+        end,
+        print = print,
+        rawequal = rawequal,
+        rawget = rawget,
+        rawset = rawset,
+        select = select,
+        setmetatable = setmetatable,
+        tonumber = tonumber,
+        tostring = tostring,
+        type = type,
+        unpack = unpack,
+        xpcall = function(f, handler, ...)
+            return rethrow(xpcall(f, function(err)
+                if __doukutsu_rs_runtime_dont_touch._limitErrors[err] then
+                    return err
+                end
+
+                return handler(err)
+            end, ...))
+        end,
+        coroutine = { create = coroutine.create,
+                      resume = function(co, ...)
+                          return rethrow(coroutine.resume(co, ...))
+                      end,
+                      running = coroutine.running, status = coroutine.status,
+                      wrap = coroutine.wrap, yield = coroutine.yield },
+        string = string_lib,
+        table = { insert = table.insert, maxn = table.maxn,
+                  concat = function(...)
+                      return checked(table.concat(...))
+                  end,
+                  remove = table.remove, sort = table.sort },
+        math = { abs = math.abs, acos = math.acos, asin = math.asin,
+                 atan = math.atan, atan2 = math.atan2, ceil = math.ceil, cos = math.cos,
+                 cosh = math.cosh, deg = math.deg, exp = math.exp, floor = math.floor,
+                 fmod = math.fmod, frexp = math.frexp, huge = math.huge,
+                 ldexp = math.ldexp, log = math.log, log10 = math.log10, max = math.max,
+                 min = math.min, modf = math.modf, pi = math.pi, pow = math.pow,
+                 rad = math.rad, random = math.random, sin = math.sin, sinh = math.sinh,
+                 sqrt = math.sqrt, tan = math.tan, tanh = math.tanh },
+        os = { clock = os.clock, difftime = os.difftime, time = os.time },
+        bit = bit,
+        doukutsu = __doukutsu_rs_runtime_dont_touch._readOnly(doukutsu),
+        ModCS = __doukutsu_rs_runtime_dont_touch._readOnly(ModCS, { Game = { Act = true } }),
+    }
+
+    env._G = env
+    env.require = function(modname)
+        return __doukutsu_rs_runtime_dont_touch._require(env, modname, true)
+    end
+
+    return env
+end
+
 __doukutsu_rs_runtime_dont_touch._registered = {
     tick = {},
+    stageEnter = {},
+    stageLeave = {},
+    eventStart = {},
+    eventEnd = {},
+    playerDamaged = {},
+    playerDied = {},
+    npcSpawned = {},
+    npcKilled = {},
+    itemAcquired = {},
+    weaponAcquired = {},
+    bulletFired = {},
+    bossDefeated = {},
+    save = {},
+    load = {},
+    draw = {},
 }
 
+__doukutsu_rs_runtime_dont_touch._dispatch = function(event, ...)
+    for _, h in pairs(__doukutsu_rs_runtime_dont_touch._registered[event]) do
+        local status, err = pcall(h, ...)
+
+        if not status then
+            __doukutsu_rs:reportError("error in " .. event .. " handler:" .. err)
+        end
+    end
+end
+
+__doukutsu_rs_runtime_dont_touch._playerRef = function(player_id)
+    if player_id == 1 then
+        return __doukutsu_rs_runtime_dont_touch._playerRef1
+    end
+
+    return __doukutsu_rs_runtime_dont_touch._playerRef0
+end
+
 __doukutsu_rs_runtime_dont_touch._handlers = setmetatable({
     tick = function(scene)
         if type(ModCS.Game.Act) == 'function' then
-            pcall(ModCS.Game.Act)
+            local status, err = pcall(ModCS.Game.Act)
+            if not status then
+                __doukutsu_rs:reportError("error in ModCS.Game.Act:" .. err)
+            end
         end
 
-        for _, h in pairs(__doukutsu_rs_runtime_dont_touch._registered.tick) do
-            pcall(h, scene)
-        end
+        __doukutsu_rs_runtime_dont_touch._dispatch("tick", scene)
+    end,
+    stageEnter = function(scene, stage_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("stageEnter", scene, stage_id)
+    end,
+    stageLeave = function(scene, stage_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("stageLeave", scene, stage_id)
+    end,
+    eventStart = function(scene, event_num)
+        __doukutsu_rs_runtime_dont_touch._dispatch("eventStart", scene, event_num)
+    end,
+    eventEnd = function(scene, event_num)
+        __doukutsu_rs_runtime_dont_touch._dispatch("eventEnd", scene, event_num)
+    end,
+    playerDamaged = function(scene, player_id, damage)
+        local player = __doukutsu_rs_runtime_dont_touch._playerRef(player_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("playerDamaged", scene, player, damage)
+    end,
+    playerDied = function(scene, player_id)
+        local player = __doukutsu_rs_runtime_dont_touch._playerRef(player_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("playerDied", scene, player)
+    end,
+    npcSpawned = function(scene, npc_id, npc_type)
+        local npc = __doukutsu_rs_runtime_dont_touch._getNPCRef(npc_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("npcSpawned", scene, npc, npc_type)
+    end,
+    npcKilled = function(scene, npc_id, npc_type)
+        local npc = __doukutsu_rs_runtime_dont_touch._getNPCRef(npc_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("npcKilled", scene, npc, npc_type)
+    end,
+    itemAcquired = function(scene, item_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("itemAcquired", scene, item_id)
+    end,
+    weaponAcquired = function(scene, weapon_id, ammo)
+        __doukutsu_rs_runtime_dont_touch._dispatch("weaponAcquired", scene, weapon_id, ammo)
+    end,
+    bulletFired = function(scene, player_id, bullet_type)
+        local player = __doukutsu_rs_runtime_dont_touch._playerRef(player_id)
+        __doukutsu_rs_runtime_dont_touch._dispatch("bulletFired", scene, player, bullet_type)
+    end,
+    bossDefeated = function(scene, boss_type)
+        __doukutsu_rs_runtime_dont_touch._dispatch("bossDefeated", scene, boss_type)
+    end,
+    save = function(scene, slot)
+        __doukutsu_rs_runtime_dont_touch._dispatch("save", scene, slot)
+    end,
+    load = function(scene, slot)
+        __doukutsu_rs_runtime_dont_touch._dispatch("load", scene, slot)
+    end,
+    draw = function(scene, layer)
+        __doukutsu_rs_runtime_dont_touch._dispatch("draw", scene, layer)
     end,
 }, {
     __index = function(self, event)
@@ -106,7 +379,7 @@ __doukutsu_rs_runtime_dont_touch._tryNPCHook = function(npc_id, npc_type)
             local status, err = pcall(hook, npc)
 
             if not status then
-                print("error in npc handler:" .. err)
+                __doukutsu_rs:reportError("error in npc handler:" .. err)
             end
         end
 
@@ -116,33 +389,339 @@ __doukutsu_rs_runtime_dont_touch._tryNPCHook = function(npc_id, npc_type)
     return false
 end
 
-__doukutsu_rs_runtime_dont_touch._initializeScript = function(script)
-    -- for compatibility with Lua 5.2+, copy-pasted from Lua mailing list
-    -- http://lua-users.org/lists/lua-l/2010-06/msg00313.html
-    local _setfenv = setfenv or function(f, t)
-        f = (type(f) == 'function' and f or debug.getinfo(f + 1, 'f').func)
-        local name
-        local up = 0
-        repeat
-            up = up + 1
-            name = debug.getupvalue(f, up)
-        until name == '_ENV' or name == nil
-        if name then
-
-            debug.upvaluejoin(f, up, function()
-                return name
-            end, 1)
-            debug.setupvalue(f, up, t)
+__doukutsu_rs_runtime_dont_touch._registeredBossHooks = {}
+
+__doukutsu_rs_runtime_dont_touch._bossRef = nil
+
+__doukutsu_rs_runtime_dont_touch._tryBossHook = function(boss_type, part_id_base, part_count)
+    local hook = __doukutsu_rs_runtime_dont_touch._registeredBossHooks[boss_type]
+    if hook == nil then
+        return false
+    end
+
+    local boss_ref = __doukutsu_rs_runtime_dont_touch._bossRef
+    if boss_ref == nil or #boss_ref.parts ~= part_count then
+        local parts = {}
+        for i = 0, part_count - 1 do
+            parts[i + 1] = __doukutsu_rs_runtime_dont_touch._getNPCRef(part_id_base + i)
         end
+
+        __doukutsu_rs_runtime_dont_touch._bossRef = { parts = parts }
     end
 
-    global_copy = {}
-    for k, v in pairs(_G) do
-        global_copy[k] = v
+    local boss = __doukutsu_rs_runtime_dont_touch._bossRef
+    boss.bossType = boss_type
+
+    local status, err = pcall(hook, boss)
+    if not status then
+        __doukutsu_rs:reportError("error in boss handler:" .. err)
     end
 
-    _setfenv(script, global_copy)
+    return true
+end
+
+__doukutsu_rs_runtime_dont_touch._registeredBulletHooks = {}
+
+__doukutsu_rs_runtime_dont_touch._bulletRef = {}
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.hitLeftWall(self)
+    return (__doukutsu_rs:bulletCommand(0x0f) & 1) ~= 0
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.hitCeiling(self)
+    return (__doukutsu_rs:bulletCommand(0x0f) & 2) ~= 0
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.hitRightWall(self)
+    return (__doukutsu_rs:bulletCommand(0x0f) & 4) ~= 0
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.hitFloor(self)
+    return (__doukutsu_rs:bulletCommand(0x0f) & 8) ~= 0
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.getAnimRect(self)
+    local l, t, r, b = __doukutsu_rs:bulletCommand(0x202)
+    return { l, t, r, b }
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.setAnimRect(self, l, t, r, b)
+    if type(l) == "number" then
+        __doukutsu_rs:bulletCommand(0x203, l, t, r, b)
+    elseif type(l) == "table" then
+        __doukutsu_rs:bulletCommand(0x203, l[1], l[2], l[3], l[4])
+    else
+        error("Invalid parameters supplied.")
+    end
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.vanish(self)
+    __doukutsu_rs:bulletCommand(0x204)
+end
+
+function __doukutsu_rs_runtime_dont_touch._bulletRef.kill(self)
+    __doukutsu_rs:bulletCommand(0x205)
+end
+
+setmetatable(__doukutsu_rs_runtime_dont_touch._bulletRef, {
+    __index = function(self, property)
+        if property == "x" then
+            return __doukutsu_rs:bulletCommand(0x10)
+        elseif property == "y" then
+            return __doukutsu_rs:bulletCommand(0x11)
+        elseif property == "velX" then
+            return __doukutsu_rs:bulletCommand(0x12)
+        elseif property == "velY" then
+            return __doukutsu_rs:bulletCommand(0x13)
+        elseif property == "actionNum" then
+            return __doukutsu_rs:bulletCommand(0x16)
+        elseif property == "animNum" then
+            return __doukutsu_rs:bulletCommand(0x17)
+        elseif property == "actionCounter" then
+            return __doukutsu_rs:bulletCommand(0x18)
+        elseif property == "animCounter" then
+            return __doukutsu_rs:bulletCommand(0x1b)
+        elseif property == "bulletType" then
+            return __doukutsu_rs:bulletCommand(0x1d)
+        elseif property == "life" then
+            return __doukutsu_rs:bulletCommand(0x1e)
+        elseif property == "direction" then
+            return __doukutsu_rs:bulletCommand(0x21)
+        elseif property == "lifetime" then
+            return __doukutsu_rs:bulletCommand(0x23)
+        elseif property == "damage" then
+            return __doukutsu_rs:bulletCommand(0x24)
+        elseif property == "counter1" then
+            return __doukutsu_rs:bulletCommand(0x25)
+        elseif property == "counter2" then
+            return __doukutsu_rs:bulletCommand(0x26)
+        elseif property == "owner" then
+            return __doukutsu_rs_runtime_dont_touch._playerRef(__doukutsu_rs:bulletCommand(0x27))
+        else
+            return nil
+        end
+    end,
+    __newindex = function(self, property, val)
+        if property == "x" then
+            __doukutsu_rs:bulletCommand(0x110, val)
+        elseif property == "y" then
+            __doukutsu_rs:bulletCommand(0x111, val)
+        elseif property == "velX" then
+            __doukutsu_rs:bulletCommand(0x112, val)
+        elseif property == "velY" then
+            __doukutsu_rs:bulletCommand(0x113, val)
+        elseif property == "actionNum" then
+            __doukutsu_rs:bulletCommand(0x116, val)
+        elseif property == "animNum" then
+            __doukutsu_rs:bulletCommand(0x117, val)
+        elseif property == "actionCounter" then
+            __doukutsu_rs:bulletCommand(0x118, val)
+        elseif property == "animCounter" then
+            __doukutsu_rs:bulletCommand(0x11b, val)
+        elseif property == "life" then
+            __doukutsu_rs:bulletCommand(0x11e, val)
+        elseif property == "direction" then
+            __doukutsu_rs:bulletCommand(0x121, val)
+        elseif property == "lifetime" then
+            __doukutsu_rs:bulletCommand(0x123, val)
+        elseif property == "damage" then
+            __doukutsu_rs:bulletCommand(0x124, val)
+        elseif property == "counter1" then
+            __doukutsu_rs:bulletCommand(0x125, val)
+        elseif property == "counter2" then
+            __doukutsu_rs:bulletCommand(0x126, val)
+        end
+
+        return nil
+    end,
+})
+
+__doukutsu_rs_runtime_dont_touch._tryBulletHook = function(bullet_type)
+    local hook = __doukutsu_rs_runtime_dont_touch._registeredBulletHooks[bullet_type]
+    if hook == nil then
+        return false
+    end
+
+    local status, err = pcall(hook, __doukutsu_rs_runtime_dont_touch._bulletRef)
+    if not status then
+        __doukutsu_rs:reportError("error in bullet handler:" .. err)
+    end
+
+    return true
+end
+
+__doukutsu_rs_runtime_dont_touch._registeredWeaponHooks = {}
+
+__doukutsu_rs_runtime_dont_touch._weaponRef = {}
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.shootHeld(self)
+    return __doukutsu_rs:weaponCommand(0x16)
+end
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.shootTriggered(self)
+    return __doukutsu_rs:weaponCommand(0x17)
+end
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.consumeAmmo(self, amount)
+    return __doukutsu_rs:weaponCommand(0x200, amount or 1)
+end
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.fireBullet(self, bullet_type, x, y, direction)
+    __doukutsu_rs:weaponCommand(0x201, bullet_type, x, y, direction)
+end
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.countBullets(self, bullet_type)
+    return __doukutsu_rs:weaponCommand(0x202, bullet_type)
+end
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.setRefireTimer(self)
+    __doukutsu_rs:weaponCommand(0x203)
+end
+
+function __doukutsu_rs_runtime_dont_touch._weaponRef.showEmpty(self)
+    __doukutsu_rs:weaponCommand(0x204)
+end
+
+setmetatable(__doukutsu_rs_runtime_dont_touch._weaponRef, {
+    __index = function(self, property)
+        if property == "weaponType" then
+            return __doukutsu_rs:weaponCommand(0x10)
+        elseif property == "level" then
+            return __doukutsu_rs:weaponCommand(0x11)
+        elseif property == "experience" then
+            return __doukutsu_rs:weaponCommand(0x12)
+        elseif property == "ammo" then
+            return __doukutsu_rs:weaponCommand(0x13)
+        elseif property == "maxAmmo" then
+            return __doukutsu_rs:weaponCommand(0x14)
+        elseif property == "owner" then
+            return __doukutsu_rs_runtime_dont_touch._playerRef(__doukutsu_rs:weaponCommand(0x15))
+        else
+            return nil
+        end
+    end,
+    __newindex = function(self, property, val)
+        if property == "ammo" then
+            __doukutsu_rs:weaponCommand(0x113, val)
+        end
+
+        return nil
+    end,
+})
+
+-- returns the type of built-in behaviour to run, -1 if none or nil if there's no handler.
+__doukutsu_rs_runtime_dont_touch._tryWeaponHook = function(weapon_type, player_id)
+    local hook = __doukutsu_rs_runtime_dont_touch._registeredWeaponHooks[weapon_type]
+    if hook == nil then
+        return nil
+    end
+
+    local player = __doukutsu_rs_runtime_dont_touch._playerRef(player_id)
+    local status, result = pcall(hook.handler, __doukutsu_rs_runtime_dont_touch._weaponRef, player)
+    if not status then
+        __doukutsu_rs:reportError("error in weapon handler:" .. result)
+        return -1
+    end
+
+    if result == false then
+        return hook.fallback
+    end
+
+    return -1
+end
+
+__doukutsu_rs_runtime_dont_touch._botHandler = nil
+
+__doukutsu_rs_runtime_dont_touch._botKeys = {
+    left = 0x0001,
+    right = 0x0002,
+    up = 0x0004,
+    down = 0x0008,
+    map = 0x0010,
+    inventory = 0x0020,
+    jump = 0x0040,
+    shoot = 0x0080,
+    nextWeapon = 0x0100,
+    prevWeapon = 0x0200,
+    pause = 0x0400,
+    enter = 0x0800,
+    skip = 0x1000,
+    strafe = 0x2000,
+    menuOk = 0x4000,
+    menuBack = 0x8000,
+}
+
+__doukutsu_rs_runtime_dont_touch._tryBotHook = function(scene)
+    local handler = __doukutsu_rs_runtime_dont_touch._botHandler
+    if handler == nil then
+        return nil
+    end
+
+    local status, keys = pcall(handler, scene)
+    if not status then
+        __doukutsu_rs:reportError("error in bot handler:" .. keys)
+        return 0
+    end
+
+    local mask = 0
+    if type(keys) == "table" then
+        for key, bit in pairs(__doukutsu_rs_runtime_dont_touch._botKeys) do
+            if keys[key] then
+                mask = mask + bit
+            end
+        end
+    end
+
+    return mask
+end
+
+__doukutsu_rs_runtime_dont_touch._tscCommands = {}
+
+-- returns the amount of ticks to wait, or -1 if the command should be executed again on next tick.
+__doukutsu_rs_runtime_dont_touch._runTSCCommand = function(name, ...)
+    local handler = __doukutsu_rs_runtime_dont_touch._tscCommands[name]
+    if handler == nil then
+        return 0
+    end
+
+    local status, result = pcall(handler, ...)
+    if not status then
+        __doukutsu_rs:reportError("error in TSC command " .. name .. " handler:" .. result)
+        return 0
+    end
+
+    if result == false then
+        return -1
+    elseif type(result) == "number" then
+        return math.max(0, math.floor(result))
+    end
+
+    return 0
+end
+
+__doukutsu_rs_runtime_dont_touch._initializeScript = function(script, sandboxed, path)
+    local env
+    if sandboxed then
+        env = __doukutsu_rs_runtime_dont_touch._createSandbox()
+    else
+        env = {}
+        for k, v in pairs(_G) do
+            env[k] = v
+        end
+    end
+
+    local old_env = __doukutsu_rs_runtime_dont_touch._scriptEnvs[path]
+    __doukutsu_rs_runtime_dont_touch._scriptEnvs[path] = env
+
+    __doukutsu_rs_runtime_dont_touch._setfenv(script, env)
+    if sandboxed then
+        __doukutsu_rs_runtime_dont_touch._disableJit(script)
+    end
     script()
+
+    if old_env ~= nil and type(env.onReload) == 'function' then
+        env.onReload(old_env)
+    end
 end
 
 __doukutsu_rs_runtime_dont_touch._createPlayerRef = function(player_id)
@@ -413,6 +992,488 @@ function doukutsu.setNPCHandler(npc_type, handler)
     __doukutsu_rs_runtime_dont_touch._registeredNPCHooks[npc_type] = handler
 end
 
+function doukutsu.setBossHandler(boss_type, handler)
+    assert(type(boss_type) == "number", "boss type must be an integer.")
+
+    __doukutsu_rs_runtime_dont_touch._registeredBossHooks[boss_type] = handler
+end
+
+function doukutsu.setBulletHandler(bullet_type, handler)
+    assert(type(bullet_type) == "number", "bullet type must be an integer.")
+
+    __doukutsu_rs_runtime_dont_touch._registeredBulletHooks[bullet_type] = handler
+end
+
+function doukutsu.setWeaponHandler(weapon_type, handler, fallback_type)
+    assert(type(weapon_type) == "number", "weapon type must be an integer.")
+    assert(fallback_type == nil or type(fallback_type) == "number", "fallback weapon type must be an integer.")
+
+    if handler == nil then
+        __doukutsu_rs_runtime_dont_touch._registeredWeaponHooks[weapon_type] = nil
+    else
+        __doukutsu_rs_runtime_dont_touch._registeredWeaponHooks[weapon_type] = {
+            handler = handler,
+            fallback = fallback_type or weapon_type,
+        }
+    end
+end
+
+function doukutsu.setBotHandler(handler)
+    assert(handler == nil or type(handler) == "function", "bot handler must be a function or nil.")
+
+    __doukutsu_rs_runtime_dont_touch._botHandler = handler
+    __doukutsu_rs:setBotEnabled(handler ~= nil)
+end
+
+function doukutsu.registerTSCCommand(name, argc, handler)
+    assert(type(name) == "string" and #name == 3, "command name must be a 3 character string.")
+    assert(type(argc) == "number", "operand count must be an integer.")
+    assert(type(handler) == "function", "command handler must be a function.")
+
+    __doukutsu_rs:registerTSCCommand(name, argc)
+    __doukutsu_rs_runtime_dont_touch._tscCommands[name] = handler
+end
+
+__doukutsu_rs_runtime_dont_touch._spriteBatches = {}
+
+__doukutsu_rs_runtime_dont_touch._createSpriteBatch = function(name)
+    local batch = { name = name }
+
+    function batch.dimensions(self)
+        return __doukutsu_rs:graphicsCommand(0x00, rawget(self, "name"))
+    end
+
+    function batch.addRect(self, x, y, rect, options)
+        assert(type(rect) == "table", "rect must be a table.")
+        options = options or {}
+
+        local flags = 0
+        if options.flipX then
+            flags = flags | 1
+        end
+        if options.flipY then
+            flags = flags | 2
+        end
+
+        local tint = options.tint or {}
+        local scaleX = options.scaleX or options.scale or 1.0
+        local scaleY = options.scaleY or options.scale or 1.0
+
+        __doukutsu_rs:graphicsCommand(0x01, rawget(self, "name"), x, y, rect[1], rect[2], rect[3], rect[4], flags,
+                tint[1] or 255, tint[2] or 255, tint[3] or 255, tint[4] or 255, scaleX, scaleY)
+    end
+
+    function batch.draw(self)
+        __doukutsu_rs:graphicsCommand(0x02, rawget(self, "name"))
+    end
+
+    return batch
+end
+
+function doukutsu.getSpriteBatch(name)
+    assert(type(name) == "string", "texture name must be a string.")
+
+    if __doukutsu_rs_runtime_dont_touch._spriteBatches[name] == nil then
+        __doukutsu_rs_runtime_dont_touch._spriteBatches[name] = __doukutsu_rs_runtime_dont_touch._createSpriteBatch(name)
+    end
+
+    return __doukutsu_rs_runtime_dont_touch._spriteBatches[name]
+end
+
+function doukutsu.drawRect(x, y, width, height, color)
+    assert(type(color) == "table", "color must be a table.")
+
+    __doukutsu_rs:graphicsCommand(0x10, x, y, width, height, color[1], color[2], color[3], color[4] or 255)
+end
+
+function doukutsu.drawText(text, x, y, options)
+    options = options or {}
+    local color = options.color or {}
+
+    __doukutsu_rs:graphicsCommand(0x20, tostring(text), x, y, color[1] or 255, color[2] or 255, color[3] or 255,
+            color[4] or 255, options.shadow == true, options.scale or 1.0)
+end
+
+function doukutsu.getTextWidth(text, scale)
+    return __doukutsu_rs:graphicsCommand(0x21, tostring(text), scale or 1.0)
+end
+
+function doukutsu.getMapSize()
+    return { __doukutsu_rs:mapCommand(0x00) }
+end
+
+function doukutsu.getTile(x, y)
+    return __doukutsu_rs:mapCommand(0x01, x, y)
+end
+
+function doukutsu.setTile(x, y, tile, smoke)
+    return __doukutsu_rs:mapCommand(0x101, x, y, tile, smoke == true)
+end
+
+function doukutsu.getTileAttribute(x, y)
+    return __doukutsu_rs:mapCommand(0x02, x, y)
+end
+
+function doukutsu.getAttribute(tile)
+    return __doukutsu_rs:mapCommand(0x03, tile)
+end
+
+function doukutsu.setAttribute(tile, attrib)
+    __doukutsu_rs:mapCommand(0x103, tile, attrib)
+end
+
+doukutsu.camera = {}
+
+function doukutsu.camera.focusOnPlayer(ticks)
+    __doukutsu_rs:cameraCommand(0x200, ticks)
+end
+
+function doukutsu.camera.focusOnNPC(npc, ticks)
+    if type(npc) == "table" then
+        npc = rawget(npc, "id")
+    end
+
+    __doukutsu_rs:cameraCommand(0x201, npc, ticks)
+end
+
+function doukutsu.camera.snap()
+    __doukutsu_rs:cameraCommand(0x202)
+end
+
+setmetatable(doukutsu.camera, {
+    __index = function(self, property)
+        if property == "x" then
+            return __doukutsu_rs:cameraCommand(0x10)
+        elseif property == "y" then
+            return __doukutsu_rs:cameraCommand(0x11)
+        elseif property == "targetX" then
+            return __doukutsu_rs:cameraCommand(0x12)
+        elseif property == "targetY" then
+            return __doukutsu_rs:cameraCommand(0x13)
+        elseif property == "wait" then
+            return __doukutsu_rs:cameraCommand(0x14)
+        elseif property == "target" then
+            local target, id = __doukutsu_rs:cameraCommand(0x15)
+            return { type = target, id = id }
+        else
+            return nil
+        end
+    end,
+    __newindex = function(self, property, val)
+        if property == "x" then
+            __doukutsu_rs:cameraCommand(0x110, val)
+        elseif property == "y" then
+            __doukutsu_rs:cameraCommand(0x111, val)
+        elseif property == "targetX" then
+            __doukutsu_rs:cameraCommand(0x112, val)
+        elseif property == "targetY" then
+            __doukutsu_rs:cameraCommand(0x113, val)
+        elseif property == "wait" then
+            __doukutsu_rs:cameraCommand(0x114, val)
+        end
+
+        return nil
+    end,
+})
+
+__doukutsu_rs_runtime_dont_touch._createInventoryRef = function(player_id)
+    local inventory_ref = { playerId = player_id }
+
+    function inventory_ref.items(self)
+        local items = {}
+        for i = 0, __doukutsu_rs:inventoryCommand(player_id, 0x00) - 1 do
+            local id, amount = __doukutsu_rs:inventoryCommand(player_id, 0x01, i)
+            table.insert(items, { id = id, amount = amount })
+        end
+
+        return items
+    end
+
+    function inventory_ref.getItemAmount(self, item_id)
+        return __doukutsu_rs:inventoryCommand(player_id, 0x02, item_id)
+    end
+
+    function inventory_ref.hasItem(self, item_id)
+        return __doukutsu_rs:inventoryCommand(player_id, 0x02, item_id) > 0
+    end
+
+    function inventory_ref.addItem(self, item_id, amount)
+        __doukutsu_rs:inventoryCommand(player_id, 0x100, item_id, amount)
+    end
+
+    function inventory_ref.removeItem(self, item_id)
+        __doukutsu_rs:inventoryCommand(player_id, 0x101, item_id)
+    end
+
+    function inventory_ref.consumeItem(self, item_id)
+        __doukutsu_rs:inventoryCommand(player_id, 0x102, item_id)
+    end
+
+    function inventory_ref.weapons(self)
+        local weapons = {}
+        for i = 0, __doukutsu_rs:inventoryCommand(player_id, 0x11) - 1 do
+            local wtype, level, experience, ammo, max_ammo = __doukutsu_rs:inventoryCommand(player_id, 0x12, i)
+            table.insert(weapons, {
+                weaponType = wtype,
+                level = level,
+                experience = experience,
+                ammo = ammo,
+                maxAmmo = max_ammo,
+            })
+        end
+
+        return weapons
+    end
+
+    function inventory_ref.hasWeapon(self, weapon_type)
+        for _, weapon in pairs(self:weapons()) do
+            if weapon.weaponType == weapon_type then
+                return true
+            end
+        end
+
+        return false
+    end
+
+    function inventory_ref.addWeapon(self, weapon_type, ammo)
+        __doukutsu_rs:inventoryCommand(player_id, 0x111, weapon_type, ammo)
+    end
+
+    function inventory_ref.removeWeapon(self, weapon_type)
+        __doukutsu_rs:inventoryCommand(player_id, 0x112, weapon_type)
+    end
+
+    function inventory_ref.setWeapon(self, weapon_type, data)
+        __doukutsu_rs:inventoryCommand(player_id, 0x113, weapon_type, data.level, data.experience, data.ammo,
+                data.maxAmmo)
+    end
+
+    function inventory_ref.addExperience(self, amount)
+        __doukutsu_rs:inventoryCommand(player_id, 0x114, amount)
+    end
+
+    function inventory_ref.takeExperience(self, amount)
+        __doukutsu_rs:inventoryCommand(player_id, 0x115, amount)
+    end
+
+    function inventory_ref.refillAmmo(self)
+        __doukutsu_rs:inventoryCommand(player_id, 0x116)
+    end
+
+    setmetatable(inventory_ref, {
+        __index = function(self, property)
+            if property == "currentWeapon" then
+                return __doukutsu_rs:inventoryCommand(player_id, 0x10)
+            else
+                return nil
+            end
+        end,
+        __newindex = function(self, property, val)
+            if property == "currentWeapon" then
+                __doukutsu_rs:inventoryCommand(player_id, 0x110, val)
+            end
+
+            return nil
+        end,
+    })
+
+    return inventory_ref
+end
+
+__doukutsu_rs_runtime_dont_touch._inventoryRefs = {
+    [0] = __doukutsu_rs_runtime_dont_touch._createInventoryRef(0),
+    [1] = __doukutsu_rs_runtime_dont_touch._createInventoryRef(1),
+}
+
+doukutsu.inventory = __doukutsu_rs_runtime_dont_touch._inventoryRefs[0]
+
+function doukutsu.getInventory(player_id)
+    return __doukutsu_rs_runtime_dont_touch._inventoryRefs[player_id or 0]
+end
+
+doukutsu.textBox = {}
+
+function doukutsu.textBox.runEvent(event_num)
+    __doukutsu_rs:textBoxCommand(0x100, event_num)
+end
+
+function doukutsu.textBox.clear()
+    __doukutsu_rs:textBoxCommand(0x200)
+end
+
+function doukutsu.textBox.getLines()
+    return { __doukutsu_rs:textBoxCommand(0x05) }
+end
+
+__doukutsu_rs_runtime_dont_touch._textBoxFlags = {
+    visible = 1,
+    backgroundVisible = 2,
+    fast = 16,
+    positionTop = 32,
+}
+
+setmetatable(doukutsu.textBox, {
+    __index = function(self, property)
+        local flag = __doukutsu_rs_runtime_dont_touch._textBoxFlags[property]
+        if flag ~= nil then
+            return (__doukutsu_rs:textBoxCommand(0x02) & flag) ~= 0
+        elseif property == "running" then
+            return __doukutsu_rs:textBoxCommand(0x00)
+        elseif property == "event" then
+            return __doukutsu_rs:textBoxCommand(0x01)
+        elseif property == "face" then
+            return __doukutsu_rs:textBoxCommand(0x03)
+        elseif property == "item" then
+            return __doukutsu_rs:textBoxCommand(0x04)
+        elseif property == "executorPlayer" then
+            return __doukutsu_rs:textBoxCommand(0x06)
+        else
+            return nil
+        end
+    end,
+    __newindex = function(self, property, val)
+        local flag = __doukutsu_rs_runtime_dont_touch._textBoxFlags[property]
+        if flag ~= nil then
+            local flags = __doukutsu_rs:textBoxCommand(0x02)
+            if val then
+                flags = flags | flag
+            else
+                flags = flags - (flags & flag)
+            end
+
+            __doukutsu_rs:textBoxCommand(0x102, flags)
+        elseif property == "face" then
+            __doukutsu_rs:textBoxCommand(0x103, val)
+        end
+
+        return nil
+    end,
+})
+
+__doukutsu_rs_runtime_dont_touch._controlFlags = {
+    tickWorld = 1,
+    controlEnabled = 2,
+    interactionsDisabled = 4,
+    creditsRunning = 8,
+    okButtonDisabled = 16,
+    friendlyFire = 16384,
+}
+
+doukutsu.controlFlags = setmetatable({}, {
+    __index = function(self, property)
+        local flag = __doukutsu_rs_runtime_dont_touch._controlFlags[property]
+        if flag ~= nil then
+            return (__doukutsu_rs:stageCommand(0x04) & flag) ~= 0
+        end
+
+        return nil
+    end,
+    __newindex = function(self, property, val)
+        local flag = __doukutsu_rs_runtime_dont_touch._controlFlags[property]
+        if flag ~= nil then
+            local flags = __doukutsu_rs:stageCommand(0x04)
+            if val then
+                flags = flags | flag
+            else
+                flags = flags - (flags & flag)
+            end
+
+            __doukutsu_rs:stageCommand(0x104, flags)
+        end
+
+        return nil
+    end,
+})
+
+function doukutsu.spawnCaret(caret_type, x, y, direction)
+    __doukutsu_rs:spawnCommand(0x00, caret_type, x, y, direction)
+end
+
+function doukutsu.spawnBullet(bullet_type, x, y, direction, owner)
+    __doukutsu_rs:spawnCommand(0x01, bullet_type, x, y, direction, owner)
+end
+
+function doukutsu.spawnNPC(npc_type, x, y, direction)
+    local npc_id = __doukutsu_rs:spawnCommand(0x02, npc_type, x, y, direction)
+    if npc_id == nil then
+        return nil
+    end
+
+    return __doukutsu_rs_runtime_dont_touch._getNPCRef(npc_id)
+end
+
+__doukutsu_rs_runtime_dont_touch._tests = {}
+__doukutsu_rs_runtime_dont_touch._currentTest = nil
+
+function doukutsu.test(name, handler)
+    assert(type(name) == "string", "test name must be a string.")
+    assert(type(handler) == "function", "test handler must be a function.")
+
+    table.insert(__doukutsu_rs_runtime_dont_touch._tests, { name = name, handler = handler })
+end
+
+function doukutsu.advanceTicks(ticks)
+    if __doukutsu_rs_runtime_dont_touch._currentTest == nil
+            or coroutine.running() ~= __doukutsu_rs_runtime_dont_touch._currentTest then
+        error("advanceTicks can only be used inside of a test.")
+    end
+
+    coroutine.yield(ticks or 1)
+end
+
+function doukutsu.assertEquals(expected, actual, message)
+    if expected ~= actual then
+        local msg = "expected " .. tostring(expected) .. ", got " .. tostring(actual)
+        if message ~= nil then
+            msg = message .. ": " .. msg
+        end
+
+        error(msg, 2)
+    end
+end
+
+__doukutsu_rs_runtime_dont_touch._testCount = function()
+    return #__doukutsu_rs_runtime_dont_touch._tests
+end
+
+__doukutsu_rs_runtime_dont_touch._startTest = function(index)
+    local test = __doukutsu_rs_runtime_dont_touch._tests[index]
+    __doukutsu_rs_runtime_dont_touch._currentTest = coroutine.create(test.handler)
+
+    return test.name
+end
+
+-- Runs the current test until it finishes or waits for ticks, returns "wait" with the number of ticks,
+-- "passed" or "failed" with the error message.
+__doukutsu_rs_runtime_dont_touch._resumeTest = function()
+    local co = __doukutsu_rs_runtime_dont_touch._currentTest
+    local status, result = coroutine.resume(co)
+
+    if not status then
+        __doukutsu_rs_runtime_dont_touch._currentTest = nil
+        return "failed", tostring(result)
+    elseif coroutine.status(co) == "dead" then
+        __doukutsu_rs_runtime_dont_touch._currentTest = nil
+        return "passed", nil
+    end
+
+    return "wait", math.max(tonumber(result) or 1, 0)
+end
+
+doukutsu.save = {}
+
+function doukutsu.save.get(key)
+    assert(type(key) == "string", "key must be a string.")
+
+    return __doukutsu_rs:storageCommand(0x00, key)
+end
+
+function doukutsu.save.set(key, value)
+    assert(type(key) == "string", "key must be a string.")
+
+    __doukutsu_rs:storageCommand(0x100, key, value)
+end
+
 function doukutsu.on(event, handler)
     assert(type(event) == "string", "event type must be a string.")
     assert(type(handler) == "function", "event handler must be a function.")
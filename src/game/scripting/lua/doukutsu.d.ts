@@ -1,5 +1,12 @@
 declare type EventHandler<T> = (this: void, param: T) => void;
 
+/**
+ * Optionally defined by scripts. When scripts are reloaded with their state preserved (for example after the
+ * script file was modified with debug mode enabled), it's called after the script was run again, with the table
+ * holding global variables of its previous instance, so the script can carry its data over.
+ */
+declare var onReload: ((this: void, oldState: { [key: string]: any }) => void) | undefined;
+
 declare interface NPC {
     /**
      * The ID of NPC, equivalent to offset in NPC list of current scene.
@@ -148,6 +155,139 @@ declare interface NPC {
     setAnimRect(left: number, top: number, right: number, bottom: number): void;
 }
 
+/**
+ * Represents the boss of current stage, passed to boss handlers.
+ */
+declare interface Boss {
+    /**
+     * The type ID of boss.
+     */
+    readonly bossType: number;
+
+    /**
+     * Parts of the boss, the first one is the main part which determines whether the boss is alive.
+     * Parts can be used the same way as regular NPCs.
+     */
+    readonly parts: NPC[];
+}
+
+/**
+ * Represents a bullet, only valid for the duration of bullet handler call.
+ */
+declare interface Bullet {
+    /**
+     * The type ID of bullet, an index in the bullet table.
+     */
+    readonly bulletType: number;
+
+    /**
+     * Player who fired the bullet.
+     */
+    readonly owner: DoukutsuPlayer;
+
+    x: number;
+    y: number;
+    velX: number;
+    velY: number;
+    actionNum: number;
+    actionCounter: number;
+    animNum: number;
+    animCounter: number;
+    direction: number;
+
+    /**
+     * Remaining hits before the bullet disappears.
+     */
+    life: number;
+
+    /**
+     * Remaining ticks before the bullet disappears.
+     */
+    lifetime: number;
+    damage: number;
+    counter1: number;
+    counter2: number;
+
+    hitLeftWall(): boolean;
+    hitCeiling(): boolean;
+    hitRightWall(): boolean;
+    hitFloor(): boolean;
+
+    getAnimRect(): [number, number, number, number];
+    setAnimRect(rect: [number, number, number, number]): void;
+    setAnimRect(left: number, top: number, right: number, bottom: number): void;
+
+    /**
+     * Removes the bullet, playing the dissipation effect.
+     */
+    vanish(): void;
+
+    /**
+     * Removes the bullet without any effects.
+     */
+    kill(): void;
+}
+
+/**
+ * Represents the currently selected weapon of a player, only valid for the duration of weapon handler call.
+ */
+declare interface Weapon {
+    readonly weaponType: number;
+    readonly level: number;
+    readonly experience: number;
+    readonly maxAmmo: number;
+
+    /**
+     * Remaining ammo, unlimited if maxAmmo is 0.
+     */
+    ammo: number;
+
+    /**
+     * Player holding the weapon.
+     */
+    readonly owner: DoukutsuPlayer;
+
+    /**
+     * Returns true if the shoot button is held down.
+     */
+    shootHeld(): boolean;
+
+    /**
+     * Returns true if the shoot button has been pressed during this tick.
+     */
+    shootTriggered(): boolean;
+
+    /**
+     * Consumes specified amount of ammo, returns true if there was enough.
+     * @param amount defaults to 1
+     */
+    consumeAmmo(amount?: number): boolean;
+
+    /**
+     * Fires a bullet of specified type, which must be defined in the bullet table.
+     * @param bulletType type ID of bullet
+     * @param x position in X axis
+     * @param y position in Y axis
+     * @param direction direction of the bullet, defaults to direction the player is facing
+     */
+    fireBullet(bulletType: number, x: number, y: number, direction?: number): void;
+
+    /**
+     * Returns the number of bullets of specified type fired by owner of the weapon, or all of them if omitted.
+     */
+    countBullets(bulletType?: number): number;
+
+    /**
+     * Prevents the weapon from firing again for next 4 ticks of repeatedly pressing shoot button.
+     */
+    setRefireTimer(): void;
+
+    /**
+     * Plays the "empty" sound and shows the text above the player.
+     */
+    showEmpty(): void;
+}
+
 /**
  * Represents an in-game player.
  */
@@ -199,6 +339,286 @@ declare interface DoukutsuRSApi {
     readonly lightingEnabled: boolean;
 }
 
+/**
+ * Buttons held down by a bot during a single tick. Omitted buttons are treated as released.
+ */
+declare interface BotKeys {
+    left?: boolean;
+    right?: boolean;
+    up?: boolean;
+    down?: boolean;
+    map?: boolean;
+    inventory?: boolean;
+    jump?: boolean;
+    shoot?: boolean;
+    nextWeapon?: boolean;
+    prevWeapon?: boolean;
+    pause?: boolean;
+    enter?: boolean;
+    skip?: boolean;
+    strafe?: boolean;
+    menuOk?: boolean;
+    menuBack?: boolean;
+}
+
+/**
+ * Color as [r, g, b, a] with components in 0-255 range. Alpha defaults to 255 if omitted.
+ */
+declare type Color = [number, number, number, number?];
+
+/**
+ * Rectangle as [left, top, right, bottom].
+ */
+declare type Rect = [number, number, number, number];
+
+/**
+ * Draw layer passed to draw handlers.
+ * - background - after the background, behind all tiles,
+ * - entities - after NPCs, bullets and players, behind foreground tiles,
+ * - hud - on top of the HUD and inventory.
+ */
+declare type DrawLayer = "background" | "entities" | "hud";
+
+declare interface SpriteRectOptions {
+    /**
+     * Flips the sprite horizontally. Ignored if the sprite is scaled.
+     */
+    flipX?: boolean;
+
+    /**
+     * Flips the sprite vertically. Ignored if the sprite is scaled.
+     */
+    flipY?: boolean;
+
+    /**
+     * Color the sprite is multiplied by.
+     */
+    tint?: Color;
+
+    /**
+     * Scale of the sprite, applied to both axes unless overridden by scaleX/scaleY.
+     */
+    scale?: number;
+    scaleX?: number;
+    scaleY?: number;
+}
+
+/**
+ * A batch of sprites sharing a single texture (eg. "MyChar" or "Npc/NpcSym").
+ * Coordinates are in canvas pixels. Can only be used inside draw handlers.
+ */
+declare interface SpriteBatch {
+    /**
+     * Name of the texture.
+     */
+    readonly name: string;
+
+    /**
+     * Returns the size of texture in canvas pixels.
+     */
+    dimensions(): [number, number];
+
+    /**
+     * Queues a part of texture to be drawn at specified position.
+     */
+    addRect(x: number, y: number, rect: Rect, options?: SpriteRectOptions): void;
+
+    /**
+     * Draws all queued sprites and clears the batch.
+     */
+    draw(): void;
+}
+
+declare interface TextOptions {
+    /**
+     * Text color, white by default.
+     */
+    color?: Color;
+
+    /**
+     * Draws a shadow under the text.
+     */
+    shadow?: boolean;
+
+    /**
+     * Scale of the text, 1.0 by default.
+     */
+    scale?: number;
+}
+
+declare interface Camera {
+    /**
+     * Current position of the camera in X axis (as floating point, not internal fixed point representation).
+     */
+    x: number;
+
+    /**
+     * Current position of the camera in Y axis (as floating point, not internal fixed point representation).
+     */
+    y: number;
+
+    /**
+     * Position in X axis the camera is moving towards.
+     */
+    targetX: number;
+
+    /**
+     * Position in Y axis the camera is moving towards.
+     */
+    targetY: number;
+
+    /**
+     * Number of ticks the camera takes to reach its target, lower is faster.
+     */
+    wait: number;
+
+    /**
+     * The entity followed by the camera, read-only.
+     */
+    readonly target: { type: "player" | "npc" | "boss"; id: number };
+
+    /**
+     * Makes the camera follow the player.
+     * @param ticks speed of the camera, 16 by default
+     */
+    focusOnPlayer(this: void, ticks?: number): void;
+
+    /**
+     * Makes the camera follow specified NPC or boss part.
+     * @param npc NPC reference or its ID
+     * @param ticks speed of the camera, 16 by default
+     */
+    focusOnNPC(this: void, npc: NPC | number, ticks?: number): void;
+
+    /**
+     * Moves the camera to its target immediately.
+     */
+    snap(this: void): void;
+}
+
+declare interface InventoryItem {
+    id: number;
+    amount: number;
+}
+
+declare interface InventoryWeapon {
+    weaponType: number;
+    /**
+     * Level of the weapon, from 1 to 3.
+     */
+    level: number;
+    experience: number;
+    ammo: number;
+    /**
+     * Maximum ammo of the weapon, 0 if it has unlimited ammo.
+     */
+    maxAmmo: number;
+}
+
+declare interface Inventory {
+    /**
+     * The ID of player owning the inventory.
+     */
+    readonly playerId: number;
+
+    /**
+     * Index of the currently selected weapon.
+     */
+    currentWeapon: number;
+
+    items(): InventoryItem[];
+    getItemAmount(itemId: number): number;
+    hasItem(itemId: number): boolean;
+    addItem(itemId: number, amount?: number): void;
+    removeItem(itemId: number): void;
+
+    /**
+     * Removes a single piece of specified item.
+     */
+    consumeItem(itemId: number): void;
+
+    weapons(): InventoryWeapon[];
+    hasWeapon(weaponType: number): boolean;
+
+    /**
+     * Adds a weapon, or adds to its ammo if it's already in the inventory.
+     */
+    addWeapon(weaponType: number, ammo: number): void;
+    removeWeapon(weaponType: number): void;
+
+    /**
+     * Changes the state of a weapon in the inventory, omitted fields are left as is.
+     */
+    setWeapon(weaponType: number, data: Partial<Omit<InventoryWeapon, "weaponType">>): void;
+
+    /**
+     * Gives experience to the current weapon, leveling it up if needed.
+     */
+    addExperience(amount: number): void;
+
+    /**
+     * Takes experience from the current weapon, leveling it down if needed.
+     */
+    takeExperience(amount: number): void;
+    refillAmmo(): void;
+}
+
+declare interface TextBox {
+    /**
+     * Whether a TSC event is being executed, read-only.
+     */
+    readonly running: boolean;
+
+    /**
+     * Number of the event being executed, read-only.
+     */
+    readonly event: number | null;
+
+    /**
+     * The ID of player who triggered the event, read-only.
+     */
+    readonly executorPlayer: number;
+
+    /**
+     * Item shown in the text box, read-only.
+     */
+    readonly item: number;
+
+    /**
+     * Face picture shown in the text box.
+     */
+    face: number;
+
+    visible: boolean;
+    backgroundVisible: boolean;
+    fast: boolean;
+    positionTop: boolean;
+
+    /**
+     * Starts executing specified event of the current stage.
+     */
+    runEvent(this: void, eventNum: number): void;
+
+    /**
+     * Clears the text shown in the text box.
+     */
+    clear(this: void): void;
+
+    /**
+     * Returns the three lines of text currently shown in the text box.
+     */
+    getLines(this: void): [string, string, string];
+}
+
+declare interface ControlFlags {
+    tickWorld: boolean;
+    controlEnabled: boolean;
+    interactionsDisabled: boolean;
+    creditsRunning: boolean;
+    okButtonDisabled: boolean;
+    friendlyFire: boolean;
+}
+
 declare namespace doukutsu {
     /**
      * A reference to main locally controlled player.
@@ -291,6 +711,208 @@ declare namespace doukutsu {
      */
     function setNPCHandler(npcType: number, handler: (this: void, npc: NPC) => void | null): void;
 
+    /**
+     * Sets the handler override for specified boss type, which is called instead of the built-in logic.
+     * Passing a null removes the handler.
+     * @param bossType
+     * @param handler
+     */
+    function setBossHandler(bossType: number, handler: ((this: void, boss: Boss) => void) | null): void;
+
+    /**
+     * Sets the handler override for specified bullet type, which is called instead of the built-in logic.
+     * Passing a null removes the handler.
+     * @param bulletType
+     * @param handler
+     */
+    function setBulletHandler(bulletType: number, handler: ((this: void, bullet: Bullet) => void) | null): void;
+
+    /**
+     * Sets the handler for specified weapon type, called on each tick while the weapon is selected.
+     * A handler replaces the behaviour of the weapon type it's registered for, which is how new weapons are defined.
+     *
+     * If the handler returns false, the built-in behaviour of fallback weapon type is run afterwards,
+     * nothing is run if the fallback type has no built-in weapon.
+     * Passing a null removes the handler.
+     * @param weaponType
+     * @param handler
+     * @param fallbackType weapon type used if handler returns false, defaults to weaponType
+     */
+    function setWeaponHandler(
+        weaponType: number,
+        handler: ((this: void, weapon: Weapon, player: DoukutsuPlayer) => boolean | void) | null,
+        fallbackType?: number,
+    ): void;
+
+    /**
+     * Replaces the input of main player with a bot, the handler is called on each tick and returns buttons to hold.
+     * Passing a null restores the regular controller.
+     * @param handler
+     */
+    function setBotHandler(handler: ((this: void, scene: DoukutsuStage) => BotKeys) | null): void;
+
+    /**
+     * Registers a custom TSC command, usable in scripts as <XYZ0001:0002 with specified number of operands.
+     * Scripts loaded after the registration can use the command.
+     *
+     * The handler's return value controls the script execution:
+     * - nothing or true continues with the next command,
+     * - a number waits for specified amount of ticks before continuing,
+     * - false blocks the script and calls the handler again on next tick.
+     * @param name 3 character command name, must not collide with built-in commands
+     * @param argc number of operands, from 0 to 8
+     * @param handler command handler procedure, called with the operands
+     */
+    function registerTSCCommand(
+        name: string,
+        argc: number,
+        handler: (this: void, ...args: number[]) => boolean | number | void,
+    ): void;
+
+    /**
+     * Returns a sprite batch for specified texture. Can only be used inside draw handlers.
+     * @param name texture name, without extension
+     */
+    function getSpriteBatch(name: string): SpriteBatch;
+
+    /**
+     * Draws a filled rectangle. Can only be used inside draw handlers.
+     * @param x position in canvas pixels
+     * @param y position in canvas pixels
+     * @param width width in canvas pixels
+     * @param height height in canvas pixels
+     * @param color fill color
+     */
+    function drawRect(x: number, y: number, width: number, height: number, color: Color): void;
+
+    /**
+     * Draws text using the game font. Can only be used inside draw handlers.
+     * @param text text to draw
+     * @param x position in canvas pixels
+     * @param y position in canvas pixels
+     * @param options text style
+     */
+    function drawText(text: string, x: number, y: number, options?: TextOptions): void;
+
+    /**
+     * Returns the width of text drawn using the game font, in canvas pixels.
+     * @param text text to measure
+     * @param scale scale of the text, 1.0 by default
+     */
+    function getTextWidth(text: string, scale?: number): number;
+
+    /**
+     * Persistent data of the current save slot, written to disk together with the profile when the game is saved.
+     * Each mod has its own set of keys, even if it shares save slots with other mods.
+     */
+    namespace save {
+        /**
+         * Returns the value stored under specified key, or null if there's none.
+         */
+        function get(key: string): any;
+
+        /**
+         * Stores a value under specified key, null removes it. Values can be booleans, numbers, strings and
+         * tables of those. Tables with keys other than consecutive integers starting from 1 are stored with
+         * their keys converted to strings.
+         */
+        function set(key: string, value: any): void;
+    }
+
+    /**
+     * Camera of current stage.
+     */
+    const camera: Camera;
+
+    /**
+     * Inventory of the main player.
+     */
+    const inventory: Inventory;
+
+    /**
+     * State of the TSC text box.
+     */
+    const textBox: TextBox;
+
+    /**
+     * Global game state flags, such as whether the player can be controlled.
+     */
+    const controlFlags: ControlFlags;
+
+    /**
+     * Returns the inventory of specified player.
+     * @param playerId 0 for the main player (default), 1 for the second player
+     */
+    function getInventory(playerId?: number): Inventory;
+
+    /**
+     * Returns the size of current map in tiles, as [width, height].
+     */
+    function getMapSize(): [number, number];
+
+    /**
+     * Returns the tile at specified position of current map, in tiles.
+     */
+    function getTile(x: number, y: number): number | null;
+
+    /**
+     * Changes the tile at specified position of current map, in tiles.
+     * @param smoke spawn smoke like <CMP does if the tile changed
+     * @returns whether the tile has changed
+     */
+    function setTile(x: number, y: number, tile: number, smoke?: boolean): boolean | null;
+
+    /**
+     * Returns the attribute (collision type) of tile at specified position of current map.
+     */
+    function getTileAttribute(x: number, y: number): number | null;
+
+    /**
+     * Returns the attribute (collision type) of specified tile type in the current tileset.
+     */
+    function getAttribute(tile: number): number;
+
+    /**
+     * Changes the attribute (collision type) of specified tile type in the current tileset.
+     */
+    function setAttribute(tile: number, attribute: number): void;
+
+    /**
+     * Spawns a caret (visual effect) at specified position.
+     */
+    function spawnCaret(caretType: number, x: number, y: number, direction?: number): void;
+
+    /**
+     * Spawns a bullet at specified position.
+     * @param owner ID of player the bullet belongs to, 0 by default
+     */
+    function spawnBullet(bulletType: number, x: number, y: number, direction?: number, owner?: number): void;
+
+    /**
+     * Spawns an NPC at specified position, like the <SNP command.
+     * @return the spawned NPC, or nil if there are no free NPC slots
+     */
+    function spawnNPC(npcType: number, x: number, y: number, direction?: number): NPC | undefined;
+
+    /**
+     * Registers a test case, run by the script test runner (`--test-scripts`) and ignored otherwise.
+     * Each test starts on a freshly loaded stage.
+     * @param name name of the test case
+     * @param handler test procedure, fails if it raises an error
+     */
+    function test(name: string, handler: (this: void) => void): void;
+
+    /**
+     * Lets the game run for specified number of ticks, can only be used inside of a test case.
+     * @param ticks number of ticks, 1 by default
+     */
+    function advanceTicks(ticks?: number): void;
+
+    /**
+     * Raises an error if the values are not equal.
+     */
+    function assertEquals(expected: any, actual: any, message?: string): void;
+
     /**
      * Registers an event handler called after all scripts are loaded.
      * @param event event name
@@ -305,5 +927,118 @@ declare namespace doukutsu {
      */
     function on(event: "tick", handler: EventHandler<DoukutsuStage>): EventHandler<DoukutsuStage>;
 
+    /**
+     * Registers an event handler called after entering or before leaving a stage.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "stageEnter" | "stageLeave",
+        handler: (this: void, scene: DoukutsuStage, stageId: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called when a TSC event starts or ends. Jumping to another event ends
+     * the previous one, while events called with <PSH are considered a part of the caller.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "eventStart" | "eventEnd",
+        handler: (this: void, scene: DoukutsuStage, eventNum: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called after a player is hurt by an enemy or hazard.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "playerDamaged",
+        handler: (this: void, scene: DoukutsuStage, player: DoukutsuPlayer, damage: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called after a player dies.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "playerDied",
+        handler: (this: void, scene: DoukutsuStage, player: DoukutsuPlayer) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called after an NPC is spawned during gameplay or killed.
+     * NPCs placed by the stage itself are not reported as spawned.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "npcSpawned" | "npcKilled",
+        handler: (this: void, scene: DoukutsuStage, npc: NPC, npcType: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called after an item is given by a script.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "itemAcquired",
+        handler: (this: void, scene: DoukutsuStage, itemId: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called after a weapon is given by a script.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "weaponAcquired",
+        handler: (this: void, scene: DoukutsuStage, weaponId: number, ammo: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called for each bullet fired by a player.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "bulletFired",
+        handler: (this: void, scene: DoukutsuStage, player: DoukutsuPlayer, bulletType: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called when a boss runs out of health or is killed by a script.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "bossDefeated",
+        handler: (this: void, scene: DoukutsuStage, bossType: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called after the game is saved to or loaded from a save slot.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "save" | "load",
+        handler: (this: void, scene: DoukutsuStage, slot: number) => void,
+    ): typeof handler;
+
+    /**
+     * Registers an event handler called during rendering of the stage, once for each draw layer.
+     * Drawing functions can only be used inside of this handler.
+     * @param event event name
+     * @param handler event handler procedure
+     */
+    function on(
+        event: "draw",
+        handler: (this: void, scene: DoukutsuStage, layer: DrawLayer) => void,
+    ): typeof handler;
+
     function on<T>(event: string, handler: EventHandler<T>): EventHandler<T>;
 }
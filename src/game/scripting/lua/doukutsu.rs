@@ -1,14 +1,22 @@
 use std::io::Read;
+use std::str::FromStr;
 
 use lua_ffi::{c_int, LuaObject, State};
 use lua_ffi::c_str;
 use lua_ffi::ffi::luaL_Reg;
 use lua_ffi::lua_method;
 
-use crate::common::{Direction, Rect};
+use crate::common::{Color, Direction, Rect};
 use crate::framework::filesystem;
-use crate::game::scripting::lua::{check_status, DRS_RUNTIME_GLOBAL, LuaScriptingState};
+use crate::framework::graphics;
+use crate::game::scripting::lua::{
+    check_status, report_error, LuaScriptingState, BOSS_PART_ID_BASE, DRS_RUNTIME_GLOBAL, GLOBAL_MEMORY_LIMIT_KB,
+};
+use crate::game::scripting::lua::scene::LuaBotBrain;
+use crate::game::scripting::tsc::opcodes::TSCOpCode;
 use crate::game::scripting::tsc::text_script::TextScriptEncoding;
+use crate::graphics::font::Font;
+use crate::input::bot_player_controller::BotPlayerController;
 use crate::scene::game_scene::LightingMode;
 use crate::util::rng::RNG;
 
@@ -169,7 +177,9 @@ impl Doukutsu {
                 }
                 0x1102 => {
                     // window title
-                    //TODO
+                    if let Some(title) = state.to_str(3) {
+                        game_state.constants.game.window_title = title.to_string();
+                    }
                 }
                 0x2000 => {
                     // font scale
@@ -214,7 +224,13 @@ impl Doukutsu {
         if let (Some(npc_id), Some(param_type)) = (state.to_int(2), state.to_int(3)) {
             let game_scene = &mut *(*self.ptr).game_scene;
 
-            let npc = match game_scene.npc_list.get_npc(npc_id as usize) {
+            let npc = if npc_id >= BOSS_PART_ID_BASE {
+                game_scene.boss.parts.get_mut((npc_id - BOSS_PART_ID_BASE) as usize)
+            } else {
+                game_scene.npc_list.get_npc(npc_id as usize)
+            };
+
+            let npc = match npc {
                 Some(npc) => npc,
                 None => {
                     state.push_nil();
@@ -477,6 +493,7 @@ impl Doukutsu {
                 }),
                 0x02 => state.push(game_state.settings.shader_effects),
                 0x03 => state.push(game_scene.stage_id as u32),
+                0x04 => state.push(game_state.control_flags.0 as u32),
                 0x101 => {
                     if let Some(v) = state.to_str(3) {
                         game_scene.lighting_mode = match v {
@@ -489,6 +506,13 @@ impl Doukutsu {
 
                     state.push_nil();
                 }
+                0x104 => {
+                    if let Some(v) = state.to_int(3) {
+                        game_state.control_flags.0 = v as u16;
+                    }
+
+                    state.push_nil();
+                }
                 _ => state.push_nil(),
             }
         } else {
@@ -498,6 +522,178 @@ impl Doukutsu {
         1
     }
 
+    unsafe fn lua_graphics_command(&self, state: &mut State) -> c_int {
+        if !(*self.ptr).drawing {
+            state.error("drawing is only allowed inside of draw handlers.");
+            return 0;
+        }
+
+        let game_state = &mut *(*self.ptr).state_ptr;
+        let ctx = &mut *(*self.ptr).ctx_ptr;
+
+        let result = match state.to_int(2) {
+            // sprite batch dimensions
+            Some(0x00) => {
+                let name = state.to_str(3).unwrap_or_default().to_string();
+
+                game_state.texture_set.get_or_load_batch(ctx, &game_state.constants, &name).map(|batch| {
+                    let (width, height) = batch.dimensions();
+                    state.push(width as i32);
+                    state.push(height as i32);
+
+                    2
+                })
+            }
+            // sprite batch add rect
+            Some(0x01) => {
+                let name = state.to_str(3).unwrap_or_default().to_string();
+                let x = state.to_float(4).unwrap_or(0.0);
+                let y = state.to_float(5).unwrap_or(0.0);
+                let rect = Rect::new(
+                    state.to_int(6).unwrap_or(0) as u16,
+                    state.to_int(7).unwrap_or(0) as u16,
+                    state.to_int(8).unwrap_or(0) as u16,
+                    state.to_int(9).unwrap_or(0) as u16,
+                );
+                let flags = state.to_int(10).unwrap_or(0);
+                let color = (
+                    state.to_int(11).unwrap_or(255) as u8,
+                    state.to_int(12).unwrap_or(255) as u8,
+                    state.to_int(13).unwrap_or(255) as u8,
+                    state.to_int(14).unwrap_or(255) as u8,
+                );
+                let scale_x = state.to_float(15).unwrap_or(1.0);
+                let scale_y = state.to_float(16).unwrap_or(1.0);
+
+                game_state.texture_set.get_or_load_batch(ctx, &game_state.constants, &name).map(|batch| {
+                    let (flip_x, flip_y) = (flags & 1 != 0, flags & 2 != 0);
+
+                    if scale_x != 1.0 || scale_y != 1.0 {
+                        batch.add_rect_flip_scaled_tinted(x, y, flip_x, flip_y, color, scale_x, scale_y, &rect);
+                    } else {
+                        batch.add_rect_flip_tinted(x, y, flip_x, flip_y, color, &rect);
+                    }
+
+                    0
+                })
+            }
+            // sprite batch draw
+            Some(0x02) => {
+                let name = state.to_str(3).unwrap_or_default().to_string();
+
+                game_state
+                    .texture_set
+                    .get_or_load_batch(ctx, &game_state.constants, &name)
+                    .and_then(|batch| batch.draw(ctx))
+                    .map(|_| 0)
+            }
+            // draw rect
+            Some(0x10) => {
+                let scale = game_state.scale;
+                let x = state.to_float(3).unwrap_or(0.0) * scale;
+                let y = state.to_float(4).unwrap_or(0.0) * scale;
+                let width = state.to_float(5).unwrap_or(0.0) * scale;
+                let height = state.to_float(6).unwrap_or(0.0) * scale;
+                let color = Color::from_rgba(
+                    state.to_int(7).unwrap_or(255) as u8,
+                    state.to_int(8).unwrap_or(255) as u8,
+                    state.to_int(9).unwrap_or(255) as u8,
+                    state.to_int(10).unwrap_or(255) as u8,
+                );
+
+                let rect = Rect::new(x as isize, y as isize, (x + width) as isize, (y + height) as isize);
+                graphics::draw_rect(ctx, rect, color).map(|_| 0)
+            }
+            // draw text
+            Some(0x20) => {
+                let text = state.to_str(3).unwrap_or_default().to_string();
+                let x = state.to_float(4).unwrap_or(0.0);
+                let y = state.to_float(5).unwrap_or(0.0);
+                let color = (
+                    state.to_int(6).unwrap_or(255) as u8,
+                    state.to_int(7).unwrap_or(255) as u8,
+                    state.to_int(8).unwrap_or(255) as u8,
+                    state.to_int(9).unwrap_or(255) as u8,
+                );
+                let shadow = state.to_bool(10).unwrap_or(false);
+                let scale = state.to_float(11).unwrap_or(1.0);
+
+                game_state
+                    .font
+                    .builder()
+                    .position(x, y)
+                    .color(color)
+                    .shadow(shadow)
+                    .scale(scale)
+                    .draw(&text, ctx, &game_state.constants, &mut game_state.texture_set)
+                    .map(|_| 0)
+            }
+            // text width
+            Some(0x21) => {
+                let text = state.to_str(3).unwrap_or_default().to_string();
+                let scale = state.to_float(4).unwrap_or(1.0);
+
+                state.push(game_state.font.builder().scale(scale).compute_width(&text));
+
+                Ok(1)
+            }
+            _ => Ok(0),
+        };
+
+        match result {
+            Ok(count) => count,
+            Err(err) => {
+                state.error(&err.to_string());
+                0
+            }
+        }
+    }
+
+    unsafe fn lua_set_bot_enabled(&self, state: &mut State) -> c_int {
+        if let Some(enabled) = state.to_bool(2) {
+            let game_state = &mut (*(*self.ptr).state_ptr);
+
+            game_state.bot = if enabled { Some(BotPlayerController::new(LuaBotBrain { ptr: self.ptr })) } else { None };
+        }
+
+        0
+    }
+
+    unsafe fn lua_register_tsc_command(&self, state: &mut State) -> c_int {
+        if let (Some(name), Some(argc)) = (state.to_str(2), state.to_int(3)) {
+            let name = name.to_string();
+
+            if name.len() != 3 || !name.is_ascii() || TSCOpCode::from_str(&name).is_ok() {
+                state.error(&format!("invalid TSC command name: {}", name));
+                return 0;
+            }
+
+            if !(0..=8).contains(&argc) {
+                state.error(&format!("invalid operand count for TSC command {}: {}", name, argc));
+                return 0;
+            }
+
+            let game_state = &mut (*(*self.ptr).state_ptr);
+            game_state.constants.tsc_custom_commands.insert(name, argc as u8);
+        }
+
+        0
+    }
+
+    unsafe fn lua_report_error(&self, state: &mut State) -> c_int {
+        if let Some(message) = state.to_str(2) {
+            report_error(&mut (*self.ptr).errors, message.to_string());
+        }
+
+        0
+    }
+
+    unsafe fn lua_memory_limit(&self, state: &mut State) -> c_int {
+        state.push(GLOBAL_MEMORY_LIMIT_KB);
+
+        1
+    }
+
     unsafe fn lua_load_script(&mut self, state: &mut State) -> c_int {
         let lua_state = &mut (*self.ptr);
 
@@ -522,19 +718,16 @@ impl Doukutsu {
                         return 0;
                     }
 
+                    lua_state.add_loaded_script(ctx, &path, false);
+
                     let res = state.load_buffer(&buf, &lua_vfs_path);
                     if let Err(err) = check_status(res, state) {
                         raise_error(&name, state, &err.to_string());
                         return 0;
                     }
 
-                    return match state.pcall(0, 1, 0) {
-                        Ok(_) => 1,
-                        Err((_, err)) => {
-                            raise_error(&name, state, &err);
-                            0
-                        }
-                    };
+                    // the chunk is run by `require`, in the environment of the calling script
+                    return 1;
                 }
                 Err(err) => {
                     raise_error(&name, state, &err.to_string());
@@ -564,6 +757,19 @@ impl LuaObject for Doukutsu {
             lua_method!("npcCommand", Doukutsu, Doukutsu::lua_npc_command),
             lua_method!("stageCommand", Doukutsu, Doukutsu::lua_stage_command),
             lua_method!("loadScript", Doukutsu, Doukutsu::lua_load_script),
+            lua_method!("setBotEnabled", Doukutsu, Doukutsu::lua_set_bot_enabled),
+            lua_method!("registerTSCCommand", Doukutsu, Doukutsu::lua_register_tsc_command),
+            lua_method!("graphicsCommand", Doukutsu, Doukutsu::lua_graphics_command),
+            lua_method!("bulletCommand", Doukutsu, Doukutsu::lua_bullet_command),
+            lua_method!("weaponCommand", Doukutsu, Doukutsu::lua_weapon_command),
+            lua_method!("reportError", Doukutsu, Doukutsu::lua_report_error),
+            lua_method!("memoryLimit", Doukutsu, Doukutsu::lua_memory_limit),
+            lua_method!("mapCommand", Doukutsu, Doukutsu::lua_map_command),
+            lua_method!("cameraCommand", Doukutsu, Doukutsu::lua_camera_command),
+            lua_method!("inventoryCommand", Doukutsu, Doukutsu::lua_inventory_command),
+            lua_method!("textBoxCommand", Doukutsu, Doukutsu::lua_text_box_command),
+            lua_method!("spawnCommand", Doukutsu, Doukutsu::lua_spawn_command),
+            lua_method!("storageCommand", Doukutsu, Doukutsu::lua_storage_command),
         ]
     }
 }
@@ -580,7 +786,33 @@ impl LuaScriptingState {
             state.push(npc_type as i32);
 
             if let Err((_, err)) = state.pcall(2, 1, 0) {
-                log::error!("npc_hook error: {}", err);
+                report_error(&mut self.errors, format!("npc_hook error: {}", err));
+            }
+
+            if let Some(val) = state.to_bool(-1) {
+                result = val;
+            }
+
+            state.pop(2);
+        }
+
+        result
+    }
+
+    /// Runs the handler registered for specified boss type, returns true if there was one.
+    pub fn try_run_boss_hook(&mut self, boss_type: u16, part_count: usize) -> bool {
+        let mut result = false;
+
+        if let Some(state) = &mut self.state {
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_tryBossHook");
+
+            state.push(boss_type as i32);
+            state.push(BOSS_PART_ID_BASE);
+            state.push(part_count as i32);
+
+            if let Err((_, err)) = state.pcall(3, 1, 0) {
+                report_error(&mut self.errors, format!("boss_hook error: {}", err));
             }
 
             if let Some(val) = state.to_bool(-1) {
@@ -592,4 +824,36 @@ impl LuaScriptingState {
 
         result
     }
+
+    /// Runs the handler of a script-registered TSC command.
+    ///
+    /// Returns the number of ticks the VM should wait before continuing, or `None` if the command
+    /// is blocking and should be executed again on next tick.
+    pub fn run_tsc_command(&mut self, name: &str, args: &[i32]) -> Option<u16> {
+        let mut result = Some(0);
+
+        if let Some(state) = &mut self.state {
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_runTSCCommand");
+
+            state.push(name);
+            for arg in args.iter() {
+                state.push(*arg);
+            }
+
+            if let Err((_, err)) = state.pcall(1 + args.len() as i32, 1, 0) {
+                report_error(&mut self.errors, format!("TSC command {} error: {}", name, err));
+            }
+
+            match state.to_int(-1) {
+                Some(ticks) if ticks < 0 => result = None,
+                Some(ticks) => result = Some(ticks.min(u16::MAX as i32) as u16),
+                None => {}
+            }
+
+            state.pop(2);
+        }
+
+        result
+    }
 }
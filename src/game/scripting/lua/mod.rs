@@ -1,8 +1,12 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
 use std::io::Read;
 use std::ptr::null_mut;
+use std::time::SystemTime;
 
 use lua_ffi::{c_int, State, ThreadStatus};
-use lua_ffi::ffi::lua_State;
+use lua_ffi::c_str;
+use lua_ffi::ffi::{lua_Debug, lua_State};
 use lua_ffi::lua_fn;
 use lua_ffi::types::LuaValue;
 
@@ -12,17 +16,61 @@ use crate::framework::error::{GameError, GameResult};
 use crate::framework::filesystem;
 use crate::framework::filesystem::File;
 use crate::game::scripting::lua::doukutsu::Doukutsu;
+use crate::game::scripting::lua::weapons::WeaponHookContext;
 use crate::game::shared_game_state::SharedGameState;
+use crate::game::weapon::bullet::Bullet;
 use crate::scene::game_scene::GameScene;
 
 mod doukutsu;
 mod scene;
+mod storage;
+pub mod test_runner;
+mod weapons;
+mod world;
+
+/// NPC ids passed to Lua starting from this value refer to parts of the boss.
+pub(crate) const BOSS_PART_ID_BASE: i32 = 0x10000;
+
+/// Number of Lua VM instructions all scripts together are allowed to execute during a single game tick
+/// or rendered frame.
+pub const INSTRUCTION_LIMIT_PER_TICK: u32 = 10_000_000;
+/// Maximum amount of memory used by the Lua heap, in kilobytes. All scripts share a single Lua state,
+/// so this caps their combined usage rather than the usage of each mod.
+pub const GLOBAL_MEMORY_LIMIT_KB: c_int = 64 * 1024;
+/// Number of most recent script errors kept for the debugger.
+const MAX_ERRORS: usize = 50;
+/// The instruction hook is called every this many instructions.
+const HOOK_INTERVAL: c_int = 1000;
+/// Number of ticks between checks for modified script files.
+const WATCH_INTERVAL: u32 = 60;
+
+const LUA_MASKCOUNT: c_int = 1 << 3;
+const LUA_GCCOLLECT: c_int = 2;
+const LUA_GCCOUNT: c_int = 3;
+
+thread_local! {
+    static INSTRUCTION_COUNT: Cell<u32> = Cell::new(0);
+}
+
+/// A script file run by the engine, either directly or through `require`.
+pub struct LoadedScript {
+    pub path: String,
+    /// Whether the file comes from the user directory rather than game data.
+    pub user: bool,
+    modified: Option<SystemTime>,
+}
 
 pub struct LuaScriptingState {
     state: Option<State>,
     state_ptr: *mut SharedGameState,
     ctx_ptr: *mut Context,
     game_scene: *mut GameScene,
+    drawing: bool,
+    bullet: *mut Bullet,
+    weapon_ctx: Option<WeaponHookContext>,
+    errors: VecDeque<String>,
+    scripts: Vec<LoadedScript>,
+    watch_timer: u32,
 }
 
 pub(crate) static DRS_API_GLOBAL: &str = "__doukutsu_rs";
@@ -52,6 +100,78 @@ pub(crate) fn check_status(status: ThreadStatus, state: &mut State) -> GameResul
     }
 }
 
+/// Records an error raised by a script, so it can be shown in the debugger.
+pub(crate) fn report_error(errors: &mut VecDeque<String>, message: String) {
+    log::error!("[Lua] {}", message);
+
+    if errors.len() >= MAX_ERRORS {
+        errors.pop_front();
+    }
+    errors.push_back(message);
+}
+
+/// Aborts scripts which exceed the instruction budget of the current tick or the memory limit.
+unsafe extern "C" fn limit_hook(l: *mut lua_State, _ar: *mut lua_Debug) {
+    let count = INSTRUCTION_COUNT.with(|c| {
+        let count = c.get().saturating_add(HOOK_INTERVAL as u32);
+        c.set(count);
+        count
+    });
+
+    if count > INSTRUCTION_LIMIT_PER_TICK {
+        lua_ffi::ffi::lua_pushstring(l, c_str!("instruction limit exceeded"));
+        lua_ffi::ffi::lua_error(l);
+        return;
+    }
+
+    if lua_ffi::ffi::lua_gc(l, LUA_GCCOUNT, 0) > GLOBAL_MEMORY_LIMIT_KB {
+        lua_ffi::ffi::lua_gc(l, LUA_GCCOLLECT, 0);
+
+        if lua_ffi::ffi::lua_gc(l, LUA_GCCOUNT, 0) > GLOBAL_MEMORY_LIMIT_KB {
+            lua_ffi::ffi::lua_pushstring(l, c_str!("memory limit exceeded"));
+            lua_ffi::ffi::lua_error(l);
+        }
+    }
+}
+
+/// Brings a script path to the form used by the virtual filesystem, so paths from chunk names and
+/// directory listings can be compared.
+fn normalize_script_path(path: &str) -> String {
+    let path = path.trim_matches('"').trim_start_matches('@').replace('\\', "/");
+
+    if path.starts_with('/') {
+        path
+    } else {
+        format!("/{}", path)
+    }
+}
+
+/// Returns the script an error comes from, taken either from the message of a loading error,
+/// or from the `path:line:` location Lua prefixes runtime errors with.
+fn error_source(error: &str) -> Option<String> {
+    if let Some(rest) = error.strip_prefix("Error ") {
+        if let Some((_, rest)) = rest.split_once(" script ") {
+            let path = rest.split_once(": ").map_or(rest, |(path, _)| path);
+            return Some(normalize_script_path(path));
+        }
+    }
+
+    let mut search_from = 0;
+    while let Some(pos) = error[search_from..].find(".lua:") {
+        let end = search_from + pos + ".lua".len();
+        let line = error[end + 1..].split(':').next().unwrap_or("");
+
+        if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) {
+            let start = error[..end].rfind(|c: char| c.is_whitespace() || c == ':').map_or(0, |i| i + 1);
+            return Some(normalize_script_path(&error[start..end]));
+        }
+
+        search_from = end;
+    }
+
+    None
+}
+
 fn print(state: &mut State) -> c_int {
     if let Some(msg) = state.to_str(1) {
         log::info!("[Lua] {}", msg);
@@ -62,7 +182,85 @@ fn print(state: &mut State) -> c_int {
 
 impl LuaScriptingState {
     pub fn new() -> LuaScriptingState {
-        LuaScriptingState { state: None, state_ptr: null_mut(), ctx_ptr: null_mut(), game_scene: null_mut() }
+        LuaScriptingState {
+            state: None,
+            state_ptr: null_mut(),
+            ctx_ptr: null_mut(),
+            game_scene: null_mut(),
+            drawing: false,
+            bullet: null_mut(),
+            weapon_ctx: None,
+            errors: VecDeque::new(),
+            scripts: Vec::new(),
+            watch_timer: 0,
+        }
+    }
+
+    /// Returns true if scripts have been loaded at least once.
+    pub fn is_loaded(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Script files loaded since the last reload, in order of loading.
+    pub fn scripts(&self) -> &[LoadedScript] {
+        &self.scripts
+    }
+
+    /// Most recent error raised in specified script file.
+    pub fn last_error_of(&self, script: &LoadedScript) -> Option<&String> {
+        let path = normalize_script_path(&script.path);
+
+        self.errors.iter().rev().find(|err| error_source(err).as_deref() == Some(path.as_str()))
+    }
+
+    fn script_modified(ctx: &Context, path: &str, user: bool) -> Option<SystemTime> {
+        if user {
+            filesystem::user_modified(ctx, path)
+        } else {
+            filesystem::modified(ctx, path)
+        }
+    }
+
+    pub(crate) fn add_loaded_script(&mut self, ctx: &Context, path: &str, user: bool) {
+        if !self.scripts.iter().any(|script| script.path == path) {
+            let modified = LuaScriptingState::script_modified(ctx, path, user);
+            self.scripts.push(LoadedScript { path: path.to_owned(), user, modified });
+        }
+    }
+
+    /// Returns true if any of the loaded script files has been modified or removed since it was loaded.
+    /// Files are only checked every [`WATCH_INTERVAL`] calls, as this is meant to be polled every tick.
+    pub fn scripts_changed(&mut self, ctx: &Context) -> bool {
+        self.watch_timer += 1;
+        if self.watch_timer < WATCH_INTERVAL {
+            return false;
+        }
+        self.watch_timer = 0;
+
+        self.scripts.iter().any(|script| {
+            let exists = if script.user {
+                filesystem::user_exists(ctx, &script.path)
+            } else {
+                filesystem::exists(ctx, &script.path)
+            };
+
+            !exists || LuaScriptingState::script_modified(ctx, &script.path, script.user) != script.modified
+        })
+    }
+
+    /// Most recent errors raised by scripts, oldest first.
+    pub fn errors(&self) -> &VecDeque<String> {
+        &self.errors
+    }
+
+    pub fn clear_errors(&mut self) {
+        self.errors.clear();
+    }
+
+    /// Gives scripts a fresh instruction budget, called at the start of each game tick and rendered frame,
+    /// as well as before loading scripts.
+    pub fn reset_instruction_count() {
+        INSTRUCTION_COUNT.with(|c| c.set(0));
     }
 
     pub fn update_refs(&mut self, state: *mut SharedGameState, ctx: *mut Context) {
@@ -74,30 +272,37 @@ impl LuaScriptingState {
         self.game_scene = game_scene;
     }
 
-    fn load_script(state: &mut State, path: &str, mut script: File) -> bool {
+    /// Loads and runs a script, mod scripts are given a sandboxed environment without access to
+    /// `io`, `os`, `ffi`, `debug` and the functions loading code from files, while user scripts are trusted.
+    fn load_script(&mut self, ctx: &Context, state: &mut State, path: &str, mut script: File, user: bool) -> bool {
         let mut buf = Vec::new();
         let res = script.read_to_end(&mut buf);
 
         if let Err(err) = res {
-            log::warn!("Error reading script {}: {}", path, err);
+            report_error(&mut self.errors, format!("Error reading script {}: {}", path, err));
             return false;
         }
 
+        self.add_loaded_script(ctx, path, user);
+
         let name = format!("@{}", path);
         let res = state.load_buffer(&buf, &name);
         let res = check_status(res, state);
         if let Err(err) = res {
-            log::warn!("Error loading script {}: {}", path, err);
+            report_error(&mut self.errors, format!("Error loading script {}: {}", path, err));
             return false;
         }
 
         state.get_global(DRS_RUNTIME_GLOBAL);
         state.get_field(-1, "_initializeScript");
         state.push_value(-3);
+        state.push(!user);
+        state.push(path);
 
-        let res = state.pcall(1, 0, 0);
+        LuaScriptingState::reset_instruction_count();
+        let res = state.pcall(3, 0, 0);
         if let Err((_, err)) = res {
-            log::warn!("Error evaluating script {}: {}", path, err);
+            report_error(&mut self.errors, format!("Error evaluating script {}: {}", path, err));
             return false;
         }
 
@@ -106,19 +311,42 @@ impl LuaScriptingState {
         true
     }
 
-    pub fn reload_scripts(&mut self, ctx: &mut Context) -> GameResult {
-        let mut state = State::new();
-        state.open_libs();
+    /// Loads all scripts again, discarding registered handlers.
+    ///
+    /// If `preserve_state` is set, scripts are run again inside of the current Lua state instead of a new one,
+    /// and the previous environment of each script is passed to its `onReload` function if it defines one.
+    pub fn reload_scripts(&mut self, ctx: &mut Context, preserve_state: bool) -> GameResult {
+        self.errors.clear();
+        self.scripts.clear();
+        self.watch_timer = 0;
+
+        let mut state = match self.state.take() {
+            Some(state) if preserve_state => state,
+            _ => {
+                let mut state = State::new();
+                state.open_libs();
+
+                unsafe {
+                    lua_ffi::ffi::lua_sethook(state.get_state(), Some(limit_hook), LUA_MASKCOUNT, HOOK_INTERVAL);
+                }
 
-        state.push(lua_fn!(print));
-        state.set_global("print");
+                state.push(lua_fn!(print));
+                state.set_global("print");
 
-        state.push(Doukutsu { ptr: self as *mut LuaScriptingState });
-        state.set_global(DRS_API_GLOBAL);
+                state.push(Doukutsu { ptr: self as *mut LuaScriptingState });
+                state.set_global(DRS_API_GLOBAL);
+
+                state
+            }
+        };
 
         log::info!("Initializing Lua scripting engine...");
+        LuaScriptingState::reset_instruction_count();
         let res = state.do_string(BOOT_SCRIPT);
-        check_status(res, &mut state)?;
+        if let Err(err) = check_status(res, &mut state) {
+            report_error(&mut self.errors, err.to_string());
+            return Err(err);
+        }
 
         if filesystem::user_exists(ctx, "/drs-scripts/") {
             let mut script_count = 0;
@@ -130,12 +358,12 @@ impl LuaScriptingState {
 
                 match filesystem::user_open(ctx, file) {
                     Ok(script) => {
-                        if LuaScriptingState::load_script(&mut state, &path.to_string_lossy(), script) {
+                        if self.load_script(ctx, &mut state, &path.to_string_lossy(), script, true) {
                             script_count += 1;
                         }
                     }
                     Err(err) => {
-                        log::warn!("Error opening script {:?}: {}", path, err);
+                        report_error(&mut self.errors, format!("Error opening script {:?}: {}", path, err));
                     }
                 }
             }
@@ -151,12 +379,12 @@ impl LuaScriptingState {
 
             match filesystem::open(ctx, modcs_path) {
                 Ok(script) => {
-                    if !LuaScriptingState::load_script(&mut state, modcs_path, script) {
+                    if !self.load_script(ctx, &mut state, modcs_path, script, false) {
                         log::warn!("Error loading ModCS main script.");
                     }
                 }
                 Err(err) => {
-                    log::warn!("Error opening script {:?}: {}", modcs_path, err);
+                    report_error(&mut self.errors, format!("Error opening script {:?}: {}", modcs_path, err));
                 }
             }
         }
@@ -165,6 +393,29 @@ impl LuaScriptingState {
 
         Ok(())
     }
+
+    /// Loads Lua entrypoints declared in the manifest of the started mod.
+    pub fn load_mod_scripts(&mut self, ctx: &mut Context, paths: &[String]) {
+        let mut state = match self.state.take() {
+            Some(state) => state,
+            None => return,
+        };
+
+        for path in paths {
+            match filesystem::open(ctx, path) {
+                Ok(script) => {
+                    if !self.load_script(ctx, &mut state, path, script, false) {
+                        log::warn!("Error loading mod script {}.", path);
+                    }
+                }
+                Err(err) => {
+                    report_error(&mut self.errors, format!("Error opening script {:?}: {}", path, err));
+                }
+            }
+        }
+
+        self.state = Some(state);
+    }
 }
 
 impl LuaValue for Rect<u16> {
@@ -230,3 +481,38 @@ impl LuaValue for Rect<f32> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_string_metatable() {
+        let mut state = State::new();
+        state.open_libs();
+
+        let res = state.do_string("__doukutsu_rs = { memoryLimit = function() return 64 * 1024 end }");
+        check_status(res, &mut state).unwrap();
+        let res = state.do_string(BOOT_SCRIPT);
+        check_status(res, &mut state).unwrap();
+
+        let res = state.do_string(
+            r#"
+            local env = __doukutsu_rs_runtime_dont_touch._createSandbox()
+            local function run(code)
+                local chunk = loadstring(code)
+                setfenv(chunk, env)
+                return pcall(chunk)
+            end
+
+            local ok, err = run('return ("x"):rep(1e9)')
+            assert(not ok and err == "memory limit exceeded", "string method skipped the memory check")
+
+            ok = run('getmetatable("").__index.format = function() return "" end')
+            assert(not ok, "string metatable is reachable")
+            assert(("%d"):format(1) == "1" and string.format("%d", 1) == "1")
+            "#,
+        );
+        check_status(res, &mut state).unwrap();
+    }
+}
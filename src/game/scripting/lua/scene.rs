@@ -3,7 +3,10 @@ use lua_ffi::c_str;
 use lua_ffi::ffi::luaL_Reg;
 use lua_ffi::lua_method;
 
-use crate::game::scripting::lua::{DRS_RUNTIME_GLOBAL, LuaScriptingState};
+use crate::game::scripting::events::{DrawLayer, ScriptEvent};
+use crate::game::scripting::lua::{report_error, DRS_RUNTIME_GLOBAL, LuaScriptingState};
+use crate::input::bot_player_controller::{BotBrain, BotView};
+use crate::input::replay_player_controller::KeyState;
 use crate::scene::game_scene::GameScene;
 
 pub struct LuaGameScene {
@@ -50,10 +53,96 @@ impl LuaScriptingState {
 
             state.push(val);
             if let Err((_, err)) = state.pcall(1, 0, 0) {
-                println!("scene_tick error: {}", err);
+                report_error(&mut self.errors, format!("tick handler error: {}", err));
             }
 
             state.pop(2);
         }
     }
+
+    /// Calls `draw` handlers, which are allowed to render only for the duration of this call.
+    pub fn draw_hook(&mut self, layer: DrawLayer) {
+        if let Some(state) = &mut self.state {
+            self.drawing = true;
+
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_handlers");
+            state.get_field(-1, "draw");
+
+            state.push(LuaGameScene::new(self.game_scene));
+            state.push(layer.name());
+
+            if let Err((_, err)) = state.pcall(2, 0, 0) {
+                report_error(&mut self.errors, format!("draw hook error: {}", err));
+            }
+
+            state.pop(2);
+
+            self.drawing = false;
+        }
+    }
+
+    pub fn dispatch_events(&mut self, events: &[ScriptEvent]) {
+        if let Some(state) = &mut self.state {
+            for event in events {
+                let args = event.args();
+
+                state.get_global(DRS_RUNTIME_GLOBAL);
+                state.get_field(-1, "_handlers");
+                state.get_field(-1, event.name());
+
+                state.push(LuaGameScene::new(self.game_scene));
+                for arg in args.iter() {
+                    state.push(*arg);
+                }
+
+                if let Err((_, err)) = state.pcall(1 + args.len() as i32, 0, 0) {
+                    report_error(&mut self.errors, format!("{} event error: {}", event.name(), err));
+                }
+
+                state.pop(2);
+            }
+        }
+    }
+}
+
+/// Bot brain that forwards decisions to the handler registered with `doukutsu.setBotHandler`.
+pub struct LuaBotBrain {
+    pub ptr: *mut LuaScriptingState,
+}
+
+impl BotBrain for LuaBotBrain {
+    fn think(&mut self, _view: &BotView, keys: &mut KeyState) {
+        unsafe {
+            if let Some(mask) = (*self.ptr).run_bot_hook() {
+                *keys = KeyState(mask);
+            }
+        }
+    }
+}
+
+impl LuaScriptingState {
+    pub fn run_bot_hook(&mut self) -> Option<u16> {
+        let mut result = None;
+
+        if let Some(state) = &mut self.state {
+            let val = LuaGameScene::new(self.game_scene);
+
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_tryBotHook");
+
+            state.push(val);
+            if let Err((_, err)) = state.pcall(1, 1, 0) {
+                report_error(&mut self.errors, format!("bot_hook error: {}", err));
+            }
+
+            if let Some(mask) = state.to_int(-1) {
+                result = Some(mask as u16);
+            }
+
+            state.pop(2);
+        }
+
+        result
+    }
 }
@@ -0,0 +1,217 @@
+use std::slice;
+
+use lua_ffi::ffi::lua_State;
+use lua_ffi::{c_int, State};
+use serde_json::{Map, Number, Value};
+
+use crate::game::scripting::lua::doukutsu::Doukutsu;
+
+const LUA_TNIL: c_int = 0;
+const LUA_TBOOLEAN: c_int = 1;
+const LUA_TNUMBER: c_int = 3;
+const LUA_TSTRING: c_int = 4;
+const LUA_TTABLE: c_int = 5;
+
+/// Limits the nesting of stored tables, which also catches tables referencing themselves.
+const MAX_DEPTH: usize = 32;
+
+enum TableKey {
+    String(String),
+    Number(f64),
+}
+
+unsafe fn to_string(l: *mut lua_State, idx: c_int) -> String {
+    let mut len = 0;
+    let ptr = lua_ffi::ffi::lua_tolstring(l, idx, &mut len);
+
+    String::from_utf8_lossy(slice::from_raw_parts(ptr as *const u8, len as usize)).into_owned()
+}
+
+fn number_to_json(n: f64) -> Result<Value, String> {
+    if n.fract() == 0.0 && n.abs() < (1u64 << 53) as f64 {
+        return Ok(Value::from(n as i64));
+    }
+
+    Number::from_f64(n).map(Value::Number).ok_or_else(|| "NaN and infinity can't be stored.".to_owned())
+}
+
+/// Converts the Lua value at specified stack index to JSON. Tables with consecutive integer keys
+/// starting from 1 become arrays, other tables become objects with their keys converted to strings.
+unsafe fn to_json(l: *mut lua_State, idx: c_int, depth: usize) -> Result<Value, String> {
+    let idx = if idx < 0 { lua_ffi::ffi::lua_gettop(l) + idx + 1 } else { idx };
+
+    match lua_ffi::ffi::lua_type(l, idx) {
+        LUA_TNIL => Ok(Value::Null),
+        LUA_TBOOLEAN => Ok(Value::Bool(lua_ffi::ffi::lua_toboolean(l, idx) != 0)),
+        LUA_TNUMBER => number_to_json(lua_ffi::ffi::lua_tonumber(l, idx) as f64),
+        LUA_TSTRING => Ok(Value::String(to_string(l, idx))),
+        LUA_TTABLE => {
+            if depth >= MAX_DEPTH {
+                return Err("Tables are nested too deeply or contain a cycle.".to_owned());
+            }
+
+            let mut entries = Vec::new();
+
+            lua_ffi::ffi::lua_pushnil(l);
+            while lua_ffi::ffi::lua_next(l, idx) != 0 {
+                let key = match lua_ffi::ffi::lua_type(l, -2) {
+                    LUA_TSTRING => TableKey::String(to_string(l, -2)),
+                    LUA_TNUMBER => TableKey::Number(lua_ffi::ffi::lua_tonumber(l, -2) as f64),
+                    _ => {
+                        lua_ffi::ffi::lua_settop(l, -3);
+                        return Err("Only strings and numbers can be used as table keys.".to_owned());
+                    }
+                };
+
+                let value = match to_json(l, -1, depth + 1) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        lua_ffi::ffi::lua_settop(l, -3);
+                        return Err(err);
+                    }
+                };
+
+                lua_ffi::ffi::lua_settop(l, -2);
+                entries.push((key, value));
+            }
+
+            Ok(table_to_json(entries))
+        }
+        _ => Err("Only nil, booleans, numbers, strings and tables can be stored.".to_owned()),
+    }
+}
+
+/// Builds the JSON value of a table from its entries, in any order.
+fn table_to_json(entries: Vec<(TableKey, Value)>) -> Value {
+    let is_array = !entries.is_empty()
+        && entries.iter().all(|(key, _)| {
+            matches!(key, TableKey::Number(n) if n.fract() == 0.0 && *n >= 1.0 && *n <= entries.len() as f64)
+        });
+
+    if is_array {
+        let mut array = vec![Value::Null; entries.len()];
+        for (key, value) in entries {
+            if let TableKey::Number(n) = key {
+                array[n as usize - 1] = value;
+            }
+        }
+
+        Value::Array(array)
+    } else {
+        let mut object = Map::new();
+        for (key, value) in entries {
+            let key = match key {
+                TableKey::String(key) => key,
+                TableKey::Number(n) => n.to_string(),
+            };
+            object.insert(key, value);
+        }
+
+        Value::Object(object)
+    }
+}
+
+unsafe fn push_json(l: *mut lua_State, value: &Value) {
+    match value {
+        Value::Null => lua_ffi::ffi::lua_pushnil(l),
+        Value::Bool(v) => lua_ffi::ffi::lua_pushboolean(l, *v as c_int),
+        Value::Number(v) => lua_ffi::ffi::lua_pushnumber(l, v.as_f64().unwrap_or(0.0) as _),
+        Value::String(v) => lua_ffi::ffi::lua_pushlstring(l, v.as_ptr() as _, v.len() as _),
+        Value::Array(values) => {
+            lua_ffi::ffi::lua_createtable(l, values.len() as c_int, 0);
+            for (i, v) in values.iter().enumerate() {
+                push_json(l, v);
+                lua_ffi::ffi::lua_rawseti(l, -2, (i + 1) as _);
+            }
+        }
+        Value::Object(values) => {
+            lua_ffi::ffi::lua_createtable(l, 0, values.len() as c_int);
+            for (k, v) in values.iter() {
+                lua_ffi::ffi::lua_pushlstring(l, k.as_ptr() as _, k.len() as _);
+                push_json(l, v);
+                lua_ffi::ffi::lua_rawset(l, -3);
+            }
+        }
+    }
+}
+
+impl Doukutsu {
+    pub(super) unsafe fn lua_storage_command(&self, state: &mut State) -> c_int {
+        let game_state = &mut *(*self.ptr).state_ptr;
+        let mod_id = game_state.get_script_storage_id();
+
+        let key = match state.to_str(3) {
+            Some(key) => key.to_string(),
+            None => {
+                state.error("Storage key must be a string.");
+                return 0;
+            }
+        };
+
+        match state.to_int(2) {
+            Some(0x00) => {
+                match game_state.script_storage.get(&mod_id, &key) {
+                    Some(value) => push_json(state.get_state(), value),
+                    None => state.push_nil(),
+                }
+
+                1
+            }
+            Some(0x100) => {
+                match to_json(state.get_state(), 4, 0) {
+                    Ok(value) => game_state.script_storage.set(&mod_id, &key, value),
+                    Err(err) => state.error(&format!("Cannot store value of {}: {}", key, err)),
+                }
+
+                0
+            }
+            _ => {
+                state.push_nil();
+                1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_table_to_json_arrays() {
+        let entries = vec![
+            (TableKey::Number(2.0), json!("b")),
+            (TableKey::Number(1.0), json!("a")),
+            (TableKey::Number(3.0), json!(true)),
+        ];
+        assert_eq!(table_to_json(entries), json!(["a", "b", true]));
+
+        // holes and keys not starting from 1 make an object
+        let entries = vec![(TableKey::Number(1.0), json!(1)), (TableKey::Number(3.0), json!(3))];
+        assert_eq!(table_to_json(entries), json!({ "1": 1, "3": 3 }));
+
+        let entries = vec![(TableKey::Number(0.0), json!(0))];
+        assert_eq!(table_to_json(entries), json!({ "0": 0 }));
+
+        let entries = vec![(TableKey::Number(1.5), json!(0))];
+        assert_eq!(table_to_json(entries), json!({ "1.5": 0 }));
+    }
+
+    #[test]
+    fn test_table_to_json_objects() {
+        assert_eq!(table_to_json(Vec::new()), json!({}));
+
+        let entries = vec![(TableKey::Number(1.0), json!("a")), (TableKey::String("name".to_owned()), json!("b"))];
+        assert_eq!(table_to_json(entries), json!({ "1": "a", "name": "b" }));
+    }
+
+    #[test]
+    fn test_number_to_json() {
+        assert_eq!(number_to_json(3.0), Ok(json!(3)));
+        assert_eq!(number_to_json(-0.5), Ok(json!(-0.5)));
+        assert!(number_to_json(f64::NAN).is_err());
+        assert!(number_to_json(f64::INFINITY).is_err());
+    }
+}
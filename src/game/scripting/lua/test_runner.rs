@@ -0,0 +1,423 @@
+//! Headless runner of test cases registered by scripts with `doukutsu.test`.
+//!
+//! Tests are loaded from `/Scripts/tests/*.lua` after the regular scripts of the tested mod,
+//! each test runs as a coroutine on a freshly loaded stage and the game is ticked whenever it calls
+//! `doukutsu.advanceTicks`. Results are written to stdout in TAP or JUnit XML format.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::common::FadeState;
+use crate::data::builtin_fs::BuiltinFS;
+use crate::framework::backend::init_backend;
+use crate::framework::context::Context;
+use crate::framework::error::{GameError, GameResult};
+use crate::framework::filesystem;
+use crate::framework::filesystem::{mount_user_vfs, mount_vfs};
+use crate::framework::vfs::PhysicalFS;
+use crate::game::init_logger;
+use crate::game::scripting::lua::{report_error, LuaScriptingState, DRS_RUNTIME_GLOBAL};
+use crate::game::shared_game_state::SharedGameState;
+use crate::scene::game_scene::GameScene;
+use crate::scene::Scene;
+
+/// Tests running for longer than this many ticks fail, so a test waiting forever doesn't stall the CI.
+const MAX_TEST_TICKS: u32 = 60 * 60 * 10;
+
+const TESTS_PATH: &str = "/Scripts/tests/";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestReportFormat {
+    Tap,
+    JUnit,
+}
+
+pub struct ScriptTestOptions {
+    /// Directory with game data used as the test fixture.
+    pub data_dir: PathBuf,
+    /// Id of the tested mod, scripts of the base game are tested if `None`.
+    pub mod_id: Option<String>,
+    /// Stage each test starts on, the new game stage is used if `None`.
+    pub stage_id: Option<usize>,
+    pub format: TestReportFormat,
+}
+
+pub struct TestResult {
+    pub name: String,
+    /// Error message of the failed test, `None` if it passed.
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+/// Directory removed when the test run ends.
+struct TempDir(PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+enum TestStep {
+    Wait(u32),
+    Passed,
+    Failed(String),
+}
+
+impl LuaScriptingState {
+    /// Loads test scripts of the current mod in sandboxed environments, returns the number of loaded files.
+    fn load_test_scripts(&mut self, ctx: &mut Context) -> usize {
+        let mut state = match self.state.take() {
+            Some(state) => state,
+            None => return 0,
+        };
+
+        let mut files: Vec<PathBuf> = match filesystem::read_dir(ctx, TESTS_PATH) {
+            Ok(files) => files.filter(|f| f.to_string_lossy().to_lowercase().ends_with(".lua")).collect(),
+            Err(_) => Vec::new(),
+        };
+        files.sort();
+
+        let mut script_count = 0;
+        for path in files {
+            match filesystem::open(ctx, &path) {
+                Ok(script) => {
+                    if self.load_script(ctx, &mut state, &path.to_string_lossy(), script, false) {
+                        script_count += 1;
+                    }
+                }
+                Err(err) => {
+                    report_error(&mut self.errors, format!("Error opening script {:?}: {}", path, err));
+                }
+            }
+        }
+
+        self.state = Some(state);
+
+        script_count
+    }
+
+    fn test_count(&mut self) -> usize {
+        let mut count = 0;
+
+        if let Some(state) = &mut self.state {
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_testCount");
+
+            LuaScriptingState::reset_instruction_count();
+            if let Err((_, err)) = state.pcall(0, 1, 0) {
+                report_error(&mut self.errors, format!("Error listing tests: {}", err));
+            }
+
+            count = state.to_int(-1).unwrap_or(0).max(0) as usize;
+            state.pop(2);
+        }
+
+        count
+    }
+
+    /// Prepares the test with specified index (starting from 1) to run, returns its name.
+    fn start_test(&mut self, index: usize) -> String {
+        let mut name = format!("test {}", index);
+
+        if let Some(state) = &mut self.state {
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_startTest");
+            state.push(index as i32);
+
+            LuaScriptingState::reset_instruction_count();
+            if let Err((_, err)) = state.pcall(1, 1, 0) {
+                report_error(&mut self.errors, format!("Error starting test {}: {}", index, err));
+            } else if let Some(test_name) = state.to_str(-1) {
+                name = test_name.to_owned();
+            }
+
+            state.pop(2);
+        }
+
+        name
+    }
+
+    fn resume_test(&mut self) -> TestStep {
+        let mut step = TestStep::Failed("Lua scripting is not initialized.".to_owned());
+
+        if let Some(state) = &mut self.state {
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_resumeTest");
+
+            LuaScriptingState::reset_instruction_count();
+            if let Err((_, err)) = state.pcall(0, 2, 0) {
+                state.pop(2);
+                return TestStep::Failed(err);
+            }
+
+            step = match state.to_str(-2) {
+                Some("wait") => TestStep::Wait(state.to_int(-1).unwrap_or(1).max(0) as u32),
+                Some("passed") => TestStep::Passed,
+                _ => TestStep::Failed(state.to_str(-1).unwrap_or("???").to_owned()),
+            };
+
+            state.pop(3);
+        }
+
+        step
+    }
+}
+
+fn create_test_scene(state: &mut SharedGameState, ctx: &mut Context, stage_id: usize) -> GameResult<GameScene> {
+    state.reset();
+
+    let mut scene = GameScene::new(state, ctx, stage_id)?;
+    scene.player1.cond.set_alive(true);
+    let (pos_x, pos_y) = state.constants.game.new_game_player_pos;
+    scene.player1.x = pos_x as i32 * scene.stage.map.tile_size.as_int() * 0x200;
+    scene.player1.y = pos_y as i32 * scene.stage.map.tile_size.as_int() * 0x200;
+
+    state.control_flags.set_control_enabled(true);
+    state.control_flags.set_tick_world(true);
+    state.fade_state = FadeState::Hidden;
+
+    Ok(scene)
+}
+
+fn run_test(state: &mut SharedGameState, ctx: &mut Context, mut scene: Box<dyn Scene>) -> Result<(), String> {
+    let mut ticks = 0;
+
+    loop {
+        match state.lua.resume_test() {
+            TestStep::Wait(count) => {
+                for _ in 0..count {
+                    if ticks >= MAX_TEST_TICKS {
+                        return Err(format!("Test timed out after {} ticks.", ticks));
+                    }
+
+                    scene.tick(state, ctx).map_err(|err| err.to_string())?;
+                    ticks += 1;
+
+                    if let Some(next_scene) = state.next_scene.take() {
+                        scene = next_scene;
+                        scene.init(state, ctx).map_err(|err| err.to_string())?;
+                    }
+                }
+            }
+            TestStep::Passed => break,
+            TestStep::Failed(err) => return Err(err),
+        }
+    }
+
+    // errors raised by hooks of the tested scripts while the game was ticking
+    match state.lua.errors().front() {
+        Some(err) => Err(err.clone()),
+        None => Ok(()),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+pub fn write_tap(out: &mut dyn Write, results: &[TestResult]) -> GameResult {
+    writeln!(out, "TAP version 13")?;
+    writeln!(out, "1..{}", results.len())?;
+
+    for (i, result) in results.iter().enumerate() {
+        match &result.error {
+            None => writeln!(out, "ok {} - {}", i + 1, result.name)?,
+            Some(err) => {
+                writeln!(out, "not ok {} - {}", i + 1, result.name)?;
+                writeln!(out, "  ---")?;
+                writeln!(out, "  message: {:?}", err)?;
+                writeln!(out, "  ...")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_junit(out: &mut dyn Write, suite_name: &str, results: &[TestResult]) -> GameResult {
+    let failures = results.iter().filter(|result| result.error.is_some()).count();
+    let time: f64 = results.iter().map(|result| result.duration.as_secs_f64()).sum();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<testsuites tests="{}" failures="{}" time="{:.3}">"#, results.len(), failures, time)?;
+    writeln!(
+        out,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" time="{:.3}">"#,
+        escape_xml(suite_name),
+        results.len(),
+        failures,
+        time
+    )?;
+
+    for result in results {
+        let name = escape_xml(&result.name);
+        let time = result.duration.as_secs_f64();
+
+        match &result.error {
+            None => writeln!(out, r#"    <testcase name="{}" time="{:.3}"/>"#, name, time)?,
+            Some(err) => {
+                writeln!(out, r#"    <testcase name="{}" time="{:.3}">"#, name, time)?;
+                writeln!(out, r#"      <failure message="{}"/>"#, escape_xml(err))?;
+                writeln!(out, "    </testcase>")?;
+            }
+        }
+    }
+
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")?;
+
+    Ok(())
+}
+
+/// Runs all tests of the mod in a headless game instance and writes the report to stdout.
+/// Returns true if all tests have passed.
+pub fn run(options: &ScriptTestOptions) -> GameResult<bool> {
+    let _ = init_logger();
+
+    let mut context = Box::pin(Context::new());
+    context.headless = true;
+
+    // keep the tests away from the real saves and settings, and from other test runs
+    let user_dir = TempDir(std::env::temp_dir().join(format!("doukutsu-rs-script-tests-{}", std::process::id())));
+    let _ = std::fs::remove_dir_all(&user_dir.0);
+    std::fs::create_dir_all(&user_dir.0)?;
+
+    mount_vfs(&mut context, Box::new(PhysicalFS::new(&options.data_dir, true)));
+    mount_user_vfs(&mut context, Box::new(PhysicalFS::new(&user_dir.0, false)));
+    mount_vfs(&mut context, Box::new(BuiltinFS::new()));
+
+    let backend = init_backend(true, context.size_hint)?;
+    let event_loop = backend.create_event_loop(&context)?;
+    context.renderer = Some(event_loop.new_renderer(&mut *context as *mut Context)?);
+    let ctx = &mut *context;
+
+    let mut game_state = Box::new(SharedGameState::new(ctx)?);
+    let state = &mut *game_state;
+    let state_ptr = state as *mut SharedGameState;
+    state.lua.update_refs(state_ptr, ctx as *mut Context);
+
+    ctx.screen_size = (640.0, 480.0);
+    state.handle_resize(ctx)?;
+
+    if let Some(mod_id) = &options.mod_id {
+        match state.mod_list.mods.iter().find(|mod_info| &mod_info.id == mod_id) {
+            Some(mod_info) => state.mod_path = Some(mod_info.path.clone()),
+            None => return Err(GameError::ResourceLoadError(format!("Mod {} not found.", mod_id))),
+        }
+    }
+
+    state.reload_resources(ctx)?;
+    state.reset();
+    state.reload_lua_scripts(ctx, false)?;
+    let file_count = state.lua.load_test_scripts(ctx);
+    log::info!("{} test scripts have been loaded.", file_count);
+
+    let mut results = Vec::new();
+
+    // errors from loading the scripts are reported as a failed test, so they are not missed
+    if let Some(err) = state.lua.errors().front() {
+        results.push(TestResult {
+            name: "load scripts".to_owned(),
+            error: Some(err.clone()),
+            duration: Duration::ZERO,
+        });
+    }
+
+    let stage_id = options.stage_id.unwrap_or(state.constants.game.new_game_stage as usize);
+
+    for index in 1..=state.lua.test_count() {
+        let started = Instant::now();
+        state.lua.clear_errors();
+
+        let mut scene: Box<dyn Scene> = Box::new(create_test_scene(state, ctx, stage_id)?);
+        scene.init(state, ctx)?;
+
+        let name = state.lua.start_test(index);
+        let error = run_test(state, ctx, scene).err();
+
+        results.push(TestResult { name, error, duration: started.elapsed() });
+    }
+
+    let suite_name = options.mod_id.as_deref().unwrap_or("base");
+    let mut out = std::io::stdout();
+    match options.format {
+        TestReportFormat::Tap => write_tap(&mut out, &results)?,
+        TestReportFormat::JUnit => write_junit(&mut out, suite_name, &results)?,
+    }
+
+    Ok(results.iter().all(|result| result.error.is_none()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Vec<TestResult> {
+        vec![
+            TestResult { name: "jumps".to_owned(), error: None, duration: Duration::from_millis(250) },
+            TestResult {
+                name: "<takes> \"damage\"".to_owned(),
+                error: Some("expected 3 & got 2".to_owned()),
+                duration: Duration::from_millis(1500),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(escape_xml("plain text"), "plain text");
+        assert_eq!(escape_xml("<a href=\"x\">'&'</a>"), "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn test_write_tap() {
+        let mut out = Vec::new();
+        write_tap(&mut out, &results()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TAP version 13\n\
+             1..2\n\
+             ok 1 - jumps\n\
+             not ok 2 - <takes> \"damage\"\n  \
+             ---\n  \
+             message: \"expected 3 & got 2\"\n  \
+             ...\n"
+        );
+    }
+
+    #[test]
+    fn test_write_junit() {
+        let mut out = Vec::new();
+        write_junit(&mut out, "my&mod", &results()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="2" failures="1" time="1.750">
+  <testsuite name="my&amp;mod" tests="2" failures="1" time="1.750">
+    <testcase name="jumps" time="0.250"/>
+    <testcase name="&lt;takes&gt; &quot;damage&quot;" time="1.500">
+      <failure message="expected 3 &amp; got 2"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
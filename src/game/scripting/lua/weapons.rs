@@ -0,0 +1,300 @@
+use std::ptr::null_mut;
+
+use lua_ffi::{c_int, State};
+use num_traits::FromPrimitive;
+
+use crate::common::{Direction, Rect};
+use crate::game::player::{Player, TargetPlayer};
+use crate::game::scripting::lua::doukutsu::Doukutsu;
+use crate::game::scripting::lua::{report_error, LuaScriptingState, DRS_RUNTIME_GLOBAL};
+use crate::game::weapon::bullet::{Bullet, BulletManager};
+use crate::game::weapon::{Weapon, WeaponType};
+
+/// Weapon currently being ticked by a Lua weapon handler.
+pub(super) struct WeaponHookContext {
+    weapon: *mut Weapon,
+    player: *mut Player,
+    player_id: TargetPlayer,
+    bullet_manager: *mut BulletManager,
+}
+
+impl Doukutsu {
+    pub(super) unsafe fn lua_bullet_command(&self, state: &mut State) -> c_int {
+        if (*self.ptr).bullet.is_null() {
+            state.push_nil();
+            return 1;
+        }
+
+        if let Some(param_type) = state.to_int(2) {
+            let bullet = &mut *(*self.ptr).bullet;
+
+            match param_type {
+                0x0e => state.push(bullet.cond.0 as i32),
+                0x0f => state.push(bullet.flags.0),
+                0x10 => state.push(bullet.x as f32 / 512.0),
+                0x11 => state.push(bullet.y as f32 / 512.0),
+                0x12 => state.push(bullet.vel_x as f32 / 512.0),
+                0x13 => state.push(bullet.vel_y as f32 / 512.0),
+                0x16 => state.push(bullet.action_num as i32),
+                0x17 => state.push(bullet.anim_num as i32),
+                0x18 => state.push(bullet.action_counter as i32),
+                0x1b => state.push(bullet.anim_counter as i32),
+                0x1d => state.push(bullet.btype as i32),
+                0x1e => state.push(bullet.life as i32),
+                0x21 => state.push(bullet.direction as i32),
+                0x23 => state.push(bullet.lifetime as i32),
+                0x24 => state.push(bullet.damage as i32),
+                0x25 => state.push(bullet.counter1 as i32),
+                0x26 => state.push(bullet.counter2 as i32),
+                0x27 => state.push(bullet.owner as i32),
+                0x110 => {
+                    if let Some(v) = state.to_float(3) {
+                        bullet.x = (v * 512.0) as i32;
+                    }
+                }
+                0x111 => {
+                    if let Some(v) = state.to_float(3) {
+                        bullet.y = (v * 512.0) as i32;
+                    }
+                }
+                0x112 => {
+                    if let Some(v) = state.to_float(3) {
+                        bullet.vel_x = (v * 512.0) as i32;
+                    }
+                }
+                0x113 => {
+                    if let Some(v) = state.to_float(3) {
+                        bullet.vel_y = (v * 512.0) as i32;
+                    }
+                }
+                0x116 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.action_num = v as u16;
+                    }
+                }
+                0x117 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.anim_num = v as u16;
+                    }
+                }
+                0x118 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.action_counter = v as u16;
+                    }
+                }
+                0x11b => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.anim_counter = v as u16;
+                    }
+                }
+                0x11e => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.life = v as u16;
+                    }
+                }
+                0x121 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.direction = Direction::from_int(v as _).unwrap_or(Direction::Left);
+                    }
+                }
+                0x123 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.lifetime = v as u16;
+                    }
+                }
+                0x124 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.damage = v as i16;
+                    }
+                }
+                0x125 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.counter1 = v as u16;
+                    }
+                }
+                0x126 => {
+                    if let Some(v) = state.to_int(3) {
+                        bullet.counter2 = v as u16;
+                    }
+                }
+                0x202 => {
+                    // get anim rect
+                    state.push(bullet.anim_rect.left as i32);
+                    state.push(bullet.anim_rect.top as i32);
+                    state.push(bullet.anim_rect.right as i32);
+                    state.push(bullet.anim_rect.bottom as i32);
+
+                    return 4;
+                }
+                0x203 => {
+                    // set anim rect
+                    if let (Some(l), Some(t), Some(r), Some(b)) =
+                        (state.to_int(3), state.to_int(4), state.to_int(5), state.to_int(6))
+                    {
+                        bullet.anim_rect = Rect { left: l as u16, top: t as u16, right: r as u16, bottom: b as u16 };
+                    } else {
+                        state.error("Invalid parameters supplied.");
+                    }
+                }
+                0x204 => {
+                    // vanish
+                    let game_state = &mut *(*self.ptr).state_ptr;
+                    bullet.vanish(game_state);
+                }
+                0x205 => {
+                    // kill silently
+                    bullet.cond.set_alive(false);
+                }
+                _ => state.push_nil(),
+            }
+        } else {
+            state.push_nil()
+        }
+
+        1
+    }
+
+    pub(super) unsafe fn lua_weapon_command(&self, state: &mut State) -> c_int {
+        let hook_ctx = match &(*self.ptr).weapon_ctx {
+            Some(hook_ctx) => hook_ctx,
+            None => {
+                state.push_nil();
+                return 1;
+            }
+        };
+
+        if let Some(param_type) = state.to_int(2) {
+            let game_state = &mut *(*self.ptr).state_ptr;
+            let weapon = &mut *hook_ctx.weapon;
+            let player = &mut *hook_ctx.player;
+            let bullet_manager = &mut *hook_ctx.bullet_manager;
+
+            match param_type {
+                0x10 => state.push(weapon.wtype as i32),
+                0x11 => state.push(weapon.level as i32),
+                0x12 => state.push(weapon.experience as i32),
+                0x13 => state.push(weapon.ammo as i32),
+                0x14 => state.push(weapon.max_ammo as i32),
+                0x15 => state.push(hook_ctx.player_id as i32),
+                0x16 => state.push(player.controller.shoot()),
+                0x17 => state.push(player.controller.trigger_shoot()),
+                0x113 => {
+                    if let Some(v) = state.to_int(3) {
+                        weapon.ammo = (v.max(0) as u16).min(weapon.max_ammo);
+                    }
+                }
+                0x200 => {
+                    // consume ammo
+                    let amount = state.to_int(3).unwrap_or(1);
+                    state.push(weapon.consume_ammo(amount.max(0) as u16));
+                }
+                0x201 => {
+                    // fire bullet
+                    if let (Some(btype), Some(x), Some(y)) = (state.to_int(3), state.to_float(4), state.to_float(5)) {
+                        let direction =
+                            state.to_int(6).and_then(|d| Direction::from_int(d as _)).unwrap_or(player.direction);
+
+                        bullet_manager.create_bullet(
+                            (x * 512.0) as i32,
+                            (y * 512.0) as i32,
+                            btype as u16,
+                            hook_ctx.player_id,
+                            direction,
+                            &game_state.constants,
+                        );
+                    } else {
+                        state.error("Invalid parameters supplied.");
+                    }
+                }
+                0x202 => {
+                    // count bullets
+                    let count = match state.to_int(3) {
+                        Some(btype) => bullet_manager.count_bullets(btype as u16, hook_ctx.player_id),
+                        None => bullet_manager.bullets.iter().filter(|b| b.owner == hook_ctx.player_id).count(),
+                    };
+                    state.push(count as i32);
+                }
+                0x203 => weapon.set_refire_timer(),
+                0x204 => weapon.draw_empty(game_state, player.x, player.y),
+                _ => state.push_nil(),
+            }
+        } else {
+            state.push_nil()
+        }
+
+        1
+    }
+}
+
+impl LuaScriptingState {
+    /// Runs the handler registered for type of specified bullet, returns true if there was one.
+    pub fn try_run_bullet_hook(&mut self, bullet: &mut Bullet) -> bool {
+        let mut result = false;
+
+        if let Some(state) = &mut self.state {
+            self.bullet = bullet as *mut Bullet;
+
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_tryBulletHook");
+
+            state.push(bullet.btype as i32);
+
+            if let Err((_, err)) = state.pcall(1, 1, 0) {
+                report_error(&mut self.errors, format!("bullet_hook error: {}", err));
+            }
+
+            if let Some(val) = state.to_bool(-1) {
+                result = val;
+            }
+
+            state.pop(2);
+
+            self.bullet = null_mut();
+        }
+
+        result
+    }
+
+    /// Runs the handler registered for type of specified weapon.
+    ///
+    /// Returns the type of built-in behaviour that should be run afterwards, which is the weapon's own
+    /// type if there's no handler, the fallback type if the handler didn't handle the tick, or `None`.
+    pub fn try_run_weapon_hook(
+        &mut self,
+        weapon: &mut Weapon,
+        player: &mut Player,
+        player_id: TargetPlayer,
+        bullet_manager: &mut BulletManager,
+    ) -> Option<WeaponType> {
+        let mut result = Some(weapon.wtype);
+
+        if let Some(state) = &mut self.state {
+            self.weapon_ctx = Some(WeaponHookContext {
+                weapon: weapon as *mut Weapon,
+                player: player as *mut Player,
+                player_id,
+                bullet_manager: bullet_manager as *mut BulletManager,
+            });
+
+            state.get_global(DRS_RUNTIME_GLOBAL);
+            state.get_field(-1, "_tryWeaponHook");
+
+            state.push(weapon.wtype as i32);
+            state.push(player_id as i32);
+
+            if let Err((_, err)) = state.pcall(2, 1, 0) {
+                report_error(&mut self.errors, format!("weapon_hook error: {}", err));
+            }
+
+            if let Some(fallback) = state.to_int(-1) {
+                result = if fallback < 0 { None } else { FromPrimitive::from_i32(fallback) };
+            }
+
+            state.pop(2);
+
+            self.weapon_ctx = None;
+        }
+
+        result
+    }
+}
@@ -0,0 +1,454 @@
+use lua_ffi::{c_int, State};
+use num_traits::FromPrimitive;
+
+use crate::common::Direction;
+use crate::game::caret::CaretType;
+use crate::game::frame::UpdateTarget;
+use crate::game::npc::NPC;
+use crate::game::player::TargetPlayer;
+use crate::game::scripting::lua::doukutsu::Doukutsu;
+use crate::game::scripting::lua::BOSS_PART_ID_BASE;
+use crate::game::scripting::tsc::text_script::TextScriptExecutionState;
+use crate::game::weapon::{WeaponLevel, WeaponType};
+
+fn weapon_level_from_int(level: i32) -> WeaponLevel {
+    match level {
+        1 => WeaponLevel::Level1,
+        2 => WeaponLevel::Level2,
+        3 => WeaponLevel::Level3,
+        _ => WeaponLevel::None,
+    }
+}
+
+impl Doukutsu {
+    pub(super) unsafe fn lua_map_command(&self, state: &mut State) -> c_int {
+        if (*self.ptr).game_scene.is_null() {
+            state.push_nil();
+            return 1;
+        }
+
+        if let Some(param_type) = state.to_int(2) {
+            let game_scene = &mut *(*self.ptr).game_scene;
+            let game_state = &mut *(*self.ptr).state_ptr;
+            let stage = &mut game_scene.stage;
+
+            let x = state.to_int(3).unwrap_or(-1);
+            let y = state.to_int(4).unwrap_or(-1);
+            let in_bounds = x >= 0 && y >= 0 && x < stage.map.width as i32 && y < stage.map.height as i32;
+
+            match param_type {
+                0x00 => {
+                    // map size
+                    state.push(stage.map.width as i32);
+                    state.push(stage.map.height as i32);
+
+                    return 2;
+                }
+                0x01 if in_bounds => state.push(stage.tile_at(x as usize, y as usize) as i32),
+                0x02 if in_bounds => state.push(stage.map.get_attribute(x as usize, y as usize) as i32),
+                0x03 => {
+                    // attribute of tile type
+                    let tile = state.to_int(3).unwrap_or(0) as u8;
+                    state.push(stage.map.attrib[tile as usize] as i32);
+                }
+                0x101 if in_bounds => {
+                    // set tile, optionally with smoke like <CMP
+                    let tile = state.to_int(5).unwrap_or(0) as u8;
+                    let changed = stage.change_tile(x as usize, y as usize, tile);
+
+                    if changed && state.to_bool(6).unwrap_or(false) {
+                        let block_size = game_state.tile_size.as_int() * 0x200;
+                        let mut npc = NPC::create(4, &game_state.npc_table);
+                        npc.cond.set_alive(true);
+                        npc.x = x * block_size;
+                        npc.y = y * block_size;
+
+                        let _ = game_scene.npc_list.spawn(0, npc.clone());
+                        let _ = game_scene.npc_list.spawn(0, npc.clone());
+                        let _ = game_scene.npc_list.spawn(0, npc);
+                    }
+
+                    state.push(changed);
+                }
+                0x103 => {
+                    // set attribute of tile type
+                    if let (Some(tile), Some(attrib)) = (state.to_int(3), state.to_int(4)) {
+                        stage.map.attrib[tile as u8 as usize] = attrib as u8;
+                    }
+
+                    state.push_nil();
+                }
+                _ => state.push_nil(),
+            }
+        } else {
+            state.push_nil()
+        }
+
+        1
+    }
+
+    pub(super) unsafe fn lua_camera_command(&self, state: &mut State) -> c_int {
+        if (*self.ptr).game_scene.is_null() {
+            state.push_nil();
+            return 1;
+        }
+
+        if let Some(param_type) = state.to_int(2) {
+            let game_scene = &mut *(*self.ptr).game_scene;
+            let game_state = &mut *(*self.ptr).state_ptr;
+            let frame = &mut game_scene.frame;
+
+            match param_type {
+                0x10 => state.push(frame.x as f32 / 512.0),
+                0x11 => state.push(frame.y as f32 / 512.0),
+                0x12 => state.push(frame.target_x as f32 / 512.0),
+                0x13 => state.push(frame.target_y as f32 / 512.0),
+                0x14 => state.push(frame.wait),
+                0x15 => {
+                    // focus target, as type and id
+                    let (target, id) = match frame.update_target {
+                        UpdateTarget::Player => ("player", 0),
+                        UpdateTarget::NPC(id) => ("npc", id as i32),
+                        UpdateTarget::Boss(id) => ("boss", id as i32),
+                    };
+                    state.push(target);
+                    state.push(id);
+
+                    return 2;
+                }
+                0x110 => {
+                    if let Some(v) = state.to_float(3) {
+                        frame.x = (v * 512.0) as i32;
+                        frame.prev_x = frame.x;
+                    }
+                }
+                0x111 => {
+                    if let Some(v) = state.to_float(3) {
+                        frame.y = (v * 512.0) as i32;
+                        frame.prev_y = frame.y;
+                    }
+                }
+                0x112 => {
+                    if let Some(v) = state.to_float(3) {
+                        frame.target_x = (v * 512.0) as i32;
+                    }
+                }
+                0x113 => {
+                    if let Some(v) = state.to_float(3) {
+                        frame.target_y = (v * 512.0) as i32;
+                    }
+                }
+                0x114 => {
+                    if let Some(v) = state.to_int(3) {
+                        frame.wait = v.max(1);
+                    }
+                }
+                0x200 => {
+                    // focus on player
+                    frame.update_target = UpdateTarget::Player;
+                    frame.wait = state.to_int(3).unwrap_or(16).max(1);
+                }
+                0x201 => {
+                    // focus on NPC or boss part
+                    if let Some(id) = state.to_int(3) {
+                        frame.update_target = if id >= BOSS_PART_ID_BASE {
+                            UpdateTarget::Boss((id - BOSS_PART_ID_BASE) as u16)
+                        } else {
+                            UpdateTarget::NPC(id as u16)
+                        };
+                        frame.wait = state.to_int(4).unwrap_or(16).max(1);
+                    }
+                }
+                0x202 => {
+                    // move to the target immediately
+                    frame.immediate_update(game_state, &game_scene.stage);
+                }
+                _ => state.push_nil(),
+            }
+        } else {
+            state.push_nil()
+        }
+
+        1
+    }
+
+    pub(super) unsafe fn lua_inventory_command(&self, state: &mut State) -> c_int {
+        if (*self.ptr).game_scene.is_null() {
+            state.push_nil();
+            return 1;
+        }
+
+        if let (Some(player_id), Some(param_type)) = (state.to_int(2), state.to_int(3)) {
+            let game_scene = &mut *(*self.ptr).game_scene;
+            let game_state = &mut *(*self.ptr).state_ptr;
+
+            let (inventory, player) = match player_id {
+                0 => (&mut game_scene.inventory_player1, &mut game_scene.player1),
+                1 => (&mut game_scene.inventory_player2, &mut game_scene.player2),
+                _ => {
+                    state.push_nil();
+                    return 1;
+                }
+            };
+
+            match param_type {
+                0x00 => {
+                    // count items
+                    let mut count = 0;
+                    while inventory.get_item_idx(count).is_some() {
+                        count += 1;
+                    }
+                    state.push(count as i32);
+                }
+                0x01 => {
+                    // item at index, as id and amount
+                    match state.to_int(4).and_then(|idx| inventory.get_item_idx(idx.max(0) as usize)) {
+                        Some(item) => {
+                            state.push(item.0 as i32);
+                            state.push(item.1 as i32);
+
+                            return 2;
+                        }
+                        None => state.push_nil(),
+                    }
+                }
+                0x02 => {
+                    // amount of item
+                    let amount = state.to_int(4).and_then(|id| inventory.get_item(id as u16)).map_or(0, |item| item.1);
+                    state.push(amount as i32);
+                }
+                0x10 => state.push(inventory.get_current_weapon_idx() as i32),
+                0x11 => state.push(inventory.get_weapon_count() as i32),
+                0x12 => {
+                    // weapon at index, as type, level, experience, ammo and max ammo
+                    match state.to_int(4).and_then(|idx| inventory.get_weapon(idx.max(0) as usize)) {
+                        Some(weapon) => {
+                            state.push(weapon.wtype as i32);
+                            state.push(weapon.level as i32);
+                            state.push(weapon.experience as i32);
+                            state.push(weapon.ammo as i32);
+                            state.push(weapon.max_ammo as i32);
+
+                            return 5;
+                        }
+                        None => state.push_nil(),
+                    }
+                }
+                0x100 => {
+                    // add item
+                    if let Some(id) = state.to_int(4) {
+                        let amount = state.to_int(5).unwrap_or(1).max(1);
+                        inventory.add_item_amount(id as u16, amount as u16);
+                    }
+                    state.push_nil();
+                }
+                0x101 => {
+                    // remove item
+                    if let Some(id) = state.to_int(4) {
+                        inventory.remove_item(id as u16);
+                    }
+                    state.push_nil();
+                }
+                0x102 => {
+                    // consume one of item
+                    if let Some(id) = state.to_int(4) {
+                        inventory.consume_item(id as u16);
+                    }
+                    state.push_nil();
+                }
+                0x110 => {
+                    // select weapon
+                    if let Some(idx) = state.to_int(4) {
+                        if idx >= 0 && (idx as usize) < inventory.get_weapon_count() {
+                            inventory.current_weapon = idx as u16;
+                        }
+                    }
+                    state.push_nil();
+                }
+                0x111 => {
+                    // add weapon
+                    if let Some(wtype) = state.to_int(4).and_then(FromPrimitive::from_i32) {
+                        let ammo = state.to_int(5).unwrap_or(0).max(0);
+                        inventory.add_weapon(wtype, ammo as u16);
+                    }
+                    state.push_nil();
+                }
+                0x112 => {
+                    // remove weapon
+                    if let Some(wtype) = state.to_int(4).and_then(FromPrimitive::from_i32) {
+                        inventory.remove_weapon(wtype);
+
+                        if inventory.current_weapon as usize >= inventory.get_weapon_count() {
+                            inventory.current_weapon = 0;
+                        }
+                    }
+                    state.push_nil();
+                }
+                0x113 => {
+                    // set weapon data
+                    let wtype: Option<WeaponType> = state.to_int(4).and_then(FromPrimitive::from_i32);
+                    if let Some(weapon) = wtype.and_then(|wtype| inventory.get_weapon_by_type_mut(wtype)) {
+                        if let Some(level) = state.to_int(5) {
+                            weapon.level = weapon_level_from_int(level);
+                        }
+                        if let Some(experience) = state.to_int(6) {
+                            weapon.experience = experience.max(0) as u16;
+                        }
+                        if let Some(max_ammo) = state.to_int(8) {
+                            weapon.max_ammo = max_ammo.max(0) as u16;
+                        }
+                        if let Some(ammo) = state.to_int(7) {
+                            weapon.ammo = ammo.max(0) as u16;
+                        }
+                    }
+                    state.push_nil();
+                }
+                0x114 => {
+                    // give experience to current weapon
+                    if let Some(exp) = state.to_int(4) {
+                        inventory.add_xp(exp.max(0) as u16, player, game_state);
+                    }
+                    state.push_nil();
+                }
+                0x115 => {
+                    // take experience from current weapon
+                    if let Some(exp) = state.to_int(4) {
+                        inventory.take_xp(exp.max(0) as u16, game_state);
+                    }
+                    state.push_nil();
+                }
+                0x116 => {
+                    inventory.refill_all_ammo();
+                    state.push_nil();
+                }
+                _ => state.push_nil(),
+            }
+        } else {
+            state.push_nil()
+        }
+
+        1
+    }
+
+    pub(super) unsafe fn lua_text_box_command(&self, state: &mut State) -> c_int {
+        if let Some(param_type) = state.to_int(2) {
+            let game_state = &mut *(*self.ptr).state_ptr;
+            let vm = &mut game_state.textscript_vm;
+
+            match param_type {
+                0x00 => state.push(vm.state != TextScriptExecutionState::Ended),
+                0x01 => match vm.current_event() {
+                    Some(event) => state.push(event as i32),
+                    None => state.push_nil(),
+                },
+                0x02 => state.push(vm.flags.0 as i32),
+                0x03 => state.push(vm.face as i32),
+                0x04 => state.push(vm.item as i32),
+                0x05 => {
+                    // text lines
+                    for line in [&vm.line_1, &vm.line_2, &vm.line_3] {
+                        let text: String = line.iter().collect();
+                        state.push(text.as_str());
+                    }
+
+                    return 3;
+                }
+                0x06 => state.push(vm.executor_player as i32),
+                0x100 => {
+                    // run event
+                    if let Some(event) = state.to_int(3) {
+                        vm.start_script(event as u16);
+                    }
+                    state.push_nil();
+                }
+                0x102 => {
+                    if let Some(flags) = state.to_int(3) {
+                        vm.flags.0 = flags as u16;
+                    }
+                    state.push_nil();
+                }
+                0x103 => {
+                    if let Some(face) = state.to_int(3) {
+                        vm.face = face as u16;
+                    }
+                    state.push_nil();
+                }
+                0x200 => {
+                    vm.clear_text_box();
+                    state.push_nil();
+                }
+                _ => state.push_nil(),
+            }
+        } else {
+            state.push_nil()
+        }
+
+        1
+    }
+
+    pub(super) unsafe fn lua_spawn_command(&self, state: &mut State) -> c_int {
+        let game_state = &mut *(*self.ptr).state_ptr;
+
+        if let (Some(param_type), Some(kind), Some(x), Some(y)) =
+            (state.to_int(2), state.to_int(3), state.to_float(4), state.to_float(5))
+        {
+            let x = (x * 512.0) as i32;
+            let y = (y * 512.0) as i32;
+            let direction = state.to_int(6).and_then(|d| Direction::from_int(d as _)).unwrap_or(Direction::Left);
+
+            match param_type {
+                0x00 => {
+                    // caret
+                    match CaretType::from_int(kind.max(0) as usize) {
+                        Some(ctype) => game_state.create_caret(x, y, ctype, direction),
+                        None => state.error("Invalid caret type."),
+                    }
+                }
+                0x01 => {
+                    // bullet
+                    if (*self.ptr).game_scene.is_null() {
+                        state.error("Bullets can be only spawned in game.");
+                        return 0;
+                    }
+
+                    let game_scene = &mut *(*self.ptr).game_scene;
+                    let owner = if state.to_int(7) == Some(1) { TargetPlayer::Player2 } else { TargetPlayer::Player1 };
+
+                    game_scene.bullet_manager.create_bullet(x, y, kind as u16, owner, direction, &game_state.constants);
+                }
+                0x02 => {
+                    // npc, like <SNP
+                    if (*self.ptr).game_scene.is_null() {
+                        state.error("NPCs can be only spawned in game.");
+                        return 0;
+                    }
+
+                    let game_scene = &mut *(*self.ptr).game_scene;
+
+                    let mut id = 0x100;
+                    while game_scene.npc_list.get_npc(id).map_or(false, |npc| npc.cond.alive()) {
+                        id += 1;
+                    }
+
+                    let mut npc = NPC::create(kind as u16, &game_state.npc_table);
+                    npc.cond.set_alive(true);
+                    npc.x = x;
+                    npc.y = y;
+                    npc.direction = direction;
+
+                    match game_scene.npc_list.spawn_at_slot(id as u16, npc) {
+                        Ok(()) => state.push(id as i32),
+                        Err(_) => state.push_nil(),
+                    }
+
+                    return 1;
+                }
+                _ => {}
+            }
+        } else {
+            state.error("Invalid parameters supplied.");
+        }
+
+        0
+    }
+}
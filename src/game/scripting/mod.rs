@@ -1,3 +1,7 @@
 #[cfg(feature = "scripting-lua")]
 pub mod lua;
+pub mod events;
+pub mod storage;
 pub mod tsc;
+#[cfg(feature = "scripting-wasm")]
+pub mod wasm;
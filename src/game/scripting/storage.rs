@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::framework::context::Context;
+use crate::framework::error::GameResult;
+use crate::framework::filesystem::{user_create, user_delete, user_exists, user_open};
+
+/// Key/value data of scripts, saved next to the profile of each save slot.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScriptStorage {
+    /// Values by key, for each mod sharing the save slot. The base game uses an empty mod id.
+    mods: HashMap<String, HashMap<String, Value>>,
+}
+
+impl ScriptStorage {
+    /// Returns the path of storage file belonging to specified profile, eg. `/Profile.dat` -> `/Profile.scripts.json`.
+    pub fn path_for_profile(profile_path: &str) -> String {
+        let stem = profile_path.strip_suffix(".dat").unwrap_or(profile_path);
+
+        format!("{}.scripts.json", stem)
+    }
+
+    pub fn load(ctx: &Context, profile_path: &str) -> ScriptStorage {
+        if let Ok(file) = user_open(ctx, ScriptStorage::path_for_profile(profile_path)) {
+            match serde_json::from_reader::<_, ScriptStorage>(file) {
+                Ok(storage) => return storage,
+                Err(err) => log::warn!("Failed to deserialize script storage: {}", err),
+            }
+        }
+
+        ScriptStorage::default()
+    }
+
+    pub fn save(&self, ctx: &Context, profile_path: &str) -> GameResult {
+        let path = ScriptStorage::path_for_profile(profile_path);
+
+        if self.mods.values().all(|values| values.is_empty()) {
+            if user_exists(ctx, &path) {
+                user_delete(ctx, &path)?;
+            }
+
+            return Ok(());
+        }
+
+        let file = user_create(ctx, path)?;
+        serde_json::to_writer(file, self)?;
+
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.mods.clear();
+    }
+
+    pub fn get(&self, mod_id: &str, key: &str) -> Option<&Value> {
+        self.mods.get(mod_id).and_then(|values| values.get(key))
+    }
+
+    /// Sets the value of specified key, `null` removes it.
+    pub fn set(&mut self, mod_id: &str, key: &str, value: Value) {
+        if value.is_null() {
+            if let Some(values) = self.mods.get_mut(mod_id) {
+                values.remove(key);
+            }
+        } else {
+            self.mods.entry(mod_id.to_owned()).or_default().insert(key.to_owned(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_path_for_profile() {
+        assert_eq!(ScriptStorage::path_for_profile("/Profile.dat"), "/Profile.scripts.json");
+        assert_eq!(ScriptStorage::path_for_profile("/Profile2.dat"), "/Profile2.scripts.json");
+        assert_eq!(ScriptStorage::path_for_profile("/mod/Profile"), "/mod/Profile.scripts.json");
+    }
+
+    #[test]
+    fn test_set_null_removes_key() {
+        let mut storage = ScriptStorage::default();
+        storage.set("", "coins", json!(10));
+        storage.set("mod", "coins", json!(20));
+        assert_eq!(storage.get("", "coins"), Some(&json!(10)));
+        assert_eq!(storage.get("mod", "coins"), Some(&json!(20)));
+
+        storage.set("", "coins", Value::Null);
+        assert_eq!(storage.get("", "coins"), None);
+        assert_eq!(storage.get("mod", "coins"), Some(&json!(20)));
+
+        // removing a key of a mod without values doesn't create an entry for it
+        storage.set("other", "coins", Value::Null);
+        assert!(!storage.mods.contains_key("other"));
+    }
+}
@@ -42,6 +42,22 @@ pub fn read_cur_varint(cursor: &mut Cursor<&[u8]>) -> GameResult<i32> {
     Ok(((result << 31) ^ (result >> 1)) as i32)
 }
 
+/// Reads name and operands of a script-registered command, following the `_CMD` opcode.
+pub fn read_cur_custom_command(cursor: &mut Cursor<&[u8]>) -> GameResult<(String, Vec<i32>)> {
+    let mut name = String::with_capacity(3);
+    for _ in 0..3 {
+        name.push(std::char::from_u32(read_cur_varint(cursor)? as u32).unwrap_or('?'));
+    }
+
+    let argc = read_cur_varint(cursor)?;
+    let mut args = Vec::with_capacity(argc.max(0) as usize);
+    for _ in 0..argc {
+        args.push(read_cur_varint(cursor)?);
+    }
+
+    Ok((name, args))
+}
+
 #[allow(unused)]
 pub fn read_varint<I: Iterator<Item=u8>>(iter: &mut I) -> GameResult<i32> {
     let mut result = 0u32;
@@ -14,8 +14,28 @@ use crate::game::scripting::tsc::text_script::{TextScript, TextScriptEncoding};
 
 impl TextScript {
     /// Compiles a decrypted text script data into internal bytecode.
-    pub fn compile(data: &[u8], strict: bool, encoding: TextScriptEncoding) -> GameResult<TextScript> {
+    ///
+    /// `custom_commands` maps names of script-registered commands to their operand count.
+    pub fn compile(
+        data: &[u8],
+        strict: bool,
+        encoding: TextScriptEncoding,
+        custom_commands: &HashMap<String, u8>,
+    ) -> GameResult<TextScript> {
+        TextScript::compile_with_source(data, strict, encoding, custom_commands, true)
+    }
+
+    /// Like [`TextScript::compile`], `keep_source` decides whether the source and source map used by
+    /// the debugger are retained.
+    pub(crate) fn compile_with_source(
+        data: &[u8],
+        strict: bool,
+        encoding: TextScriptEncoding,
+        custom_commands: &HashMap<String, u8>,
+        keep_source: bool,
+    ) -> GameResult<TextScript> {
         let mut event_map = HashMap::new();
+        let mut source_map = HashMap::new();
         let mut iter = data.iter().copied().peekable();
         let mut last_event = 0;
 
@@ -45,9 +65,20 @@ impl TextScript {
                         }
                     }
 
-                    let bytecode = TextScript::compile_event(&mut iter, strict, encoding)?;
+                    let mut positions = Vec::new();
+                    let bytecode = TextScript::compile_event(
+                        &mut iter,
+                        data.len(),
+                        &mut positions,
+                        strict,
+                        encoding,
+                        custom_commands,
+                    )?;
                     log::info!("Successfully compiled event #{} ({} bytes generated).", event_num, bytecode.len());
                     event_map.insert(event_num, bytecode);
+                    if keep_source {
+                        source_map.insert(event_num, positions);
+                    }
                 }
                 b'\r' | b'\n' | b' ' | b'\t' => {
                     iter.next();
@@ -64,27 +95,39 @@ impl TextScript {
             }
         }
 
-        Ok(TextScript { event_map })
+        let source = if keep_source { data.to_vec() } else { Vec::new() };
+
+        Ok(TextScript { event_map, source, source_map, encoding })
     }
 
-    fn compile_event<I: Iterator<Item=u8>>(
+    /// Compiles a single event, recording the source offset of each emitted instruction into `positions`
+    /// as `(bytecode offset, source offset)` pairs.
+    fn compile_event<I: ExactSizeIterator<Item=u8>>(
         iter: &mut Peekable<I>,
+        source_len: usize,
+        positions: &mut Vec<(u32, u32)>,
         strict: bool,
         encoding: TextScriptEncoding,
+        custom_commands: &HashMap<String, u8>,
     ) -> GameResult<Vec<u8>> {
         let mut bytecode = Vec::new();
         let mut char_buf = Vec::with_capacity(16);
         let mut allow_next_event = true;
+        let mut str_pos = 0;
 
         while let Some(&chr) = iter.peek() {
+            let pos = (source_len - iter.len()) as u32;
+
             match chr {
                 b'#' if allow_next_event => {
                     if !char_buf.is_empty() {
+                        positions.push((bytecode.len() as u32, str_pos));
                         put_varint(TSCOpCode::_STR as i32, &mut bytecode);
                         put_string(&mut char_buf, &mut bytecode, encoding);
                     }
 
                     // some events end without <END marker.
+                    positions.push((bytecode.len() as u32, pos));
                     put_varint(TSCOpCode::_END as i32, &mut bytecode);
                     break;
                 }
@@ -92,10 +135,12 @@ impl TextScript {
                     allow_next_event = false;
 
                     if !char_buf.is_empty() {
+                        positions.push((bytecode.len() as u32, str_pos));
                         put_varint(TSCOpCode::_STR as i32, &mut bytecode);
                         put_string(&mut char_buf, &mut bytecode, encoding);
                     }
 
+                    positions.push((bytecode.len() as u32, pos));
                     iter.next();
                     let n = iter
                         .next_tuple::<(u8, u8, u8)>()
@@ -104,18 +149,24 @@ impl TextScript {
 
                     let code = String::from_utf8_lossy(&n);
 
-                    TextScript::compile_code(&code, strict, iter, &mut bytecode)?;
+                    TextScript::compile_code(&code, strict, custom_commands, iter, &mut bytecode)?;
                 }
                 b'\r' => {
                     iter.next();
                 }
                 b'\n' => {
                     allow_next_event = true;
+                    if char_buf.is_empty() {
+                        str_pos = pos;
+                    }
                     char_buf.push(chr);
 
                     iter.next();
                 }
                 _ => {
+                    if char_buf.is_empty() {
+                        str_pos = pos;
+                    }
                     char_buf.push(chr);
 
                     iter.next();
@@ -125,6 +176,7 @@ impl TextScript {
 
         // Some nicalis challenges are very broken
         if !strict {
+            positions.push((bytecode.len() as u32, (source_len - iter.len()) as u32));
             put_varint(TSCOpCode::_END as i32, &mut bytecode);
         }
 
@@ -134,10 +186,37 @@ impl TextScript {
     fn compile_code<I: Iterator<Item=u8>>(
         code: &str,
         strict: bool,
+        custom_commands: &HashMap<String, u8>,
         iter: &mut Peekable<I>,
         out: &mut Vec<u8>,
     ) -> GameResult {
-        let instr = TSCOpCode::from_str(code).map_err(|_| ParseError(format!("Unknown opcode: {}", code)))?;
+        let instr = match TSCOpCode::from_str(code) {
+            Ok(instr) => instr,
+            Err(_) => {
+                let argc = *custom_commands.get(code).ok_or_else(|| ParseError(format!("Unknown opcode: {}", code)))?;
+
+                put_varint(TSCOpCode::_CMD as i32, out);
+                for &chr in code.as_bytes() {
+                    put_varint(chr as i32, out);
+                }
+                put_varint(argc as i32, out);
+
+                for i in 0..argc {
+                    if i != 0 {
+                        if strict {
+                            expect_char(b':', iter)?;
+                        } else {
+                            iter.next().ok_or_else(|| ParseError("Script unexpectedly ended.".to_owned()))?;
+                        }
+                    }
+
+                    let operand = read_number(iter)?;
+                    put_varint(operand as i32, out);
+                }
+
+                return Ok(());
+            }
+        };
 
         match instr {
             // Zero operand codes
@@ -303,7 +382,7 @@ impl TextScript {
                 put_varint(operand_c as i32, out);
                 put_varint(operand_d as i32, out);
             }
-            TSCOpCode::_NOP | TSCOpCode::_UNI | TSCOpCode::_STR | TSCOpCode::_END => {
+            TSCOpCode::_NOP | TSCOpCode::_UNI | TSCOpCode::_STR | TSCOpCode::_END | TSCOpCode::_CMD => {
                 unreachable!()
             }
         }
@@ -10,6 +10,7 @@ use crate::framework::error::GameResult;
 use crate::game::scripting::tsc::bytecode_utils::{put_varint, read_cur_varint};
 use crate::game::scripting::tsc::encryption::decrypt_tsc;
 use crate::game::scripting::tsc::opcodes::CreditOpCode;
+use crate::game::scripting::tsc::text_script::TextScriptEncoding;
 use crate::game::shared_game_state::SharedGameState;
 
 pub struct CreditScript {
@@ -28,7 +29,11 @@ impl Default for CreditScript {
 
 impl CreditScript {
     /// Loads, decrypts and compiles a credit script from specified stream.
-    pub fn load_from<R: io::Read>(mut data: R, constants: &EngineConstants) -> GameResult<CreditScript> {
+    pub fn load_from<R: io::Read>(
+        mut data: R,
+        constants: &EngineConstants,
+        encoding: TextScriptEncoding,
+    ) -> GameResult<CreditScript> {
         let mut buf = Vec::new();
         data.read_to_end(&mut buf)?;
 
@@ -36,7 +41,7 @@ impl CreditScript {
             decrypt_tsc(&mut buf);
         }
 
-        CreditScript::compile(&buf, false, constants.textscript.encoding)
+        CreditScript::compile(&buf, false, encoding)
     }
 }
 
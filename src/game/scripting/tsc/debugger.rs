@@ -0,0 +1,249 @@
+use std::collections::{BTreeSet, HashSet};
+use std::io::Cursor;
+
+use num_traits::FromPrimitive;
+
+use crate::game::scripting::tsc::bytecode_utils::read_cur_varint;
+use crate::game::scripting::tsc::opcodes::TSCOpCode;
+use crate::game::scripting::tsc::text_script::{TextScript, TextScriptEncoding};
+use crate::util::encoding::{read_cur_shift_jis, read_cur_wtf8};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StepMode {
+    Continue,
+    /// Halts on the next executed instruction, following `EVE`, `PSH` and jumps.
+    Into,
+    /// Halts on the next instruction of the same event and call depth, running any pushed events
+    /// to completion first. If the instruction jumps to `target`, halts at the start of that event instead.
+    Over {
+        event: u16,
+        depth: usize,
+        target: Option<u16>,
+    },
+}
+
+/// Breakpoint and stepping state of the text script VM.
+pub struct TextScriptDebugger {
+    /// Halts when execution enters any of these events.
+    pub event_breakpoints: BTreeSet<u16>,
+    /// Halts before executing any of these opcodes.
+    pub opcode_breakpoints: HashSet<TSCOpCode>,
+    halted: Option<(u16, u32)>,
+    resume_from: Option<(u16, u32)>,
+    step: StepMode,
+}
+
+impl TextScriptDebugger {
+    pub fn new() -> TextScriptDebugger {
+        TextScriptDebugger {
+            event_breakpoints: BTreeSet::new(),
+            opcode_breakpoints: HashSet::new(),
+            halted: None,
+            resume_from: None,
+            step: StepMode::Continue,
+        }
+    }
+
+    /// Returns the event number and bytecode offset of the instruction the VM is halted at.
+    pub fn halted_at(&self) -> Option<(u16, u32)> {
+        self.halted
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.halted.is_some()
+            || self.step != StepMode::Continue
+            || !self.event_breakpoints.is_empty()
+            || !self.opcode_breakpoints.is_empty()
+    }
+
+    /// Called by the VM before executing an instruction, returns true if it should halt instead.
+    pub fn check(&mut self, event: u16, ip: u32, depth: usize, bytecode: &[u8]) -> bool {
+        if self.halted.is_some() {
+            return true;
+        }
+
+        if self.resume_from.take() == Some((event, ip)) {
+            return false;
+        }
+
+        let hit = match self.step {
+            StepMode::Continue => false,
+            StepMode::Into => true,
+            StepMode::Over { event: step_event, depth: step_depth, target } => {
+                depth < step_depth
+                    || (depth == step_depth && event == step_event)
+                    || (depth == step_depth && ip == 0 && Some(event) == target)
+            }
+        } || (ip == 0 && self.event_breakpoints.contains(&event))
+            || opcode_at(bytecode, ip).map_or(false, |op| self.opcode_breakpoints.contains(&op));
+
+        if hit {
+            log::info!("TSC debugger: halted at #{:04}:{}", event, ip);
+            self.halted = Some((event, ip));
+            self.step = StepMode::Continue;
+        }
+
+        hit
+    }
+
+    /// Called by the VM when the script ends, cancels pending steps.
+    pub fn script_ended(&mut self) {
+        self.step = StepMode::Continue;
+        self.resume_from = None;
+    }
+
+    /// Halts on the next executed instruction.
+    pub fn pause(&mut self) {
+        if self.halted.is_none() {
+            self.step = StepMode::Into;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.resume_from = self.halted.take();
+    }
+
+    pub fn step_into(&mut self) {
+        self.step = StepMode::Into;
+        self.resume();
+    }
+
+    /// Steps over the current instruction, `depth` is the current size of the VM call stack
+    /// and `bytecode` is the bytecode of the event the VM is halted in.
+    pub fn step_over(&mut self, depth: usize, bytecode: &[u8]) {
+        if let Some((event, ip)) = self.halted {
+            self.step = StepMode::Over { event, depth, target: jump_target(bytecode, ip) };
+        }
+        self.resume();
+    }
+}
+
+/// Returns the event an instruction may transfer execution to without returning, eg. `<EVE` or `<FLJ`.
+pub fn jump_target(bytecode: &[u8], ip: u32) -> Option<u16> {
+    let mut cursor: Cursor<&[u8]> = Cursor::new(bytecode);
+    cursor.set_position(ip as u64);
+
+    let op: TSCOpCode = read_cur_varint(&mut cursor).ok().and_then(FromPrimitive::from_i32)?;
+    let operand = match op {
+        TSCOpCode::EVE | TSCOpCode::UNJ | TSCOpCode::MPJ | TSCOpCode::YNJ => 0,
+        TSCOpCode::NCJ | TSCOpCode::ECJ | TSCOpCode::FLJ | TSCOpCode::ITJ | TSCOpCode::SKJ | TSCOpCode::AMJ => 1,
+        TSCOpCode::INJ => 2,
+        _ => return None,
+    };
+
+    let mut target = 0;
+    for _ in 0..=operand {
+        target = read_cur_varint(&mut cursor).ok()?;
+    }
+
+    Some(target as u16)
+}
+
+impl TextScript {
+    /// Returns source lines around the instruction at specified bytecode offset of an event,
+    /// along with the index of the line containing it.
+    pub fn source_around(&self, event: u16, ip: u32, context: usize) -> Option<(Vec<String>, usize)> {
+        let positions = self.source_map.get(&event)?;
+        let pos = positions.iter().take_while(|(offset, _)| *offset <= ip).last()?.1 as usize;
+        let pos = pos.min(self.source.len());
+
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(self.source.iter().enumerate().filter(|(_, b)| **b == b'\n').map(|(i, _)| i + 1))
+            .collect();
+        let current = line_starts.iter().rposition(|start| *start <= pos).unwrap_or(0);
+        let first = current.saturating_sub(context);
+        let last = (current + context).min(line_starts.len() - 1);
+
+        let lines = (first..=last)
+            .map(|line| {
+                let end = line_starts.get(line + 1).map_or(self.source.len(), |end| end - 1);
+                decode_line(&self.source[line_starts[line]..end], self.encoding)
+            })
+            .collect();
+
+        Some((lines, current - first))
+    }
+}
+
+fn decode_line(bytes: &[u8], encoding: TextScriptEncoding) -> String {
+    let mut cursor = Cursor::new(bytes);
+    let mut remaining = bytes.len() as u32;
+    let mut line = String::with_capacity(bytes.len());
+
+    while remaining > 0 {
+        let (consumed, chr) = match encoding {
+            TextScriptEncoding::UTF8 => read_cur_wtf8(&mut cursor, remaining),
+            TextScriptEncoding::ShiftJIS => read_cur_shift_jis(&mut cursor, remaining),
+        };
+
+        remaining = remaining.saturating_sub(consumed.max(1));
+        line.push(chr);
+    }
+
+    line.trim_end_matches('\r').to_string()
+}
+
+/// Decodes the opcode at specified offset of event bytecode.
+pub fn opcode_at(bytecode: &[u8], ip: u32) -> Option<TSCOpCode> {
+    let mut cursor: Cursor<&[u8]> = Cursor::new(bytecode);
+    cursor.set_position(ip as u64);
+
+    read_cur_varint(&mut cursor).ok().and_then(FromPrimitive::from_i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn compile(source: &str) -> TextScript {
+        TextScript::compile(source.as_bytes(), true, TextScriptEncoding::UTF8, &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_step_over_jump_halts_at_target() {
+        let script = compile("#0100\n<MSGHello<EVE0200\n#0200\n<END\n");
+        let bytecode = &script.event_map[&100];
+        let eve_ip = script.source_map[&100][2].0;
+        assert_eq!(jump_target(bytecode, eve_ip), Some(200));
+
+        let mut debugger = TextScriptDebugger::new();
+        debugger.event_breakpoints.insert(100);
+        assert!(debugger.check(100, 0, 0, bytecode));
+        debugger.halted = Some((100, eve_ip));
+        debugger.step_over(0, bytecode);
+
+        assert!(!debugger.check(100, eve_ip, 0, bytecode));
+        assert!(debugger.check(200, 0, 0, &script.event_map[&200]));
+        assert_eq!(debugger.halted_at(), Some((200, 0)));
+    }
+
+    #[test]
+    fn test_source_around() {
+        let script = compile("#0100\r\n<MSG\r\nHello\r\n<NOD<END\r\n#0200\r\n<END\r\n");
+        let positions = &script.source_map[&100];
+
+        let (lines, current) = script.source_around(100, positions[0].0, 1).unwrap();
+        assert_eq!(lines, vec!["#0100", "<MSG", "Hello"]);
+        assert_eq!(current, 1);
+
+        let (lines, current) = script.source_around(100, positions[3].0, 0).unwrap();
+        assert_eq!(lines, vec!["<NOD<END"]);
+        assert_eq!(current, 0);
+
+        assert!(script.source_around(300, 0, 1).is_none());
+    }
+
+    #[test]
+    fn test_source_not_kept() {
+        let source = b"#0100\n<MSGHello<END\n";
+        let script = TextScript::compile_with_source(source, true, TextScriptEncoding::UTF8, &HashMap::new(), false)
+            .unwrap();
+
+        assert!(script.source.is_empty());
+        assert!(script.source_map.is_empty());
+        assert!(script.has_event(100));
+        assert!(script.source_around(100, 0, 1).is_none());
+    }
+}
@@ -5,7 +5,7 @@ use num_traits::FromPrimitive;
 
 use crate::framework::error::GameError::InvalidValue;
 use crate::framework::error::GameResult;
-use crate::game::scripting::tsc::bytecode_utils::read_cur_varint;
+use crate::game::scripting::tsc::bytecode_utils::{read_cur_custom_command, read_cur_varint};
 use crate::game::scripting::tsc::opcodes::TSCOpCode;
 use crate::game::scripting::tsc::text_script::TextScript;
 
@@ -170,6 +170,15 @@ impl TextScript {
                             }
                             result.push_str("\")\n");
                         }
+                        TSCOpCode::_CMD => {
+                            let (name, args) = read_cur_custom_command(&mut cursor)?;
+
+                            write!(&mut result, "%custom({}", name).unwrap();
+                            for arg in args {
+                                write!(&mut result, ", {}", arg).unwrap();
+                            }
+                            result.push_str(")\n");
+                        }
                         TSCOpCode::_NOP => result.push_str("%no_op()\n"),
                         TSCOpCode::_UNI => result.push_str("%unimplemented()\n"),
                         TSCOpCode::_END => result.push_str("%end_marker()\n"),
@@ -184,4 +193,41 @@ impl TextScript {
             Err(InvalidValue("Unknown script.".to_string()))
         }
     }
+
+    /// Returns all text printed by an event, with commands stripped.
+    pub fn event_text(&self, id: u16) -> Option<String> {
+        let mut text = String::new();
+
+        for line in self.decompile_event(id).ok()?.lines() {
+            let value = match line.strip_prefix("%string(").and_then(|l| l.split_once("value = \"")) {
+                Some((_, value)) => value.strip_suffix("\")").unwrap_or(value),
+                None => continue,
+            };
+            let mut chars = value.chars();
+
+            while let Some(chr) = chars.next() {
+                if chr != '\\' {
+                    text.push(chr);
+                    continue;
+                }
+
+                match chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('u') => {
+                        let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                        text.extend(u32::from_str_radix(&code, 16).ok().and_then(std::char::from_u32));
+                    }
+                    Some(chr) => {
+                        text.push('\\');
+                        text.push(chr);
+                    }
+                    None => text.push('\\'),
+                }
+            }
+        }
+
+        Some(text)
+    }
 }
@@ -1,8 +1,9 @@
 mod bytecode_utils;
 mod compiler;
 pub mod credit_script;
+pub mod debugger;
 mod decompiler;
 mod encryption;
-mod opcodes;
+pub mod opcodes;
 mod parse_utils;
 pub mod text_script;
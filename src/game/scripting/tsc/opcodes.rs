@@ -1,7 +1,7 @@
 use num_derive::FromPrimitive;
 
 /// Engine's text script VM operation codes.
-#[derive(EnumString, Debug, FromPrimitive, PartialEq, Copy, Clone)]
+#[derive(EnumString, Debug, FromPrimitive, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum TSCOpCode {
     // ---- Internal opcodes (used by bytecode, no TSC representation)
     /// internal: no operation
@@ -265,6 +265,8 @@ pub enum TSCOpCode {
     /// <FRE related to player 2?
     FR2,
     // ---- Custom opcodes, for use by modders ----
+    /// internal: command registered by a script, followed by its 3 character name and operands
+    _CMD,
 }
 
 #[derive(FromPrimitive, PartialEq, Copy, Clone)]
@@ -20,7 +20,9 @@ use crate::framework::error::GameResult;
 use crate::game::frame::UpdateTarget;
 use crate::game::npc::NPC;
 use crate::game::player::{ControlMode, TargetPlayer};
-use crate::game::scripting::tsc::bytecode_utils::read_cur_varint;
+use crate::game::scripting::events::ScriptEvent;
+use crate::game::scripting::tsc::bytecode_utils::{read_cur_custom_command, read_cur_varint};
+use crate::game::scripting::tsc::debugger::TextScriptDebugger;
 use crate::game::scripting::tsc::encryption::decrypt_tsc;
 use crate::game::scripting::tsc::opcodes::TSCOpCode;
 use crate::game::shared_game_state::ReplayState;
@@ -43,7 +45,7 @@ bitfield! {
     pub cutscene_skip, set_cutscene_skip: 7;
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
 #[repr(u8)]
 pub enum TextScriptEncoding {
     UTF8 = 0,
@@ -61,13 +63,7 @@ impl From<&str> for TextScriptEncoding {
 
 impl TextScriptEncoding {
     pub fn invalid_encoding(encoding: TextScriptEncoding, state: &SharedGameState) -> bool {
-        let required_encoding = if (state.loc.code == "jp" || state.loc.code == "en") && state.constants.is_base() {
-            TextScriptEncoding::ShiftJIS
-        } else {
-            TextScriptEncoding::UTF8
-        };
-
-        encoding != required_encoding
+        encoding != state.loc.encoding(state.constants.is_base())
     }
 }
 
@@ -159,6 +155,11 @@ pub struct TextScriptVM {
     pub illustration_state: IllustrationState,
     prev_char: char,
     pub substitution_rect_map: [(char, Rect<u16>); TSC_SUBSTITUTION_MAP_SIZE],
+    pub debugger: TextScriptDebugger,
+    /// Event reported to scripts as running, `PSH` calls are considered a part of it.
+    active_event: Option<u16>,
+    /// Events queued for scripts until the VM runs next time.
+    pending_events: Vec<ScriptEvent>,
 }
 
 pub struct Scripts {
@@ -176,28 +177,31 @@ pub struct Scripts {
 
 impl Scripts {
     pub fn find_script(&self, mode: ScriptMode, event_num: u16) -> Option<&Vec<u8>> {
+        self.find_text_script(mode, event_num).and_then(|script| script.event_map.get(&event_num))
+    }
+
+    /// Returns the script which an event would be executed from in specified mode.
+    pub fn find_text_script(&self, mode: ScriptMode, event_num: u16) -> Option<&TextScript> {
         match mode {
             ScriptMode::Map | ScriptMode::Debug => {
-                if mode == ScriptMode::Debug {
-                    if let Some(tsc) = self.debug_script.event_map.get(&event_num) {
-                        return Some(tsc);
-                    }
+                if mode == ScriptMode::Debug && self.debug_script.has_event(event_num) {
+                    return Some(&self.debug_script);
                 }
 
-                if let Some(tsc) = self.scene_script.event_map.get(&event_num) {
-                    return Some(tsc);
-                } else if let Some(tsc) = self.global_script.event_map.get(&event_num) {
-                    return Some(tsc);
+                if self.scene_script.has_event(event_num) {
+                    return Some(&self.scene_script);
+                } else if self.global_script.has_event(event_num) {
+                    return Some(&self.global_script);
                 }
             }
             ScriptMode::Inventory => {
-                if let Some(tsc) = self.inventory_script.event_map.get(&event_num) {
-                    return Some(tsc);
+                if self.inventory_script.has_event(event_num) {
+                    return Some(&self.inventory_script);
                 }
             }
             ScriptMode::StageSelect => {
-                if let Some(tsc) = self.stage_select_script.event_map.get(&event_num) {
-                    return Some(tsc);
+                if self.stage_select_script.has_event(event_num) {
+                    return Some(&self.stage_select_script);
                 }
             }
         }
@@ -235,6 +239,9 @@ impl TextScriptVM {
             illustration_state: IllustrationState::Hidden,
             prev_char: '\x00',
             substitution_rect_map: [('=', Rect::new(0, 0, 0, 0))],
+            debugger: TextScriptDebugger::new(),
+            active_event: None,
+            pending_events: Vec::new(),
         }
     }
 
@@ -280,6 +287,7 @@ impl TextScriptVM {
     }
 
     pub fn reset(&mut self) {
+        self.leave_event();
         self.state = TextScriptExecutionState::Ended;
         self.flags.0 = 0;
         self.current_illustration = None;
@@ -305,11 +313,49 @@ impl TextScriptVM {
         self.reset();
         self.reset_invicibility = true;
         self.state = TextScriptExecutionState::Running(event_num, 0);
+        self.enter_event(event_num);
 
         log::info!("Started script: #{:04}", event_num);
     }
 
+    /// Reports to scripts that execution has moved to specified event, ending the previous one.
+    pub fn enter_event(&mut self, event_num: u16) {
+        self.leave_event();
+        self.active_event = Some(event_num);
+        self.pending_events.push(ScriptEvent::EventStart { event_num });
+    }
+
+    fn leave_event(&mut self) {
+        if let Some(event_num) = self.active_event.take() {
+            self.pending_events.push(ScriptEvent::EventEnd { event_num });
+        }
+    }
+
+    /// Returns the number of the event that is currently being executed, if any.
+    pub fn current_event(&self) -> Option<u16> {
+        match self.state {
+            TextScriptExecutionState::Running(event, _)
+            | TextScriptExecutionState::Msg(event, _, _, _)
+            | TextScriptExecutionState::MsgNewLine(event, _, _, _, _)
+            | TextScriptExecutionState::WaitTicks(event, _, _)
+            | TextScriptExecutionState::WaitInput(event, _, _)
+            | TextScriptExecutionState::WaitStanding(event, _)
+            | TextScriptExecutionState::WaitConfirmation(event, _, _, _, _)
+            | TextScriptExecutionState::WaitFade(event, _)
+            | TextScriptExecutionState::FallingIsland(event, _, _, _, _, _)
+            | TextScriptExecutionState::SaveProfile(event, _) => Some(event),
+            _ => None,
+        }
+    }
+
     pub fn run(state: &mut SharedGameState, game_scene: &mut GameScene, ctx: &mut Context) -> GameResult {
+        let result = TextScriptVM::run_script(state, game_scene, ctx);
+        state.script_events.append(&mut state.textscript_vm.pending_events);
+
+        result
+    }
+
+    fn run_script(state: &mut SharedGameState, game_scene: &mut GameScene, ctx: &mut Context) -> GameResult {
         let scripts_ref = state.textscript_vm.scripts.clone();
         let scripts = scripts_ref.borrow();
         let mut cached_event: Option<(u16, &Vec<u8>)> = None;
@@ -322,6 +368,7 @@ impl TextScriptVM {
             match state.textscript_vm.state {
                 TextScriptExecutionState::Ended => {
                     state.control_flags.set_interactions_disabled(false);
+                    state.textscript_vm.debugger.script_ended();
                     break;
                 }
                 TextScriptExecutionState::Running(event, ip) => {
@@ -339,14 +386,29 @@ impl TextScriptVM {
                         _ => (),
                     }
 
+                    if let Some((_, bytecode)) = cached_event {
+                        let depth = state.textscript_vm.stack.len();
+                        let debugger = &mut state.textscript_vm.debugger;
+
+                        if debugger.is_active() && debugger.check(event, ip, depth, bytecode) {
+                            break;
+                        }
+                    }
+
+                    let depth = state.textscript_vm.stack.len();
                     state.textscript_vm.state = if let Some((_, bytecode)) = cached_event {
                         TextScriptVM::execute(bytecode, event, ip, state, game_scene, ctx)?
                     } else {
                         TextScriptExecutionState::Ended
                     };
 
-                    if state.textscript_vm.state == TextScriptExecutionState::Ended {
-                        state.textscript_vm.reset();
+                    match state.textscript_vm.state {
+                        TextScriptExecutionState::Ended => state.textscript_vm.reset(),
+                        // jumped to another event, calls made with <PSH grow the stack instead
+                        TextScriptExecutionState::Running(next, 0) if state.textscript_vm.stack.len() <= depth => {
+                            state.textscript_vm.enter_event(next);
+                        }
+                        _ => (),
                     }
                 }
                 TextScriptExecutionState::Msg(event, ip, remaining, counter) => {
@@ -581,6 +643,7 @@ impl TextScriptVM {
                             ConfirmSelection::No => {
                                 state.textscript_vm.clear_text_box();
                                 state.textscript_vm.state = TextScriptExecutionState::Running(no_event, 0);
+                                state.textscript_vm.enter_event(no_event);
                             }
                         }
                     }
@@ -704,6 +767,26 @@ impl TextScriptVM {
                     exec_state = TextScriptExecutionState::Running(event, cursor.position() as u32);
                 }
             }
+            TSCOpCode::_CMD => {
+                #[cfg(feature = "scripting-lua")]
+                let (name, args) = read_cur_custom_command(&mut cursor)?;
+
+                #[cfg(not(feature = "scripting-lua"))]
+                {
+                    read_cur_custom_command(&mut cursor)?;
+                    exec_state = TextScriptExecutionState::Running(event, cursor.position() as u32);
+                }
+
+                #[cfg(feature = "scripting-lua")]
+                {
+                    exec_state = match state.lua.run_tsc_command(&name, &args) {
+                        Some(0) => TextScriptExecutionState::Running(event, cursor.position() as u32),
+                        Some(ticks) => TextScriptExecutionState::WaitTicks(event, cursor.position() as u32, ticks),
+                        // blocked, run the command again on next tick
+                        None => TextScriptExecutionState::WaitTicks(event, ip, 1),
+                    };
+                }
+            }
             TSCOpCode::_END => {
                 // Vanilla keeps execution going into the next event if no proper end condition is met
                 let scripts_ref = state.textscript_vm.scripts.clone();
@@ -1241,6 +1324,8 @@ impl TextScriptVM {
                 state.textscript_vm.line_3.clear();
                 state.textscript_vm.suspend = true;
                 state.next_scene = Some(Box::new(new_scene));
+                // before the jump to the event in new stage is reported
+                game_scene.check_stage_leave(state);
 
                 log::info!("Transitioning to stage {}, with script #{:04}", map_id, event_num);
                 exec_state = TextScriptExecutionState::Running(event_num, 0);
@@ -1619,6 +1704,7 @@ impl TextScriptVM {
                     state.mod_requirements.append_item(ctx, item_id)?;
                 }
 
+                state.script_events.push(ScriptEvent::ItemAcquired { item_id });
                 exec_state = TextScriptExecutionState::Running(event, cursor.position() as u32);
             }
             TSCOpCode::IpN => {
@@ -1637,6 +1723,7 @@ impl TextScriptVM {
                     state.mod_requirements.append_item(ctx, item_id)?;
                 }
 
+                state.script_events.push(ScriptEvent::ItemAcquired { item_id });
                 exec_state = TextScriptExecutionState::Running(event, cursor.position() as u32);
             }
             TSCOpCode::ITm => {
@@ -1662,6 +1749,9 @@ impl TextScriptVM {
                     game_scene.inventory_player1.add_weapon(wtype, max_ammo);
                     game_scene.inventory_player2.add_weapon(wtype, max_ammo);
                     state.mod_requirements.append_weapon(ctx, weapon_id as u16)?;
+                    state
+                        .script_events
+                        .push(ScriptEvent::WeaponAcquired { weapon_id: weapon_id as u16, ammo: max_ammo });
                 }
 
                 exec_state = TextScriptExecutionState::Running(event, cursor.position() as u32);
@@ -1876,11 +1966,21 @@ impl TextScriptVM {
 
 pub struct TextScript {
     pub(crate) event_map: HashMap<u16, Vec<u8>>,
+    /// Decrypted source of the script, used by the debugger. Empty unless it was requested when loading.
+    pub(crate) source: Vec<u8>,
+    /// Maps bytecode offsets of each event's instructions to offsets in the source.
+    pub(crate) source_map: HashMap<u16, Vec<(u32, u32)>>,
+    pub(crate) encoding: TextScriptEncoding,
 }
 
 impl Clone for TextScript {
     fn clone(&self) -> Self {
-        Self { event_map: self.event_map.clone() }
+        Self {
+            event_map: self.event_map.clone(),
+            source: self.source.clone(),
+            source_map: self.source_map.clone(),
+            encoding: self.encoding,
+        }
     }
 }
 
@@ -1892,11 +1992,24 @@ impl Default for TextScript {
 
 impl TextScript {
     pub fn new() -> TextScript {
-        Self { event_map: HashMap::new() }
+        Self {
+            event_map: HashMap::new(),
+            source: Vec::new(),
+            source_map: HashMap::new(),
+            encoding: TextScriptEncoding::UTF8,
+        }
     }
 
     /// Loads, decrypts and compiles a text script from specified stream.
-    pub fn load_from<R: io::Read>(mut data: R, constants: &EngineConstants) -> GameResult<TextScript> {
+    ///
+    /// See [`EngineConstants::text_script_encoding`] for picking the encoding of the script.
+    /// The source is kept for the debugger only if `keep_source` is set.
+    pub fn load_from<R: io::Read>(
+        mut data: R,
+        constants: &EngineConstants,
+        encoding: TextScriptEncoding,
+        keep_source: bool,
+    ) -> GameResult<TextScript> {
         let mut buf = Vec::new();
         data.read_to_end(&mut buf)?;
 
@@ -1904,7 +2017,7 @@ impl TextScript {
             decrypt_tsc(&mut buf);
         }
 
-        TextScript::compile(&buf, false, constants.textscript.encoding)
+        TextScript::compile_with_source(&buf, false, encoding, &constants.tsc_custom_commands, keep_source)
     }
 
     pub fn get_event_ids(&self) -> Vec<u16> {
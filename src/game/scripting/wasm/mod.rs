@@ -0,0 +1,433 @@
+//! WebAssembly scripting backend, running mods compiled to WASM in an embedded interpreter.
+//!
+//! Modules import host functions from the `doukutsu` namespace and may export:
+//! - `drs_init()` - called once after the module is instantiated,
+//! - `drs_tick()` - called at the end of each game tick,
+//! - `drs_npc_hook(npc_id: i32, npc_type: i32) -> i32` - called instead of the built-in logic of NPC types
+//!   registered with `register_npc_hook`, returning 0 runs the built-in logic anyway.
+//!
+//! Positions and velocities are passed in the internal fixed point representation (1 pixel = 0x200).
+//! Each call into a module may execute at most [`FUEL_PER_CALL`] units of fuel, roughly one per instruction.
+
+use std::collections::HashSet;
+use std::io::Read;
+use std::ptr::null_mut;
+
+use wasmi::{Caller, Config, Engine, Extern, Instance, Linker, Module, Store, TypedFunc};
+
+use crate::framework::context::Context;
+use crate::framework::error::{GameError, GameResult};
+use crate::framework::filesystem;
+use crate::game::npc::NPC;
+use crate::game::player::Player;
+use crate::game::shared_game_state::SharedGameState;
+use crate::scene::game_scene::GameScene;
+
+/// Amount of fuel a module is given for each call from the engine, calls running out of it are aborted.
+pub const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// Data of a single module's store, giving host functions access to the game.
+struct WasmHost {
+    ptr: *mut WasmScriptingState,
+    npc_hooks: HashSet<u16>,
+}
+
+struct WasmMod {
+    path: String,
+    store: Store<WasmHost>,
+    tick: Option<TypedFunc<(), ()>>,
+    npc_hook: Option<TypedFunc<(i32, i32), i32>>,
+}
+
+pub struct WasmScriptingState {
+    mods: Vec<WasmMod>,
+    state_ptr: *mut SharedGameState,
+    ctx_ptr: *mut Context,
+    game_scene: *mut GameScene,
+}
+
+fn wasm_error(path: &str, err: impl std::fmt::Display) -> GameError {
+    GameError::ResourceLoadError(format!("WASM module {}: {}", path, err))
+}
+
+fn create_engine() -> Engine {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+
+    Engine::new(&config)
+}
+
+/// Tops up the fuel of a store to [`FUEL_PER_CALL`], called before each call into the module.
+fn refuel(store: &mut Store<WasmHost>) {
+    let remaining = store.consume_fuel(0).unwrap_or(0);
+    let _ = store.add_fuel(FUEL_PER_CALL.saturating_sub(remaining));
+}
+
+fn read_string(caller: &Caller<'_, WasmHost>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let mut buf = vec![0u8; len.max(0) as usize];
+    memory.read(caller, ptr.max(0) as usize, &mut buf).ok()?;
+
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+unsafe fn game_state<'a>(caller: &Caller<'_, WasmHost>) -> &'a mut SharedGameState {
+    &mut *(*caller.data().ptr).state_ptr
+}
+
+unsafe fn game_scene<'a>(caller: &Caller<'_, WasmHost>) -> Option<&'a mut GameScene> {
+    let game_scene = (*caller.data().ptr).game_scene;
+
+    if game_scene.is_null() {
+        None
+    } else {
+        Some(&mut *game_scene)
+    }
+}
+
+fn npc_param(npc: &mut NPC, param: i32, value: Option<i32>) -> i32 {
+    macro_rules! param {
+        ($field:expr, $ty:ty) => {{
+            if let Some(value) = value {
+                $field = value as $ty;
+            }
+            $field as i32
+        }};
+    }
+
+    match param {
+        0x0e => param!(npc.cond.0, u16),
+        0x0f => param!(npc.flags.0, u32),
+        0x10 => param!(npc.x, i32),
+        0x11 => param!(npc.y, i32),
+        0x12 => param!(npc.vel_x, i32),
+        0x13 => param!(npc.vel_y, i32),
+        0x14 => param!(npc.vel_x2, i32),
+        0x15 => param!(npc.vel_y2, i32),
+        0x16 => param!(npc.action_num, u16),
+        0x17 => param!(npc.anim_num, u16),
+        0x18 => param!(npc.action_counter, u16),
+        0x19 => param!(npc.action_counter2, u16),
+        0x1b => param!(npc.anim_counter, u16),
+        0x1d => npc.npc_type as i32,
+        0x1e => param!(npc.life, u16),
+        0x20 => param!(npc.event_num, u16),
+        0x30 => param!(npc.anim_rect.left, u16),
+        0x31 => param!(npc.anim_rect.top, u16),
+        0x32 => param!(npc.anim_rect.right, u16),
+        0x33 => param!(npc.anim_rect.bottom, u16),
+        _ => 0,
+    }
+}
+
+fn player_param(player: &mut Player, param: i32, value: Option<i32>) -> i32 {
+    macro_rules! param {
+        ($field:expr, $ty:ty) => {{
+            if let Some(value) = value {
+                $field = value as $ty;
+            }
+            $field as i32
+        }};
+    }
+
+    match param {
+        0x0e => param!(player.cond.0, u16),
+        0x10 => param!(player.x, i32),
+        0x11 => param!(player.y, i32),
+        0x12 => param!(player.vel_x, i32),
+        0x13 => param!(player.vel_y, i32),
+        0x1e => param!(player.life, u16),
+        _ => 0,
+    }
+}
+
+fn create_linker(engine: &Engine) -> Result<Linker<WasmHost>, wasmi::errors::LinkerError> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap("doukutsu", "log", |caller: Caller<'_, WasmHost>, ptr: i32, len: i32| {
+        if let Some(msg) = read_string(&caller, ptr, len) {
+            log::info!("[WASM] {}", msg);
+        }
+    })?;
+
+    linker.func_wrap("doukutsu", "play_sfx", |caller: Caller<'_, WasmHost>, id: i32| unsafe {
+        match u8::try_from(id) {
+            Ok(id) => game_state(&caller).sound_manager.play_sfx(id),
+            Err(_) => log::warn!("[WASM] Invalid sound effect id {}, must be at most 255", id),
+        }
+    })?;
+
+    linker.func_wrap("doukutsu", "play_sfx_loop", |caller: Caller<'_, WasmHost>, id: i32| unsafe {
+        match u8::try_from(id) {
+            Ok(id) => game_state(&caller).sound_manager.loop_sfx(id),
+            Err(_) => log::warn!("[WASM] Invalid sound effect id {}, must be at most 255", id),
+        }
+    })?;
+
+    linker.func_wrap("doukutsu", "play_song", |caller: Caller<'_, WasmHost>, id: i32, fadeout: i32| unsafe {
+        let game_state = game_state(&caller);
+        let ctx = &mut *(*caller.data().ptr).ctx_ptr;
+
+        let _ = game_state.sound_manager.play_song(
+            id.max(0) as usize,
+            &game_state.constants,
+            &game_state.settings,
+            ctx,
+            fadeout != 0,
+        );
+    })?;
+
+    linker.func_wrap("doukutsu", "get_flag", |caller: Caller<'_, WasmHost>, id: i32| unsafe {
+        game_state(&caller).get_flag(id.max(0) as usize) as i32
+    })?;
+
+    linker.func_wrap("doukutsu", "set_flag", |caller: Caller<'_, WasmHost>, id: i32, value: i32| unsafe {
+        game_state(&caller).set_flag(id.max(0) as usize, value != 0);
+    })?;
+
+    linker.func_wrap("doukutsu", "get_skip_flag", |caller: Caller<'_, WasmHost>, id: i32| unsafe {
+        game_state(&caller).get_skip_flag(id.max(0) as usize) as i32
+    })?;
+
+    linker.func_wrap("doukutsu", "set_skip_flag", |caller: Caller<'_, WasmHost>, id: i32, value: i32| unsafe {
+        game_state(&caller).set_skip_flag(id.max(0) as usize, value != 0);
+    })?;
+
+    linker.func_wrap("doukutsu", "stage_id", |caller: Caller<'_, WasmHost>| unsafe {
+        game_scene(&caller).map_or(-1, |game_scene| game_scene.stage_id as i32)
+    })?;
+
+    linker.func_wrap("doukutsu", "register_npc_hook", |mut caller: Caller<'_, WasmHost>, npc_type: i32| {
+        caller.data_mut().npc_hooks.insert(npc_type as u16);
+    })?;
+
+    linker.func_wrap("doukutsu", "npc_get", |caller: Caller<'_, WasmHost>, npc_id: i32, param: i32| unsafe {
+        game_scene(&caller)
+            .and_then(|game_scene| game_scene.npc_list.get_npc(npc_id.max(0) as usize))
+            .map_or(0, |npc| npc_param(npc, param, None))
+    })?;
+
+    linker.func_wrap(
+        "doukutsu",
+        "npc_set",
+        |caller: Caller<'_, WasmHost>, npc_id: i32, param: i32, value: i32| unsafe {
+            if let Some(npc) =
+                game_scene(&caller).and_then(|game_scene| game_scene.npc_list.get_npc(npc_id.max(0) as usize))
+            {
+                npc_param(npc, param, Some(value));
+            }
+        },
+    )?;
+
+    linker.func_wrap("doukutsu", "player_get", |caller: Caller<'_, WasmHost>, player_id: i32, param: i32| unsafe {
+        match (game_scene(&caller), player_id) {
+            (Some(game_scene), 0) => player_param(&mut game_scene.player1, param, None),
+            (Some(game_scene), 1) => player_param(&mut game_scene.player2, param, None),
+            _ => 0,
+        }
+    })?;
+
+    linker.func_wrap(
+        "doukutsu",
+        "player_set",
+        |caller: Caller<'_, WasmHost>, player_id: i32, param: i32, value: i32| unsafe {
+            match (game_scene(&caller), player_id) {
+                (Some(game_scene), 0) => player_param(&mut game_scene.player1, param, Some(value)),
+                (Some(game_scene), 1) => player_param(&mut game_scene.player2, param, Some(value)),
+                _ => 0,
+            };
+        },
+    )?;
+
+    Ok(linker)
+}
+
+impl WasmScriptingState {
+    pub fn new() -> WasmScriptingState {
+        WasmScriptingState { mods: Vec::new(), state_ptr: null_mut(), ctx_ptr: null_mut(), game_scene: null_mut() }
+    }
+
+    pub fn update_refs(&mut self, state: *mut SharedGameState, ctx: *mut Context) {
+        self.state_ptr = state;
+        self.ctx_ptr = ctx;
+    }
+
+    pub fn set_game_scene(&mut self, game_scene: *mut GameScene) {
+        self.game_scene = game_scene;
+    }
+
+    fn load_module(&mut self, engine: &Engine, linker: &Linker<WasmHost>, path: &str, data: &[u8]) -> GameResult {
+        let module = Module::new(engine, data).map_err(|err| wasm_error(path, err))?;
+
+        let mut store =
+            Store::new(engine, WasmHost { ptr: self as *mut WasmScriptingState, npc_hooks: HashSet::new() });
+        refuel(&mut store);
+        let instance: Instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| wasm_error(path, err))?;
+
+        if let Ok(init) = instance.get_typed_func::<(), ()>(&store, "drs_init") {
+            refuel(&mut store);
+            init.call(&mut store, ()).map_err(|err| wasm_error(path, err))?;
+        }
+
+        let tick = instance.get_typed_func::<(), ()>(&store, "drs_tick").ok();
+        let npc_hook = instance.get_typed_func::<(i32, i32), i32>(&store, "drs_npc_hook").ok();
+
+        self.mods.push(WasmMod { path: path.to_owned(), store, tick, npc_hook });
+
+        Ok(())
+    }
+
+    /// Loads `/Scripts/main.wasm` of the current mod and all modules in `drs-scripts` user directory.
+    pub fn reload_modules(&mut self, ctx: &mut Context) -> GameResult {
+        self.mods.clear();
+
+        let engine = create_engine();
+        let linker = create_linker(&engine).map_err(|err| wasm_error("host", err))?;
+
+        let mut files = Vec::new();
+        if filesystem::user_exists(ctx, "/drs-scripts/") {
+            match filesystem::user_read_dir(ctx, "/drs-scripts/") {
+                Ok(paths) => {
+                    for path in paths {
+                        if path.to_string_lossy().to_lowercase().ends_with(".wasm") {
+                            files.push((path.to_string_lossy().to_string(), filesystem::user_open(ctx, &path)));
+                        }
+                    }
+                }
+                Err(err) => log::warn!("Error listing WASM modules in drs-scripts: {}", err),
+            }
+        }
+
+        let main_path = "/Scripts/main.wasm";
+        if filesystem::exists(ctx, main_path) {
+            files.push((main_path.to_owned(), filesystem::open(ctx, main_path)));
+        }
+
+        for (path, file) in files {
+            let mut data = Vec::new();
+            let res = file.and_then(|mut file| file.read_to_end(&mut data).map_err(GameError::from));
+
+            match res.and_then(|_| self.load_module(&engine, &linker, &path, &data)) {
+                Ok(()) => log::info!("Successfully loaded WASM module: {}", path),
+                Err(err) => log::warn!("Error loading WASM module {}: {}", path, err),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn scene_tick(&mut self) {
+        for module in self.mods.iter_mut() {
+            if let Some(tick) = &module.tick {
+                refuel(&mut module.store);
+                if let Err(err) = tick.call(&mut module.store, ()) {
+                    log::error!("{} tick error: {}", module.path, err);
+                }
+            }
+        }
+    }
+
+    /// Runs the hooks registered for specified NPC type, returns true if the built-in logic should be skipped.
+    /// Modules are asked in load order until one of them handles the NPC.
+    pub fn try_run_npc_hook(&mut self, npc_id: u16, npc_type: u16) -> bool {
+        for module in self.mods.iter_mut() {
+            if let Some(npc_hook) = &module.npc_hook {
+                if !module.store.data().npc_hooks.contains(&npc_type) {
+                    continue;
+                }
+
+                refuel(&mut module.store);
+                match npc_hook.call(&mut module.store, (npc_id as i32, npc_type as i32)) {
+                    Ok(handled) if handled != 0 => return true,
+                    Ok(_) => (),
+                    Err(err) => log::error!("{} npc_hook error: {}", module.path, err),
+                }
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MODULE: &str = r#"
+        (module
+            (import "doukutsu" "register_npc_hook" (func $register_npc_hook (param i32)))
+            (global $ticks (mut i32) (i32.const 0))
+            (func (export "drs_init")
+                (call $register_npc_hook (i32.const 7)))
+            (func (export "drs_tick")
+                (global.set $ticks (i32.add (global.get $ticks) (i32.const 1)))
+                ;; every other tick never finishes, which has to be stopped by the fuel limit
+                (if (i32.eqz (i32.rem_u (global.get $ticks) (i32.const 2)))
+                    (then (loop $forever (br $forever)))))
+            (func (export "drs_npc_hook") (param $npc_id i32) (param $npc_type i32) (result i32)
+                (i32.eq (local.get $npc_id) (i32.const 1))))
+    "#;
+
+    // handles the NPCs the test module leaves to the engine
+    const SECOND_TEST_MODULE: &str = r#"
+        (module
+            (import "doukutsu" "register_npc_hook" (func $register_npc_hook (param i32)))
+            (func (export "drs_init")
+                (call $register_npc_hook (i32.const 7)))
+            (func (export "drs_npc_hook") (param $npc_id i32) (param $npc_type i32) (result i32)
+                (i32.eq (local.get $npc_id) (i32.const 2))))
+    "#;
+
+    fn load_module_source(wasm: &mut WasmScriptingState, path: &str, source: &str) {
+        let data = wat::parse_str(source).unwrap();
+        let engine = create_engine();
+        let linker = create_linker(&engine).unwrap();
+
+        wasm.load_module(&engine, &linker, path, &data).unwrap();
+    }
+
+    fn load_test_module(wasm: &mut WasmScriptingState) {
+        load_module_source(wasm, "test.wasm", TEST_MODULE);
+    }
+
+    #[test]
+    fn test_npc_hooks() {
+        let mut wasm = WasmScriptingState::new();
+        load_test_module(&mut wasm);
+
+        assert_eq!(wasm.mods.len(), 1);
+        assert!(wasm.mods[0].store.data().npc_hooks.contains(&7));
+
+        assert!(wasm.try_run_npc_hook(1, 7));
+        assert!(!wasm.try_run_npc_hook(2, 7));
+        // types without a registered hook are never passed to the module
+        assert!(!wasm.try_run_npc_hook(1, 8));
+    }
+
+    #[test]
+    fn test_fuel_is_refilled_per_call() {
+        let mut wasm = WasmScriptingState::new();
+        load_test_module(&mut wasm);
+
+        for _ in 0..4 {
+            wasm.scene_tick();
+        }
+
+        // the module keeps working after running out of fuel
+        assert!(wasm.try_run_npc_hook(1, 7));
+    }
+
+    #[test]
+    fn test_npc_hook_falls_through_modules() {
+        let mut wasm = WasmScriptingState::new();
+        load_test_module(&mut wasm);
+        load_module_source(&mut wasm, "second.wasm", SECOND_TEST_MODULE);
+
+        assert!(wasm.try_run_npc_hook(1, 7));
+        assert!(wasm.try_run_npc_hook(2, 7));
+        assert!(!wasm.try_run_npc_hook(3, 7));
+    }
+}
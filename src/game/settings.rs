@@ -87,6 +87,9 @@ pub struct Settings {
     pub discord_rpc: bool,
     #[serde(default = "default_true")]
     pub allow_strafe: bool,
+    /// Ids of mods layered on top of the game data, later mods take precedence over earlier ones.
+    #[serde(default)]
+    pub enabled_mods: Vec<String>,
 }
 
 fn default_true() -> bool {
@@ -455,6 +458,7 @@ impl Default for Settings {
             cutscene_skip_mode: CutsceneSkipMode::Hold,
             discord_rpc: true,
             allow_strafe: true,
+            enabled_mods: Vec::new(),
         }
     }
 }
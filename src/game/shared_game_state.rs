@@ -20,6 +20,10 @@ use crate::game::player::TargetPlayer;
 use crate::game::profile::GameProfile;
 #[cfg(feature = "scripting-lua")]
 use crate::game::scripting::lua::LuaScriptingState;
+#[cfg(feature = "scripting-wasm")]
+use crate::game::scripting::wasm::WasmScriptingState;
+use crate::game::scripting::events::ScriptEvent;
+use crate::game::scripting::storage::ScriptStorage;
 use crate::game::scripting::tsc::credit_script::{CreditScript, CreditScriptVM};
 use crate::game::scripting::tsc::text_script::{ScriptMode, TextScript, TextScriptEncoding, TextScriptExecutionState, TextScriptVM};
 use crate::game::settings::Settings;
@@ -27,7 +31,9 @@ use crate::game::stage::StageData;
 use crate::graphics::bmfont::BMFont;
 use crate::graphics::texture_set::TextureSet;
 use crate::i18n::Locale;
+use crate::input::bot_player_controller::BotPlayerController;
 use crate::input::touch_controls::TouchControls;
+use crate::live_debugger::remote_control::RemoteControl;
 use crate::mod_list::ModList;
 use crate::mod_requirements::ModRequirements;
 use crate::scene::game_scene::GameScene;
@@ -103,7 +109,8 @@ impl WindowMode {
     }
 }
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug, num_derive::FromPrimitive)]
+#[derive(PartialEq, Eq, Copy, Clone, Debug, num_derive::FromPrimitive, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GameDifficulty {
     Normal = 0,
     Easy = 2,
@@ -151,11 +158,13 @@ pub struct FontData {
     pub path: String,
     pub scale: f32,
     pub space_offset: f32,
+    /// Fonts used for characters missing from the main one, eg. Arabic glyphs in a Latin font.
+    pub fallbacks: Vec<String>,
 }
 
 impl FontData {
     pub fn new(path: String, scale: f32, space_offset: f32) -> FontData {
-        FontData { path, scale, space_offset }
+        FontData { path, scale, space_offset, fallbacks: Vec::new() }
     }
 }
 
@@ -334,6 +343,10 @@ pub struct SharedGameState {
     pub texture_set: TextureSet,
     #[cfg(feature = "scripting-lua")]
     pub lua: LuaScriptingState,
+    #[cfg(feature = "scripting-wasm")]
+    pub wasm: WasmScriptingState,
+    /// Data stored by scripts in the current save slot.
+    pub script_storage: ScriptStorage,
     pub sound_manager: SoundManager,
     pub settings: Settings,
     pub save_slot: usize,
@@ -342,7 +355,14 @@ pub struct SharedGameState {
     pub player_count_modified_in_game: bool,
     pub player2_skin_location: PlayerSkinLocation,
     pub replay_state: ReplayState,
+    /// If set, player 1 is driven by this bot instead of the configured input device.
+    pub bot: Option<BotPlayerController>,
+    pub remote_control: Option<RemoteControl>,
+    /// Events waiting to be dispatched to scripts at the end of current tick.
+    pub script_events: Vec<ScriptEvent>,
     pub mod_requirements: ModRequirements,
+    /// Incremented whenever game data is reloaded, lets caches derived from it notice the change.
+    pub resources_generation: u32,
     pub loc: Locale,
     pub tutorial_counter: u16,
     pub more_rust: bool,
@@ -369,8 +389,8 @@ impl SharedGameState {
         };
 
         #[cfg(not(target_os = "horizon"))]
-        if let Some(vanilla_extractor) =
-            VanillaExtractor::from(ctx, vanilla_ext_exe.to_string(), vanilla_ext_outdir.to_string())
+        if let Some(mut vanilla_extractor) =
+            VanillaExtractor::from(vanilla_ext_exe.to_string(), vanilla_ext_outdir.to_string())
         {
             let result = vanilla_extractor.extract_data();
             if let Err(e) = result {
@@ -414,21 +434,20 @@ impl SharedGameState {
         }
 
         let season = Season::current();
-        constants.rebuild_path_list(None, season, &settings);
+        constants.rebuild_path_list(&[], season, &settings);
 
         constants.load_locales(ctx)?;
 
         let locale = SharedGameState::get_locale(&constants, &settings.locale).unwrap_or_default();
-        if (locale.code == "jp" || locale.code == "en") && constants.is_base() {
-            constants.textscript.encoding =  TextScriptEncoding::ShiftJIS
-        } else {
-            constants.textscript.encoding =  TextScriptEncoding::UTF8
-        }
+        constants.textscript.encoding =
+            if constants.is_base() { TextScriptEncoding::ShiftJIS } else { TextScriptEncoding::UTF8 };
+        constants.locale_encoding = locale.encoding(constants.is_base());
         
-        let font = BMFont::load(&constants.base_paths, &locale.font.path, ctx, locale.font.scale).or_else(|e| {
+        let mut font = BMFont::load(&constants.base_paths, &locale.font.path, ctx, locale.font.scale).or_else(|e| {
             log::warn!("Failed to load font, using built-in: {}", e);
             BMFont::load(&vec!["/".to_owned()], "builtin/builtin_font.fnt", ctx, 1.0)
         })?;
+        font.load_fallbacks(&constants.base_paths, &locale.font.fallbacks, ctx);
 
         let mod_list = ModList::load(ctx, &constants.string_table)?;
 
@@ -461,7 +480,7 @@ impl SharedGameState {
 
         Ok(SharedGameState {
             control_flags: ControlFlags(0),
-            game_flags: BitVec::with_size(8000),
+            game_flags: BitVec::with_size(constants.game.flag_count),
             skip_flags: BitVec::with_size(64),
             map_flags: BitVec::with_size(128),
             fade_state: FadeState::Hidden,
@@ -502,6 +521,9 @@ impl SharedGameState {
             texture_set: TextureSet::new(),
             #[cfg(feature = "scripting-lua")]
             lua: LuaScriptingState::new(),
+            #[cfg(feature = "scripting-wasm")]
+            wasm: WasmScriptingState::new(),
+            script_storage: ScriptStorage::default(),
             sound_manager,
             settings,
             save_slot: 1,
@@ -510,7 +532,11 @@ impl SharedGameState {
             player_count_modified_in_game: false,
             player2_skin_location: PlayerSkinLocation::default(),
             replay_state: ReplayState::None,
+            bot: None,
+            remote_control: None,
+            script_events: Vec::new(),
             mod_requirements,
+            resources_generation: 0,
             loc: locale,
             tutorial_counter: 0,
             more_rust,
@@ -521,72 +547,148 @@ impl SharedGameState {
     }
 
     pub fn reload_resources(&mut self, ctx: &mut Context) -> GameResult {
-        self.constants.rebuild_path_list(self.mod_path.clone(), self.season, &self.settings);
+        let mod_paths = self.get_mod_paths();
+        self.constants.rebuild_path_list(&mod_paths, self.season, &self.settings);
+        if self.mod_list.update_conflicts(ctx, &mod_paths) {
+            for conflict in self.mod_list.conflicts() {
+                log::warn!("{} is provided by multiple mods: {}", conflict.path, conflict.mods.join(", "));
+            }
+        }
+        self.constants.reset_overridable_sections()?;
         if !self.constants.is_demo {
             //TODO find a more elegant way to handle this
             self.constants.special_treatment_for_csplus_mods(self.mod_path.as_ref());
         }
         self.constants.load_csplus_tables(ctx)?;
+        self.constants.apply_constant_json_files(ctx)?;
         self.constants.load_animated_faces(ctx)?;
         self.constants.load_texture_size_hints(ctx)?;
-        let stages = StageData::load_stage_table(ctx, &self.constants.base_paths, self.constants.is_switch)?;
-        self.stages = stages;
+        self.load_stage_table(ctx)?;
 
         let npc_tbl = filesystem::open_find(ctx, &self.constants.base_paths, "npc.tbl")?;
         let npc_table = NPCTable::load_from(npc_tbl)?;
         self.npc_table = npc_table;
 
-        let head_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "Head.tsc")?;
-        let head_script = TextScript::load_from(head_tsc, &self.constants)?;
-        self.textscript_vm.set_global_script(head_script);
-
-        let arms_item_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "ArmsItem.tsc")?;
-        let arms_item_script = TextScript::load_from(arms_item_tsc, &self.constants)?;
-        self.textscript_vm.set_inventory_script(arms_item_script);
+        #[cfg(not(feature = "scripting-lua"))]
+        self.load_global_text_scripts(ctx)?;
 
-        let stage_select_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "StageSelect.tsc")?;
-        let stage_select_script = TextScript::load_from(stage_select_tsc, &self.constants)?;
-        self.textscript_vm.set_stage_select_script(stage_select_script);
+        #[cfg(feature = "scripting-lua")]
+        if self.lua.is_loaded() {
+            self.load_global_text_scripts(ctx)?;
+        } else {
+            // Scripts may use commands registered by Lua scripts, so those have to be loaded first.
+            self.reload_lua_scripts(ctx, false)?;
+        }
 
         let substitution_rect_map = [('=', self.constants.textscript.textbox_item_marker_rect)];
         self.textscript_vm.set_substitution_rect_map(substitution_rect_map);
 
+        self.load_credit_script(ctx)?;
+
+        self.texture_set.unload_all();
+
+        self.sound_manager.load_custom_sound_effects(ctx, &self.constants.base_paths)?;
+
+        Ok(())
+    }
+
+    /// Loads the stage table with the stage names of the current locale.
+    fn load_stage_table(&mut self, ctx: &mut Context) -> GameResult {
+        let mut stages = StageData::load_stage_table(ctx, &self.constants.base_paths, self.constants.is_switch)?;
+        StageData::apply_translated_names(&mut stages, ctx, &self.constants.locale_paths);
+        self.stages = stages;
+
+        Ok(())
+    }
+
+    fn load_credit_script(&mut self, ctx: &mut Context) -> GameResult {
         if filesystem::exists_find(ctx, &self.constants.base_paths, "Credit.tsc") {
             let credit_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "Credit.tsc")?;
-            let credit_script = CreditScript::load_from(credit_tsc, &self.constants)?;
+            let encoding = self.constants.text_script_encoding(ctx, "Credit.tsc");
+            let credit_script = CreditScript::load_from(credit_tsc, &self.constants, encoding)?;
             self.creditscript_vm.set_script(credit_script);
         }
 
-        self.texture_set.unload_all();
+        Ok(())
+    }
 
-        self.sound_manager.load_custom_sound_effects(ctx, &self.constants.base_paths)?;
+    /// Whether text scripts should keep their source for the TSC debugger, which is only reachable with
+    /// debug keys enabled.
+    pub fn keep_script_sources(&self) -> bool {
+        cfg!(debug_assertions) || self.settings.debug_mode || self.debugger
+    }
+
+    /// Loads Head.tsc, ArmsItem.tsc and StageSelect.tsc.
+    pub fn load_global_text_scripts(&mut self, ctx: &mut Context) -> GameResult {
+        let keep_source = self.keep_script_sources();
+        let head_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "Head.tsc")?;
+        let encoding = self.constants.text_script_encoding(ctx, "Head.tsc");
+        let head_script = TextScript::load_from(head_tsc, &self.constants, encoding, keep_source)?;
+        self.textscript_vm.set_global_script(head_script);
+
+        let arms_item_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "ArmsItem.tsc")?;
+        let encoding = self.constants.text_script_encoding(ctx, "ArmsItem.tsc");
+        let arms_item_script = TextScript::load_from(arms_item_tsc, &self.constants, encoding, keep_source)?;
+        self.textscript_vm.set_inventory_script(arms_item_script);
+
+        let stage_select_tsc = filesystem::open_find(ctx, &self.constants.base_paths, "StageSelect.tsc")?;
+        let encoding = self.constants.text_script_encoding(ctx, "StageSelect.tsc");
+        let stage_select_script = TextScript::load_from(stage_select_tsc, &self.constants, encoding, keep_source)?;
+        self.textscript_vm.set_stage_select_script(stage_select_script);
+        self.resources_generation = self.resources_generation.wrapping_add(1);
 
         Ok(())
     }
 
+    /// Reloads Lua scripts and recompiles global text scripts, as the set of custom TSC commands might have changed.
+    ///
+    /// See [`LuaScriptingState::reload_scripts`] for the meaning of `preserve_state`.
+    #[cfg(feature = "scripting-lua")]
+    pub fn reload_lua_scripts(&mut self, ctx: &mut Context, preserve_state: bool) -> GameResult {
+        self.constants.tsc_custom_commands.clear();
+        self.lua.reload_scripts(ctx, preserve_state)?;
+        for mod_path in self.get_mod_paths() {
+            let scripts = self.mod_list.get_scripts_from_path(mod_path);
+            self.lua.load_mod_scripts(ctx, &scripts);
+        }
+        self.load_global_text_scripts(ctx)
+    }
+
     pub fn reload_graphics(&mut self) {
-        self.constants.rebuild_path_list(self.mod_path.clone(), self.season, &self.settings);
+        self.constants.rebuild_path_list(&self.get_mod_paths(), self.season, &self.settings);
         self.texture_set.unload_all();
     }
 
     pub fn update_locale(&mut self, ctx: &mut Context) {
         if let Some(locale) = SharedGameState::get_locale(&self.constants, &self.settings.locale) {
             self.loc = locale;
-            if (self.loc.code == "jp" || self.loc.code == "en") && self.constants.is_base() {
-                self.constants.textscript.encoding =  TextScriptEncoding::ShiftJIS
-            } else {
-                self.constants.textscript.encoding =  TextScriptEncoding::UTF8
-            }
+            self.constants.locale_encoding = self.loc.encoding(self.constants.is_base());
         }
 
-        let font = BMFont::load(&self.constants.base_paths, &self.loc.font.path, ctx, self.loc.font.scale)
+        // switch to the translated data of the new locale
+        self.reload_graphics();
+
+        let mut font = BMFont::load(&self.constants.base_paths, &self.loc.font.path, ctx, self.loc.font.scale)
             .or_else(|e| {
                 log::warn!("Failed to load font, using built-in: {}", e);
                 BMFont::load(&vec!["/".to_owned()], "builtin/builtin_font.fnt", ctx, 1.0)
             })
             .unwrap();
+        font.load_fallbacks(&self.constants.base_paths, &self.loc.font.fallbacks, ctx);
 
         self.font = font;
+
+        if let Err(e) = self.reload_translated_data(ctx) {
+            log::error!("Failed to reload translated data: {}", e);
+        }
+    }
+
+    /// Reloads stage names and text scripts, which may come from other files and use another encoding
+    /// after the locale has changed.
+    fn reload_translated_data(&mut self, ctx: &mut Context) -> GameResult {
+        self.load_stage_table(ctx)?;
+        self.load_global_text_scripts(ctx)?;
+        self.load_credit_script(ctx)
     }
 
     pub fn graphics_reset(&mut self) {
@@ -596,7 +698,9 @@ impl SharedGameState {
     pub fn start_new_game(&mut self, ctx: &mut Context) -> GameResult {
         self.reset();
         #[cfg(feature = "scripting-lua")]
-        self.lua.reload_scripts(ctx)?;
+        self.reload_lua_scripts(ctx, false)?;
+        #[cfg(feature = "scripting-wasm")]
+        self.wasm.reload_modules(ctx)?;
 
         #[cfg(feature = "discord-rpc")]
         self.discord_rpc.update_difficulty(self.difficulty)?;
@@ -612,6 +716,7 @@ impl SharedGameState {
         self.control_flags.set_tick_world(true);
         self.fade_state = FadeState::Hidden;
         self.textscript_vm.state = TextScriptExecutionState::Running(self.constants.game.new_game_event, 0);
+        self.textscript_vm.enter_event(self.constants.game.new_game_event);
         self.tutorial_counter = 300;
 
         self.next_scene = Some(Box::new(next_scene));
@@ -621,7 +726,9 @@ impl SharedGameState {
 
     pub fn start_intro(&mut self, ctx: &mut Context) -> GameResult {
         #[cfg(feature = "scripting-lua")]
-        self.lua.reload_scripts(ctx)?;
+        self.reload_lua_scripts(ctx, false)?;
+        #[cfg(feature = "scripting-wasm")]
+        self.wasm.reload_modules(ctx)?;
 
         let start_stage_id = self.constants.game.intro_stage as usize;
 
@@ -649,9 +756,15 @@ impl SharedGameState {
 
     pub fn save_game(&mut self, game_scene: &mut GameScene, ctx: &mut Context, target_player: Option<TargetPlayer>) -> GameResult {
         if let Some(save_path) = self.get_save_filename(self.save_slot) {
-            if let Ok(data) = filesystem::open_options(ctx, save_path, OpenOptions::new().write(true).create(true)) {
+            if let Ok(data) = filesystem::open_options(ctx, &save_path, OpenOptions::new().write(true).create(true)) {
                 let profile = GameProfile::dump(self, game_scene, target_player);
                 profile.write_save(data)?;
+
+                if let Err(err) = self.script_storage.save(ctx, &save_path) {
+                    log::warn!("Cannot save script storage: {}", err);
+                }
+
+                self.script_events.push(ScriptEvent::Save { slot: self.save_slot });
             } else {
                 log::warn!("Cannot open save file.");
             }
@@ -668,16 +781,20 @@ impl SharedGameState {
                 match GameProfile::load_from_save(data) {
                     Ok(profile) => {
                         self.reset();
+                        // loaded before the stage, so its script can use custom commands
+                        #[cfg(feature = "scripting-lua")]
+                        self.reload_lua_scripts(ctx, false)?;
+                        #[cfg(feature = "scripting-wasm")]
+                        self.wasm.reload_modules(ctx)?;
+
                         let mut next_scene = GameScene::new(self, ctx, profile.current_map as usize)?;
 
                         profile.apply(self, &mut next_scene, ctx);
 
-                        #[cfg(feature = "scripting-lua")]
-                        self.lua.reload_scripts(ctx)?;
-
                         #[cfg(feature = "discord-rpc")]
                         self.discord_rpc.update_difficulty(self.difficulty)?;
 
+                        self.script_events.push(ScriptEvent::Load { slot: self.save_slot });
                         self.next_scene = Some(Box::new(next_scene));
                         return Ok(());
                     }
@@ -697,7 +814,7 @@ impl SharedGameState {
 
     pub fn reset(&mut self) {
         self.control_flags.0 = 0;
-        self.game_flags = BitVec::with_size(8000);
+        self.game_flags = BitVec::with_size(self.constants.game.flag_count);
         self.fade_state = FadeState::Hidden;
         self.game_rng = XorShift::new(chrono::Local::now().timestamp() as i32);
         self.teleporter_slots.clear();
@@ -705,6 +822,7 @@ impl SharedGameState {
         self.carets.clear();
         self.textscript_vm.set_mode(ScriptMode::Map);
         self.textscript_vm.suspend = true;
+        self.script_storage.clear();
     }
 
     pub fn handle_resize(&mut self, ctx: &mut Context) -> GameResult {
@@ -832,6 +950,22 @@ impl SharedGameState {
         }
     }
 
+    /// Returns the roots of active mods in load order, the started mod is layered on top of the enabled ones.
+    pub fn get_mod_paths(&self) -> Vec<String> {
+        self.mod_list.get_mod_paths(&self.settings.enabled_mods, self.mod_path.as_ref())
+    }
+
+    /// Id of the current mod used to separate its script storage from other mods sharing the save slot.
+    /// Mods missing from the mod list are told apart by their path, the base game uses an empty id.
+    pub fn get_script_storage_id(&self) -> String {
+        match &self.mod_path {
+            Some(mod_path) => {
+                self.mod_list.get_id_from_path(mod_path.to_string()).unwrap_or(mod_path.as_str()).to_owned()
+            }
+            None => String::new(),
+        }
+    }
+
     pub fn get_rec_filename(&self) -> String {
         if let Some(mod_path) = &self.mod_path {
             let name = self.mod_list.get_name_from_path(mod_path.to_string());
@@ -880,23 +1014,14 @@ impl SharedGameState {
     }
 
     fn get_locale(constants: &EngineConstants, user_locale: &str) -> Option<Locale> {
-        let mut out_locale = None;
-
-        for locale in &constants.locales {
-            if locale.code == "en" {
-                out_locale = Some(locale.clone());
-            }
-
-            if locale.code == user_locale {
-                out_locale = Some(locale.clone());
-                break;
-            }
-        }
-
-        out_locale
+        Locale::resolve(&constants.locales, user_locale)
     }
 
     pub fn tt(&self, key: &str, args: &[(&str, &str)]) -> String {
         return self.loc.tt(key, args);
     }
+
+    pub fn tp(&self, key: &str, count: i64, args: &[(&str, &str)]) -> String {
+        return self.loc.tp(key, count, args);
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::io::{Cursor, Read};
 use std::str::from_utf8;
 
@@ -535,6 +536,40 @@ impl StageData {
 
         Err(ResourceLoadError("No stage table found.".to_string()))
     }
+
+    /// Replaces stage names with the ones from `stage_names.json` files in translation roots,
+    /// which map names of the map files to the translated names. Earlier roots take precedence.
+    pub fn apply_translated_names(stages: &mut [StageData], ctx: &mut Context, roots: &[String]) {
+        for root in roots.iter().rev() {
+            let path = format!("{}stage_names.json", root);
+            let file = match filesystem::open(ctx, &path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            let names: HashMap<String, String> = match serde_json::from_reader(file) {
+                Ok(names) => names,
+                Err(err) => {
+                    log::warn!("Failed to parse {}: {}", path, err);
+                    continue;
+                }
+            };
+
+            info!("Loading translated stage names from {}", &path);
+
+            for stage in stages.iter_mut() {
+                // "u" marks the intro stage, which shows the intro text instead of its name
+                if stage.name == "u" {
+                    continue;
+                }
+
+                if let Some(name) = names.get(&stage.map) {
+                    stage.name = name.clone();
+                    stage.name_jp = name.clone();
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -569,10 +604,13 @@ impl Stage {
         &self,
         roots: &Vec<String>,
         constants: &EngineConstants,
+        keep_source: bool,
         ctx: &mut Context,
     ) -> GameResult<TextScript> {
-        let tsc_file = filesystem::open_find(ctx, roots, ["Stage/", &self.data.map, ".tsc"].join(""))?;
-        let text_script = TextScript::load_from(tsc_file, constants)?;
+        let tsc_path = ["Stage/", &self.data.map, ".tsc"].join("");
+        let tsc_file = filesystem::open_find(ctx, roots, &tsc_path)?;
+        let encoding = constants.text_script_encoding(ctx, &tsc_path);
+        let text_script = TextScript::load_from(tsc_file, constants, encoding, keep_source)?;
 
         Ok(text_script)
     }
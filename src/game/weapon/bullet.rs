@@ -1662,7 +1662,15 @@ impl Bullet {
             return;
         }
 
+        #[allow(unused_mut, unused_assignments)]
+        let mut bullet_hook_ran = false;
+        #[cfg(feature = "scripting-lua")]
+        {
+            bullet_hook_ran = state.lua.try_run_bullet_hook(self);
+        }
+
         match self.btype {
+            _ if bullet_hook_ran => {}
             1 => self.tick_snake_1(state),
             2 | 3 => self.tick_snake_2(state, npc_list),
             4 | 5 | 6 => self.tick_polar_star(state),
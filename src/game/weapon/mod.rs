@@ -4,6 +4,7 @@ use crate::common::Direction;
 use crate::engine_constants::EngineConstants;
 use crate::game::caret::CaretType;
 use crate::game::player::{Player, TargetPlayer};
+use crate::game::scripting::events::ScriptEvent;
 use crate::game::shared_game_state::SharedGameState;
 use crate::game::weapon::bullet::BulletManager;
 
@@ -183,22 +184,34 @@ impl Weapon {
             self.refire_timer = 4;
         }
 
-        // todo lua hook
-
-        match self.wtype {
-            WeaponType::None => {}
-            WeaponType::Snake => self.tick_snake(player, player_id, bullet_manager, state),
-            WeaponType::PolarStar => self.tick_polar_star(player, player_id, bullet_manager, state),
-            WeaponType::Fireball => self.tick_fireball(player, player_id, bullet_manager, state),
-            WeaponType::MachineGun => self.tick_machine_gun(player, player_id, bullet_manager, state),
-            WeaponType::MissileLauncher => self.tick_missile_launcher(player, player_id, bullet_manager, state),
-            WeaponType::Bubbler => self.tick_bubbler(player, player_id, bullet_manager, state),
-            WeaponType::Blade => self.tick_blade(player, player_id, bullet_manager, state),
-            WeaponType::SuperMissileLauncher => {
-                self.tick_super_missile_launcher(player, player_id, bullet_manager, state)
+        let bullet_count = bullet_manager.bullets.len();
+
+        #[cfg(feature = "scripting-lua")]
+        let behaviour = state.lua.try_run_weapon_hook(self, player, player_id, bullet_manager);
+        #[cfg(not(feature = "scripting-lua"))]
+        let behaviour = Some(self.wtype);
+
+        // the hook returns None when a script handled the tick, or the fallback id has no built-in weapon
+        if let Some(wtype) = behaviour {
+            match wtype {
+                WeaponType::None => {}
+                WeaponType::Snake => self.tick_snake(player, player_id, bullet_manager, state),
+                WeaponType::PolarStar => self.tick_polar_star(player, player_id, bullet_manager, state),
+                WeaponType::Fireball => self.tick_fireball(player, player_id, bullet_manager, state),
+                WeaponType::MachineGun => self.tick_machine_gun(player, player_id, bullet_manager, state),
+                WeaponType::MissileLauncher => self.tick_missile_launcher(player, player_id, bullet_manager, state),
+                WeaponType::Bubbler => self.tick_bubbler(player, player_id, bullet_manager, state),
+                WeaponType::Blade => self.tick_blade(player, player_id, bullet_manager, state),
+                WeaponType::SuperMissileLauncher => {
+                    self.tick_super_missile_launcher(player, player_id, bullet_manager, state)
+                }
+                WeaponType::Nemesis => self.tick_nemesis(player, player_id, bullet_manager, state),
+                WeaponType::Spur => self.tick_spur(player, player_id, bullet_manager, state),
             }
-            WeaponType::Nemesis => self.tick_nemesis(player, player_id, bullet_manager, state),
-            WeaponType::Spur => self.tick_spur(player, player_id, bullet_manager, state),
+        }
+
+        for bullet in bullet_manager.bullets.iter().skip(bullet_count) {
+            state.script_events.push(ScriptEvent::BulletFired { player_id: player_id as u8, bullet_type: bullet.btype });
         }
     }
 }
@@ -12,6 +12,7 @@ use crate::framework::error::GameError::ResourceLoadError;
 use crate::framework::error::GameResult;
 use crate::framework::filesystem;
 use crate::graphics::font::{EMPTY_SYMBOLS, Font, Symbols, TextBuilderFlag};
+use crate::graphics::text_layout;
 use crate::graphics::texture_set::TextureSet;
 
 #[derive(Debug)]
@@ -133,6 +134,8 @@ pub struct BMFont {
     font: BMFontMetadata,
     font_scale: f32,
     pages: Vec<String>,
+    /// Fonts consulted for glyphs which this one lacks, in order.
+    fallbacks: Vec<BMFont>,
 }
 
 impl Font for BMFont {
@@ -141,27 +144,11 @@ impl Font for BMFont {
     }
 
     fn compute_width(&self, text: &mut dyn Iterator<Item = char>, symbols: Option<&Symbols>) -> f32 {
-        let mut offset_x = 0.0;
+        // shaping can merge letters or pick glyphs of other widths, so the text is measured as it's drawn
+        let mut text: Vec<char> = text.collect();
+        text_layout::layout(&mut text, &|c| self.has_glyph(c));
 
-        if let Some(syms) = symbols {
-            for chr in text {
-                let rect_map_entry = syms.symbols.iter().find(|(c, _)| *c == chr);
-
-                if let Some((_, rect)) = rect_map_entry {
-                    offset_x += rect.width() as f32;
-                } else if let Some(glyph) = self.font.chars.get(&chr) {
-                    offset_x += glyph.x_advance as f32 * self.font_scale;
-                }
-            }
-        } else {
-            for chr in text {
-                if let Some(glyph) = self.font.chars.get(&chr) {
-                    offset_x += glyph.x_advance as f32 * self.font_scale;
-                }
-            }
-        }
-
-        offset_x
+        self.text_width(&text, symbols)
     }
 
     fn draw(
@@ -187,15 +174,18 @@ impl Font for BMFont {
                 TEXT_BUF.push(c);
             }
 
-            if flags.centered() {
-                let text_width = self.compute_width(&mut TEXT_BUF.iter().copied(), symbols.as_ref());
+            // the shaped and reordered text is measured and drawn as is, for the shadow too
+            text_layout::layout(&mut TEXT_BUF, &|c| self.has_glyph(c));
 
-                x += (box_width - text_width) * 0.5;
+            if flags.centered() {
+                x += (box_width - self.text_width(&TEXT_BUF, symbols.as_ref())) * 0.5;
+            } else if flags.right_aligned() {
+                x += box_width - self.text_width(&TEXT_BUF, symbols.as_ref());
             }
 
             if flags.shadow() {
-                self.draw_text_line(
-                    &mut TEXT_BUF.iter().copied(),
+                self.draw_runs(
+                    &TEXT_BUF,
                     x + scale,
                     y + scale,
                     scale,
@@ -207,17 +197,7 @@ impl Font for BMFont {
                 )?;
             }
 
-            self.draw_text_line(
-                &mut TEXT_BUF.iter().copied(),
-                x,
-                y,
-                scale,
-                color,
-                constants,
-                texture_set,
-                symbols.as_ref(),
-                ctx,
-            )?;
+            self.draw_runs(&TEXT_BUF, x, y, scale, color, constants, texture_set, symbols.as_ref(), ctx)?;
         }
 
         Ok(())
@@ -252,7 +232,94 @@ impl BMFont {
             pages.push(page_path);
         }
 
-        Ok(Self { font, font_scale, pages })
+        Ok(Self { font, font_scale, pages, fallbacks: Vec::new() })
+    }
+
+    /// Loads fonts used for characters missing from this one, skipping the ones which fail to load.
+    pub fn load_fallbacks(&mut self, roots: &Vec<String>, desc_paths: &[String], ctx: &mut Context) {
+        for desc_path in desc_paths {
+            match BMFont::load(roots, desc_path, ctx, self.font_scale) {
+                Ok(font) => self.fallbacks.push(font),
+                Err(e) => log::warn!("Failed to load fallback font {}: {}", desc_path, e),
+            }
+        }
+    }
+
+    /// Returns true if this font or any of its fallbacks can draw the character.
+    pub fn has_glyph(&self, chr: char) -> bool {
+        self.font.chars.contains_key(&chr) || self.fallbacks.iter().any(|font| font.has_glyph(chr))
+    }
+
+    /// Returns the font which draws the character, which is this one unless only a fallback has it.
+    fn glyph_font(&self, chr: char) -> &BMFont {
+        if self.font.chars.contains_key(&chr) {
+            return self;
+        }
+
+        self.fallbacks.iter().find(|font| font.has_glyph(chr)).map(|font| font.glyph_font(chr)).unwrap_or(self)
+    }
+
+    /// Returns the advance of the character, combining marks don't move the pen.
+    fn char_width(&self, chr: char, symbols: Option<&Symbols>) -> f32 {
+        if let Some((_, rect)) = symbols.and_then(|syms| syms.symbols.iter().find(|(c, _)| *c == chr)) {
+            rect.width() as f32
+        } else if text_layout::is_combining(chr) {
+            0.0
+        } else {
+            let font = self.glyph_font(chr);
+            font.font.chars.get(&chr).map_or(0.0, |glyph| glyph.x_advance as f32 * font.font_scale)
+        }
+    }
+
+    /// Width of text which has already been laid out.
+    fn text_width(&self, text: &[char], symbols: Option<&Symbols>) -> f32 {
+        text.iter().map(|&chr| self.char_width(chr, symbols)).sum()
+    }
+
+    /// Draws a line in visual order, splitting it into runs of characters drawn by the same font.
+    fn draw_runs(
+        &self,
+        text: &[char],
+        x: f32,
+        y: f32,
+        scale: f32,
+        color: (u8, u8, u8, u8),
+        constants: &EngineConstants,
+        texture_set: &mut TextureSet,
+        symbols: Option<&Symbols>,
+        ctx: &mut Context,
+    ) -> GameResult {
+        let mut offset_x = x;
+        let mut start = 0;
+
+        while start < text.len() {
+            // the run goes on while its font has the characters, unless this font has them too and takes
+            // precedence, so the fallbacks are only searched at the start of a run
+            let font = self.glyph_font(text[start]);
+            let is_fallback = !std::ptr::eq(font, self);
+            let end = (start + 1..text.len())
+                .find(|&i| {
+                    let chr = text[i];
+                    let primary = is_fallback && self.font.chars.contains_key(&chr) && !text_layout::is_combining(chr);
+                    !font.font.chars.contains_key(&chr) || primary
+                })
+                .unwrap_or(text.len());
+
+            offset_x = font.draw_text_line(
+                &mut text[start..end].iter().copied(),
+                offset_x,
+                y,
+                scale,
+                color,
+                constants,
+                texture_set,
+                symbols,
+                ctx,
+            )?;
+            start = end;
+        }
+
+        Ok(())
     }
 
     fn draw_text_line(
@@ -266,7 +333,9 @@ impl BMFont {
         texture_set: &mut TextureSet,
         symbols: Option<&Symbols>,
         ctx: &mut Context,
-    ) -> GameResult {
+    ) -> GameResult<f32> {
+        let mut end_x = x;
+
         unsafe {
             static mut RECTS_BUF: Vec<(f32, f32, *const Rect<u16>)> = Vec::new();
 
@@ -276,6 +345,7 @@ impl BMFont {
             if self.pages.len() == 1 {
                 let batch = texture_set.get_or_load_batch(ctx, constants, self.pages.get(0).unwrap())?;
                 let mut offset_x = x;
+                let mut last_advance = 0.0;
 
                 for chr in iter {
                     if let Some(glyph) = self.font.chars.get(&chr) {
@@ -288,9 +358,13 @@ impl BMFont {
                                 rect as *const _,
                             ));
                             offset_x += rect.width() as f32;
+                            last_advance = rect.width() as f32;
                         } else {
+                            let advance = glyph.x_advance as f32 * self.font_scale * scale;
+                            let glyph_x = Self::glyph_x(chr, offset_x, advance, last_advance);
+
                             batch.add_rect_scaled_tinted(
-                                offset_x + (glyph.x_offset as f32 * self.font_scale),
+                                glyph_x + (glyph.x_offset as f32 * self.font_scale),
                                 y + (glyph.y_offset as f32 * self.font_scale),
                                 color,
                                 self.font_scale * scale,
@@ -303,11 +377,15 @@ impl BMFont {
                                 ),
                             );
 
-                            offset_x += glyph.x_advance as f32 * self.font_scale * scale;
+                            if !text_layout::is_combining(chr) {
+                                offset_x += advance;
+                                last_advance = advance;
+                            }
                         }
                     }
                 }
 
+                end_x = offset_x;
                 batch.draw(ctx)?;
             } else {
                 let mut pages = HashSet::new();
@@ -329,6 +407,7 @@ impl BMFont {
 
                     let batch = texture_set.get_or_load_batch(ctx, constants, page_tex)?;
                     let mut offset_x = x;
+                    let mut last_advance = 0.0;
 
                     for (chr, glyph) in chars.iter() {
                         let rect_map_entry = syms.symbols.iter().find(|(c, _)| *c == *chr);
@@ -336,10 +415,14 @@ impl BMFont {
                         if let Some((_, rect)) = rect_map_entry {
                             RECTS_BUF.push((offset_x, y + self.line_height() / 2.0 - rect.height() as f32 / 2.0, rect));
                             offset_x += rect.width() as f32;
+                            last_advance = rect.width() as f32;
                         } else {
+                            let advance = scale * (glyph.x_advance as f32 * self.font_scale);
+
                             if glyph.page == page {
                                 batch.add_rect_scaled_tinted(
-                                    offset_x + (glyph.x_offset as f32 * self.font_scale),
+                                    Self::glyph_x(*chr, offset_x, advance, last_advance)
+                                        + (glyph.x_offset as f32 * self.font_scale),
                                     y + (glyph.y_offset as f32 * self.font_scale),
                                     color,
                                     self.font_scale * scale,
@@ -353,10 +436,14 @@ impl BMFont {
                                 );
                             }
 
-                            offset_x += scale * (glyph.x_advance as f32 * self.font_scale);
+                            if !text_layout::is_combining(*chr) {
+                                offset_x += advance;
+                                last_advance = advance;
+                            }
                         }
                     }
 
+                    end_x = offset_x;
                     batch.draw(ctx)?;
                 }
             }
@@ -372,14 +459,74 @@ impl BMFont {
             }
         }
 
-        Ok(())
+        Ok(end_x)
+    }
+
+    /// Returns the position of a glyph, centering combining marks over the preceding glyph.
+    fn glyph_x(chr: char, offset_x: f32, advance: f32, last_advance: f32) -> f32 {
+        if text_layout::is_combining(chr) {
+            offset_x - (last_advance + advance) / 2.0
+        } else {
+            offset_x
+        }
     }
 
     pub fn scale(&mut self, scale: f32) {
         self.font_scale = scale;
+
+        for fallback in self.fallbacks.iter_mut() {
+            fallback.scale(scale);
+        }
     }
 
     pub fn get_scale(&self) -> f32 {
         self.font_scale
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font(advances: &[(char, i16)], fallbacks: Vec<BMFont>) -> BMFont {
+        let chars = advances
+            .iter()
+            .map(|&(chr, x_advance)| {
+                let glyph = BMChar {
+                    x: 0,
+                    y: 0,
+                    width: 8,
+                    height: 8,
+                    x_offset: 0,
+                    y_offset: 0,
+                    x_advance,
+                    page: 0,
+                    channel: 0,
+                };
+                (chr, glyph)
+            })
+            .collect();
+        let font = BMFontMetadata { pages: 1, font_size: 12, line_height: 16, base: 12, chars };
+
+        BMFont { font, font_scale: 1.0, pages: vec!["font_0".to_owned()], fallbacks }
+    }
+
+    #[test]
+    fn test_compute_width_measures_laid_out_text() {
+        // lam + alef are drawn as a single ligature
+        let font = font(&[('\u{0644}', 6), ('\u{0627}', 4), ('\u{FEFB}', 7), ('a', 5)], Vec::new());
+
+        assert_eq!(font.compute_width(&mut "\u{0644}\u{0627}".chars(), None), 7.0);
+        assert_eq!(font.compute_width(&mut "a\u{0644}\u{0627}a".chars(), None), 17.0);
+        assert_eq!(font.compute_width(&mut "aa".chars(), None), 10.0);
+    }
+
+    #[test]
+    fn test_compute_width_scales_fallback_glyphs() {
+        let fallback = font(&[('あ', 12)], Vec::new());
+        let mut font = font(&[('a', 5)], vec![fallback]);
+        font.scale(0.5);
+
+        assert_eq!(font.compute_width(&mut "aあ".chars(), None), 8.5);
+    }
+}
@@ -12,6 +12,7 @@ bitfield! {
 
     pub shadow, set_shadow: 0;
     pub centered, set_centered: 1;
+    pub right_aligned, set_right_aligned: 2;
 }
 
 #[derive(Copy, Clone)]
@@ -162,6 +163,16 @@ impl<'a, 'b> TextBuilder<'a, 'b> {
         self
     }
 
+    /// Aligns the text to the right edge of a box starting at the set position, used for right-to-left text.
+    #[inline]
+    pub fn right_align(mut self, box_width: f32) -> Self {
+        self.box_width = box_width;
+        self.flags.set_right_aligned(true);
+
+        self
+    }
+
+    /// Width of the text as drawn, after it's been shaped and reordered for display.
     #[inline]
     pub fn compute_width(&self, text: &str) -> f32 {
         self.compute_width_iter(text.chars())
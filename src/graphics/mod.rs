@@ -1,3 +1,4 @@
 pub mod bmfont;
 pub mod font;
+pub mod text_layout;
 pub mod texture_set;
@@ -0,0 +1,399 @@
+//! Bidirectional layout and contextual shaping of text drawn with bitmap fonts.
+//!
+//! Bitmap fonts only know how to place glyphs left to right, so right-to-left runs are reordered
+//! into visual order and Arabic letters are replaced with their presentation forms beforehand.
+
+const LAM: char = '\u{0644}';
+
+/// Isolated, final, initial and medial presentation forms of Arabic letters.
+/// Letters which only join to the preceding one have no initial and medial forms.
+const ARABIC_FORMS: [(char, [char; 4]); 42] = [
+    ('\u{0621}', ['\u{FE80}', '\0', '\0', '\0']),
+    ('\u{0622}', ['\u{FE81}', '\u{FE82}', '\0', '\0']),
+    ('\u{0623}', ['\u{FE83}', '\u{FE84}', '\0', '\0']),
+    ('\u{0624}', ['\u{FE85}', '\u{FE86}', '\0', '\0']),
+    ('\u{0625}', ['\u{FE87}', '\u{FE88}', '\0', '\0']),
+    ('\u{0626}', ['\u{FE89}', '\u{FE8A}', '\u{FE8B}', '\u{FE8C}']),
+    ('\u{0627}', ['\u{FE8D}', '\u{FE8E}', '\0', '\0']),
+    ('\u{0628}', ['\u{FE8F}', '\u{FE90}', '\u{FE91}', '\u{FE92}']),
+    ('\u{0629}', ['\u{FE93}', '\u{FE94}', '\0', '\0']),
+    ('\u{062A}', ['\u{FE95}', '\u{FE96}', '\u{FE97}', '\u{FE98}']),
+    ('\u{062B}', ['\u{FE99}', '\u{FE9A}', '\u{FE9B}', '\u{FE9C}']),
+    ('\u{062C}', ['\u{FE9D}', '\u{FE9E}', '\u{FE9F}', '\u{FEA0}']),
+    ('\u{062D}', ['\u{FEA1}', '\u{FEA2}', '\u{FEA3}', '\u{FEA4}']),
+    ('\u{062E}', ['\u{FEA5}', '\u{FEA6}', '\u{FEA7}', '\u{FEA8}']),
+    ('\u{062F}', ['\u{FEA9}', '\u{FEAA}', '\0', '\0']),
+    ('\u{0630}', ['\u{FEAB}', '\u{FEAC}', '\0', '\0']),
+    ('\u{0631}', ['\u{FEAD}', '\u{FEAE}', '\0', '\0']),
+    ('\u{0632}', ['\u{FEAF}', '\u{FEB0}', '\0', '\0']),
+    ('\u{0633}', ['\u{FEB1}', '\u{FEB2}', '\u{FEB3}', '\u{FEB4}']),
+    ('\u{0634}', ['\u{FEB5}', '\u{FEB6}', '\u{FEB7}', '\u{FEB8}']),
+    ('\u{0635}', ['\u{FEB9}', '\u{FEBA}', '\u{FEBB}', '\u{FEBC}']),
+    ('\u{0636}', ['\u{FEBD}', '\u{FEBE}', '\u{FEBF}', '\u{FEC0}']),
+    ('\u{0637}', ['\u{FEC1}', '\u{FEC2}', '\u{FEC3}', '\u{FEC4}']),
+    ('\u{0638}', ['\u{FEC5}', '\u{FEC6}', '\u{FEC7}', '\u{FEC8}']),
+    ('\u{0639}', ['\u{FEC9}', '\u{FECA}', '\u{FECB}', '\u{FECC}']),
+    ('\u{063A}', ['\u{FECD}', '\u{FECE}', '\u{FECF}', '\u{FED0}']),
+    ('\u{0641}', ['\u{FED1}', '\u{FED2}', '\u{FED3}', '\u{FED4}']),
+    ('\u{0642}', ['\u{FED5}', '\u{FED6}', '\u{FED7}', '\u{FED8}']),
+    ('\u{0643}', ['\u{FED9}', '\u{FEDA}', '\u{FEDB}', '\u{FEDC}']),
+    ('\u{0644}', ['\u{FEDD}', '\u{FEDE}', '\u{FEDF}', '\u{FEE0}']),
+    ('\u{0645}', ['\u{FEE1}', '\u{FEE2}', '\u{FEE3}', '\u{FEE4}']),
+    ('\u{0646}', ['\u{FEE5}', '\u{FEE6}', '\u{FEE7}', '\u{FEE8}']),
+    ('\u{0647}', ['\u{FEE9}', '\u{FEEA}', '\u{FEEB}', '\u{FEEC}']),
+    ('\u{0648}', ['\u{FEED}', '\u{FEEE}', '\0', '\0']),
+    ('\u{0649}', ['\u{FEEF}', '\u{FEF0}', '\0', '\0']),
+    ('\u{064A}', ['\u{FEF1}', '\u{FEF2}', '\u{FEF3}', '\u{FEF4}']),
+    ('\u{067E}', ['\u{FB56}', '\u{FB57}', '\u{FB58}', '\u{FB59}']),
+    ('\u{0686}', ['\u{FB7A}', '\u{FB7B}', '\u{FB7C}', '\u{FB7D}']),
+    ('\u{0698}', ['\u{FB8A}', '\u{FB8B}', '\0', '\0']),
+    ('\u{06A9}', ['\u{FB8E}', '\u{FB8F}', '\u{FB90}', '\u{FB91}']),
+    ('\u{06AF}', ['\u{FB92}', '\u{FB93}', '\u{FB94}', '\u{FB95}']),
+    ('\u{06CC}', ['\u{FBFC}', '\u{FBFD}', '\u{FBFE}', '\u{FBFF}']),
+];
+
+/// Isolated and final forms of the mandatory lam-alef ligatures, keyed by the alef variant.
+const LAM_ALEF_FORMS: [(char, [char; 2]); 4] = [
+    ('\u{0622}', ['\u{FEF5}', '\u{FEF6}']),
+    ('\u{0623}', ['\u{FEF7}', '\u{FEF8}']),
+    ('\u{0625}', ['\u{FEF9}', '\u{FEFA}']),
+    ('\u{0627}', ['\u{FEFB}', '\u{FEFC}']),
+];
+
+const MIRRORED_PAIRS: [(char, char); 6] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>'), ('«', '»'), ('‹', '›')];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Joining {
+    None,
+    Transparent,
+    Right,
+    Dual,
+    Causing,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BidiClass {
+    Left,
+    Right,
+    Number,
+    Mark,
+    Neutral,
+}
+
+/// Returns true for letters of scripts written right to left.
+pub fn is_rtl(c: char) -> bool {
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+        && !is_combining(c)
+        && !is_arabic_digit(c)
+}
+
+/// Returns true for combining marks, which are drawn over the preceding character instead of after it.
+pub fn is_combining(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{0591}'..='\u{05BD}'
+            | '\u{05BF}'
+            | '\u{05C1}'..='\u{05C2}'
+            | '\u{05C4}'..='\u{05C5}'
+            | '\u{05C7}'
+            | '\u{0610}'..='\u{061A}'
+            | '\u{064B}'..='\u{065F}'
+            | '\u{0670}'
+            | '\u{06D6}'..='\u{06DC}'
+            | '\u{06DF}'..='\u{06E4}'
+            | '\u{06E7}'..='\u{06E8}'
+            | '\u{06EA}'..='\u{06ED}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Returns true if the first strongly directional character of the text is written right to left.
+pub fn is_rtl_text(text: impl IntoIterator<Item = char>) -> bool {
+    for c in text {
+        match bidi_class(c) {
+            BidiClass::Left => return false,
+            BidiClass::Right => return true,
+            _ => (),
+        }
+    }
+
+    false
+}
+
+/// Shapes the text and reorders it from logical to visual order in place.
+///
+/// `has_glyph` tells which presentation forms the font can draw, letters are left as they are otherwise.
+pub fn layout(text: &mut Vec<char>, has_glyph: &dyn Fn(char) -> bool) {
+    if !text.iter().any(|&c| is_rtl(c)) {
+        return;
+    }
+
+    shape(text, has_glyph);
+    reorder(text);
+}
+
+/// Replaces Arabic letters with the presentation forms matching how they join with their neighbours.
+pub fn shape(text: &mut Vec<char>, has_glyph: &dyn Fn(char) -> bool) {
+    if !text.iter().any(|&c| joining(c) == Joining::Dual || joining(c) == Joining::Right) {
+        return;
+    }
+
+    let mut shaped = Vec::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < text.len() {
+        let c = text[i];
+        let forms = match ARABIC_FORMS.iter().find(|(letter, _)| *letter == c) {
+            Some((_, forms)) => forms,
+            None => {
+                shaped.push(c);
+                i += 1;
+                continue;
+            }
+        };
+
+        let prev = text[..i].iter().rev().copied().find(|&c| joining(c) != Joining::Transparent);
+        let next = text[i + 1..].iter().position(|&c| joining(c) != Joining::Transparent).map(|pos| i + 1 + pos);
+
+        let joins_prev =
+            joining(c) != Joining::None && matches!(prev.map(joining), Some(Joining::Dual | Joining::Causing));
+
+        if c == LAM {
+            let ligature = next
+                .filter(|&pos| pos == i + 1)
+                .and_then(|pos| LAM_ALEF_FORMS.iter().find(|(alef, _)| *alef == text[pos]))
+                .map(|(_, ligature)| ligature[joins_prev as usize])
+                .filter(|&ligature| has_glyph(ligature));
+
+            if let Some(ligature) = ligature {
+                shaped.push(ligature);
+                i += 2;
+                continue;
+            }
+        }
+
+        let joins_next = joining(c) == Joining::Dual
+            && matches!(next.map(|pos| joining(text[pos])), Some(Joining::Dual | Joining::Right | Joining::Causing));
+
+        let form = match (joins_prev, joins_next) {
+            (false, false) => forms[0],
+            (true, false) => forms[1],
+            (false, true) => forms[2],
+            (true, true) => forms[3],
+        };
+
+        shaped.push(if has_glyph(form) { form } else { c });
+        i += 1;
+    }
+
+    *text = shaped;
+}
+
+/// Reorders a single line of text from logical to visual order, following a simplified Unicode bidi algorithm.
+pub fn reorder(text: &mut Vec<char>) {
+    let base_level: u8 = if is_rtl_text(text.iter().copied()) { 1 } else { 0 };
+    let mut classes: Vec<BidiClass> = text.iter().map(|&c| bidi_class(c)).collect();
+
+    // numbers directly joined by a separator, eg. "1,000" are kept together
+    for i in 1..classes.len().saturating_sub(1) {
+        if classes[i] == BidiClass::Neutral
+            && matches!(text[i], ',' | '.' | ':' | '/')
+            && classes[i - 1] == BidiClass::Number
+            && classes[i + 1] == BidiClass::Number
+        {
+            classes[i] = BidiClass::Number;
+        }
+    }
+
+    // numbers follow the direction of the preceding text when resolving neutrals
+    let mut directions = Vec::with_capacity(classes.len());
+    let mut last_strong = if base_level == 1 { BidiClass::Right } else { BidiClass::Left };
+    for &class in classes.iter() {
+        directions.push(match class {
+            BidiClass::Left | BidiClass::Right => {
+                last_strong = class;
+                class
+            }
+            BidiClass::Number if last_strong == BidiClass::Left => BidiClass::Left,
+            BidiClass::Number => BidiClass::Right,
+            _ => class,
+        });
+    }
+
+    let base_direction = if base_level == 1 { BidiClass::Right } else { BidiClass::Left };
+    let mut levels = vec![base_level; text.len()];
+    let mut i = 0;
+
+    while i < text.len() {
+        match classes[i] {
+            BidiClass::Left => levels[i] = if base_level == 1 { 2 } else { 0 },
+            BidiClass::Right => levels[i] = 1,
+            BidiClass::Number => levels[i] = if base_level == 0 && directions[i] == BidiClass::Left { 0 } else { 2 },
+            BidiClass::Mark => levels[i] = if i > 0 { levels[i - 1] } else { base_level },
+            BidiClass::Neutral => {
+                let end = (i..text.len()).find(|&j| classes[j] != BidiClass::Neutral).unwrap_or(text.len());
+                let is_strong = |d: &BidiClass| *d == BidiClass::Left || *d == BidiClass::Right;
+                let before = directions[..i].iter().rev().copied().find(is_strong);
+                let after = directions[end..].iter().copied().find(is_strong);
+
+                // trailing whitespace always takes the direction of the line
+                let direction = match (before.unwrap_or(base_direction), after) {
+                    (_, None) if text[i..end].iter().all(|c| c.is_whitespace()) => base_direction,
+                    (before, after) if before == after.unwrap_or(base_direction) => before,
+                    _ => base_direction,
+                };
+
+                let level = match direction {
+                    BidiClass::Right => 1,
+                    _ if base_level == 1 => 2,
+                    _ => 0,
+                };
+                levels[i..end].iter_mut().for_each(|l| *l = level);
+
+                i = end;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    // combining marks are kept after the character they belong to
+    let mut clusters: Vec<(u8, Vec<char>)> = Vec::new();
+    for (&c, &level) in text.iter().zip(levels.iter()) {
+        match clusters.last_mut() {
+            Some((_, cluster)) if is_combining(c) => cluster.push(c),
+            _ => clusters.push((level, vec![c])),
+        }
+    }
+
+    let max_level = clusters.iter().map(|(level, _)| *level).max().unwrap_or(0);
+    for level in (1..=max_level).rev() {
+        let mut start = 0;
+
+        while start < clusters.len() {
+            if clusters[start].0 < level {
+                start += 1;
+                continue;
+            }
+
+            let end = (start..clusters.len()).find(|&j| clusters[j].0 < level).unwrap_or(clusters.len());
+            clusters[start..end].reverse();
+            start = end;
+        }
+    }
+
+    text.clear();
+    for (level, cluster) in clusters {
+        for c in cluster {
+            text.push(if level % 2 == 1 { mirror(c) } else { c });
+        }
+    }
+}
+
+fn bidi_class(c: char) -> BidiClass {
+    if is_combining(c) {
+        BidiClass::Mark
+    } else if c.is_ascii_digit() || is_arabic_digit(c) {
+        BidiClass::Number
+    } else if is_rtl(c) {
+        BidiClass::Right
+    } else if c.is_alphabetic() {
+        BidiClass::Left
+    } else {
+        BidiClass::Neutral
+    }
+}
+
+fn joining(c: char) -> Joining {
+    if is_combining(c) {
+        return Joining::Transparent;
+    }
+
+    match c {
+        '\u{0640}' | '\u{200D}' => Joining::Causing,
+        _ => match ARABIC_FORMS.iter().find(|(letter, _)| *letter == c) {
+            Some((_, forms)) if forms[2] != '\0' => Joining::Dual,
+            Some((_, forms)) if forms[1] != '\0' => Joining::Right,
+            _ => Joining::None,
+        },
+    }
+}
+
+fn is_arabic_digit(c: char) -> bool {
+    matches!(c, '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}')
+}
+
+fn mirror(c: char) -> char {
+    for &(open, close) in MIRRORED_PAIRS.iter() {
+        if c == open {
+            return close;
+        } else if c == close {
+            return open;
+        }
+    }
+
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn laid_out(text: &str) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+        layout(&mut chars, &|_| true);
+        chars.into_iter().collect()
+    }
+
+    #[test]
+    fn test_ltr_text_is_untouched() {
+        assert_eq!(laid_out("Hello, world (1,000)!"), "Hello, world (1,000)!");
+    }
+
+    #[test]
+    fn test_rtl_text_is_reversed() {
+        assert_eq!(laid_out("שלום"), "םולש");
+        assert_eq!(laid_out("שלום עולם"), "םלוע םולש");
+    }
+
+    #[test]
+    fn test_embedded_runs_keep_their_direction() {
+        assert_eq!(laid_out("abc שלום def"), "abc םולש def");
+        assert_eq!(laid_out("שלום abc 123"), "abc 123 םולש");
+        assert_eq!(laid_out("שלום 1,000"), "1,000 םולש");
+    }
+
+    #[test]
+    fn test_brackets_are_mirrored() {
+        assert_eq!(laid_out("(שלום)"), "(םולש)");
+    }
+
+    #[test]
+    fn test_combining_marks_stay_after_their_base() {
+        assert_eq!(laid_out("שָׁלוֹם"), "םוֹלשָׁ");
+    }
+
+    #[test]
+    fn test_arabic_shaping() {
+        // beh + alef: initial beh joined to final alef
+        let mut text: Vec<char> = "\u{0628}\u{0627}".chars().collect();
+        shape(&mut text, &|_| true);
+        assert_eq!(text, vec!['\u{FE91}', '\u{FE8E}']);
+
+        // lam + alef ligature
+        let mut text: Vec<char> = "\u{0644}\u{0627}".chars().collect();
+        shape(&mut text, &|_| true);
+        assert_eq!(text, vec!['\u{FEFB}']);
+
+        // forms the font lacks are left as they are
+        let mut text: Vec<char> = "\u{0628}\u{0628}".chars().collect();
+        shape(&mut text, &|c| c != '\u{FE91}');
+        assert_eq!(text, vec!['\u{0628}', '\u{FE90}']);
+    }
+}
@@ -74,6 +74,19 @@ pub trait SpriteBatch {
         rect: &common::Rect<u16>,
     );
 
+    #[allow(clippy::too_many_arguments)]
+    fn add_rect_flip_scaled_tinted(
+        &mut self,
+        x: f32,
+        y: f32,
+        flip_x: bool,
+        flip_y: bool,
+        color: (u8, u8, u8, u8),
+        scale_x: f32,
+        scale_y: f32,
+        rect: &common::Rect<u16>,
+    );
+
     fn draw(&mut self, ctx: &mut Context) -> GameResult;
 
     fn draw_filtered(&mut self, _filter: FilterMode, _ctx: &mut Context) -> GameResult;
@@ -88,6 +101,42 @@ impl SpriteBatch for DummyBatch {
         1
     }
 
+    fn add_rect_flip_scaled_tinted(
+        &mut self,
+        x: f32,
+        y: f32,
+        flip_x: bool,
+        flip_y: bool,
+        color: (u8, u8, u8, u8),
+        scale_x: f32,
+        scale_y: f32,
+        rect: &common::Rect<u16>,
+    ) {
+        if (rect.right.saturating_sub(rect.left)) == 0 || (rect.bottom.saturating_sub(rect.top)) == 0 {
+            return;
+        }
+
+        let mag = unsafe { I_MAG };
+
+        self.batch.add(SpriteBatchCommand::DrawRectFlipTinted(
+            Rect {
+                left: rect.left as f32 / self.scale_x,
+                top: rect.top as f32 / self.scale_y,
+                right: rect.right as f32 / self.scale_x,
+                bottom: rect.bottom as f32 / self.scale_y,
+            },
+            Rect {
+                left: x * mag,
+                top: y * mag,
+                right: (x + rect.width() as f32 * scale_x) * mag,
+                bottom: (y + rect.height() as f32 * scale_y) * mag,
+            },
+            flip_x,
+            flip_y,
+            color.into(),
+        ));
+    }
+
     fn height(&self) -> usize {
         1
     }
@@ -148,6 +197,18 @@ impl SpriteBatch for DummyBatch {
         _rect: &Rect<u16>,
     ) {}
 
+    fn add_rect_flip_scaled_tinted(
+        &mut self,
+        _x: f32,
+        _y: f32,
+        _flip_x: bool,
+        _flip_y: bool,
+        _color: (u8, u8, u8, u8),
+        _scale_x: f32,
+        _scale_y: f32,
+        _rect: &Rect<u16>,
+    ) {}
+
     fn draw(&mut self, _ctx: &mut Context) -> GameResult {
         Ok(())
     }
@@ -462,6 +523,20 @@ impl SpriteBatch for CombinedBatch {
         self.main_batch.add_rect_scaled_tinted(x, y, color, scale_x, scale_y, rect)
     }
 
+    fn add_rect_flip_scaled_tinted(
+        &mut self,
+        x: f32,
+        y: f32,
+        flip_x: bool,
+        flip_y: bool,
+        color: (u8, u8, u8, u8),
+        scale_x: f32,
+        scale_y: f32,
+        rect: &Rect<u16>,
+    ) {
+        self.main_batch.add_rect_flip_scaled_tinted(x, y, flip_x, flip_y, color, scale_x, scale_y, rect)
+    }
+
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         self.main_batch.draw(ctx)
     }
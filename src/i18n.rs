@@ -1,15 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
 
 use crate::framework::context::Context;
+use crate::framework::error::GameResult;
 use crate::framework::filesystem;
+use crate::game::scripting::tsc::text_script::TextScriptEncoding;
 use crate::game::shared_game_state::FontData;
 
+/// Code of the locale which every fallback chain ends with and which other locales are checked against.
+pub const REFERENCE_LOCALE: &str = "en";
+
+/// Keys describing the locale itself rather than translated strings.
+const METADATA_KEYS: [&str; 6] = ["name", "font", "font_scale", "fallback_fonts", "fallback", "encoding"];
+
 #[derive(Debug, Clone)]
 pub struct Locale {
     pub code: String,
     pub name: String,
     pub font: FontData,
     strings: HashMap<String, String>,
+    /// Locale consulted when a string is missing, eg. `pt` for `pt-BR`.
+    fallback: Option<Box<Locale>>,
 }
 
 impl Default for Locale {
@@ -20,9 +31,11 @@ impl Default for Locale {
             font: FontData {
                 path: String::new(),
                 scale: 1.0,
-                space_offset: 0.0
+                space_offset: 0.0,
+                fallbacks: Vec::new(),
             },
             strings: HashMap::new(),
+            fallback: None,
         }
     }
 }
@@ -34,13 +47,76 @@ impl Locale {
 
         let strings = Locale::flatten(&json);
 
-        let name = strings["name"].clone();
+        let name = strings.get("name").cloned().unwrap_or_else(|| code.to_owned());
+
+        // partial locales may leave the font to their fallback
+        let font_name = strings.get("font").cloned().unwrap_or_default();
+        let font_scale = strings.get("font_scale").and_then(|s| s.parse::<f32>().ok()).unwrap_or(1.0);
+        let mut font = FontData::new(font_name, font_scale, 0.0);
+        if let Some(fallbacks) = strings.get("fallback_fonts") {
+            font.fallbacks =
+                fallbacks.split(',').map(str::trim).filter(|path| !path.is_empty()).map(String::from).collect();
+        }
+
+        Locale { code: code.to_string(), name, font, strings, fallback: None }
+    }
+
+    /// Resolves the locale with the given code from the loaded ones, chaining all its fallbacks.
+    pub fn resolve(locales: &[Locale], code: &str) -> Option<Locale> {
+        let mut chain: Vec<&Locale> = Locale::fallback_chain(locales, code)
+            .iter()
+            .filter_map(|code| locales.iter().find(|locale| &locale.code == code))
+            .collect();
+
+        let mut locale = chain.pop()?.clone();
+        while let Some(next) = chain.pop() {
+            locale = next.clone().with_fallback(locale);
+        }
+
+        Some(locale)
+    }
+
+    /// Returns the codes of locales consulted for the given one in order, eg. `pt-BR` → `pt` → `en`.
+    ///
+    /// A locale may name its fallback explicitly with the `fallback` key, otherwise the region suffix is stripped.
+    pub fn fallback_chain(locales: &[Locale], code: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let mut next = Some(code.to_owned());
+
+        while let Some(code) = next {
+            if chain.contains(&code) {
+                break;
+            }
+
+            next = locales
+                .iter()
+                .find(|locale| locale.code == code)
+                .and_then(|locale| locale.strings.get("fallback").cloned())
+                .or_else(|| code.rfind(|c| c == '-' || c == '_').map(|idx| code[..idx].to_owned()));
+            chain.push(code);
+        }
+
+        if !chain.iter().any(|code| code == REFERENCE_LOCALE) {
+            chain.push(REFERENCE_LOCALE.to_owned());
+        }
 
-        let font_name = strings["font"].clone();
-        let font_scale = strings["font_scale"].parse::<f32>().unwrap_or(1.0);
-        let font = FontData::new(font_name, font_scale, 0.0);
+        chain
+    }
 
-        Locale { code: code.to_string(), name, font, strings }
+    fn with_fallback(mut self, fallback: Locale) -> Locale {
+        if !self.strings.contains_key("font") {
+            self.font = fallback.font.clone();
+        } else {
+            // strings left to the fallback locale still need its glyphs
+            for path in std::iter::once(&fallback.font.path).chain(fallback.font.fallbacks.iter()) {
+                if !path.is_empty() && *path != self.font.path && !self.font.fallbacks.contains(path) {
+                    self.font.fallbacks.push(path.clone());
+                }
+            }
+        }
+
+        self.fallback = Some(Box::new(fallback));
+        self
     }
 
     fn flatten(json: &serde_json::Value) -> HashMap<String, String> {
@@ -65,8 +141,15 @@ impl Locale {
         strings
     }
 
+    fn get(&self, key: &str) -> Option<&str> {
+        match self.strings.get(key) {
+            Some(string) => Some(string),
+            None => self.fallback.as_ref().and_then(|fallback| fallback.get(key)),
+        }
+    }
+
     pub fn t<'a: 'b, 'b>(&'a self, key: &'b str) -> &'b str {
-        if let Some(str) = self.strings.get(key) {
+        if let Some(str) = self.get(key) {
             str
         } else {
             key
@@ -83,7 +166,343 @@ impl Locale {
         string
     }
 
+    /// Returns the encoding of text scripts translated to this locale, which can be set with the `encoding` key.
+    pub fn encoding(&self, is_base: bool) -> TextScriptEncoding {
+        match self.get("encoding") {
+            Some(encoding) => TextScriptEncoding::from(encoding),
+            None if is_base && (self.code == "jp" || self.code == "en") => TextScriptEncoding::ShiftJIS,
+            None => TextScriptEncoding::UTF8,
+        }
+    }
+
+    /// Translates a pluralised string, picking `key.one`, `key.few` etc. for `count`
+    /// and falling back to `key.other`. `{count}` is replaced with the formatted number.
+    pub fn tp(&self, key: &str, count: i64, args: &[(&str, &str)]) -> String {
+        let mut string = match self.get_plural(key, count) {
+            Some(string) => string.to_owned(),
+            None => key.to_owned(),
+        };
+
+        string = string.replace("{count}", &self.format_number(count));
+        for (key, value) in args.iter() {
+            string = string.replace(&format!("{{{}}}", key), &value);
+        }
+
+        string
+    }
+
+    /// Looks up the plural forms of `key` in this locale and then in its fallbacks, the category is picked
+    /// by the rules of the locale providing the forms, as other languages may not have the same categories.
+    fn get_plural(&self, key: &str, count: i64) -> Option<&str> {
+        let plural_key = format!("{}.{}", key, self.plural_category(count));
+        let other_key = format!("{}.other", key);
+
+        match self.strings.get(&plural_key).or_else(|| self.strings.get(&other_key)) {
+            Some(string) => Some(string),
+            None => self.fallback.as_ref().and_then(|fallback| fallback.get_plural(key, count)),
+        }
+    }
+
+    /// Returns the CLDR plural category of `count` in the language of this locale.
+    pub fn plural_category(&self, count: i64) -> &'static str {
+        let n = count.unsigned_abs();
+        let language = self.code.split(|c| c == '-' || c == '_').next().unwrap_or_default();
+
+        match language {
+            "jp" | "ja" | "zh" | "ko" | "vi" | "th" | "id" => "other",
+            "fr" | "pt" => {
+                if n <= 1 {
+                    "one"
+                } else {
+                    "other"
+                }
+            }
+            "ru" | "uk" | "be" | "pl" => {
+                let (n10, n100) = (n % 10, n % 100);
+                if n == 1 || (language != "pl" && n10 == 1 && n100 != 11) {
+                    "one"
+                } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+                    "few"
+                } else {
+                    "many"
+                }
+            }
+            "cs" | "sk" => match n {
+                1 => "one",
+                2..=4 => "few",
+                _ => "other",
+            },
+            _ => {
+                if n == 1 {
+                    "one"
+                } else {
+                    "other"
+                }
+            }
+        }
+    }
+
+    /// Formats an integer with the digit group separator of this locale.
+    pub fn format_number(&self, value: i64) -> String {
+        let separator = self.get("number.group_separator").unwrap_or(",");
+        let digits = value.unsigned_abs().to_string();
+
+        let mut formatted = String::new();
+        if value < 0 {
+            formatted.push('-');
+        }
+
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                formatted.push_str(separator);
+            }
+            formatted.push(digit);
+        }
+
+        formatted
+    }
+
+    /// Returns keys of the reference locale which neither this locale nor its fallbacks translate.
+    pub fn missing_keys(&self, reference: &Locale) -> Vec<String> {
+        let mut keys: Vec<String> = reference
+            .strings
+            .keys()
+            .filter(|key| !METADATA_KEYS.contains(&key.as_str()) && !self.translates(key, &reference.code))
+            .cloned()
+            .collect();
+
+        keys.sort();
+        keys
+    }
+
+    /// Returns keys of this locale and its fallbacks which are absent from the reference locale.
+    pub fn unused_keys(&self, reference: &Locale) -> Vec<String> {
+        let mut keys = BTreeSet::new();
+        let mut locale = Some(self);
+
+        while let Some(current) = locale {
+            if current.code != reference.code {
+                keys.extend(
+                    current
+                        .strings
+                        .keys()
+                        .filter(|key| !METADATA_KEYS.contains(&key.as_str()) && !reference.strings.contains_key(*key))
+                        .cloned(),
+                );
+            }
+
+            locale = current.fallback.as_deref();
+        }
+
+        keys.into_iter().collect()
+    }
+
+    /// Returns codes of this locale and its fallbacks in lookup order.
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain = vec![self.code.clone()];
+        let mut locale = self.fallback.as_deref();
+
+        while let Some(current) = locale {
+            chain.push(current.code.clone());
+            locale = current.fallback.as_deref();
+        }
+
+        chain
+    }
+
+    fn translates(&self, key: &str, reference_code: &str) -> bool {
+        if self.code != reference_code && self.strings.contains_key(key) {
+            return true;
+        }
+
+        self.fallback.as_ref().map_or(false, |fallback| fallback.translates(key, reference_code))
+    }
+
     pub fn set_font(&mut self, font: FontData) {
         self.font = font;
     }
 }
+
+/// Collects translation keys passed as string literals to `t`, `tt` and `tp` in the Rust sources under `src_dir`.
+///
+/// Keys built at runtime (eg. with `format!`) can't be found this way.
+pub fn extract_keys(src_dir: &Path) -> GameResult<BTreeSet<String>> {
+    let mut keys = BTreeSet::new();
+
+    for entry in std::fs::read_dir(src_dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            keys.extend(extract_keys(&path)?);
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            let source = std::fs::read_to_string(&path)?;
+
+            for call in [".t(\"", ".tt(\"", ".tp(\""] {
+                for (idx, _) in source.match_indices(call) {
+                    let literal = &source[idx + call.len()..];
+                    let key = match literal.find('"') {
+                        Some(end) => &literal[..end],
+                        None => continue,
+                    };
+
+                    let valid = key.contains('.')
+                        && key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.');
+                    if valid {
+                        keys.insert(key.to_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Builds a locale template containing every key used in the sources under `src_dir`,
+/// prefilled with the strings of the reference locale where they exist.
+pub fn extract_template(src_dir: &Path) -> GameResult<String> {
+    let reference_path = src_dir.join("data/builtin/builtin_data/locale").join(format!("{}.json", REFERENCE_LOCALE));
+    let reference: serde_json::Value = serde_json::from_reader(std::fs::File::open(reference_path)?)?;
+
+    let mut template = serde_json::Map::new();
+    for key in extract_keys(src_dir)? {
+        let value = reference
+            .pointer(&format!("/{}", key.replace('.', "/")))
+            .cloned()
+            .unwrap_or_else(|| serde_json::Value::String(String::new()));
+
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let last = parts.pop().unwrap_or_default();
+
+        let mut object = &mut template;
+        for part in parts {
+            let entry = object.entry(part.to_owned()).or_insert_with(|| serde_json::Value::Object(Default::default()));
+            if !entry.is_object() {
+                *entry = serde_json::Value::Object(Default::default());
+            }
+
+            object = entry.as_object_mut().unwrap();
+        }
+
+        object.insert(last.to_owned(), value);
+    }
+
+    Ok(serde_json::to_string_pretty(&serde_json::Value::Object(template))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn locale(code: &str, strings: &[(&str, &str)]) -> Locale {
+        let strings = strings.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        Locale { code: code.to_owned(), strings, ..Locale::default() }
+    }
+
+    #[test]
+    fn test_plural_category() {
+        let en = locale("en", &[]);
+        assert_eq!(en.plural_category(0), "other");
+        assert_eq!(en.plural_category(1), "one");
+        assert_eq!(en.plural_category(-1), "one");
+        assert_eq!(en.plural_category(2), "other");
+
+        let fr = locale("fr", &[]);
+        assert_eq!(fr.plural_category(0), "one");
+        assert_eq!(fr.plural_category(1), "one");
+        assert_eq!(fr.plural_category(2), "other");
+
+        let ru = locale("ru", &[]);
+        for (count, category) in [(1, "one"), (21, "one"), (11, "many"), (3, "few"), (24, "few"), (13, "many")] {
+            assert_eq!(ru.plural_category(count), category, "{}", count);
+        }
+
+        let pl = locale("pl", &[]);
+        assert_eq!(pl.plural_category(1), "one");
+        assert_eq!(pl.plural_category(21), "many");
+        assert_eq!(pl.plural_category(22), "few");
+
+        assert_eq!(locale("cs", &[]).plural_category(4), "few");
+        assert_eq!(locale("pt-BR", &[]).plural_category(0), "one");
+        assert_eq!(locale("jp", &[]).plural_category(1), "other");
+    }
+
+    #[test]
+    fn test_tp_uses_rules_of_providing_locale() {
+        let en = locale("en", &[("items.one", "{count} item"), ("items.other", "{count} items")]);
+        let ru = locale("ru", &[("lives.one", "{count} жизнь"), ("lives.few", "{count} жизни")]).with_fallback(en);
+
+        // 21 is "one" in Russian, but the English fallback has to use its own rules
+        assert_eq!(ru.tp("items", 21, &[]), "21 items");
+        assert_eq!(ru.tp("items", 1, &[]), "1 item");
+        assert_eq!(ru.tp("lives", 21, &[]), "21 жизнь");
+        assert_eq!(ru.tp("lives", 1002, &[]), "1,002 жизни");
+        assert_eq!(ru.tp("missing", 3, &[]), "missing");
+    }
+
+    #[test]
+    fn test_format_number() {
+        let en = locale("en", &[]);
+        assert_eq!(en.format_number(0), "0");
+        assert_eq!(en.format_number(999), "999");
+        assert_eq!(en.format_number(1000), "1,000");
+        assert_eq!(en.format_number(-1234567), "-1,234,567");
+
+        let de = locale("de", &[("number.group_separator", ".")]);
+        assert_eq!(de.format_number(123456), "123.456");
+        assert_eq!(locale("de-AT", &[]).with_fallback(de).format_number(1000), "1.000");
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        let locales = vec![
+            locale("en", &[]),
+            locale("pt", &[]),
+            locale("pt-BR", &[]),
+            locale("gl", &[("fallback", "es")]),
+            locale("es", &[("fallback", "gl")]),
+        ];
+
+        assert_eq!(Locale::fallback_chain(&locales, "en"), vec!["en"]);
+        assert_eq!(Locale::fallback_chain(&locales, "pt-BR"), vec!["pt-BR", "pt", "en"]);
+        assert_eq!(Locale::fallback_chain(&locales, "zh_TW"), vec!["zh_TW", "zh", "en"]);
+
+        // explicit fallbacks are followed, cycles are broken
+        assert_eq!(Locale::fallback_chain(&locales, "gl"), vec!["gl", "es", "en"]);
+
+        let resolved = Locale::resolve(&locales, "pt-BR").unwrap();
+        assert_eq!(resolved.chain(), vec!["pt-BR", "pt", "en"]);
+        assert_eq!(Locale::resolve(&locales, "xx").map(|locale| locale.code), Some("en".to_owned()));
+    }
+
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_extract_keys() {
+        let dir = TempDir(std::env::temp_dir().join(format!("doukutsu-rs-i18n-test-{}", std::process::id())));
+        std::fs::create_dir_all(dir.0.join("menu")).unwrap();
+
+        std::fs::write(
+            dir.0.join("main.rs"),
+            r#"state.loc.t("menus.main_menu.start"); loc.tt("game.cutscene_skip", &[]); loc.t(key);"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.0.join("menu/mod.rs"),
+            r#"loc.tp("menus.mod_browser.conflicts", 2, &[]); loc.t("Not A Key"); loc.t("nodots");"#,
+        )
+        .unwrap();
+        std::fs::write(dir.0.join("menu/notes.txt"), r#"loc.t("ignored.key")"#).unwrap();
+
+        let keys: Vec<String> = extract_keys(&dir.0).unwrap().into_iter().collect();
+        assert_eq!(keys, vec!["game.cutscene_skip", "menus.main_menu.start", "menus.mod_browser.conflicts"]);
+    }
+}
@@ -0,0 +1,358 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::framework::context::Context;
+use crate::framework::error::GameResult;
+use crate::game::frame::Frame;
+use crate::game::npc::list::NPCList;
+use crate::game::npc::NPC;
+use crate::game::player::Player;
+use crate::game::shared_game_state::SharedGameState;
+use crate::game::stage::Stage;
+use crate::input::player_controller::PlayerController;
+use crate::input::replay_player_controller::KeyState;
+
+/// A read-only snapshot of the game world passed to bot brains every tick.
+pub struct BotView<'a> {
+    pub state: &'a SharedGameState,
+    pub player: &'a Player,
+    pub npc_list: &'a NPCList,
+    pub stage: &'a Stage,
+    pub frame: &'a Frame,
+    pub stage_id: usize,
+    pub tick: u32,
+}
+
+impl<'a> BotView<'a> {
+    /// Returns the number of the event that is currently being executed by the TSC VM, if any.
+    pub fn current_event(&self) -> Option<u16> {
+        self.state.textscript_vm.current_event()
+    }
+
+    pub fn get_flag(&self, id: usize) -> bool {
+        self.state.get_flag(id)
+    }
+
+    /// Returns player position in tiles.
+    pub fn player_tile_pos(&self) -> (i32, i32) {
+        let tile_size = self.state.tile_size.as_int() * 0x200;
+
+        (self.player.x / tile_size, self.player.y / tile_size)
+    }
+
+    /// Returns the tile attribute at specified tile coordinates, 0 if out of bounds.
+    pub fn attribute_at(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x as usize >= self.stage.map.width as usize || y as usize >= self.stage.map.height as usize
+        {
+            return 0;
+        }
+
+        self.stage.map.get_attribute(x as usize, y as usize)
+    }
+
+    pub fn npcs(&self) -> impl Iterator<Item = &NPC> {
+        self.npc_list.iter_alive().map(|npc| &*npc)
+    }
+}
+
+/// Decides which buttons a bot presses each tick.
+pub trait BotBrain {
+    fn think(&mut self, view: &BotView, keys: &mut KeyState);
+}
+
+impl<F: FnMut(&BotView, &mut KeyState)> BotBrain for F {
+    fn think(&mut self, view: &BotView, keys: &mut KeyState) {
+        self(view, keys)
+    }
+}
+
+/// A player controller driven by a [BotBrain] instead of a physical input device.
+/// The brain and the held keys are shared between clones, so the controller can be handed out to players freely.
+#[derive(Clone)]
+pub struct BotPlayerController {
+    brain: Rc<RefCell<Box<dyn BotBrain>>>,
+    keys: Rc<Cell<KeyState>>,
+    state: KeyState,
+    old_state: KeyState,
+    trigger: KeyState,
+}
+
+impl BotPlayerController {
+    pub fn new<B: BotBrain + 'static>(brain: B) -> BotPlayerController {
+        BotPlayerController {
+            brain: Rc::new(RefCell::new(Box::new(brain))),
+            keys: Rc::new(Cell::new(KeyState(0))),
+            state: KeyState(0),
+            old_state: KeyState(0),
+            trigger: KeyState(0),
+        }
+    }
+
+    /// Asks the brain for inputs for the next tick.
+    pub fn think(&self, view: &BotView) {
+        let mut keys = KeyState(0);
+        self.brain.borrow_mut().think(view, &mut keys);
+        self.set_keys(keys);
+    }
+
+    /// Sets the keys held by this bot and all its clones from the next update on.
+    pub fn set_keys(&self, keys: KeyState) {
+        self.keys.set(keys);
+    }
+
+    /// Returns true if both controllers are clones of the same bot.
+    pub fn is_same(&self, other: &BotPlayerController) -> bool {
+        Rc::ptr_eq(&self.brain, &other.brain)
+    }
+}
+
+impl PlayerController for BotPlayerController {
+    fn update(&mut self, _state: &mut SharedGameState, _ctx: &mut Context) -> GameResult {
+        self.state = self.keys.get();
+
+        Ok(())
+    }
+
+    fn update_trigger(&mut self) {
+        let mut trigger = self.state.0 ^ self.old_state.0;
+        trigger &= self.state.0;
+        self.old_state = self.state;
+        self.trigger = KeyState(trigger);
+    }
+
+    fn move_up(&self) -> bool {
+        self.state.up()
+    }
+
+    fn move_left(&self) -> bool {
+        self.state.left()
+    }
+
+    fn move_down(&self) -> bool {
+        self.state.down()
+    }
+
+    fn move_right(&self) -> bool {
+        self.state.right()
+    }
+
+    fn prev_weapon(&self) -> bool {
+        self.state.prev_weapon()
+    }
+
+    fn next_weapon(&self) -> bool {
+        self.state.next_weapon()
+    }
+
+    fn map(&self) -> bool {
+        self.state.map()
+    }
+
+    fn inventory(&self) -> bool {
+        self.state.inventory()
+    }
+
+    fn jump(&self) -> bool {
+        self.state.jump()
+    }
+
+    fn shoot(&self) -> bool {
+        self.state.shoot()
+    }
+
+    fn skip(&self) -> bool {
+        self.state.skip()
+    }
+
+    fn strafe(&self) -> bool {
+        self.state.strafe()
+    }
+
+    fn trigger_up(&self) -> bool {
+        self.trigger.up()
+    }
+
+    fn trigger_left(&self) -> bool {
+        self.trigger.left()
+    }
+
+    fn trigger_down(&self) -> bool {
+        self.trigger.down()
+    }
+
+    fn trigger_right(&self) -> bool {
+        self.trigger.right()
+    }
+
+    fn trigger_prev_weapon(&self) -> bool {
+        self.trigger.prev_weapon()
+    }
+
+    fn trigger_next_weapon(&self) -> bool {
+        self.trigger.next_weapon()
+    }
+
+    fn trigger_map(&self) -> bool {
+        self.trigger.map()
+    }
+
+    fn trigger_inventory(&self) -> bool {
+        self.trigger.inventory()
+    }
+
+    fn trigger_jump(&self) -> bool {
+        self.trigger.jump()
+    }
+
+    fn trigger_shoot(&self) -> bool {
+        self.trigger.shoot()
+    }
+
+    fn trigger_skip(&self) -> bool {
+        self.trigger.skip()
+    }
+
+    fn trigger_strafe(&self) -> bool {
+        self.trigger.strafe()
+    }
+
+    fn trigger_menu_ok(&self) -> bool {
+        self.trigger.menu_ok() || self.trigger.enter()
+    }
+
+    fn trigger_menu_back(&self) -> bool {
+        self.trigger.menu_back() || self.trigger.escape()
+    }
+
+    fn trigger_menu_pause(&self) -> bool {
+        self.trigger.escape()
+    }
+
+    fn look_up(&self) -> bool {
+        self.state.up()
+    }
+
+    fn look_left(&self) -> bool {
+        self.state.left()
+    }
+
+    fn look_down(&self) -> bool {
+        self.state.down()
+    }
+
+    fn look_right(&self) -> bool {
+        self.state.right()
+    }
+
+    fn move_analog_x(&self) -> f64 {
+        if self.state.left() && self.state.right() {
+            0.0
+        } else if self.state.left() {
+            -1.0
+        } else if self.state.right() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn move_analog_y(&self) -> f64 {
+        if self.state.up() && self.state.down() {
+            0.0
+        } else if self.state.up() {
+            -1.0
+        } else if self.state.down() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn set_rumble(&mut self, _low_freq: u16, _hi_freq: u16, _ticks: u32) {}
+}
+
+/// Puts a bot in place of the controller of a player and gives the previous controller back once it's disabled.
+#[derive(Default)]
+pub struct BotSlot {
+    installed: Option<BotPlayerController>,
+    previous: Option<Box<dyn PlayerController>>,
+}
+
+impl BotSlot {
+    /// Installs `bot` into `controller` if it isn't there yet, or restores the previous controller if it's `None`.
+    pub fn sync(&mut self, bot: Option<&BotPlayerController>, controller: &mut Box<dyn PlayerController>) {
+        match bot {
+            Some(bot) if self.installed.as_ref().map_or(false, |installed| installed.is_same(bot)) => {}
+            Some(bot) => {
+                let previous = std::mem::replace(controller, Box::new(bot.clone()));
+                // a bot replacing another bot keeps the original controller stashed
+                if self.previous.is_none() {
+                    self.previous = Some(previous);
+                }
+
+                self.installed = Some(bot.clone());
+            }
+            None => {
+                if self.installed.take().is_some() {
+                    if let Some(previous) = self.previous.take() {
+                        *controller = previous;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a bot holding `keys`, as if it was updated by the game.
+    fn holding(keys: KeyState) -> BotPlayerController {
+        let mut bot = BotPlayerController::new(|_: &BotView, _: &mut KeyState| {});
+        bot.set_keys(keys);
+        bot.state = bot.keys.get();
+        bot
+    }
+
+    #[test]
+    fn test_bot_slot_restores_previous_controller() {
+        let jump = KeyState(0x40);
+        let mut controller: Box<dyn PlayerController> = Box::new(holding(jump));
+        assert!(controller.jump());
+
+        let bot = holding(KeyState(0));
+        let mut slot = BotSlot::default();
+
+        slot.sync(Some(&bot), &mut controller);
+        assert!(slot.installed.is_some());
+        assert!(!controller.jump());
+
+        // syncing the same bot again must not stash the bot itself
+        slot.sync(Some(&bot), &mut controller);
+        slot.sync(Some(&holding(KeyState(0))), &mut controller);
+        assert!(!controller.jump());
+
+        slot.sync(None, &mut controller);
+        assert!(!slot.installed.is_some());
+        assert!(controller.jump());
+
+        slot.sync(None, &mut controller);
+        assert!(controller.jump());
+    }
+
+    #[test]
+    fn test_bot_keys_are_shared_between_clones() {
+        let bot = holding(KeyState(0));
+        let mut installed = bot.clone();
+
+        bot.set_keys(KeyState(0x01)); // left
+        installed.state = installed.keys.get();
+        installed.update_trigger();
+
+        assert!(installed.move_left());
+        assert!(installed.trigger_left());
+        assert!(bot.is_same(&installed));
+        assert!(!bot.is_same(&holding(KeyState(0))));
+    }
+}
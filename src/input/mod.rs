@@ -1,3 +1,4 @@
+pub mod bot_player_controller;
 pub mod combined_menu_controller;
 pub mod combined_player_controller;
 pub mod dummy_player_controller;
@@ -1,14 +1,433 @@
+use std::io::{Read, Write};
+
 use num_traits::FromPrimitive;
 
+use crate::framework::context::Context;
 use crate::framework::error::{GameError::CommandLineError, GameResult};
+use crate::framework::filesystem;
+use crate::game::npc::boss::BossNPC;
 use crate::game::npc::NPC;
 use crate::game::scripting::tsc::text_script::{ScriptMode, TextScript, TextScriptEncoding};
-use crate::game::shared_game_state::SharedGameState;
+use crate::game::shared_game_state::{GameDifficulty, SharedGameState};
 use crate::game::weapon::WeaponType;
 use crate::scene::game_scene::GameScene;
+use crate::util::rng::RNG;
+
+const HISTORY_FILE: &str = "/console_history.txt";
+const HISTORY_MAX_LEN: usize = 100;
+/// Prevents macros from recursively running themselves forever.
+const MACRO_MAX_DEPTH: usize = 8;
+
+static FLAG_KINDS: &[&str] = &["game", "skip", "map"];
+static TOGGLES: &[&str] = &["god_mode", "noclip", "infinite_booster"];
+static DIFFICULTIES: &[&str] = &["easy", "normal", "hard"];
+
+static WEAPON_TYPES: [WeaponType; 10] = [
+    WeaponType::Snake,
+    WeaponType::PolarStar,
+    WeaponType::Fireball,
+    WeaponType::MachineGun,
+    WeaponType::MissileLauncher,
+    WeaponType::Bubbler,
+    WeaponType::Blade,
+    WeaponType::SuperMissileLauncher,
+    WeaponType::Nemesis,
+    WeaponType::Spur,
+];
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ArgType {
+    UInt,
+    Float,
+    /// Item ID or name.
+    Item,
+    /// Weapon ID or name.
+    Weapon,
+    /// NPC type ID or name.
+    NPC,
+    /// Boss type ID or name.
+    Boss,
+    /// Stage ID or map name.
+    Stage,
+    /// Song ID or name.
+    Song,
+    /// One of listed keywords.
+    Keyword(&'static [&'static str]),
+    /// Consumes the rest of the line.
+    Text,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub arg_type: ArgType,
+    pub optional: bool,
+}
+
+impl ArgSpec {
+    const fn required(name: &'static str, arg_type: ArgType) -> ArgSpec {
+        ArgSpec { name, arg_type, optional: false }
+    }
+
+    const fn optional(name: &'static str, arg_type: ArgType) -> ArgSpec {
+        ArgSpec { name, arg_type, optional: true }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub args: &'static [ArgSpec],
+    pub help: &'static str,
+}
+
+impl CommandSpec {
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+
+        for arg in self.args {
+            if arg.optional {
+                usage.push_str(&format!(" [{}]", arg.name));
+            } else {
+                usage.push_str(&format!(" <{}>", arg.name));
+            }
+        }
+
+        usage
+    }
+}
+
+/// Registry of all commands understood by the command line.
+#[rustfmt::skip]
+pub static COMMANDS: &[CommandSpec] = &[
+    CommandSpec { name: "help", args: &[ArgSpec::optional("command", ArgType::Text)], help: "Lists commands or shows help for a command." },
+    CommandSpec { name: "add_item", args: &[ArgSpec::required("item", ArgType::Item)], help: "Adds an item to the inventory." },
+    CommandSpec { name: "remove_item", args: &[ArgSpec::required("item", ArgType::Item)], help: "Removes an item from the inventory." },
+    CommandSpec {
+        name: "add_weapon",
+        args: &[ArgSpec::required("weapon", ArgType::Weapon), ArgSpec::required("ammo", ArgType::UInt)],
+        help: "Adds a weapon with specified amount of ammo.",
+    },
+    CommandSpec { name: "remove_weapon", args: &[ArgSpec::required("weapon", ArgType::Weapon)], help: "Removes a weapon." },
+    CommandSpec { name: "add_weapon_ammo", args: &[ArgSpec::required("ammo", ArgType::UInt)], help: "Adds ammo to current weapon." },
+    CommandSpec { name: "set_weapon_max_ammo", args: &[ArgSpec::required("ammo", ArgType::UInt)], help: "Sets max ammo of current weapon." },
+    CommandSpec { name: "refill_ammo", args: &[], help: "Refills ammo of all weapons." },
+    CommandSpec { name: "refill_hp", args: &[], help: "Refills HP of player." },
+    CommandSpec { name: "add_xp", args: &[ArgSpec::required("xp", ArgType::UInt)], help: "Adds XP to current weapon." },
+    CommandSpec { name: "remove_xp", args: &[ArgSpec::required("xp", ArgType::UInt)], help: "Removes XP from current weapon." },
+    CommandSpec { name: "set_max_hp", args: &[ArgSpec::required("hp", ArgType::UInt)], help: "Sets max HP of player and refills it." },
+    CommandSpec { name: "spawn_npc", args: &[ArgSpec::required("npc", ArgType::NPC)], help: "Spawns an NPC in front of player." },
+    CommandSpec {
+        name: "teleport_player",
+        args: &[ArgSpec::required("x", ArgType::Float), ArgSpec::required("y", ArgType::Float)],
+        help: "Teleports players to specified position (in pixels).",
+    },
+    CommandSpec {
+        name: "set_flag",
+        args: &[
+            ArgSpec::required("kind", ArgType::Keyword(FLAG_KINDS)),
+            ArgSpec::required("start", ArgType::UInt),
+            ArgSpec::optional("end", ArgType::UInt),
+        ],
+        help: "Sets a flag or an inclusive range of game, skip or map flags.",
+    },
+    CommandSpec {
+        name: "clear_flag",
+        args: &[
+            ArgSpec::required("kind", ArgType::Keyword(FLAG_KINDS)),
+            ArgSpec::required("start", ArgType::UInt),
+            ArgSpec::optional("end", ArgType::UInt),
+        ],
+        help: "Clears a flag or an inclusive range of game, skip or map flags.",
+    },
+    CommandSpec {
+        name: "get_flag",
+        args: &[
+            ArgSpec::required("kind", ArgType::Keyword(FLAG_KINDS)),
+            ArgSpec::required("start", ArgType::UInt),
+            ArgSpec::optional("end", ArgType::UInt),
+        ],
+        help: "Lists set flags within an inclusive range of game, skip or map flags.",
+    },
+    CommandSpec {
+        name: "warp",
+        args: &[
+            ArgSpec::required("stage", ArgType::Stage),
+            ArgSpec::optional("event", ArgType::UInt),
+            ArgSpec::optional("x", ArgType::UInt),
+            ArgSpec::optional("y", ArgType::UInt),
+        ],
+        help: "Warps to a stage and runs an event, position is in tiles.",
+    },
+    CommandSpec { name: "kill_npc", args: &[ArgSpec::required("id", ArgType::UInt)], help: "Kills an NPC by its ID." },
+    CommandSpec {
+        name: "kill_npc_type",
+        args: &[ArgSpec::required("npc", ArgType::NPC)],
+        help: "Kills all NPCs of specified type.",
+    },
+    CommandSpec {
+        name: "remove_npc",
+        args: &[ArgSpec::required("id", ArgType::UInt)],
+        help: "Removes an NPC by its ID without any death effects.",
+    },
+    CommandSpec {
+        name: "remove_npc_type",
+        args: &[ArgSpec::required("npc", ArgType::NPC)],
+        help: "Removes all NPCs of specified type without any death effects.",
+    },
+    CommandSpec {
+        name: "toggle",
+        args: &[ArgSpec::required("option", ArgType::Keyword(TOGGLES))],
+        help: "Toggles god mode, noclip or infinite booster fuel.",
+    },
+    CommandSpec {
+        name: "set_difficulty",
+        args: &[ArgSpec::required("difficulty", ArgType::Keyword(DIFFICULTIES))],
+        help: "Changes game difficulty.",
+    },
+    CommandSpec { name: "play_song", args: &[ArgSpec::required("song", ArgType::Song)], help: "Plays a song." },
+    CommandSpec { name: "play_sfx", args: &[ArgSpec::required("id", ArgType::UInt)], help: "Plays a sound effect." },
+    CommandSpec { name: "spawn_boss", args: &[ArgSpec::required("boss", ArgType::Boss)], help: "Spawns a boss." },
+    CommandSpec { name: "tsc", args: &[ArgSpec::required("script", ArgType::Text)], help: "Executes TSC, use \\n for newlines." },
+    CommandSpec {
+        name: "run",
+        args: &[ArgSpec::required("file", ArgType::Text)],
+        help: "Runs a file of commands, looked up in user directory first, then in game data.",
+    },
+];
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.name == name)
+}
 
 #[derive(Clone)]
+enum ArgValue {
+    UInt(u16),
+    Float(f32),
+    Text(String),
+}
+
+impl ArgValue {
+    fn uint(&self) -> u16 {
+        match self {
+            ArgValue::UInt(v) => *v,
+            _ => 0,
+        }
+    }
+
+    fn float(&self) -> f32 {
+        match self {
+            ArgValue::Float(v) => *v,
+            _ => 0.0,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            ArgValue::Text(v) => v.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagKind {
+    Game,
+    Skip,
+    Map,
+}
+
+impl FlagKind {
+    fn from_keyword(keyword: &str) -> FlagKind {
+        match keyword {
+            "skip" => FlagKind::Skip,
+            "map" => FlagKind::Map,
+            _ => FlagKind::Game,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FlagKind::Game => "game",
+            FlagKind::Skip => "skip",
+            FlagKind::Map => "map",
+        }
+    }
+
+    fn get(self, state: &SharedGameState, id: usize) -> bool {
+        match self {
+            FlagKind::Game => state.get_flag(id),
+            FlagKind::Skip => state.get_skip_flag(id),
+            FlagKind::Map => state.get_map_flag(id),
+        }
+    }
+
+    fn set(self, state: &mut SharedGameState, id: usize, value: bool) {
+        match self {
+            FlagKind::Game => state.set_flag(id, value),
+            FlagKind::Skip => state.set_skip_flag(id, value),
+            FlagKind::Map => state.set_map_flag(id, value),
+        }
+    }
+
+    fn len(self, state: &SharedGameState) -> usize {
+        match self {
+            FlagKind::Game => state.game_flags.len(),
+            FlagKind::Skip => state.skip_flags.len(),
+            FlagKind::Map => state.map_flags.len(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebugToggle {
+    GodMode,
+    Noclip,
+    InfiniteBooster,
+}
+
+/// Item, weapon, NPC, boss, stage and song names used for name arguments and tab completion.
+#[derive(Default)]
+pub struct NameTables {
+    pub items: Vec<(u16, String)>,
+    pub weapons: Vec<(u16, String)>,
+    pub npcs: Vec<(u16, String)>,
+    pub bosses: Vec<(u16, String)>,
+    pub stages: Vec<(u16, String)>,
+    pub songs: Vec<(u16, String)>,
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i != 0 {
+            result.push('_');
+        }
+        result.push(c.to_ascii_lowercase());
+    }
+
+    result
+}
+
+/// Turns the first line of an item description (eg. `=Arthur's Key=`) into an identifier like `arthurs_key`.
+fn to_identifier(text: &str) -> Option<String> {
+    let line = text
+        .lines()
+        .map(|l| l.trim_matches(|c: char| c == '=' || c == '\u{ff1d}' || c.is_whitespace()))
+        .find(|l| !l.is_empty())?;
+    let mut result = String::with_capacity(line.len());
+
+    for c in line.chars().filter(|c| *c != '\'') {
+        if c.is_alphanumeric() {
+            result.extend(c.to_lowercase());
+        } else if !result.is_empty() && !result.ends_with('_') {
+            result.push('_');
+        }
+    }
+
+    let result = result.trim_end_matches('_');
+    (!result.is_empty()).then(|| result.to_string())
+}
+
+/// Collects `<prefix><id>_<name>` keys of a serialized constants table, eg. `n001_experience`.
+fn prefixed_names(value: &serde_json::Value, prefix: char) -> Vec<(u16, String)> {
+    let mut names = Vec::new();
+
+    if let serde_json::Value::Object(map) = value {
+        for key in map.keys() {
+            if let Some((id, name)) = key.strip_prefix(prefix).and_then(|k| k.split_once('_')) {
+                if let Ok(id) = id.parse::<u16>() {
+                    names.push((id, name.to_string()));
+                }
+            }
+        }
+    }
+
+    names.sort_by_key(|(id, _)| *id);
+    names.dedup_by_key(|(id, _)| *id);
+    names
+}
+
+impl NameTables {
+    pub fn new(state: &SharedGameState) -> NameTables {
+        let constants = &state.constants;
+
+        // item names are taken from their descriptions in ArmsItem.tsc, shown in the inventory as event 5000 + ID
+        let items = {
+            let scripts = state.textscript_vm.scripts.borrow();
+            let script = &scripts.inventory_script;
+
+            script
+                .get_event_ids()
+                .into_iter()
+                .filter(|id| (5001..6000).contains(id))
+                .filter_map(|id| Some((id - 5000, to_identifier(&script.event_text(id)?)?)))
+                .collect()
+        };
+
+        let weapons =
+            WEAPON_TYPES.iter().map(|wtype| (*wtype as u16, to_snake_case(&format!("{:?}", wtype)))).collect();
+
+        // NPC and boss rect tables are named after the NPC they belong to (eg. n001_experience, b01_omega)
+        let npc_rects = serde_json::to_value(&constants.npc).unwrap_or_default();
+        let npcs = prefixed_names(&npc_rects, 'n');
+        let bosses = prefixed_names(&npc_rects, 'b');
+
+        let stages = state
+            .stages
+            .iter()
+            .enumerate()
+            .filter(|(_, stage)| !stage.map.is_empty() && stage.map != "0")
+            .map(|(id, stage)| (id as u16, stage.map.to_lowercase()))
+            .collect();
+
+        let songs = constants
+            .music_table
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(id, name)| (id as u16, name.to_lowercase()))
+            .collect();
+
+        NameTables { items, weapons, npcs, bosses, stages, songs }
+    }
+
+    fn table(&self, arg_type: ArgType) -> Option<&Vec<(u16, String)>> {
+        match arg_type {
+            ArgType::Item => Some(&self.items),
+            ArgType::Weapon => Some(&self.weapons),
+            ArgType::NPC => Some(&self.npcs),
+            ArgType::Boss => Some(&self.bosses),
+            ArgType::Stage => Some(&self.stages),
+            ArgType::Song => Some(&self.songs),
+            _ => None,
+        }
+    }
+
+    fn candidates(&self, arg_type: ArgType) -> Vec<String> {
+        match arg_type {
+            ArgType::Keyword(keywords) => keywords.iter().map(|k| k.to_string()).collect(),
+            _ => self.table(arg_type).map(|t| t.iter().map(|(_, name)| name.clone()).collect()).unwrap_or_default(),
+        }
+    }
+
+    fn lookup(&self, arg_type: ArgType, value: &str) -> Option<u16> {
+        if let Ok(id) = value.parse::<u16>() {
+            return Some(id);
+        }
+
+        let value = value.to_lowercase();
+        self.table(arg_type)?.iter().find(|(_, name)| *name == value).map(|(id, _)| *id)
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CommandLineCommand {
+    Help(Option<String>),
     AddItem(u16),
     RemoveItem(u16),
     AddWeapon(u16, u16),
@@ -16,161 +435,191 @@ pub enum CommandLineCommand {
     AddWeaponAmmo(u16),
     SetWeaponMaxAmmo(u16),
     RefillAmmo,
+    #[serde(rename = "refill_hp")]
     RefillHP,
+    #[serde(rename = "add_xp")]
     AddXP(u16),
+    #[serde(rename = "remove_xp")]
     RemoveXP(u16),
+    #[serde(rename = "set_max_hp")]
     SetMaxHP(u16),
+    #[serde(rename = "spawn_npc")]
     SpawnNPC(u16),
     TeleportPlayer(f32, f32),
+    #[serde(rename = "tsc")]
     TSC(String),
+    #[serde(rename = "run")]
+    RunMacro(String),
+    SetFlag(FlagKind, u16, u16),
+    ClearFlag(FlagKind, u16, u16),
+    GetFlag(FlagKind, u16, u16),
+    Warp(u16, u16, Option<(u16, u16)>),
+    #[serde(rename = "kill_npc")]
+    KillNPC(u16),
+    #[serde(rename = "kill_npc_type")]
+    KillNPCType(u16),
+    #[serde(rename = "remove_npc")]
+    RemoveNPC(u16),
+    #[serde(rename = "remove_npc_type")]
+    RemoveNPCType(u16),
+    Toggle(DebugToggle),
+    SetDifficulty(GameDifficulty),
+    PlaySong(u16),
+    #[serde(rename = "play_sfx")]
+    PlaySFX(u8),
+    SpawnBoss(u16),
 }
 
 impl CommandLineCommand {
-    pub fn from_components(components: Vec<&str>) -> Option<CommandLineCommand> {
-        if components.len() == 0 {
-            return None;
+    /// Parses a single command line, validating arguments against the command registry.
+    pub fn parse(line: &str, names: &NameTables) -> Result<CommandLineCommand, String> {
+        let line = line.trim();
+
+        if line.starts_with('<') {
+            return Ok(CommandLineCommand::TSC(line.replace("\\n", "\n")));
         }
 
-        let command = components[0];
+        let (name, mut rest) = match line.split_once(char::is_whitespace) {
+            Some((name, rest)) => (name, rest.trim_start()),
+            None => (line, ""),
+        };
 
-        if command.starts_with("<") {
-            return Some(CommandLineCommand::TSC(components.join(" ").replace("\\n", "\n")));
+        if name.is_empty() {
+            return Err("Empty command".to_string());
         }
 
-        match command.replacen("/", "", 1).as_str() {
-            "add_item" => {
-                if components.len() < 2 {
-                    return None;
-                }
+        let name = name.strip_prefix('/').unwrap_or(name);
+        let spec = find_command(name).ok_or_else(|| format!("Unknown command /{}, try /help", name))?;
 
-                let item_id = components[1].parse::<u16>();
-                if let Ok(item_id) = item_id {
-                    return Some(CommandLineCommand::AddItem(item_id));
-                }
-            }
-            "remove_item" => {
-                if components.len() < 2 {
-                    return None;
+        let mut values = Vec::with_capacity(spec.args.len());
+        for arg in spec.args {
+            if rest.is_empty() {
+                if arg.optional {
+                    break;
                 }
 
-                let item_id = components[1].parse::<u16>();
-                if let Ok(item_id) = item_id {
-                    return Some(CommandLineCommand::RemoveItem(item_id));
-                }
+                return Err(format!("Missing argument <{}>, usage: {}", arg.name, spec.usage()));
             }
-            "add_weapon" => {
-                if components.len() < 3 {
-                    return None;
-                }
 
-                let weapon_id = components[1].parse::<u16>();
-                let ammo_count = components[2].parse::<u16>();
+            let token = if arg.arg_type == ArgType::Text {
+                let token = rest;
+                rest = "";
+                token
+            } else {
+                let (token, next) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = next.trim_start();
+                token
+            };
 
-                if let (Ok(weapon_id), Ok(ammo_count)) = (weapon_id, ammo_count) {
-                    return Some(CommandLineCommand::AddWeapon(weapon_id, ammo_count));
+            let value = match arg.arg_type {
+                ArgType::UInt => token.parse::<u16>().ok().map(ArgValue::UInt),
+                ArgType::Float => token.parse::<f32>().ok().map(ArgValue::Float),
+                ArgType::Item | ArgType::Weapon | ArgType::NPC | ArgType::Boss | ArgType::Stage | ArgType::Song => {
+                    names.lookup(arg.arg_type, token).map(ArgValue::UInt)
                 }
-            }
-            "remove_weapon" => {
-                if components.len() < 2 {
-                    return None;
+                ArgType::Keyword(keywords) => {
+                    let token = token.to_lowercase();
+                    keywords.iter().find(|k| **k == token).map(|k| ArgValue::Text(k.to_string()))
                 }
+                ArgType::Text => Some(ArgValue::Text(token.to_string())),
+            };
 
-                let weapon_id = components[1].parse::<u16>();
-                if let Ok(weapon_id) = weapon_id {
-                    return Some(CommandLineCommand::RemoveWeapon(weapon_id));
-                }
+            match value {
+                Some(value) => values.push(value),
+                None => return Err(format!("Invalid value '{}' for <{}>, usage: {}", token, arg.name, spec.usage())),
             }
-            "add_weapon_ammo" => {
-                if components.len() < 2 {
-                    return None;
-                }
+        }
 
-                let ammo_count = components[1].parse::<u16>();
-                if let Ok(ammo_count) = ammo_count {
-                    return Some(CommandLineCommand::AddWeaponAmmo(ammo_count));
-                }
-            }
-            "set_weapon_max_ammo" => {
-                if components.len() < 2 {
-                    return None;
-                }
+        if !rest.is_empty() {
+            return Err(format!("Too many arguments, usage: {}", spec.usage()));
+        }
 
-                let max_ammo_count = components[1].parse::<u16>();
-                if let Ok(max_ammo_count) = max_ammo_count {
-                    return Some(CommandLineCommand::SetWeaponMaxAmmo(max_ammo_count));
-                }
-            }
-            "refill_ammo" => {
-                return Some(CommandLineCommand::RefillAmmo);
-            }
-            "refill_hp" => {
-                return Some(CommandLineCommand::RefillHP);
-            }
-            "add_xp" => {
-                if components.len() < 2 {
-                    return None;
-                }
+        let command = match spec.name {
+            "help" => CommandLineCommand::Help(values.get(0).map(|v| v.text())),
+            "add_item" => CommandLineCommand::AddItem(values[0].uint()),
+            "remove_item" => CommandLineCommand::RemoveItem(values[0].uint()),
+            "add_weapon" => CommandLineCommand::AddWeapon(values[0].uint(), values[1].uint()),
+            "remove_weapon" => CommandLineCommand::RemoveWeapon(values[0].uint()),
+            "add_weapon_ammo" => CommandLineCommand::AddWeaponAmmo(values[0].uint()),
+            "set_weapon_max_ammo" => CommandLineCommand::SetWeaponMaxAmmo(values[0].uint()),
+            "refill_ammo" => CommandLineCommand::RefillAmmo,
+            "refill_hp" => CommandLineCommand::RefillHP,
+            "add_xp" => CommandLineCommand::AddXP(values[0].uint()),
+            "remove_xp" => CommandLineCommand::RemoveXP(values[0].uint()),
+            "set_max_hp" => CommandLineCommand::SetMaxHP(values[0].uint()),
+            "spawn_npc" => CommandLineCommand::SpawnNPC(values[0].uint()),
+            "teleport_player" => CommandLineCommand::TeleportPlayer(values[0].float(), values[1].float()),
+            "tsc" => CommandLineCommand::TSC(values[0].text().replace("\\n", "\n")),
+            "run" => CommandLineCommand::RunMacro(values[0].text()),
+            "set_flag" | "clear_flag" | "get_flag" => {
+                let kind = FlagKind::from_keyword(&values[0].text());
+                let start = values[1].uint();
+                let end = values.get(2).map(|v| v.uint()).unwrap_or(start);
 
-                let xp_count = components[1].parse::<u16>();
-                if let Ok(xp_count) = xp_count {
-                    return Some(CommandLineCommand::AddXP(xp_count));
-                }
-            }
-            "remove_xp" => {
-                if components.len() < 2 {
-                    return None;
+                if end < start {
+                    return Err(format!("Invalid flag range {}..{}", start, end));
                 }
 
-                let xp_count = components[1].parse::<u16>();
-                if let Ok(xp_count) = xp_count {
-                    return Some(CommandLineCommand::RemoveXP(xp_count));
+                match spec.name {
+                    "set_flag" => CommandLineCommand::SetFlag(kind, start, end),
+                    "clear_flag" => CommandLineCommand::ClearFlag(kind, start, end),
+                    _ => CommandLineCommand::GetFlag(kind, start, end),
                 }
             }
-            "set_max_hp" => {
-                if components.len() < 2 {
-                    return None;
-                }
+            "warp" => {
+                let pos = match (values.get(2), values.get(3)) {
+                    (Some(x), Some(y)) => Some((x.uint(), y.uint())),
+                    (Some(_), None) => return Err(format!("Missing argument <y>, usage: {}", spec.usage())),
+                    _ => None,
+                };
 
-                let hp_count = components[1].parse::<u16>();
-                if let Ok(hp_count) = hp_count {
-                    return Some(CommandLineCommand::SetMaxHP(hp_count));
-                }
-            }
-            "spawn_npc" => {
-                if components.len() < 2 {
-                    return None;
-                }
-                let npc_id = components[1].parse::<u16>();
-                if let Ok(npc_id) = npc_id {
-                    return Some(CommandLineCommand::SpawnNPC(npc_id));
-                }
-            }
-            "teleport_player" => {
-                if components.len() < 2 {
-                    return None;
-                }
-                let x = components[1].parse::<f32>();
-                let y = components[2].parse::<f32>();
-                if let (Ok(x), Ok(y)) = (x, y) {
-                    return Some(CommandLineCommand::TeleportPlayer(x, y));
-                }
+                CommandLineCommand::Warp(values[0].uint(), values.get(1).map(|v| v.uint()).unwrap_or(0), pos)
             }
-            "tsc" => {
-                if components.len() < 2 {
-                    return None;
-                }
+            "kill_npc" => CommandLineCommand::KillNPC(values[0].uint()),
+            "kill_npc_type" => CommandLineCommand::KillNPCType(values[0].uint()),
+            "remove_npc" => CommandLineCommand::RemoveNPC(values[0].uint()),
+            "remove_npc_type" => CommandLineCommand::RemoveNPCType(values[0].uint()),
+            "toggle" => CommandLineCommand::Toggle(match values[0].text().as_str() {
+                "god_mode" => DebugToggle::GodMode,
+                "noclip" => DebugToggle::Noclip,
+                _ => DebugToggle::InfiniteBooster,
+            }),
+            "set_difficulty" => CommandLineCommand::SetDifficulty(match values[0].text().as_str() {
+                "easy" => GameDifficulty::Easy,
+                "hard" => GameDifficulty::Hard,
+                _ => GameDifficulty::Normal,
+            }),
+            "play_song" => CommandLineCommand::PlaySong(values[0].uint()),
+            "play_sfx" => match u8::try_from(values[0].uint()) {
+                Ok(sfx_id) => CommandLineCommand::PlaySFX(sfx_id),
+                Err(_) => return Err(format!("Invalid sound effect id {}, must be at most 255", values[0].uint())),
+            },
+            "spawn_boss" => CommandLineCommand::SpawnBoss(values[0].uint()),
+            _ => return Err(format!("Command /{} is not implemented", spec.name)),
+        };
 
-                let script = components[1..].join(" ").replace("\\n", "\n");
-                return Some(CommandLineCommand::TSC(script));
-            }
-            _ => return None,
-        }
+        Ok(command)
+    }
 
-        None
+    /// Executes the command, returns a feedback message on success.
+    pub fn execute(
+        &mut self,
+        game_scene: &mut GameScene,
+        state: &mut SharedGameState,
+        ctx: &mut Context,
+    ) -> GameResult<String> {
+        self.execute_nested(game_scene, state, ctx, 0)
     }
 
-    pub fn execute(&mut self, game_scene: &mut GameScene, state: &mut SharedGameState) -> GameResult {
+    fn execute_nested(
+        &mut self,
+        game_scene: &mut GameScene,
+        state: &mut SharedGameState,
+        ctx: &mut Context,
+        depth: usize,
+    ) -> GameResult<String> {
         match self.clone() {
+            CommandLineCommand::Help(_) => {}
             CommandLineCommand::AddItem(item_id) => {
                 game_scene.inventory_player1.add_item(item_id);
             }
@@ -248,8 +697,10 @@ impl CommandLineCommand {
                 game_scene.player2.y = game_scene.player1.y;
             }
             CommandLineCommand::TSC(script) => {
-                log::info!("Executing TSC script: {}", format!("#9999\n{}", script));
-                match TextScript::compile(format!("#9999\n{}", script).as_bytes(), true, TextScriptEncoding::UTF8) {
+                let data = format!("#9999\n{}", script);
+                log::info!("Executing TSC script: {}", data);
+                let custom_commands = &state.constants.tsc_custom_commands;
+                match TextScript::compile(data.as_bytes(), true, TextScriptEncoding::UTF8, custom_commands) {
                     Ok(text_script) => {
                         state.textscript_vm.set_debug_script(text_script);
                         state.textscript_vm.set_mode(ScriptMode::Debug);
@@ -260,14 +711,125 @@ impl CommandLineCommand {
                     }
                 };
             }
+            CommandLineCommand::RunMacro(path) => {
+                if depth >= MACRO_MAX_DEPTH {
+                    return Err(CommandLineError("Macros are nested too deeply".to_string()));
+                }
+
+                let lines = read_macro(ctx, &path)?;
+                let names = NameTables::new(state);
+
+                for (line_no, line) in lines.iter().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+
+                    let mut command = CommandLineCommand::parse(line, &names)
+                        .map_err(|e| CommandLineError(format!("{}:{}: {}", path, line_no + 1, e)))?;
+                    command
+                        .execute_nested(game_scene, state, ctx, depth + 1)
+                        .map_err(|e| CommandLineError(format!("{}:{}: {}", path, line_no + 1, e)))?;
+                }
+            }
+            CommandLineCommand::SetFlag(kind, start, end) | CommandLineCommand::ClearFlag(kind, start, end) => {
+                let value = matches!(self, CommandLineCommand::SetFlag(..));
+                check_flag_range(state, kind, end)?;
+
+                for id in start..=end {
+                    kind.set(state, id as usize, value);
+                }
+            }
+            CommandLineCommand::GetFlag(kind, start, end) => {
+                check_flag_range(state, kind, end)?;
+
+                let set: Vec<String> =
+                    (start..=end).filter(|id| kind.get(state, *id as usize)).map(|id| id.to_string()).collect();
+
+                return Ok(if start == end {
+                    format!("{} flag {} is {}.", kind.name(), start, if set.is_empty() { "clear" } else { "set" })
+                } else if set.is_empty() {
+                    format!("No {} flags are set within {}..{}.", kind.name(), start, end)
+                } else {
+                    format!("Set {} flags within {}..{}: {}", kind.name(), start, end, set.join(", "))
+                });
+            }
+            CommandLineCommand::Warp(stage_id, event_num, pos) => {
+                if stage_id as usize >= state.stages.len() {
+                    return Err(CommandLineError(format!("Invalid stage id {}", stage_id)));
+                }
+
+                let (x, y) = pos.unwrap_or_else(|| {
+                    let tile_size = state.tile_size.as_int() * 0x200;
+                    ((game_scene.player1.x / tile_size) as u16, (game_scene.player1.y / tile_size) as u16)
+                });
+
+                let script = format!("<TRA{:04}:{:04}:{:04}:{:04}", stage_id, event_num, x, y);
+                CommandLineCommand::TSC(script).execute_nested(game_scene, state, ctx, depth)?;
+            }
+            CommandLineCommand::KillNPC(id) | CommandLineCommand::RemoveNPC(id) => {
+                match game_scene.npc_list.get_npc(id as usize) {
+                    Some(npc) if npc.cond.alive() => {
+                        if matches!(self, CommandLineCommand::KillNPC(_)) {
+                            game_scene.npc_list.kill_npc(id as usize, false, false, state);
+                        } else {
+                            npc.cond.set_alive(false);
+                        }
+                    }
+                    _ => return Err(CommandLineError(format!("NPC {} does not exist", id))),
+                }
+            }
+            CommandLineCommand::KillNPCType(npc_type) => {
+                game_scene.npc_list.kill_npcs_by_type(npc_type, true, state);
+            }
+            CommandLineCommand::RemoveNPCType(npc_type) => {
+                for npc in game_scene.npc_list.iter_alive().filter(|n| n.npc_type == npc_type) {
+                    npc.cond.set_alive(false);
+                }
+            }
+            CommandLineCommand::Toggle(toggle) => {
+                let value = match toggle {
+                    DebugToggle::GodMode => &mut state.settings.god_mode,
+                    DebugToggle::Noclip => &mut state.settings.noclip,
+                    DebugToggle::InfiniteBooster => &mut state.settings.infinite_booster,
+                };
+                *value = !*value;
+
+                return Ok(format!("Toggled {:?} {}.", toggle, if *value { "on" } else { "off" }));
+            }
+            CommandLineCommand::SetDifficulty(difficulty) => {
+                state.difficulty = difficulty;
+            }
+            CommandLineCommand::PlaySong(song_id) => {
+                state.sound_manager.play_song(song_id as usize, &state.constants, &state.settings, ctx, false)?;
+            }
+            CommandLineCommand::PlaySFX(sfx_id) => {
+                state.sound_manager.play_sfx(sfx_id);
+            }
+            CommandLineCommand::SpawnBoss(boss_type) => {
+                if boss_type == 0 {
+                    return Err(CommandLineError(format!("Invalid boss type {}", boss_type)));
+                }
+
+                game_scene.boss = BossNPC::new();
+                game_scene.boss.init_rng(state.game_rng.next());
+                game_scene.boss.boss_type = boss_type;
+                for part in game_scene.boss.parts.iter_mut() {
+                    part.x = game_scene.player1.x;
+                    part.y = game_scene.player1.y;
+                }
+                game_scene.boss_life_bar.set_boss_target(&game_scene.boss);
+            }
         }
 
-        Ok(())
+        Ok(self.feedback_string())
     }
 
     #[allow(dead_code)]
     pub fn to_command(&self) -> String {
         match self {
+            CommandLineCommand::Help(Some(command)) => format!("/help {}", command),
+            CommandLineCommand::Help(None) => "/help".to_string(),
             CommandLineCommand::AddItem(item_id) => format!("/add_item {}", item_id),
             CommandLineCommand::RemoveItem(item_id) => format!("/remove_item {}", item_id),
             CommandLineCommand::AddWeapon(weapon_id, ammo_count) => format!("/add_weapon {} {}", weapon_id, ammo_count),
@@ -282,11 +844,41 @@ impl CommandLineCommand {
             CommandLineCommand::SpawnNPC(npc_id) => format!("/spawn_npc {}", npc_id),
             CommandLineCommand::TeleportPlayer(x, y) => format!("/teleport_player {} {}", x, y),
             CommandLineCommand::TSC(script) => format!("/tsc {}", script.replace("\n", "\\n")),
+            CommandLineCommand::RunMacro(path) => format!("/run {}", path),
+            CommandLineCommand::SetFlag(kind, start, end) => format!("/set_flag {} {} {}", kind.name(), start, end),
+            CommandLineCommand::ClearFlag(kind, start, end) => format!("/clear_flag {} {} {}", kind.name(), start, end),
+            CommandLineCommand::GetFlag(kind, start, end) => format!("/get_flag {} {} {}", kind.name(), start, end),
+            CommandLineCommand::Warp(stage_id, event_num, Some((x, y))) => {
+                format!("/warp {} {} {} {}", stage_id, event_num, x, y)
+            }
+            CommandLineCommand::Warp(stage_id, event_num, None) => format!("/warp {} {}", stage_id, event_num),
+            CommandLineCommand::KillNPC(id) => format!("/kill_npc {}", id),
+            CommandLineCommand::KillNPCType(npc_type) => format!("/kill_npc_type {}", npc_type),
+            CommandLineCommand::RemoveNPC(id) => format!("/remove_npc {}", id),
+            CommandLineCommand::RemoveNPCType(npc_type) => format!("/remove_npc_type {}", npc_type),
+            CommandLineCommand::Toggle(DebugToggle::GodMode) => "/toggle god_mode".to_string(),
+            CommandLineCommand::Toggle(DebugToggle::Noclip) => "/toggle noclip".to_string(),
+            CommandLineCommand::Toggle(DebugToggle::InfiniteBooster) => "/toggle infinite_booster".to_string(),
+            CommandLineCommand::SetDifficulty(GameDifficulty::Easy) => "/set_difficulty easy".to_string(),
+            CommandLineCommand::SetDifficulty(GameDifficulty::Normal) => "/set_difficulty normal".to_string(),
+            CommandLineCommand::SetDifficulty(GameDifficulty::Hard) => "/set_difficulty hard".to_string(),
+            CommandLineCommand::PlaySong(song_id) => format!("/play_song {}", song_id),
+            CommandLineCommand::PlaySFX(sfx_id) => format!("/play_sfx {}", sfx_id),
+            CommandLineCommand::SpawnBoss(boss_type) => format!("/spawn_boss {}", boss_type),
         }
     }
 
     pub fn feedback_string(&self) -> String {
         match self {
+            CommandLineCommand::Help(None) => {
+                format!("Commands: {}", COMMANDS.iter().map(|c| format!("/{}", c.name)).collect::<Vec<_>>().join(", "))
+            }
+            CommandLineCommand::Help(Some(command)) => {
+                match find_command(command.trim().strip_prefix('/').unwrap_or(command.trim())) {
+                    Some(spec) => format!("{} - {}", spec.usage(), spec.help),
+                    None => format!("Unknown command {}", command),
+                }
+            }
             CommandLineCommand::AddItem(item_id) => format!("Added item with ID {}.", item_id),
             CommandLineCommand::RemoveItem(item_id) => format!("Removed item with ID {}.", item_id),
             CommandLineCommand::AddWeapon(weapon_id, ammo_count) => {
@@ -305,13 +897,65 @@ impl CommandLineCommand {
             CommandLineCommand::SpawnNPC(npc_id) => format!("Spawned NPC ID {} in front of player.", npc_id),
             CommandLineCommand::TeleportPlayer(x, y) => format!("Teleported players to ({}, {}).", x, y),
             CommandLineCommand::TSC(_) => "Executed TSC script.".to_string(),
+            CommandLineCommand::RunMacro(path) => format!("Executed macro {}.", path),
+            CommandLineCommand::SetFlag(kind, start, end) if start == end => {
+                format!("Set {} flag {}.", kind.name(), start)
+            }
+            CommandLineCommand::SetFlag(kind, start, end) => format!("Set {} flags {}..{}.", kind.name(), start, end),
+            CommandLineCommand::ClearFlag(kind, start, end) if start == end => {
+                format!("Cleared {} flag {}.", kind.name(), start)
+            }
+            CommandLineCommand::ClearFlag(kind, start, end) => {
+                format!("Cleared {} flags {}..{}.", kind.name(), start, end)
+            }
+            CommandLineCommand::GetFlag(kind, start, end) => {
+                format!("Queried {} flags {}..{}.", kind.name(), start, end)
+            }
+            CommandLineCommand::Warp(stage_id, event_num, _) => {
+                format!("Warped to stage {} with event {}.", stage_id, event_num)
+            }
+            CommandLineCommand::KillNPC(id) => format!("Killed NPC {}.", id),
+            CommandLineCommand::KillNPCType(npc_type) => format!("Killed all NPCs of type {}.", npc_type),
+            CommandLineCommand::RemoveNPC(id) => format!("Removed NPC {}.", id),
+            CommandLineCommand::RemoveNPCType(npc_type) => format!("Removed all NPCs of type {}.", npc_type),
+            CommandLineCommand::Toggle(toggle) => format!("Toggled {:?}.", toggle),
+            CommandLineCommand::SetDifficulty(difficulty) => format!("Set difficulty to {:?}.", difficulty),
+            CommandLineCommand::PlaySong(song_id) => format!("Playing song {}.", song_id),
+            CommandLineCommand::PlaySFX(sfx_id) => format!("Played sound effect {}.", sfx_id),
+            CommandLineCommand::SpawnBoss(boss_type) => format!("Spawned boss type {}.", boss_type),
         }
     }
 }
 
+fn check_flag_range(state: &SharedGameState, kind: FlagKind, end: u16) -> GameResult {
+    if end as usize >= kind.len(state) {
+        return Err(CommandLineError(format!("{} flag {} is out of range", kind.name(), end)));
+    }
+
+    Ok(())
+}
+
+fn read_macro(ctx: &mut Context, path: &str) -> GameResult<Vec<String>> {
+    let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+
+    let mut file = match filesystem::user_open(ctx, &path) {
+        Ok(file) => file,
+        Err(_) => filesystem::open(ctx, &path)
+            .map_err(|_| CommandLineError(format!("Macro file {} could not be opened", path)))?,
+    };
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+
+    Ok(contents.lines().map(|l| l.to_string()).collect())
+}
+
 pub struct CommandLineParser {
-    command_history: Vec<CommandLineCommand>,
+    command_history: Vec<String>,
     cursor: usize,
+    history_loaded: bool,
+    names: Option<NameTables>,
+    names_generation: u32,
     pub last_feedback: String,
     pub last_feedback_color: [f32; 4],
     pub buffer: String,
@@ -321,44 +965,285 @@ impl CommandLineParser {
     pub fn new() -> CommandLineParser {
         CommandLineParser {
             command_history: Vec::new(),
-            last_feedback: "Awaiting command.".to_string(),
+            last_feedback: "Awaiting command. Type /help for a list of commands.".to_string(),
             last_feedback_color: [1.0, 1.0, 1.0, 1.0],
             cursor: 0,
+            history_loaded: false,
+            names: None,
+            names_generation: 0,
             buffer: String::new(),
         }
     }
 
-    pub fn push(&mut self, command: String) -> Option<CommandLineCommand> {
-        let components = command.split_whitespace().collect::<Vec<&str>>();
-        let command = CommandLineCommand::from_components(components);
+    fn load_names(&mut self, state: &SharedGameState) {
+        // mods, locale or constants may have changed the names since the tables were built
+        if self.names.is_none() || self.names_generation != state.resources_generation {
+            self.names = Some(NameTables::new(state));
+            self.names_generation = state.resources_generation;
+        }
+    }
+
+    pub fn push(&mut self, command: String, state: &SharedGameState) -> Result<CommandLineCommand, String> {
+        self.load_names(state);
+        self.push_line(command)
+    }
+
+    fn push_line(&mut self, command: String) -> Result<CommandLineCommand, String> {
+        let line = command.trim().to_string();
+        let result = CommandLineCommand::parse(&line, self.names.get_or_insert_with(NameTables::default));
+
+        if !line.is_empty() && self.command_history.last() != Some(&line) {
+            self.command_history.push(line);
+
+            if self.command_history.len() > HISTORY_MAX_LEN {
+                self.command_history.remove(0);
+            }
+        }
+        self.cursor = self.command_history.len();
+
+        result
+    }
+
+    /// Loads the command history from the user directory, does nothing if it was loaded already.
+    pub fn load_history(&mut self, ctx: &Context) {
+        if self.history_loaded {
+            return;
+        }
+        self.history_loaded = true;
 
-        match command {
-            Some(command) => {
-                self.command_history.push(command.clone());
-                self.cursor = self.command_history.len() - 1;
+        if let Ok(mut file) = filesystem::user_open(ctx, HISTORY_FILE) {
+            let mut contents = String::new();
+            if file.read_to_string(&mut contents).is_ok() {
+                let mut history: Vec<String> =
+                    contents.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()).collect();
+                history.extend(self.command_history.drain(..));
 
-                Some(command)
+                let len = history.len();
+                self.command_history = history.split_off(len.saturating_sub(HISTORY_MAX_LEN));
+                self.cursor = self.command_history.len();
             }
-            None => None,
         }
     }
 
-    #[allow(dead_code)]
-    pub fn traverse(&mut self, delta: i16) -> Option<&CommandLineCommand> {
+    pub fn save_history(&self, ctx: &Context) -> GameResult {
+        let mut file = filesystem::user_create(ctx, HISTORY_FILE)?;
+
+        for line in self.command_history.iter() {
+            file.write_all(line.as_bytes())?;
+            file.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the history cursor by `delta` entries, returns the line at new position
+    /// or an empty string if moved past the most recent entry.
+    pub fn traverse(&mut self, delta: i16) -> Option<&str> {
         if self.command_history.is_empty() {
             return None;
         }
 
-        let command = self.command_history.get(self.cursor);
+        let cursor = (self.cursor as isize + delta as isize).clamp(0, self.command_history.len() as isize) as usize;
+        self.cursor = cursor;
+
+        Some(self.command_history.get(cursor).map(|s| s.as_str()).unwrap_or(""))
+    }
+
+    /// Completes the last word of given line, returns the completed line if anything changed.
+    /// If the completion is ambiguous, the candidates are listed in the feedback line.
+    pub fn complete(&mut self, line: &str, state: &SharedGameState) -> Option<String> {
+        self.load_names(state);
+        self.complete_line(line)
+    }
+
+    fn complete_line(&mut self, line: &str) -> Option<String> {
+        let (head, word) = match line.rfind(char::is_whitespace) {
+            Some(pos) => (&line[..pos + 1], &line[pos + 1..]),
+            None => ("", line),
+        };
 
-        if delta == -1 && self.cursor == 0 {
-            self.cursor = self.command_history.len() - 1;
-        } else if delta == 1 && self.cursor == self.command_history.len() - 1 {
-            self.cursor = 0;
+        let candidates: Vec<String> = if head.is_empty() {
+            let word = word.strip_prefix('/').unwrap_or(word);
+            COMMANDS.iter().filter(|c| c.name.starts_with(word)).map(|c| format!("/{}", c.name)).collect()
         } else {
-            self.cursor = (self.cursor as i16 + delta) as usize;
+            let mut tokens = head.split_whitespace();
+            let name = tokens.next().unwrap_or("");
+            let spec = find_command(name.strip_prefix('/').unwrap_or(name))?;
+
+            if name.strip_prefix('/').unwrap_or(name) == "help" {
+                COMMANDS.iter().filter(|c| c.name.starts_with(word)).map(|c| c.name.to_string()).collect()
+            } else {
+                let arg = spec.args.get(tokens.count())?;
+                let word = word.to_lowercase();
+
+                let names = self.names.get_or_insert_with(NameTables::default);
+                names.candidates(arg.arg_type).into_iter().filter(|name| name.starts_with(&word)).collect()
+            }
+        };
+
+        match candidates.len() {
+            0 => None,
+            1 => Some(format!("{}{} ", head, candidates[0])),
+            _ => {
+                let mut prefix = candidates[0].clone();
+                for candidate in candidates.iter().skip(1) {
+                    let common = prefix.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).count();
+                    prefix = prefix.chars().take(common).collect();
+                }
+
+                self.last_feedback = candidates.join(" ");
+                self.last_feedback_color = [1.0, 1.0, 1.0, 1.0];
+
+                if prefix.len() > word.len() {
+                    Some(format!("{}{}", head, prefix))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> NameTables {
+        NameTables {
+            items: vec![(1, "arthurs_key".to_string()), (2, "map_system".to_string())],
+            weapons: vec![(2, "polar_star".to_string())],
+            npcs: vec![(1, "experience".to_string())],
+            bosses: vec![(1, "omega".to_string())],
+            stages: vec![(12, "mimi".to_string())],
+            songs: vec![(1, "egg".to_string())],
         }
+    }
+
+    fn parser() -> CommandLineParser {
+        let mut parser = CommandLineParser::new();
+        parser.names = Some(names());
+        parser
+    }
+
+    #[test]
+    fn test_to_identifier() {
+        assert_eq!(to_identifier("=Arthur's Key=\nA key with a rabbit on it.").as_deref(), Some("arthurs_key"));
+        assert_eq!(to_identifier("\n  Booster v0.8 \n").as_deref(), Some("booster_v0_8"));
+        assert_eq!(to_identifier("= =\n"), None);
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        let names = names();
+
+        assert!(matches!(
+            CommandLineCommand::parse("/add_item arthurs_key", &names),
+            Ok(CommandLineCommand::AddItem(1))
+        ));
+        assert!(matches!(CommandLineCommand::parse("add_item 7", &names), Ok(CommandLineCommand::AddItem(7))));
+        assert!(matches!(
+            CommandLineCommand::parse("/add_weapon Polar_Star 50", &names),
+            Ok(CommandLineCommand::AddWeapon(2, 50))
+        ));
+        assert!(matches!(
+            CommandLineCommand::parse("/tsc <MSGhello\\n<END", &names),
+            Ok(CommandLineCommand::TSC(script)) if script == "<MSGhello\n<END"
+        ));
+        assert!(matches!(CommandLineCommand::parse("<FL+0001", &names), Ok(CommandLineCommand::TSC(_))));
+        assert!(matches!(CommandLineCommand::parse("/help", &names), Ok(CommandLineCommand::Help(None))));
 
-        command
+        assert!(CommandLineCommand::parse("/add_item", &names).unwrap_err().starts_with("Missing argument <item>"));
+        assert!(CommandLineCommand::parse("/add_item unknown", &names).unwrap_err().starts_with("Invalid value"));
+        assert!(CommandLineCommand::parse("/refill_hp now", &names).unwrap_err().starts_with("Too many arguments"));
+        assert!(CommandLineCommand::parse("/fly", &names).unwrap_err().starts_with("Unknown command /fly"));
+        assert!(CommandLineCommand::parse("   ", &names).is_err());
+    }
+
+    #[test]
+    fn test_parse_debug_commands() {
+        let names = names();
+
+        assert!(matches!(
+            CommandLineCommand::parse("/set_flag game 10 20", &names),
+            Ok(CommandLineCommand::SetFlag(FlagKind::Game, 10, 20))
+        ));
+        assert!(matches!(
+            CommandLineCommand::parse("/get_flag SKIP 5", &names),
+            Ok(CommandLineCommand::GetFlag(FlagKind::Skip, 5, 5))
+        ));
+        assert!(CommandLineCommand::parse("/clear_flag map 20 10", &names).is_err());
+        assert!(CommandLineCommand::parse("/set_flag world 1", &names).is_err());
+
+        assert!(matches!(CommandLineCommand::parse("/warp mimi", &names), Ok(CommandLineCommand::Warp(12, 0, None))));
+        assert!(matches!(
+            CommandLineCommand::parse("/warp 12 90 4 5", &names),
+            Ok(CommandLineCommand::Warp(12, 90, Some((4, 5))))
+        ));
+        assert!(CommandLineCommand::parse("/warp mimi 90 4", &names).unwrap_err().starts_with("Missing argument <y>"));
+
+        assert!(matches!(
+            CommandLineCommand::parse("/toggle noclip", &names),
+            Ok(CommandLineCommand::Toggle(DebugToggle::Noclip))
+        ));
+        assert!(matches!(
+            CommandLineCommand::parse("/set_difficulty hard", &names),
+            Ok(CommandLineCommand::SetDifficulty(GameDifficulty::Hard))
+        ));
+        assert!(matches!(CommandLineCommand::parse("/play_song egg", &names), Ok(CommandLineCommand::PlaySong(1))));
+        assert!(matches!(CommandLineCommand::parse("/spawn_boss omega", &names), Ok(CommandLineCommand::SpawnBoss(1))));
+        assert!(matches!(
+            CommandLineCommand::parse("/kill_npc_type experience", &names),
+            Ok(CommandLineCommand::KillNPCType(1))
+        ));
+
+        assert!(matches!(CommandLineCommand::parse("/play_sfx 255", &names), Ok(CommandLineCommand::PlaySFX(255))));
+        assert!(CommandLineCommand::parse("/play_sfx 256", &names)
+            .unwrap_err()
+            .starts_with("Invalid sound effect id 256"));
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut parser = parser();
+
+        assert_eq!(parser.complete_line("/refill_h").as_deref(), Some("/refill_hp "));
+        assert_eq!(parser.complete_line("/add_item ar").as_deref(), Some("/add_item arthurs_key "));
+        assert_eq!(parser.complete_line("/help remove_w").as_deref(), Some("/help remove_weapon "));
+        assert_eq!(parser.complete_line("/add_item x"), None);
+        assert_eq!(parser.complete_line("/refill_hp "), None);
+        assert_eq!(parser.complete_line("/set_flag sk").as_deref(), Some("/set_flag skip "));
+        assert_eq!(parser.complete_line("/toggle g").as_deref(), Some("/toggle god_mode "));
+        assert_eq!(parser.complete_line("/warp m").as_deref(), Some("/warp mimi "));
+        assert_eq!(parser.complete_line("/spawn_boss o").as_deref(), Some("/spawn_boss omega "));
+        assert_eq!(parser.complete_line("/play_song e").as_deref(), Some("/play_song egg "));
+        assert_eq!(parser.complete_line("/set_flag game 1"), None);
+
+        // ambiguous completions extend the common prefix and list candidates
+        assert_eq!(parser.complete_line("/add_w").as_deref(), Some("/add_weapon"));
+        assert_eq!(parser.last_feedback, "/add_weapon /add_weapon_ammo");
+    }
+
+    #[test]
+    fn test_history_navigation() {
+        let mut parser = parser();
+        assert_eq!(parser.traverse(-1), None);
+
+        let _ = parser.push_line("/refill_hp".to_string());
+        let _ = parser.push_line("/refill_hp".to_string());
+        let _ = parser.push_line("/unknown".to_string());
+
+        assert_eq!(parser.command_history, vec!["/refill_hp", "/unknown"]);
+        assert_eq!(parser.traverse(-1), Some("/unknown"));
+        assert_eq!(parser.traverse(-1), Some("/refill_hp"));
+        assert_eq!(parser.traverse(-1), Some("/refill_hp"));
+        assert_eq!(parser.traverse(1), Some("/unknown"));
+        assert_eq!(parser.traverse(1), Some(""));
+        assert_eq!(parser.traverse(1), Some(""));
+
+        for i in 0..HISTORY_MAX_LEN + 5 {
+            let _ = parser.push_line(format!("/add_xp {}", i));
+        }
+        assert_eq!(parser.command_history.len(), HISTORY_MAX_LEN);
+        assert_eq!(parser.command_history.last().map(|s| s.as_str()), Some("/add_xp 104"));
     }
 }
@@ -1,8 +1,14 @@
-use imgui::{CollapsingHeader, Condition, ImStr, ImString, Slider, Window};
+use std::str::FromStr;
+
+use imgui::{
+    ChildWindow, CollapsingHeader, Condition, HistoryDirection, ImStr, ImString, InputTextCallback,
+    InputTextCallbackHandler, Slider, TextCallbackData, Window,
+};
 use itertools::Itertools;
 
 use crate::framework::context::Context;
 use crate::framework::error::GameResult;
+use crate::game::scripting::tsc::opcodes::TSCOpCode;
 use crate::game::scripting::tsc::text_script::TextScriptExecutionState;
 use crate::game::shared_game_state::SharedGameState;
 use crate::scene::game_scene::GameScene;
@@ -10,6 +16,7 @@ use crate::scene::game_scene::GameScene;
 use self::command_line::CommandLineParser;
 
 pub mod command_line;
+pub mod remote_control;
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 #[repr(u8)]
@@ -20,12 +27,42 @@ pub enum ScriptType {
     StageSelect,
 }
 
+struct CommandLineCallbackHandler<'a> {
+    parser: &'a mut CommandLineParser,
+    state: &'a SharedGameState,
+}
+
+impl InputTextCallbackHandler for CommandLineCallbackHandler<'_> {
+    fn on_completion(&mut self, mut data: TextCallbackData) {
+        if let Some(line) = self.parser.complete(data.str(), self.state) {
+            data.clear();
+            data.push_str(&line);
+        }
+    }
+
+    fn on_history(&mut self, dir: HistoryDirection, mut data: TextCallbackData) {
+        let delta = match dir {
+            HistoryDirection::Up => -1,
+            HistoryDirection::Down => 1,
+        };
+
+        if let Some(line) = self.parser.traverse(delta) {
+            let line = line.to_string();
+            data.clear();
+            data.push_str(&line);
+        }
+    }
+}
+
 pub struct LiveDebugger {
     map_selector_visible: bool,
     events_visible: bool,
     flags_visible: bool,
     npc_inspector_visible: bool,
     hotkey_list_visible: bool,
+    locale_visible: bool,
+    #[cfg(feature = "scripting-lua")]
+    lua_visible: bool,
     command_line_parser: CommandLineParser,
     command_line_focused: bool,
     last_stage_id: usize,
@@ -35,6 +72,8 @@ pub struct LiveDebugger {
     event_ids: Vec<(ScriptType, u16)>,
     selected_event: i32,
     text_windows: Vec<(u32, ImString, ImString)>,
+    breakpoint_input: String,
+    inspected_flag: i32,
     error: Option<ImString>,
 }
 
@@ -46,6 +85,9 @@ impl LiveDebugger {
             flags_visible: false,
             npc_inspector_visible: false,
             hotkey_list_visible: false,
+            locale_visible: false,
+            #[cfg(feature = "scripting-lua")]
+            lua_visible: false,
             command_line_parser: CommandLineParser::new(),
             command_line_focused: false,
             last_stage_id: usize::MAX,
@@ -55,6 +97,8 @@ impl LiveDebugger {
             event_ids: Vec::new(),
             selected_event: -1,
             text_windows: Vec::new(),
+            breakpoint_input: String::new(),
+            inspected_flag: 0,
             error: None,
         }
     }
@@ -73,6 +117,8 @@ impl LiveDebugger {
         }
 
         if state.command_line {
+            self.command_line_parser.load_history(ctx);
+
             let width = state.screen_size.0;
             let height = 85.0;
             let x = 0.0 as f32;
@@ -94,7 +140,14 @@ impl LiveDebugger {
                         self.command_line_focused = true;
                     }
 
-                    ui.input_text("", &mut self.command_line_parser.buffer).build();
+                    let mut buffer = std::mem::take(&mut self.command_line_parser.buffer);
+                    ui.input_text("", &mut buffer)
+                        .callback(
+                            InputTextCallback::HISTORY | InputTextCallback::COMPLETION,
+                            CommandLineCallbackHandler { parser: &mut self.command_line_parser, state },
+                        )
+                        .build();
+                    self.command_line_parser.buffer = buffer;
 
                     iw.pop(ui);
 
@@ -107,10 +160,16 @@ impl LiveDebugger {
                     ui.same_line();
                     if ui.is_key_released(imgui::Key::Enter) || ui.button("Execute") {
                         log::info!("Executing command: {}", self.command_line_parser.buffer);
-                        match self.command_line_parser.push(self.command_line_parser.buffer.clone()) {
-                            Some(mut command) => match command.execute(game_scene, state) {
-                                Ok(()) => {
-                                    self.command_line_parser.last_feedback = command.feedback_string();
+                        let line = std::mem::take(&mut self.command_line_parser.buffer);
+                        let result = self.command_line_parser.push(line, state);
+                        if let Err(e) = self.command_line_parser.save_history(ctx) {
+                            log::warn!("Failed to save command history: {}", e);
+                        }
+
+                        match result {
+                            Ok(mut command) => match command.execute(game_scene, state, ctx) {
+                                Ok(feedback) => {
+                                    self.command_line_parser.last_feedback = feedback;
                                     self.command_line_parser.last_feedback_color = [0.0, 1.0, 0.0, 1.0];
                                     state.sound_manager.play_sfx(5);
                                 }
@@ -120,8 +179,8 @@ impl LiveDebugger {
                                     state.sound_manager.play_sfx(12);
                                 }
                             },
-                            None => {
-                                self.command_line_parser.last_feedback = "Invalid command".to_string();
+                            Err(e) => {
+                                self.command_line_parser.last_feedback = e;
                                 self.command_line_parser.last_feedback_color = [1.0, 0.0, 0.0, 1.0];
                                 state.sound_manager.play_sfx(12);
                             }
@@ -201,11 +260,11 @@ impl LiveDebugger {
                 #[cfg(feature = "scripting-lua")]
                 {
                     ui.same_line();
-                    if ui.button("Reload Lua Scripts") {
-                        if let Err(err) = state.lua.reload_scripts(ctx) {
-                            log::error!("Error reloading scripts: {:?}", err);
-                            self.error = Some(ImString::new(err.to_string()));
-                        }
+                    let error_count = state.lua.errors().len();
+                    let label =
+                        if error_count > 0 { format!("Lua ({} errors)", error_count) } else { "Lua".to_owned() };
+                    if ui.button(label) {
+                        self.lua_visible = !self.lua_visible;
                     }
                 }
 
@@ -240,6 +299,18 @@ impl LiveDebugger {
                     state.command_line = !state.command_line;
                 }
 
+                ui.same_line();
+                if ui.button("Locale") {
+                    self.locale_visible = !self.locale_visible;
+                }
+
+                ui.same_line();
+                if ui.button("Dump Overridable Constants") {
+                    if let Err(err) = state.constants.dump_overridable_json(ctx) {
+                        self.error = Some(ImString::new(format!("Failed to dump constants: {}", err)));
+                    }
+                }
+
                 ui.checkbox("noclip", &mut state.settings.noclip);
                 ui.same_line();
                 ui.checkbox("more rust", &mut state.more_rust);
@@ -310,7 +381,7 @@ impl LiveDebugger {
             Window::new("TSC Scripts")
                 .resizable(false)
                 .position([80.0, 80.0], Condition::Appearing)
-                .size([300.0, 320.0], Condition::Appearing)
+                .size([300.0, 560.0], Condition::Appearing)
                 .build(ui, || {
                     if self.events.is_empty() {
                         self.event_ids.clear();
@@ -392,6 +463,16 @@ impl LiveDebugger {
                             }
                         }
                     }
+
+                    ui.same_line();
+                    if ui.button("Break") {
+                        if let Some((_, event_num)) = self.event_ids.get(self.selected_event as usize) {
+                            state.textscript_vm.debugger.event_breakpoints.insert(*event_num);
+                        }
+                    }
+
+                    ui.separator();
+                    self.draw_tsc_debugger(state, ui);
                 });
         }
 
@@ -481,6 +562,108 @@ impl LiveDebugger {
                 });
         }
 
+        #[cfg(feature = "scripting-lua")]
+        if self.lua_visible {
+            Window::new("Lua")
+                .position([80.0, 80.0], Condition::FirstUseEver)
+                .size([400.0, 300.0], Condition::FirstUseEver)
+                .build(ui, || {
+                    if ui.button("Reload Scripts") {
+                        if let Err(err) = state.reload_lua_scripts(ctx, true) {
+                            log::error!("Error reloading scripts: {:?}", err);
+                            self.error = Some(ImString::new(err.to_string()));
+                        }
+                    }
+
+                    ui.same_line();
+                    if ui.button("Restart Scripts") {
+                        if let Err(err) = state.reload_lua_scripts(ctx, false) {
+                            log::error!("Error restarting scripts: {:?}", err);
+                            self.error = Some(ImString::new(err.to_string()));
+                        }
+                    }
+
+                    ui.same_line();
+                    if ui.button("Clear Errors") {
+                        state.lua.clear_errors();
+                    }
+
+                    ui.text(format!(
+                        "Limits: {} instructions per tick, {} KB of memory shared by all scripts",
+                        crate::game::scripting::lua::INSTRUCTION_LIMIT_PER_TICK,
+                        crate::game::scripting::lua::GLOBAL_MEMORY_LIMIT_KB
+                    ));
+
+                    if state.settings.debug_mode {
+                        ui.text("Modified scripts are reloaded automatically.");
+                    }
+
+                    if CollapsingHeader::new("Loaded scripts").default_open(true).build(ui) {
+                        if state.lua.scripts().is_empty() {
+                            ui.text_disabled("No scripts loaded.");
+                        }
+
+                        for script in state.lua.scripts() {
+                            let label =
+                                if script.user { format!("{} (user)", script.path) } else { script.path.clone() };
+                            ui.bullet_text(label);
+
+                            if let Some(error) = state.lua.last_error_of(script) {
+                                ui.indent();
+                                ui.text_colored([1.0, 0.4, 0.4, 1.0], error);
+                                ui.unindent();
+                            }
+                        }
+                    }
+
+                    ChildWindow::new("lua_errors").horizontal_scrollbar(true).build(ui, || {
+                        if state.lua.errors().is_empty() {
+                            ui.text_disabled("No errors.");
+                        }
+
+                        for error in state.lua.errors().iter().rev() {
+                            ui.text_colored([1.0, 0.4, 0.4, 1.0], error);
+                        }
+                    });
+                });
+        }
+
+        if self.locale_visible {
+            Window::new("Locale")
+                .position([80.0, 80.0], Condition::FirstUseEver)
+                .size([400.0, 300.0], Condition::FirstUseEver)
+                .build(ui, || {
+                    ui.text(format!("Locale: {} ({})", state.loc.name, state.loc.chain().join(" > ")));
+
+                    let reference = state.constants.locales.iter().find(|l| l.code == crate::i18n::REFERENCE_LOCALE);
+                    let reference = match reference {
+                        Some(reference) => reference,
+                        None => {
+                            ui.text_disabled("The reference locale is not loaded.");
+                            return;
+                        }
+                    };
+
+                    let missing = state.loc.missing_keys(reference);
+                    let unused = state.loc.unused_keys(reference);
+
+                    ChildWindow::new("locale_keys").horizontal_scrollbar(true).build(ui, || {
+                        if CollapsingHeader::new(&ImString::from(format!("Missing keys ({})", missing.len()))).build(ui)
+                        {
+                            for key in missing.iter() {
+                                ui.bullet_text(key);
+                            }
+                        }
+
+                        if CollapsingHeader::new(&ImString::from(format!("Unused keys ({})", unused.len()))).build(ui) {
+                            for key in unused.iter() {
+                                ui.bullet_text(key);
+                            }
+                        }
+                    });
+                });
+        }
+
         if self.hotkey_list_visible {
             Window::new("Hotkeys")
                 .position([400.0, 5.0], Condition::FirstUseEver)
@@ -557,6 +740,134 @@ impl LiveDebugger {
         Ok(())
     }
 
+    fn draw_tsc_debugger(&mut self, state: &mut SharedGameState, ui: &imgui::Ui) {
+        let depth = state.textscript_vm.stack.len();
+        let debugger = &mut state.textscript_vm.debugger;
+        let halted_at = debugger.halted_at();
+
+        if let Some((event, ip)) = halted_at {
+            ui.text_colored([1.0, 1.0, 0.0, 1.0], format!("Halted at #{:04}, offset {}", event, ip));
+
+            if ui.button("Continue") {
+                debugger.resume();
+            }
+            ui.same_line();
+            if ui.button("Step Into") {
+                debugger.step_into();
+            }
+            ui.same_line();
+            if ui.button("Step Over") {
+                let scripts = state.textscript_vm.scripts.borrow();
+                let bytecode = scripts.find_script(state.textscript_vm.mode, event).map_or(&[][..], |b| b.as_slice());
+                debugger.step_over(depth, bytecode);
+            }
+        } else if ui.button("Pause") {
+            debugger.pause();
+        }
+
+        let iw = ui.push_item_width(150.0);
+        ui.input_text("##breakpoint", &mut self.breakpoint_input).build();
+        iw.pop(ui);
+        ui.same_line();
+        if ui.button("Add Breakpoint") {
+            let input = self.breakpoint_input.trim().trim_start_matches('#');
+
+            if let Ok(event_num) = input.parse::<u16>() {
+                debugger.event_breakpoints.insert(event_num);
+                self.breakpoint_input.clear();
+            } else if let Ok(op) = TSCOpCode::from_str(input) {
+                debugger.opcode_breakpoints.insert(op);
+                self.breakpoint_input.clear();
+            } else {
+                self.error =
+                    Some(ImString::new(format!("Invalid breakpoint, expected event number or opcode: {}", input)));
+            }
+        }
+
+        let mut removed_event = None;
+        for event_num in debugger.event_breakpoints.iter() {
+            if ui.small_button(format!("x##ev{}", event_num)) {
+                removed_event = Some(*event_num);
+            }
+            ui.same_line();
+            ui.text(format!("Event #{:04}", event_num));
+        }
+        if let Some(event_num) = removed_event {
+            debugger.event_breakpoints.remove(&event_num);
+        }
+
+        let mut removed_op = None;
+        for op in debugger.opcode_breakpoints.iter() {
+            if ui.small_button(format!("x##op{:?}", op)) {
+                removed_op = Some(*op);
+            }
+            ui.same_line();
+            ui.text(format!("Opcode {:?}", op));
+        }
+        if let Some(op) = removed_op {
+            debugger.opcode_breakpoints.remove(&op);
+        }
+
+        let (event, ip) = match halted_at {
+            Some(pos) => pos,
+            None => return,
+        };
+
+        if CollapsingHeader::new("Source").default_open(true).build(ui) {
+            let scripts = state.textscript_vm.scripts.borrow();
+
+            let script = scripts.find_text_script(state.textscript_vm.mode, event);
+
+            match script.and_then(|script| script.source_around(event, ip, 8)) {
+                Some((lines, current)) => {
+                    ChildWindow::new("tsc_source").size([0.0, 160.0]).horizontal_scrollbar(true).build(ui, || {
+                        for (i, line) in lines.iter().enumerate() {
+                            if i == current {
+                                ui.text_colored([1.0, 1.0, 0.0, 1.0], format!("> {}", line));
+                                ui.set_scroll_here_y();
+                            } else {
+                                ui.text(format!("  {}", line));
+                            }
+                        }
+                    });
+                }
+                None if script.map_or(false, |script| script.source.is_empty()) => {
+                    ui.text("Source is not kept outside of debug mode, it will be available after reloading the stage.")
+                }
+                None => ui.text("Event source not found."),
+            }
+        }
+
+        if CollapsingHeader::new("VM state").default_open(false).build(ui) {
+            ui.text(format!("Stack depth: {}", depth));
+            ui.text(format!("Numbers: {:?}", state.textscript_vm.numbers));
+            ui.text(format!("Face: {}, item: {}", state.textscript_vm.face, state.textscript_vm.item));
+
+            ui.input_int("Flag", &mut self.inspected_flag).build();
+            self.inspected_flag = self.inspected_flag.clamp(0, state.game_flags.len() as i32 - 1);
+            let id = self.inspected_flag as usize;
+
+            let mut value = state.get_flag(id);
+            if ui.checkbox("Game flag", &mut value) {
+                state.set_flag(id, value);
+            }
+
+            if id < state.skip_flags.len() {
+                let mut value = state.get_skip_flag(id);
+                if ui.checkbox("Skip flag", &mut value) {
+                    state.set_skip_flag(id, value);
+                }
+            }
+
+            if id < state.map_flags.len() {
+                let mut value = state.get_map_flag(id);
+                if ui.checkbox("Map flag", &mut value) {
+                    state.set_map_flag(id, value);
+                }
+            }
+        }
+    }
+
     fn draw_left_label(&mut self, ui: &imgui::Ui, text: &str) {
         self.draw_text_with_top_padding(ui, text, 6.0);
     }
@@ -0,0 +1,396 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddrV4, TcpListener, TcpStream};
+
+use serde_json::{json, Value};
+
+use crate::framework::context::Context;
+use crate::framework::error::GameResult;
+use crate::game::scripting::tsc::text_script::TextScriptExecutionState;
+use crate::game::shared_game_state::SharedGameState;
+use crate::live_debugger::command_line::{CommandLineCommand, CommandLineParser};
+use crate::scene::game_scene::GameScene;
+
+pub const DEFAULT_REMOTE_CONTROL_PORT: u16 = 14077;
+
+/// Maximum number of bytes buffered for a single client before it gets disconnected.
+const MAX_CLIENT_BUFFER: usize = 64 * 1024;
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RemoteRequest {
+    /// Executes a structured debugger command, eg. `{"type":"command","command":{"add_item":5}}`.
+    Command { command: CommandLineCommand },
+    /// Executes a command line string, as if typed into the debugger console.
+    CommandLine { line: String },
+    /// Returns the state of player 1.
+    Player,
+    /// Returns values of a range of game flags, all of them by default.
+    Flags {
+        #[serde(default)]
+        start: usize,
+        #[serde(default = "default_flag_count")]
+        count: usize,
+    },
+    /// Returns a list of alive NPCs.
+    Npcs,
+}
+
+fn default_flag_count() -> usize {
+    usize::MAX
+}
+
+/// Clamps a requested range of flags to the flags which exist.
+fn flag_range(start: usize, count: usize, flag_count: usize) -> std::ops::Range<usize> {
+    start.min(flag_count)..start.saturating_add(count).min(flag_count)
+}
+
+#[derive(serde::Deserialize)]
+struct RemoteMessage {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(flatten)]
+    request: RemoteRequest,
+}
+
+struct RemoteClient {
+    stream: TcpStream,
+    read_buffer: Vec<u8>,
+    write_buffer: Vec<u8>,
+    disconnected: bool,
+}
+
+impl RemoteClient {
+    fn new(stream: TcpStream) -> RemoteClient {
+        RemoteClient { stream, read_buffer: Vec::new(), write_buffer: Vec::new(), disconnected: false }
+    }
+
+    fn send(&mut self, value: &Value) {
+        self.write_buffer.extend_from_slice(value.to_string().as_bytes());
+        self.write_buffer.push(b'\n');
+
+        if self.write_buffer.len() > MAX_CLIENT_BUFFER {
+            log::warn!("Remote control client is not reading responses, disconnecting.");
+            self.disconnected = true;
+        }
+    }
+
+    fn receive(&mut self) -> Vec<String> {
+        let mut buf = [0u8; 4096];
+
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.disconnected = true;
+                    break;
+                }
+                Ok(n) => self.read_buffer.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.disconnected = true;
+                    break;
+                }
+            }
+        }
+
+        if self.read_buffer.len() > MAX_CLIENT_BUFFER {
+            log::warn!("Remote control client sent an overly long line, disconnecting.");
+            self.disconnected = true;
+        }
+
+        let mut lines = Vec::new();
+        while let Some(pos) = self.read_buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.read_buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+
+        lines
+    }
+
+    fn flush(&mut self) {
+        while !self.write_buffer.is_empty() {
+            match self.stream.write(&self.write_buffer) {
+                Ok(0) => {
+                    self.disconnected = true;
+                    break;
+                }
+                Ok(n) => {
+                    self.write_buffer.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.disconnected = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// A local TCP endpoint accepting newline-delimited JSON requests, used for tooling and test automation.
+///
+/// Only binds to localhost and is disabled unless the game is started with `--remote-control[=port]`.
+/// It's ticked by the main loop, so it keeps responding in menus, where requests needing a stage fail.
+pub struct RemoteControl {
+    listener: TcpListener,
+    clients: Vec<RemoteClient>,
+    parser: CommandLineParser,
+    last_stage_id: Option<usize>,
+    last_event: Option<u16>,
+    last_life: u16,
+}
+
+impl RemoteControl {
+    pub fn bind(port: u16) -> GameResult<RemoteControl> {
+        let listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+
+        log::info!("Remote control listening on 127.0.0.1:{}", port);
+
+        Ok(RemoteControl {
+            listener,
+            clients: Vec::new(),
+            parser: CommandLineParser::new(),
+            last_stage_id: None,
+            last_event: None,
+            last_life: 0,
+        })
+    }
+
+    pub fn tick(&mut self, state: &mut SharedGameState, mut game_scene: Option<&mut GameScene>, ctx: &mut Context) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    if stream.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+
+                    log::info!("Remote control client connected: {}", addr);
+                    self.clients.push(RemoteClient::new(stream));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("Remote control accept error: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if let Some(game_scene) = game_scene.as_deref() {
+            self.poll_events(state, game_scene);
+        }
+
+        for idx in 0..self.clients.len() {
+            let lines = self.clients[idx].receive();
+
+            for line in lines {
+                let response = self.handle_line(&line, state, game_scene.as_deref_mut(), ctx);
+                self.clients[idx].send(&response);
+            }
+        }
+
+        for client in self.clients.iter_mut() {
+            client.flush();
+        }
+
+        self.clients.retain(|c| !c.disconnected);
+    }
+
+    fn broadcast(&mut self, value: Value) {
+        for client in self.clients.iter_mut() {
+            client.send(&value);
+        }
+    }
+
+    fn poll_events(&mut self, state: &SharedGameState, game_scene: &GameScene) {
+        if self.last_stage_id != Some(game_scene.stage_id) {
+            self.last_stage_id = Some(game_scene.stage_id);
+            self.last_event = None;
+            self.last_life = game_scene.player1.life;
+            self.broadcast(json!({
+                "event": "stage_change",
+                "stage_id": game_scene.stage_id,
+                "name": game_scene.stage.data.name,
+            }));
+        }
+
+        let current_event = match state.textscript_vm.state {
+            TextScriptExecutionState::MapSystem
+            | TextScriptExecutionState::LoadProfile
+            | TextScriptExecutionState::Reset => self.last_event,
+            _ => state.textscript_vm.current_event(),
+        };
+
+        if current_event != self.last_event {
+            if let Some(event) = self.last_event {
+                self.broadcast(json!({ "event": "script_end", "event_num": event }));
+            }
+
+            if let Some(event) = current_event {
+                self.broadcast(json!({ "event": "script_start", "event_num": event }));
+            }
+
+            self.last_event = current_event;
+        }
+
+        let life = game_scene.player1.life;
+        if life == 0 && self.last_life != 0 {
+            self.broadcast(json!({ "event": "death", "stage_id": game_scene.stage_id }));
+        }
+        self.last_life = life;
+    }
+
+    fn handle_line(
+        &mut self,
+        line: &str,
+        state: &mut SharedGameState,
+        game_scene: Option<&mut GameScene>,
+        ctx: &mut Context,
+    ) -> Value {
+        let message: RemoteMessage = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return json!({ "ok": false, "error": format!("Invalid request: {}", e) }),
+        };
+
+        let result = match (message.request, game_scene) {
+            (RemoteRequest::Flags { start, count }, _) => {
+                let flags: Vec<bool> =
+                    flag_range(start, count, state.game_flags.len()).map(|id| state.get_flag(id)).collect();
+
+                Ok(json!({ "start": start, "flags": flags }))
+            }
+            (_, None) => Err("Not in game.".to_owned()),
+            (RemoteRequest::Command { mut command }, Some(game_scene)) => {
+                match command.execute(game_scene, state, ctx) {
+                    Ok(feedback) => Ok(json!(feedback)),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            (RemoteRequest::CommandLine { line }, Some(game_scene)) => match self.parser.push(line, state) {
+                Ok(mut command) => match command.execute(game_scene, state, ctx) {
+                    Ok(feedback) => Ok(json!(feedback)),
+                    Err(e) => Err(e.to_string()),
+                },
+                Err(e) => Err(e),
+            },
+            (RemoteRequest::Player, Some(game_scene)) => {
+                let player = &game_scene.player1;
+                let inventory = &game_scene.inventory_player1;
+
+                Ok(json!({
+                    "x": player.x as f32 / 512.0,
+                    "y": player.y as f32 / 512.0,
+                    "vel_x": player.vel_x as f32 / 512.0,
+                    "vel_y": player.vel_y as f32 / 512.0,
+                    "life": player.life,
+                    "max_life": player.max_life,
+                    "direction": player.direction as usize,
+                    "equip": player.equip.0,
+                    "stage_id": game_scene.stage_id,
+                    "current_weapon": inventory.get_current_weapon().map(|w| w.wtype as u16),
+                }))
+            }
+            (RemoteRequest::Npcs, Some(game_scene)) => {
+                let npcs: Vec<Value> = game_scene
+                    .npc_list
+                    .iter_alive()
+                    .map(|npc| {
+                        json!({
+                            "id": npc.id,
+                            "npc_type": npc.npc_type,
+                            "x": npc.x as f32 / 512.0,
+                            "y": npc.y as f32 / 512.0,
+                            "life": npc.life,
+                            "action_num": npc.action_num,
+                            "event_num": npc.event_num,
+                            "flag_num": npc.flag_num,
+                        })
+                    })
+                    .collect();
+
+                Ok(json!(npcs))
+            }
+        };
+
+        let mut response = match result {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        };
+
+        if let Some(id) = message.id {
+            response["id"] = id;
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_range_is_clamped() {
+        assert_eq!(flag_range(0, default_flag_count(), 8000), 0..8000);
+        assert_eq!(flag_range(10, 5, 8000), 10..15);
+        assert_eq!(flag_range(7990, 100, 8000), 7990..8000);
+        assert_eq!(flag_range(9000, 10, 8000), 8000..8000);
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let message: RemoteMessage = serde_json::from_str(r#"{"id":3,"type":"flags","start":100}"#).unwrap();
+        assert_eq!(message.id, Some(json!(3)));
+        assert!(matches!(message.request, RemoteRequest::Flags { start: 100, count: usize::MAX }));
+
+        let message: RemoteMessage = serde_json::from_str(r#"{"type":"command_line","line":"/flag 5"}"#).unwrap();
+        assert!(message.id.is_none());
+        assert!(matches!(message.request, RemoteRequest::CommandLine { line } if line == "/flag 5"));
+
+        assert!(serde_json::from_str::<RemoteMessage>(r#"{"type":"unknown"}"#).is_err());
+    }
+
+    /// Polls the non-blocking client until it has received `count` lines or got disconnected,
+    /// failing after a generous deadline instead of relying on fixed delays.
+    fn receive_lines(client: &mut RemoteClient, count: usize) -> Vec<String> {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        let mut lines = Vec::new();
+
+        while lines.len() < count && !client.disconnected {
+            assert!(std::time::Instant::now() < deadline, "timed out waiting for {} lines, got {:?}", count, lines);
+
+            lines.extend(client.receive());
+            std::thread::yield_now();
+        }
+
+        lines
+    }
+
+    #[test]
+    fn test_client_splits_lines() {
+        let listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut client = RemoteClient::new(stream);
+
+        sender.write_all(b"{\"type\":\"player\"}\n\n  {\"type\":").unwrap();
+        sender.flush().unwrap();
+        assert_eq!(receive_lines(&mut client, 1), vec!["{\"type\":\"player\"}".to_owned()]);
+
+        sender.write_all(b"\"npcs\"}\n").unwrap();
+        sender.flush().unwrap();
+        assert_eq!(receive_lines(&mut client, 1), vec!["{\"type\":\"npcs\"}".to_owned()]);
+        assert!(!client.disconnected);
+
+        // nothing is left over once the sender is gone
+        drop(sender);
+        assert!(receive_lines(&mut client, usize::MAX).is_empty());
+        assert!(client.disconnected);
+    }
+}
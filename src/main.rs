@@ -4,7 +4,15 @@ use std::process::exit;
 
 fn main() {
     let args = std::env::args();
-    let mut options = doukutsu_rs::game::LaunchOptions { server_mode: false, editor: false };
+    let mut options = doukutsu_rs::game::LaunchOptions {
+        server_mode: false,
+        editor: false,
+        remote_control: None,
+        dump_constants: false,
+        extract_locale_keys: None,
+    };
+    #[cfg(feature = "scripting-lua")]
+    let mut script_tests: Option<doukutsu_rs::game::scripting::lua::test_runner::ScriptTestOptions> = None;
 
     for arg in args {
         if arg == "--server-mode" {
@@ -14,6 +22,65 @@ fn main() {
         if arg == "--editor" {
             options.editor = true;
         }
+
+        if arg == "--dump-constants" {
+            options.dump_constants = true;
+        }
+
+        if arg == "--extract-locale-keys" {
+            options.extract_locale_keys = Some(std::path::PathBuf::from("src"));
+        } else if let Some(src_dir) = arg.strip_prefix("--extract-locale-keys=") {
+            options.extract_locale_keys = Some(std::path::PathBuf::from(src_dir));
+        }
+
+        #[cfg(feature = "scripting-lua")]
+        {
+            use doukutsu_rs::game::scripting::lua::test_runner::{ScriptTestOptions, TestReportFormat};
+
+            let test_options = || ScriptTestOptions {
+                data_dir: std::path::PathBuf::from("data"),
+                mod_id: None,
+                stage_id: None,
+                format: TestReportFormat::Tap,
+            };
+
+            if arg == "--test-scripts" {
+                script_tests.get_or_insert_with(test_options);
+            } else if let Some(mod_id) = arg.strip_prefix("--test-scripts=") {
+                script_tests.get_or_insert_with(test_options).mod_id = Some(mod_id.to_owned());
+            } else if let Some(data_dir) = arg.strip_prefix("--test-data=") {
+                script_tests.get_or_insert_with(test_options).data_dir = std::path::PathBuf::from(data_dir);
+            } else if let Some(stage_id) = arg.strip_prefix("--test-stage=") {
+                match stage_id.parse::<usize>() {
+                    Ok(stage_id) => script_tests.get_or_insert_with(test_options).stage_id = Some(stage_id),
+                    Err(_) => {
+                        eprintln!("Invalid test stage: {}", stage_id);
+                        exit(1);
+                    }
+                }
+            } else if let Some(format) = arg.strip_prefix("--test-format=") {
+                script_tests.get_or_insert_with(test_options).format = match format {
+                    "tap" => TestReportFormat::Tap,
+                    "junit" => TestReportFormat::JUnit,
+                    _ => {
+                        eprintln!("Invalid test report format: {}, expected tap or junit.", format);
+                        exit(1);
+                    }
+                };
+            }
+        }
+
+        if arg == "--remote-control" {
+            options.remote_control = Some(doukutsu_rs::game::DEFAULT_REMOTE_CONTROL_PORT);
+        } else if let Some(port) = arg.strip_prefix("--remote-control=") {
+            match port.parse::<u16>() {
+                Ok(port) => options.remote_control = Some(port),
+                Err(_) => {
+                    eprintln!("Invalid remote control port: {}", port);
+                    exit(1);
+                }
+            }
+        }
     }
 
     if options.server_mode && options.editor {
@@ -21,6 +88,18 @@ fn main() {
         exit(1);
     }
 
+    #[cfg(feature = "scripting-lua")]
+    if let Some(test_options) = script_tests {
+        match doukutsu_rs::game::scripting::lua::test_runner::run(&test_options) {
+            Ok(true) => exit(0),
+            Ok(false) => exit(1),
+            Err(e) => {
+                eprintln!("Failed to run script tests: {}", e);
+                exit(2);
+            }
+        }
+    }
+
     let result = doukutsu_rs::game::init(options);
 
     #[cfg(target_os = "windows")]
@@ -7,11 +7,13 @@ use crate::framework::error::GameResult;
 use crate::framework::graphics;
 use crate::game::shared_game_state::{GameDifficulty, MenuCharacter, SharedGameState};
 use crate::graphics::font::Font;
+use crate::graphics::text_layout;
 use crate::input::combined_menu_controller::CombinedMenuController;
 use crate::menu::save_select_menu::MenuSaveInfo;
 
 pub mod controls_menu;
 pub mod coop_menu;
+pub mod mod_browser_menu;
 pub mod pause_menu;
 pub mod save_select_menu;
 pub mod settings_menu;
@@ -464,6 +466,8 @@ impl<T: std::cmp::PartialEq + std::default::Default + Clone> Menu<T> {
                     for line in lines.iter() {
                         let x = if *is_centered {
                             (state.canvas_size.0 as f32 - state.font.builder().compute_width(&line)) / 2.0
+                        } else if text_layout::is_rtl_text(line.chars()) {
+                            self.x as f32 + self.width as f32 - 20.0 - state.font.builder().compute_width(&line)
                         } else {
                             self.x as f32 + 20.0
                         };
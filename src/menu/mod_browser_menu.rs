@@ -0,0 +1,301 @@
+use crate::common::Rect;
+use crate::framework::context::Context;
+use crate::framework::error::GameResult;
+use crate::game::shared_game_state::SharedGameState;
+use crate::graphics::font::Font;
+use crate::input::combined_menu_controller::CombinedMenuController;
+use crate::menu::{Menu, MenuEntry, MenuSelectionResult};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ModListMenuEntry {
+    Mod(usize),
+    Back,
+}
+
+impl Default for ModListMenuEntry {
+    fn default() -> Self {
+        ModListMenuEntry::Back
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ModDetailsMenuEntry {
+    Title,
+    Info(usize),
+    Enabled,
+    LoadOrder,
+    Start,
+    Back,
+}
+
+impl Default for ModDetailsMenuEntry {
+    fn default() -> Self {
+        ModDetailsMenuEntry::Start
+    }
+}
+
+enum CurrentMenu {
+    ModList,
+    ModDetails,
+}
+
+/// Lists installed mods along with the details from their manifests.
+pub struct ModBrowserMenu {
+    current_menu: CurrentMenu,
+    list_menu: Menu<ModListMenuEntry>,
+    details_menu: Menu<ModDetailsMenuEntry>,
+    /// Index of the mod shown in the details menu.
+    selected_mod: usize,
+}
+
+impl ModBrowserMenu {
+    pub fn new() -> ModBrowserMenu {
+        ModBrowserMenu {
+            current_menu: CurrentMenu::ModList,
+            list_menu: Menu::new(0, 0, 150, 0),
+            details_menu: Menu::new(0, 0, 200, 0),
+            selected_mod: 0,
+        }
+    }
+
+    pub fn init(&mut self, state: &mut SharedGameState) -> GameResult {
+        self.current_menu = CurrentMenu::ModList;
+        self.list_menu = Menu::new(0, 0, 150, 0);
+
+        let mut selected = ModListMenuEntry::Back;
+        for (idx, mod_info) in state.mod_list.mods.iter().enumerate() {
+            if mod_info.satisfies_requirement(&state.mod_requirements) {
+                self.list_menu.push_entry(ModListMenuEntry::Mod(idx), MenuEntry::Active(mod_info.name.clone()));
+
+                if selected == ModListMenuEntry::Back {
+                    selected = ModListMenuEntry::Mod(idx);
+                }
+            } else {
+                self.list_menu.push_entry(ModListMenuEntry::Mod(idx), MenuEntry::Disabled("???".to_owned()));
+            }
+        }
+
+        self.list_menu.push_entry(ModListMenuEntry::Back, MenuEntry::Active(state.loc.t("common.back").to_owned()));
+        self.list_menu.selected = selected;
+
+        self.update_sizes(state);
+
+        Ok(())
+    }
+
+    fn init_details(&mut self, state: &mut SharedGameState, idx: usize) {
+        self.selected_mod = idx;
+        self.details_menu = Menu::new(0, 0, 200, 0);
+
+        let mod_info = match state.mod_list.mods.get(idx) {
+            Some(mod_info) => mod_info,
+            None => return,
+        };
+
+        let mut info = Vec::new();
+        if let Some(manifest) = &mod_info.manifest {
+            if let Some(author) = &manifest.author {
+                info.push(state.loc.tt("menus.mod_browser.author", &[("author", author.as_str())]));
+            }
+            if let Some(version) = &manifest.version {
+                info.push(state.loc.tt("menus.mod_browser.version", &[("version", version.as_str())]));
+            }
+        }
+        if !mod_info.description.is_empty() {
+            info.push(mod_info.description.clone());
+        }
+        if let Some(manifest) = &mod_info.manifest {
+            if !manifest.dependencies.is_empty() {
+                let dependencies = manifest.dependencies.join(", ");
+                info.push(state.loc.tt("menus.mod_browser.dependencies", &[("mods", dependencies.as_str())]));
+            }
+        }
+        info.extend(mod_info.issues.iter().cloned());
+
+        let count = state.mod_list.conflicts().iter().filter(|conflict| conflict.mods.contains(&mod_info.id)).count();
+        if count > 0 {
+            info.push(state.loc.tp("menus.mod_browser.conflicts", count as i64, &[]));
+        }
+
+        self.details_menu.push_entry(ModDetailsMenuEntry::Title, MenuEntry::Title(mod_info.name.clone(), true, true));
+        for (i, line) in info.into_iter().enumerate() {
+            self.details_menu.push_entry(ModDetailsMenuEntry::Info(i), MenuEntry::LongText(line, false, false));
+        }
+
+        // challenges replace the game rather than extend it, so they can't be layered
+        if mod_info.valid && mod_info.save_slot >= 0 {
+            self.details_menu.push_entry(ModDetailsMenuEntry::Enabled, MenuEntry::Hidden);
+            self.details_menu.push_entry(ModDetailsMenuEntry::LoadOrder, MenuEntry::Hidden);
+            self.update_layer_entries(state);
+        }
+
+        let start = state.loc.t("menus.mod_browser.start").to_owned();
+        if mod_info.valid {
+            self.details_menu.push_entry(ModDetailsMenuEntry::Start, MenuEntry::Active(start));
+            self.details_menu.selected = ModDetailsMenuEntry::Start;
+        } else {
+            self.details_menu.push_entry(ModDetailsMenuEntry::Start, MenuEntry::Disabled(start));
+            self.details_menu.selected = ModDetailsMenuEntry::Back;
+        }
+
+        self.details_menu
+            .push_entry(ModDetailsMenuEntry::Back, MenuEntry::Active(state.loc.t("common.back").to_owned()));
+
+        self.update_sizes(state);
+    }
+
+    fn update_layer_entries(&mut self, state: &SharedGameState) {
+        let mod_info = match state.mod_list.mods.get(self.selected_mod) {
+            Some(mod_info) => mod_info,
+            None => return,
+        };
+
+        let enabled_mods = &state.settings.enabled_mods;
+        let position = enabled_mods.iter().position(|id| id == &mod_info.id);
+
+        self.details_menu.set_entry(
+            ModDetailsMenuEntry::Enabled,
+            MenuEntry::Toggle(state.loc.t("menus.mod_browser.enabled").to_owned(), position.is_some()),
+        );
+
+        match position {
+            Some(position) => {
+                let positions = (1..=enabled_mods.len()).map(|i| i.to_string()).collect();
+                self.details_menu.set_entry(
+                    ModDetailsMenuEntry::LoadOrder,
+                    MenuEntry::Options(state.loc.t("menus.mod_browser.load_order").to_owned(), position, positions),
+                );
+            }
+            None => self.details_menu.set_entry(ModDetailsMenuEntry::LoadOrder, MenuEntry::Hidden),
+        }
+    }
+
+    /// Moves the selected mod by `delta` positions in the load order, wrapping around at the ends.
+    fn move_layer(&mut self, state: &mut SharedGameState, ctx: &mut Context, delta: isize) -> GameResult {
+        let id = &state.mod_list.mods[self.selected_mod].id;
+        let enabled_mods = &mut state.settings.enabled_mods;
+
+        if let Some(position) = enabled_mods.iter().position(|enabled| enabled == id) {
+            let new_position = (position as isize + delta).rem_euclid(enabled_mods.len() as isize) as usize;
+            let id = enabled_mods.remove(position);
+            enabled_mods.insert(new_position, id);
+        }
+
+        self.apply_layers(state, ctx)
+    }
+
+    /// Saves the changed set of enabled mods and reloads the game data with the new layers.
+    fn apply_layers(&mut self, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
+        let _ = state.settings.save(ctx);
+        state.reload_resources(ctx)?;
+
+        self.update_layer_entries(state);
+        self.update_sizes(state);
+
+        Ok(())
+    }
+
+    fn update_sizes(&mut self, state: &SharedGameState) {
+        self.list_menu.update_width(state);
+        self.list_menu.update_height(state);
+        self.list_menu.x = ((state.canvas_size.0 - self.list_menu.width as f32) / 2.0).floor() as isize;
+        self.list_menu.y = ((state.canvas_size.1 + 30.0 - self.list_menu.height as f32) / 2.0).floor() as isize;
+
+        self.details_menu.update_width(state);
+        self.details_menu.update_height(state);
+        self.details_menu.x = ((state.canvas_size.0 - self.details_menu.width as f32) / 2.0).floor() as isize;
+        self.details_menu.y = ((state.canvas_size.1 + 30.0 - self.details_menu.height as f32) / 2.0).floor() as isize;
+    }
+
+    /// Returns the index of the mod which the player has chosen to start.
+    pub fn tick(
+        &mut self,
+        exit_action: &mut dyn FnMut(),
+        controller: &mut CombinedMenuController,
+        state: &mut SharedGameState,
+        ctx: &mut Context,
+    ) -> GameResult<Option<usize>> {
+        self.update_sizes(state);
+
+        match self.current_menu {
+            CurrentMenu::ModList => match self.list_menu.tick(controller, state) {
+                MenuSelectionResult::Selected(ModListMenuEntry::Mod(idx), _) => {
+                    self.init_details(state, idx);
+                    self.current_menu = CurrentMenu::ModDetails;
+                }
+                MenuSelectionResult::Selected(ModListMenuEntry::Back, _) | MenuSelectionResult::Canceled => {
+                    exit_action();
+                }
+                _ => (),
+            },
+            CurrentMenu::ModDetails => match self.details_menu.tick(controller, state) {
+                MenuSelectionResult::Selected(ModDetailsMenuEntry::Start, _) => {
+                    return Ok(Some(self.selected_mod));
+                }
+                MenuSelectionResult::Selected(ModDetailsMenuEntry::Enabled, _) => {
+                    let id = state.mod_list.mods[self.selected_mod].id.clone();
+                    let enabled_mods = &mut state.settings.enabled_mods;
+
+                    if enabled_mods.contains(&id) {
+                        enabled_mods.retain(|enabled| enabled != &id);
+                    } else {
+                        enabled_mods.push(id);
+                    }
+
+                    self.apply_layers(state, ctx)?;
+                }
+                MenuSelectionResult::Left(ModDetailsMenuEntry::LoadOrder, _, _) => {
+                    self.move_layer(state, ctx, -1)?;
+                }
+                MenuSelectionResult::Right(ModDetailsMenuEntry::LoadOrder, _, _)
+                | MenuSelectionResult::Selected(ModDetailsMenuEntry::LoadOrder, _) => {
+                    self.move_layer(state, ctx, 1)?;
+                }
+                MenuSelectionResult::Selected(ModDetailsMenuEntry::Back, _) | MenuSelectionResult::Canceled => {
+                    self.current_menu = CurrentMenu::ModList;
+                }
+                _ => (),
+            },
+        }
+
+        Ok(None)
+    }
+
+    pub fn draw(&self, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
+        match self.current_menu {
+            CurrentMenu::ModList => self.list_menu.draw(state, ctx)?,
+            CurrentMenu::ModDetails => {
+                self.draw_preview(state, ctx)?;
+                self.details_menu.draw(state, ctx)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws the preview image of the selected mod above the details.
+    fn draw_preview(&self, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
+        let mod_info = match state.mod_list.mods.get(self.selected_mod) {
+            Some(mod_info) => mod_info,
+            None => return Ok(()),
+        };
+
+        let preview = match mod_info.manifest.as_ref().and_then(|manifest| manifest.preview.as_ref()) {
+            Some(preview) => {
+                format!("{}/{}", mod_info.path.trim_matches('/'), preview.trim_start_matches('/'))
+            }
+            None => return Ok(()),
+        };
+
+        if let Ok(batch) = state.texture_set.get_or_load_batch(ctx, &state.constants, &preview) {
+            let (width, height) = (batch.width() as f32, batch.height() as f32);
+            let x = ((state.canvas_size.0 - width) / 2.0).floor();
+            let y = (self.details_menu.y as f32 - height - 4.0).max(state.font.line_height() * 2.0);
+
+            batch.add_rect(x, y, &Rect::new(0, 0, width as u16, height as u16));
+            batch.draw(ctx)?;
+        }
+
+        Ok(())
+    }
+}
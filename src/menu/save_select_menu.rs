@@ -2,6 +2,7 @@ use crate::framework::context::Context;
 use crate::framework::error::GameResult;
 use crate::framework::filesystem;
 use crate::game::profile::GameProfile;
+use crate::game::scripting::storage::ScriptStorage;
 use crate::game::shared_game_state::{GameDifficulty, SharedGameState};
 use crate::input::combined_menu_controller::CombinedMenuController;
 use crate::menu::coop_menu::PlayerCountMenu;
@@ -308,7 +309,13 @@ impl SaveSelectMenu {
                     match self.save_menu.selected {
                         SaveMenuEntry::Load(slot) => {
                             state.sound_manager.play_sfx(17); // Player Death sfx
-                            filesystem::user_delete(ctx, state.get_save_filename(slot + 1).unwrap_or(String::new()))?;
+                            let save_path = state.get_save_filename(slot + 1).unwrap_or(String::new());
+                            filesystem::user_delete(ctx, &save_path)?;
+
+                            let storage_path = ScriptStorage::path_for_profile(&save_path);
+                            if filesystem::user_exists(ctx, &storage_path) {
+                                filesystem::user_delete(ctx, storage_path)?;
+                            }
                         }
                         _ => (),
                     }
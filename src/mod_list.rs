@@ -1,16 +1,37 @@
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader};
 use std::iter::Peekable;
+use std::path::PathBuf;
 use std::str::Chars;
 
 use crate::framework::context::Context;
 use crate::framework::error::GameResult;
 use crate::framework::filesystem;
+use crate::framework::vfs::ZipFS;
 use crate::mod_requirements::ModRequirements;
 
+/// A file provided by more than one enabled mod.
+#[derive(Debug, Clone)]
+pub struct ModConflict {
+    /// Path of the file relative to the mod roots.
+    pub path: String,
+    /// Ids of the mods providing the file in load order, the last one takes precedence.
+    pub mods: Vec<String>,
+}
+
+/// Where the mod has been found, decides the menu it's listed in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModKind {
+    /// Listed in mods.txt, shown in the Challenges menu like the Cave Story+ challenges.
+    Challenge,
+    /// Discovered through its mod.json or zip archive, only shown in the mod browser.
+    Mod,
+}
+
 #[derive(Debug)]
 pub struct ModInfo {
     pub id: String,
+    pub kind: ModKind,
     pub requirement: Requirement,
     pub priority: u32,
     pub save_slot: i32,
@@ -18,6 +39,10 @@ pub struct ModInfo {
     pub name: String,
     pub description: String,
     pub valid: bool,
+    /// Contents of mod.json, if the mod has one.
+    pub manifest: Option<ModManifest>,
+    /// Reasons why the mod can't be started, eg. missing dependencies.
+    pub issues: Vec<String>,
 }
 
 impl ModInfo {
@@ -30,6 +55,103 @@ impl ModInfo {
             Requirement::RequireWeapon(weapon_id) => mod_requirements.has_weapon(weapon_id),
         }
     }
+
+    /// Overrides the data read from mod.txt with the manifest. Mods without a mod.txt extend the base game,
+    /// so unless the manifest says otherwise they use its save files.
+    fn apply_manifest(&mut self, manifest: ModManifest) {
+        if !self.valid {
+            // only the "mod.txt not found" placeholder
+            self.description.clear();
+        }
+        if let Some(id) = &manifest.id {
+            self.id = id.clone();
+        }
+        if let Some(name) = &manifest.name {
+            self.name = name.clone();
+        }
+        if let Some(description) = &manifest.description {
+            self.description = description.clone();
+        }
+        match manifest.save_slot {
+            Some(save_slot) => self.save_slot = save_slot.to_save_slot(),
+            None if !self.valid => self.save_slot = SaveSlotPolicy::Shared.to_save_slot(),
+            None => {}
+        }
+
+        self.valid = true;
+        self.manifest = Some(manifest);
+    }
+}
+
+/// How the mod stores its progress, maps to the save slot number of mod.txt.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaveSlotPolicy {
+    /// Progress isn't saved, like with Cave Story+ challenges.
+    None,
+    /// Save files of the base game are used.
+    Shared,
+    /// Save files separate from the base game, mods using the same number share them.
+    Dedicated(u32),
+}
+
+impl SaveSlotPolicy {
+    pub fn to_save_slot(self) -> i32 {
+        match self {
+            SaveSlotPolicy::None => -1,
+            SaveSlotPolicy::Shared => 0,
+            SaveSlotPolicy::Dedicated(slot) => slot.max(1) as i32,
+        }
+    }
+}
+
+/// Mod metadata read from `mod.json` in the mod directory, overrides the data from mod.txt.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ModManifest {
+    /// Identifier used by dependencies of other mods, defaults to the one derived from mods.txt or the directory name.
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    /// Ids of mods which have to be installed for this one to work.
+    pub dependencies: Vec<String>,
+    /// Minimum version of the engine, eg. `0.101.0`.
+    pub engine_version: Option<String>,
+    /// Lua scripts loaded when the mod is started, relative to the mod directory.
+    pub scripts: Vec<String>,
+    /// Defaults to the save slot of mod.txt, or to the shared one if the mod has no mod.txt.
+    pub save_slot: Option<SaveSlotPolicy>,
+    /// Preview image relative to the mod directory, without extension.
+    pub preview: Option<String>,
+}
+
+/// Returns true if the version is older than the required one, versions are compared by their numeric components.
+fn is_version_older(version: &str, required: &str) -> bool {
+    fn components(version: &str) -> Vec<u32> {
+        version
+            .trim()
+            .trim_start_matches(">=")
+            .trim_start_matches('v')
+            .split(|c| c == '.' || c == '-' || c == '+')
+            .map_while(|c| c.parse().ok())
+            .collect()
+    }
+
+    let version = components(version);
+    let required = components(required);
+
+    for i in 0..version.len().max(required.len()) {
+        let a = version.get(i).copied().unwrap_or(0);
+        let b = required.get(i).copied().unwrap_or(0);
+
+        if a != b {
+            return a < b;
+        }
+    }
+
+    false
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -48,6 +170,9 @@ pub enum Requirement {
 
 pub struct ModList {
     pub mods: Vec<ModInfo>,
+    /// Mod roots the cached conflicts have been found for.
+    conflict_paths: Vec<String>,
+    conflicts: Vec<ModConflict>,
 }
 
 impl ModList {
@@ -149,37 +274,206 @@ impl ModList {
                     }
                 }
 
-                let mut valid = false;
-                let mut name = String::new();
-                let mut description = String::new();
-                let mut save_slot = -1;
-
-                if let Ok(file) = filesystem::open(ctx, [&path, "/mod.txt"].join("")) {
-                    valid = true;
-                    let reader = BufReader::new(file);
-                    let mut lines = reader.lines();
-                    if let Some(line) = lines.nth(1) {
-                        save_slot = line.unwrap_or("-1".to_string()).parse::<i32>().unwrap_or(-1);
-                    }
-                    if let Some(line) = lines.next() {
-                        let read_name = line.unwrap_or("No Mod Name".to_string()).to_string();
-                        name = string_table.get(&read_name).unwrap_or(&read_name).to_string();
-                    }
-                    if let Some(line) = lines.next() {
-                        description = line.unwrap_or("No Description".to_string()).to_string();
-                    }
-                } else {
-                    name = path.clone();
-                    description = "mod.txt not found".to_string();
-                }
+                let (valid, save_slot, name, description) = ModList::read_mod_txt(ctx, &path, string_table);
 
-                mods.push(ModInfo { id, requirement, priority, save_slot, path, name, description, valid })
+                mods.push(ModInfo {
+                    id,
+                    kind: ModKind::Challenge,
+                    requirement,
+                    priority,
+                    save_slot,
+                    path,
+                    name,
+                    description,
+                    valid,
+                    manifest: None,
+                    issues: Vec::new(),
+                })
             }
         }
 
+        ModList::load_archives(ctx, string_table, &mut mods);
+        ModList::discover_manifest_mods(ctx, string_table, &mut mods);
+
+        for mod_info in mods.iter_mut() {
+            ModList::load_manifest(ctx, mod_info);
+        }
+        ModList::check_requirements(&mut mods);
+
         mods.sort_by(|a, b| a.priority.cmp(&b.priority));
 
-        Ok(ModList { mods })
+        Ok(ModList { mods, conflict_paths: Vec::new(), conflicts: Vec::new() })
+    }
+
+    /// Reads validity, save slot, name and description of the mod from its mod.txt.
+    fn read_mod_txt(ctx: &Context, path: &str, string_table: &HashMap<String, String>) -> (bool, i32, String, String) {
+        let mut valid = false;
+        let mut name = String::new();
+        let mut description = String::new();
+        let mut save_slot = -1;
+
+        if let Ok(file) = filesystem::open(ctx, [path, "/mod.txt"].join("")) {
+            valid = true;
+            let reader = BufReader::new(file);
+            let mut lines = reader.lines();
+            if let Some(line) = lines.nth(1) {
+                save_slot = line.unwrap_or("-1".to_string()).parse::<i32>().unwrap_or(-1);
+            }
+            if let Some(line) = lines.next() {
+                let read_name = line.unwrap_or("No Mod Name".to_string()).to_string();
+                name = string_table.get(&read_name).unwrap_or(&read_name).to_string();
+            }
+            if let Some(line) = lines.next() {
+                description = line.unwrap_or("No Description".to_string()).to_string();
+            }
+        } else {
+            name = path.to_string();
+            description = "mod.txt not found".to_string();
+        }
+
+        (valid, save_slot, name, description)
+    }
+
+    /// Mounts zipped mods placed in the data directory (eg. `data/mymod.zip` as `/mymod/`)
+    /// and adds those which aren't listed in mods.txt.
+    fn load_archives(ctx: &mut Context, string_table: &HashMap<String, String>, mods: &mut Vec<ModInfo>) {
+        let archives: Vec<PathBuf> = match filesystem::read_dir(ctx, "/") {
+            Ok(files) => files.filter(|f| f.to_string_lossy().to_lowercase().ends_with(".zip")).collect(),
+            Err(_) => return,
+        };
+
+        for archive in archives {
+            let stem = match archive.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            // the data archive itself is mounted by the filesystem container
+            if stem.eq_ignore_ascii_case("data") {
+                continue;
+            }
+
+            let path = format!("/{}/", stem);
+            let zip_fs = match filesystem::open(ctx, &archive) {
+                Ok(filesystem::File::VfsFile(file)) => {
+                    ZipFS::new(file, &archive.to_string_lossy(), &PathBuf::from(&path))
+                }
+                Err(e) => Err(e),
+            };
+
+            match zip_fs {
+                Ok(zip_fs) => {
+                    log::info!("Mounting mod archive {:?} as {}", archive, path);
+                    filesystem::mount_vfs(ctx, Box::new(zip_fs));
+                }
+                Err(e) => {
+                    log::error!("Failed to mount mod archive {:?}: {}", archive, e);
+                    continue;
+                }
+            }
+
+            if mods.iter().any(|m| m.path.trim_end_matches('/').eq_ignore_ascii_case(path.trim_end_matches('/'))) {
+                continue;
+            }
+
+            let (valid, save_slot, name, description) = ModList::read_mod_txt(ctx, &path, string_table);
+            mods.push(ModInfo {
+                id: format!("csmod_{}", stem),
+                kind: ModKind::Mod,
+                requirement: Requirement::Unlocked,
+                priority: 1000,
+                save_slot,
+                path,
+                name,
+                description,
+                valid,
+                manifest: None,
+                issues: Vec::new(),
+            });
+        }
+    }
+
+    /// Adds directories with a mod.json which aren't listed in mods.txt.
+    fn discover_manifest_mods(ctx: &mut Context, string_table: &HashMap<String, String>, mods: &mut Vec<ModInfo>) {
+        let dirs: Vec<PathBuf> = match filesystem::read_dir(ctx, "/") {
+            Ok(files) => files.collect(),
+            Err(_) => return,
+        };
+
+        for dir in dirs {
+            let stem = match dir.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            let path = format!("/{}/", stem);
+            if mods.iter().any(|m| m.path.trim_end_matches('/').eq_ignore_ascii_case(path.trim_end_matches('/')))
+                || !filesystem::exists(ctx, format!("{}mod.json", path))
+            {
+                continue;
+            }
+
+            let (valid, save_slot, name, description) = ModList::read_mod_txt(ctx, &path, string_table);
+            mods.push(ModInfo {
+                id: format!("csmod_{}", stem),
+                kind: ModKind::Mod,
+                requirement: Requirement::Unlocked,
+                priority: 1000,
+                save_slot,
+                path,
+                name,
+                description,
+                valid,
+                manifest: None,
+                issues: Vec::new(),
+            });
+        }
+    }
+
+    /// Reads mod.json of the mod and overrides the data read from mod.txt with it.
+    fn load_manifest(ctx: &Context, mod_info: &mut ModInfo) {
+        let manifest_path = format!("{}/mod.json", mod_info.path.trim_end_matches('/'));
+        let file = match filesystem::open(ctx, &manifest_path) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+
+        let manifest: ModManifest = match serde_json::from_reader(file) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                log::error!("Failed to parse {}: {}", manifest_path, e);
+                mod_info.issues.push(format!("Invalid mod.json: {}", e));
+                return;
+            }
+        };
+
+        mod_info.apply_manifest(manifest);
+    }
+
+    /// Checks the engine version and dependencies required by the manifests.
+    fn check_requirements(mods: &mut Vec<ModInfo>) {
+        let ids: Vec<String> = mods.iter().map(|m| m.id.clone()).collect();
+
+        for mod_info in mods.iter_mut() {
+            if let Some(manifest) = &mod_info.manifest {
+                if let Some(engine_version) = &manifest.engine_version {
+                    if is_version_older(env!("CARGO_PKG_VERSION"), engine_version) {
+                        mod_info.issues.push(format!("Requires engine version {}", engine_version));
+                    }
+                }
+
+                for dependency in manifest.dependencies.iter() {
+                    if !ids.contains(dependency) {
+                        mod_info.issues.push(format!("Requires mod {}", dependency));
+                    }
+                }
+            }
+
+            if !mod_info.issues.is_empty() {
+                log::warn!("Mod {} can't be started: {}", mod_info.id, mod_info.issues.join(", "));
+                mod_info.valid = false;
+            }
+        }
     }
 
     pub fn get_save_from_path(&self, mod_path: String) -> i32 {
@@ -190,6 +484,101 @@ impl ModList {
         }
     }
 
+    /// Returns the id of the mod at specified path, or `None` if it's not in the list.
+    pub fn get_id_from_path(&self, mod_path: String) -> Option<&str> {
+        self.mods.iter().find(|x| x.path == mod_path).map(|mod_sel| mod_sel.id.as_str())
+    }
+
+    /// Returns paths of the Lua entrypoints declared in the manifest of the mod.
+    pub fn get_scripts_from_path(&self, mod_path: String) -> Vec<String> {
+        match self.mods.iter().find(|x| x.path == mod_path).and_then(|m| m.manifest.as_ref()) {
+            Some(manifest) => manifest
+                .scripts
+                .iter()
+                .map(|script| format!("{}/{}", mod_path.trim_end_matches('/'), script.trim_start_matches('/')))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the roots of the given enabled mods in load order, missing and invalid mods are skipped.
+    pub fn get_enabled_paths(&self, enabled_mods: &[String]) -> Vec<String> {
+        enabled_mods
+            .iter()
+            .filter_map(|id| self.mods.iter().find(|m| &m.id == id && m.valid && m.save_slot >= 0))
+            .map(|m| m.path.clone())
+            .collect()
+    }
+
+    /// Returns the roots of active mods in load order, the started mod is layered on top of the enabled ones.
+    pub fn get_mod_paths(&self, enabled_mods: &[String], started_mod_path: Option<&String>) -> Vec<String> {
+        let mut mod_paths = self.get_enabled_paths(enabled_mods);
+        if let Some(mod_path) = started_mod_path {
+            mod_paths.retain(|path| path != mod_path);
+            mod_paths.push(mod_path.clone());
+        }
+
+        mod_paths
+    }
+
+    /// Finds the files provided by more than one of the given mod roots, unless they're the same roots
+    /// as in the previous call. Returns whether the conflicts have been searched for again.
+    pub fn update_conflicts(&mut self, ctx: &Context, mod_paths: &[String]) -> bool {
+        if self.conflict_paths == mod_paths {
+            return false;
+        }
+
+        self.conflicts = if mod_paths.len() > 1 {
+            let mod_files = mod_paths
+                .iter()
+                .map(|mod_path| {
+                    let mut files = Vec::new();
+                    list_files(ctx, mod_path, &mut files);
+                    (mod_path.clone(), files)
+                })
+                .collect::<Vec<_>>();
+
+            self.find_conflicts(&mod_files)
+        } else {
+            Vec::new()
+        };
+        self.conflict_paths = mod_paths.to_vec();
+
+        true
+    }
+
+    /// Files provided by more than one active mod, as found by the last [`ModList::update_conflicts`] call.
+    pub fn conflicts(&self) -> &[ModConflict] {
+        &self.conflicts
+    }
+
+    /// Lists files which are provided by more than one mod, given the files of each mod root in load order.
+    fn find_conflicts(&self, mod_files: &[(String, Vec<String>)]) -> Vec<ModConflict> {
+        let mut providers: HashMap<String, ModConflict> = HashMap::new();
+
+        for (mod_path, files) in mod_files {
+            let id = self.get_id_from_path(mod_path.clone()).unwrap_or(mod_path.as_str()).to_owned();
+
+            for file in files {
+                let relative = file.trim_start_matches(mod_path.as_str()).to_owned();
+                if relative == "mod.txt" || relative == "mod.json" {
+                    continue;
+                }
+
+                let conflict = providers
+                    .entry(relative.to_lowercase())
+                    .or_insert_with(|| ModConflict { path: relative, mods: Vec::new() });
+                if !conflict.mods.contains(&id) {
+                    conflict.mods.push(id.clone());
+                }
+            }
+        }
+
+        let mut conflicts: Vec<ModConflict> = providers.into_values().filter(|c| c.mods.len() > 1).collect();
+        conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+        conflicts
+    }
+
     pub fn get_name_from_path(&self, mod_path: String) -> &str {
         if let Some(mod_sel) = self.mods.iter().find(|x| x.path == mod_path) {
             &mod_sel.name
@@ -198,3 +587,168 @@ impl ModList {
         }
     }
 }
+
+/// Recursively collects paths of all files in the given directory.
+fn list_files(ctx: &Context, dir: &str, files: &mut Vec<String>) {
+    let entries = match filesystem::read_dir(ctx, dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries {
+        let path = entry.to_string_lossy().replace('\\', "/");
+
+        if filesystem::is_dir(ctx, &entry) {
+            list_files(ctx, &format!("{}/", path.trim_end_matches('/')), files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mod_info(id: &str, path: &str, valid: bool, save_slot: i32) -> ModInfo {
+        ModInfo {
+            id: id.to_owned(),
+            kind: ModKind::Mod,
+            requirement: Requirement::Unlocked,
+            priority: 1000,
+            save_slot,
+            path: path.to_owned(),
+            name: id.to_owned(),
+            description: String::new(),
+            valid,
+            manifest: None,
+            issues: Vec::new(),
+        }
+    }
+
+    fn mod_list() -> ModList {
+        let mods = vec![
+            mod_info("a", "/a/", true, 0),
+            mod_info("b", "/b/", true, 1),
+            mod_info("c", "/c/", true, 0),
+            mod_info("broken", "/broken/", false, 0),
+            mod_info("challenge", "/challenge/", true, -1),
+        ];
+
+        ModList { mods, conflict_paths: Vec::new(), conflicts: Vec::new() }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_get_enabled_paths() {
+        let mod_list = mod_list();
+
+        // load order is the order of enabling, not of the mod list
+        assert_eq!(mod_list.get_enabled_paths(&ids(&["c", "a"])), ids(&["/c/", "/a/"]));
+        assert_eq!(mod_list.get_enabled_paths(&ids(&["a", "c", "b"])), ids(&["/a/", "/c/", "/b/"]));
+
+        // missing, invalid and challenge mods can't be layered
+        assert_eq!(mod_list.get_enabled_paths(&ids(&["missing", "broken", "b", "challenge"])), ids(&["/b/"]));
+    }
+
+    #[test]
+    fn test_get_mod_paths() {
+        let mod_list = mod_list();
+        let enabled = ids(&["c", "a", "b"]);
+
+        assert_eq!(mod_list.get_mod_paths(&enabled, None), ids(&["/c/", "/a/", "/b/"]));
+
+        // the started mod goes on top, even if it's enabled too
+        assert_eq!(mod_list.get_mod_paths(&enabled, Some(&"/a/".to_owned())), ids(&["/c/", "/b/", "/a/"]));
+        assert_eq!(
+            mod_list.get_mod_paths(&enabled, Some(&"/challenge/".to_owned())),
+            ids(&["/c/", "/a/", "/b/", "/challenge/"])
+        );
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let mod_list = mod_list();
+        let mod_files = vec![
+            ("/a/".to_owned(), ids(&["/a/mod.txt", "/a/Stage/Pens1.pxm", "/a/Npc/NpcSym.pbm", "/a/a.txt"])),
+            ("/b/".to_owned(), ids(&["/b/mod.txt", "/b/Npc/NpcSym.pbm", "/b/stage/pens1.pxm"])),
+            ("/unlisted/".to_owned(), ids(&["/unlisted/mod.json", "/unlisted/Npc/NpcSym.pbm"])),
+        ];
+
+        let conflicts = mod_list.find_conflicts(&mod_files);
+        let conflicts: Vec<_> = conflicts.iter().map(|c| (c.path.as_str(), c.mods.clone())).collect();
+
+        // paths are case insensitive and listed as provided by the first mod, mods are in load order
+        assert_eq!(
+            conflicts,
+            vec![
+                ("Npc/NpcSym.pbm", ids(&["a", "b", "/unlisted/"])),
+                ("Stage/Pens1.pxm", ids(&["a", "b"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_version_older() {
+        assert!(is_version_older("0.100.0", "0.101.0"));
+        assert!(is_version_older("0.9.9", "0.10.0"));
+        assert!(is_version_older("1.2", "1.2.1"));
+        assert!(!is_version_older("1.2.0", "1.2"));
+        assert!(!is_version_older("0.101.0", "0.101.0"));
+        assert!(!is_version_older("1.0.0", "0.999.999"));
+
+        // prefixes and pre-release suffixes
+        assert!(is_version_older("0.100.0-beta5", ">=0.101.0"));
+        assert!(!is_version_older("0.101.0-beta5", "v0.101.0"));
+        assert!(!is_version_older("0.101.0+git", " >=0.100 "));
+    }
+
+    #[test]
+    fn test_to_save_slot() {
+        assert_eq!(SaveSlotPolicy::None.to_save_slot(), -1);
+        assert_eq!(SaveSlotPolicy::Shared.to_save_slot(), 0);
+        assert_eq!(SaveSlotPolicy::Dedicated(3).to_save_slot(), 3);
+
+        // slot 0 would share the saves of the base game
+        assert_eq!(SaveSlotPolicy::Dedicated(0).to_save_slot(), 1);
+    }
+
+    #[test]
+    fn test_apply_manifest_save_slot() {
+        // mod.json only
+        let mut info = mod_info("csmod_a", "/a/", false, -1);
+        info.apply_manifest(serde_json::from_str(r#"{ "name": "A" }"#).unwrap());
+        assert!(info.valid);
+        assert_eq!(info.save_slot, 0);
+        assert_eq!(info.name, "A");
+
+        let mut info = mod_info("csmod_a", "/a/", false, -1);
+        info.apply_manifest(serde_json::from_str(r#"{ "save_slot": "none" }"#).unwrap());
+        assert_eq!(info.save_slot, -1);
+
+        // the save slot of mod.txt is kept
+        let mut info = mod_info("csmod_a", "/a/", true, 2);
+        info.apply_manifest(ModManifest::default());
+        assert_eq!(info.save_slot, 2);
+
+        let mut info = mod_info("csmod_a", "/a/", true, -1);
+        info.apply_manifest(ModManifest::default());
+        assert_eq!(info.save_slot, -1);
+
+        let mut info = mod_info("csmod_a", "/a/", true, 2);
+        info.apply_manifest(serde_json::from_str(r#"{ "save_slot": { "dedicated": 4 } }"#).unwrap());
+        assert_eq!(info.save_slot, 4);
+    }
+
+    #[test]
+    fn test_save_slot_policy_from_json() {
+        let manifest: ModManifest = serde_json::from_str(r#"{ "save_slot": { "dedicated": 2 } }"#).unwrap();
+        assert_eq!(manifest.save_slot, Some(SaveSlotPolicy::Dedicated(2)));
+
+        let manifest: ModManifest = serde_json::from_str(r#"{ "save_slot": "shared" }"#).unwrap();
+        assert_eq!(manifest.save_slot, Some(SaveSlotPolicy::Shared));
+    }
+}
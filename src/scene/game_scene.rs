@@ -39,6 +39,11 @@ use crate::game::npc::list::NPCList;
 use crate::game::npc::{NPCLayer, NPC};
 use crate::game::physics::{PhysicalEntity, OFFSETS};
 use crate::game::player::{ControlMode, Player, TargetPlayer};
+#[cfg(feature = "scripting-lua")]
+use crate::game::scripting::events::DrawLayer;
+use crate::game::scripting::events::ScriptEvent;
+#[cfg(feature = "scripting-lua")]
+use crate::game::scripting::lua::LuaScriptingState;
 use crate::game::scripting::tsc::credit_script::CreditScriptVM;
 use crate::game::scripting::tsc::text_script::{ScriptMode, TextScriptExecutionState, TextScriptVM};
 use crate::game::settings::ControllerType;
@@ -48,6 +53,7 @@ use crate::game::weapon::bullet::BulletManager;
 use crate::game::weapon::{Weapon, WeaponType};
 use crate::graphics::font::{Font, Symbols};
 use crate::graphics::texture_set::SpriteBatch;
+use crate::input::bot_player_controller::{BotSlot, BotView};
 use crate::input::touch_controls::TouchControlType;
 use crate::menu::pause_menu::PauseMenu;
 use crate::scene::title_scene::TitleScene;
@@ -88,9 +94,15 @@ pub struct GameScene {
     pub pause_menu: PauseMenu,
     pub stage_textures: Rc<RefCell<StageTexturePaths>>,
     pub replay: Replay,
+    bot_slot: BotSlot,
     map_name_counter: u16,
     skip_counter: u16,
     inventory_dim: f32,
+    /// Whether the boss was alive when last checked.
+    boss_alive: bool,
+    /// Whether `BossDefeated` has been sent for the current fight.
+    boss_defeat_reported: bool,
+    stage_left: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -139,6 +151,7 @@ impl GameScene {
         };
 
         let mut player2 = Player::new(state, ctx);
+        player2.target_player = TargetPlayer::Player2;
 
         if state.player2_skin_location.texture_index != 0 {
             let skinsheet_name =
@@ -182,7 +195,11 @@ impl GameScene {
             map_name_counter: 0,
             skip_counter: 0,
             inventory_dim: 0.0,
+            boss_alive: false,
+            boss_defeat_reported: false,
+            stage_left: false,
             replay: Replay::new(),
+            bot_slot: BotSlot::default(),
         })
     }
 
@@ -1272,6 +1289,11 @@ impl GameScene {
                     if npc.life == 0 {
                         npc.life = npc.id;
 
+                        if idx == 0 && !self.boss_defeat_reported {
+                            self.boss_defeat_reported = true;
+                            state.script_events.push(ScriptEvent::BossDefeated { boss_type: self.boss.boss_type });
+                        }
+
                         if self.player1.cond.alive() && npc.npc_flags.event_when_killed() {
                             state.control_flags.set_tick_world(true);
                             state.control_flags.set_interactions_disabled(true);
@@ -1324,6 +1346,52 @@ impl GameScene {
         }
     }
 
+    /// Queues events of NPCs spawned during this tick, then hands all queued events to scripts.
+    /// Queues `BossDefeated` if the boss has died without being shot down, eg. killed by a script.
+    fn check_boss_defeated(&mut self, state: &mut SharedGameState) {
+        let alive = self.boss.boss_type != 0 && self.boss.parts[0].cond.alive();
+
+        if alive && !self.boss_alive {
+            self.boss_defeat_reported = false;
+        } else if !alive && self.boss_alive && !self.boss_defeat_reported {
+            self.boss_defeat_reported = true;
+            state.script_events.push(ScriptEvent::BossDefeated { boss_type: self.boss.boss_type });
+        }
+
+        self.boss_alive = alive;
+    }
+
+    /// Queues `StageLeave` once the scene is about to be replaced, be it by a transport, returning to title,
+    /// or loading a save. Returns true if it has been queued.
+    pub(crate) fn check_stage_leave(&mut self, state: &mut SharedGameState) -> bool {
+        if state.next_scene.is_none() || self.stage_left {
+            return false;
+        }
+
+        self.stage_left = true;
+        state.script_events.push(ScriptEvent::StageLeave { stage_id: self.stage_id as u16 });
+        true
+    }
+
+    fn dispatch_script_events(&mut self, state: &mut SharedGameState) {
+        for npc_id in self.npc_list.take_spawned() {
+            if let Some(npc) = self.npc_list.get_npc(npc_id as usize) {
+                if npc.cond.alive() {
+                    state.script_events.push(ScriptEvent::NPCSpawned { npc_id, npc_type: npc.npc_type });
+                }
+            }
+        }
+
+        #[cfg(feature = "scripting-lua")]
+        {
+            let events = std::mem::take(&mut state.script_events);
+            state.lua.dispatch_events(&events);
+        }
+
+        #[cfg(not(feature = "scripting-lua"))]
+        state.script_events.clear();
+    }
+
     fn tick_world(&mut self, state: &mut SharedGameState) -> GameResult {
         self.nikumaru.tick(state, &self.player1)?;
         self.background.tick()?;
@@ -1641,6 +1709,8 @@ impl GameScene {
 
 impl Scene for GameScene {
     fn init(&mut self, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
+        state.script_events.push(ScriptEvent::StageEnter { stage_id: self.stage_id as u16 });
+
         if state.mod_path.is_some() && state.replay_state == ReplayState::Recording {
             self.replay.initialize_recording(state);
         }
@@ -1661,12 +1731,15 @@ impl Scene for GameScene {
         state.textscript_vm.set_scene_script(self.stage.load_text_script(
             &state.constants.base_paths,
             &state.constants,
+            state.keep_script_sources(),
             ctx,
         )?);
         state.textscript_vm.suspend = false;
         state.tile_size = self.stage.map.tile_size;
         #[cfg(feature = "scripting-lua")]
         state.lua.set_game_scene(self as *mut _);
+        #[cfg(feature = "scripting-wasm")]
+        state.wasm.set_game_scene(self as *mut _);
 
         self.player1.controller = state.settings.create_player1_controller();
         self.player2.controller = state.settings.create_player2_controller();
@@ -1691,6 +1764,9 @@ impl Scene for GameScene {
             self.npc_list.spawn_at_slot(npc_data.id, npc)?;
         }
 
+        // NPCs placed by the stage are part of it, not spawned during gameplay.
+        self.npc_list.take_spawned();
+
         state.npc_table.stage_textures = self.stage_textures.clone();
 
         self.boss.boss_type = self.stage.data.boss_no as u16;
@@ -1754,6 +1830,10 @@ impl Scene for GameScene {
     }
 
     fn tick(&mut self, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
+        // all hooks and events run during the tick share one instruction budget
+        #[cfg(feature = "scripting-lua")]
+        LuaScriptingState::reset_instruction_count();
+
         if !self.pause_menu.is_paused() {
             if let ReplayState::Playback(_) = state.replay_state {
                 self.replay.tick(state, (ctx, &mut self.player1))?;
@@ -1770,6 +1850,20 @@ impl Scene for GameScene {
             state.player_count_modified_in_game = false;
         }
 
+        if let Some(bot) = state.bot.take() {
+            bot.think(&BotView {
+                state,
+                player: &self.player1,
+                npc_list: &self.npc_list,
+                stage: &self.stage,
+                frame: &self.frame,
+                stage_id: self.stage_id,
+                tick: self.tick,
+            });
+            state.bot = Some(bot);
+        }
+        self.bot_slot.sync(state.bot.as_ref(), &mut self.player1.controller);
+
         self.player1.controller.update(state, ctx)?;
         self.player1.controller.update_trigger();
         self.player2.controller.update(state, ctx)?;
@@ -1803,6 +1897,9 @@ impl Scene for GameScene {
 
         if self.pause_menu.is_paused() {
             self.pause_menu.tick(state, ctx)?;
+            if self.check_stage_leave(state) {
+                self.dispatch_script_events(state);
+            }
             return Ok(());
         }
 
@@ -1890,9 +1987,28 @@ impl Scene for GameScene {
         self.flash.tick(state, ())?;
         self.text_boxes.tick(state, ())?;
 
+        self.check_boss_defeated(state);
+        self.check_stage_leave(state);
+        self.dispatch_script_events(state);
+
         #[cfg(feature = "scripting-lua")]
         state.lua.scene_tick();
 
+        #[cfg(feature = "scripting-wasm")]
+        state.wasm.scene_tick();
+
+        // reloading re-registers custom TSC commands, so it waits until no event is running
+        #[cfg(feature = "scripting-lua")]
+        if state.settings.debug_mode
+            && state.textscript_vm.state == TextScriptExecutionState::Ended
+            && state.lua.scripts_changed(ctx)
+        {
+            log::info!("Lua scripts have been modified, reloading...");
+            if let Err(err) = state.reload_lua_scripts(ctx, true) {
+                log::error!("Error reloading scripts: {:?}", err);
+            }
+        }
+
         if state.control_flags.tick_world() {
             self.tick = self.tick.wrapping_add(1);
         }
@@ -1987,12 +2103,19 @@ impl Scene for GameScene {
     fn draw(&self, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
         //graphics::set_canvas(ctx, Some(&state.game_canvas));
 
+        #[cfg(feature = "scripting-lua")]
+        LuaScriptingState::reset_instruction_count();
+
         if self.player1.control_mode == ControlMode::IronHead {
             self.set_ironhead_clip(state, ctx)?;
         }
 
         let stage_textures_ref = &*self.stage_textures.deref().borrow();
         self.background.draw(state, ctx, &self.frame, stage_textures_ref, &self.stage)?;
+
+        #[cfg(feature = "scripting-lua")]
+        state.lua.draw_hook(DrawLayer::Background);
+
         self.tilemap.draw(state, ctx, &self.frame, TileLayer::Background, stage_textures_ref, &self.stage)?;
         self.draw_npc_layer(state, ctx, NPCLayer::Background)?;
         self.tilemap.draw(state, ctx, &self.frame, TileLayer::Middleground, stage_textures_ref, &self.stage)?;
@@ -2011,6 +2134,9 @@ impl Scene for GameScene {
             self.whimsical_star.draw(state, ctx, &self.frame)?;
         }
 
+        #[cfg(feature = "scripting-lua")]
+        state.lua.draw_hook(DrawLayer::Entities);
+
         self.water_renderer.draw(state, ctx, &self.frame, WaterLayer::Back)?;
         self.tilemap.draw(state, ctx, &self.frame, TileLayer::Foreground, stage_textures_ref, &self.stage)?;
         self.tilemap.draw(state, ctx, &self.frame, TileLayer::Snack, stage_textures_ref, &self.stage)?;
@@ -2150,6 +2276,9 @@ impl Scene for GameScene {
             _ => {}
         }
 
+        #[cfg(feature = "scripting-lua")]
+        state.lua.draw_hook(DrawLayer::HUD);
+
         self.map_system.draw(state, ctx, &self.stage, [&self.player1, &self.player2])?;
         self.fade.draw(state, ctx, &self.frame)?;
 
@@ -15,9 +15,11 @@ use crate::graphics::font::Font;
 use crate::input::combined_menu_controller::CombinedMenuController;
 use crate::input::touch_controls::TouchControlType;
 use crate::menu::coop_menu::PlayerCountMenu;
+use crate::menu::mod_browser_menu::ModBrowserMenu;
 use crate::menu::save_select_menu::SaveSelectMenu;
 use crate::menu::settings_menu::SettingsMenu;
 use crate::menu::{Menu, MenuEntry, MenuSelectionResult};
+use crate::mod_list::ModKind;
 use crate::scene::jukebox_scene::JukeboxScene;
 use crate::scene::Scene;
 
@@ -31,12 +33,14 @@ enum CurrentMenu {
     ChallengesMenu,
     ChallengeConfirmMenu,
     PlayerCountMenu,
+    ModBrowserMenu,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum MainMenuEntry {
     Start,
     Challenges,
+    Mods,
     Options,
     Editor,
     Jukebox,
@@ -85,6 +89,9 @@ pub struct TitleScene {
     challenges_menu: Menu<ChallengesMenuEntry>,
     confirm_menu: Menu<ConfirmMenuEntry>,
     coop_menu: PlayerCountMenu,
+    mod_browser_menu: ModBrowserMenu,
+    /// Menu to return to when backing out of the selected mod.
+    mod_return_menu: CurrentMenu,
     settings_menu: SettingsMenu,
     background: Background,
     frame: Frame,
@@ -127,6 +134,8 @@ impl TitleScene {
             challenges_menu: Menu::new(0, 0, 150, 0),
             confirm_menu: Menu::new(0, 0, 150, 0),
             coop_menu: PlayerCountMenu::new(),
+            mod_browser_menu: ModBrowserMenu::new(),
+            mod_return_menu: CurrentMenu::ChallengesMenu,
             settings_menu,
             background: Background::new(),
             frame: Frame::new(),
@@ -137,6 +146,52 @@ impl TitleScene {
         }
     }
 
+    /// Sets up the given mod and opens the menu for starting it.
+    fn select_mod(&mut self, idx: usize, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
+        if let Some(mod_info) = state.mod_list.mods.get(idx) {
+            state.mod_path = Some(mod_info.path.clone());
+            if mod_info.save_slot >= 0 {
+                self.save_select_menu.init(state, ctx)?;
+                self.save_select_menu.set_skip_difficulty_menu(true);
+                self.nikumaru_rec.load_counter(state, ctx)?;
+                self.current_menu = CurrentMenu::SaveSelectMenu;
+            } else {
+                let mod_name = mod_info.name.clone();
+                self.confirm_menu.width = (state.font.builder().compute_width(&mod_name).max(50.0) + 32.0) as u16;
+
+                self.confirm_menu.set_entry(ConfirmMenuEntry::Title, MenuEntry::Disabled(mod_name));
+
+                if state.has_replay_data(ctx, ReplayKind::Best) {
+                    self.confirm_menu.set_entry(
+                        ConfirmMenuEntry::Replay(ReplayKind::Best),
+                        MenuEntry::Active(state.loc.t("menus.challenge_menu.replay_best").to_owned()),
+                    );
+                    self.confirm_menu.set_entry(
+                        ConfirmMenuEntry::DeleteReplay,
+                        MenuEntry::Active(state.loc.t("menus.challenge_menu.delete_replay").to_owned()),
+                    );
+                } else {
+                    self.confirm_menu.set_entry(ConfirmMenuEntry::Replay(ReplayKind::Best), MenuEntry::Hidden);
+                    self.confirm_menu.set_entry(ConfirmMenuEntry::DeleteReplay, MenuEntry::Hidden);
+                }
+
+                if state.has_replay_data(ctx, ReplayKind::Last) {
+                    self.confirm_menu.set_entry(
+                        ConfirmMenuEntry::Replay(ReplayKind::Last),
+                        MenuEntry::Active(state.loc.t("menus.challenge_menu.replay_last").to_owned()),
+                    );
+                } else {
+                    self.confirm_menu.set_entry(ConfirmMenuEntry::Replay(ReplayKind::Last), MenuEntry::Hidden);
+                }
+
+                self.nikumaru_rec.load_counter(state, ctx)?;
+                self.current_menu = CurrentMenu::ChallengeConfirmMenu;
+            }
+        }
+
+        Ok(())
+    }
+
     fn draw_text_centered(&self, text: &str, y: f32, state: &mut SharedGameState, ctx: &mut Context) -> GameResult {
         state.font.builder().center(state.canvas_size.0).y(y).shadow(true).draw(
             text,
@@ -212,13 +267,18 @@ impl Scene for TitleScene {
         self.main_menu
             .push_entry(MainMenuEntry::Start, MenuEntry::Active(state.loc.t("menus.main_menu.start").to_owned()));
 
-        if !state.mod_list.mods.is_empty() {
+        if state.mod_list.mods.iter().any(|mod_info| mod_info.kind == ModKind::Challenge) {
             self.main_menu.push_entry(
                 MainMenuEntry::Challenges,
                 MenuEntry::Active(state.loc.t("menus.main_menu.challenges").to_owned()),
             );
         }
 
+        if state.mod_list.mods.iter().any(|mod_info| mod_info.kind == ModKind::Mod || mod_info.manifest.is_some()) {
+            self.main_menu
+                .push_entry(MainMenuEntry::Mods, MenuEntry::Active(state.loc.t("menus.main_menu.mods").to_owned()));
+        }
+
         self.main_menu
             .push_entry(MainMenuEntry::Options, MenuEntry::Active(state.loc.t("menus.main_menu.options").to_owned()));
 
@@ -244,10 +304,15 @@ impl Scene for TitleScene {
         self.coop_menu.on_title = true;
         self.coop_menu.init(state)?;
 
+        self.mod_browser_menu.init(state)?;
+
         let mut selected = ChallengesMenuEntry::Back;
         let mut mutate_selection = true;
 
         for (idx, mod_info) in state.mod_list.mods.iter().enumerate() {
+            if mod_info.kind != ModKind::Challenge {
+                continue;
+            }
             if !mod_info.valid {
                 self.challenges_menu
                     .push_entry(ChallengesMenuEntry::Challenge(idx), MenuEntry::Disabled(mod_info.path.clone()));
@@ -339,6 +404,9 @@ impl Scene for TitleScene {
                 MenuSelectionResult::Selected(MainMenuEntry::Challenges, _) => {
                     self.current_menu = CurrentMenu::ChallengesMenu;
                 }
+                MenuSelectionResult::Selected(MainMenuEntry::Mods, _) => {
+                    self.current_menu = CurrentMenu::ModBrowserMenu;
+                }
                 MenuSelectionResult::Selected(MainMenuEntry::Options, _) => {
                     self.current_menu = CurrentMenu::OptionMenu;
                 }
@@ -375,7 +443,7 @@ impl Scene for TitleScene {
             }
             CurrentMenu::SaveSelectMenu => {
                 let cm = &mut self.current_menu;
-                let rm = if state.mod_path.is_none() { CurrentMenu::MainMenu } else { CurrentMenu::ChallengesMenu };
+                let rm = if state.mod_path.is_none() { CurrentMenu::MainMenu } else { self.mod_return_menu };
                 self.save_select_menu.tick(
                     &mut || {
                         *cm = rm;
@@ -387,49 +455,8 @@ impl Scene for TitleScene {
             }
             CurrentMenu::ChallengesMenu => match self.challenges_menu.tick(&mut self.controller, state) {
                 MenuSelectionResult::Selected(ChallengesMenuEntry::Challenge(idx), _) => {
-                    if let Some(mod_info) = state.mod_list.mods.get(idx) {
-                        state.mod_path = Some(mod_info.path.clone());
-                        if mod_info.save_slot >= 0 {
-                            self.save_select_menu.init(state, ctx)?;
-                            self.save_select_menu.set_skip_difficulty_menu(true);
-                            self.nikumaru_rec.load_counter(state, ctx)?;
-                            self.current_menu = CurrentMenu::SaveSelectMenu;
-                        } else {
-                            let mod_name = mod_info.name.clone();
-                            self.confirm_menu.width =
-                                (state.font.builder().compute_width(&mod_name).max(50.0) + 32.0) as u16;
-
-                            self.confirm_menu.set_entry(ConfirmMenuEntry::Title, MenuEntry::Disabled(mod_name));
-
-                            if state.has_replay_data(ctx, ReplayKind::Best) {
-                                self.confirm_menu.set_entry(
-                                    ConfirmMenuEntry::Replay(ReplayKind::Best),
-                                    MenuEntry::Active(state.loc.t("menus.challenge_menu.replay_best").to_owned()),
-                                );
-                                self.confirm_menu.set_entry(
-                                    ConfirmMenuEntry::DeleteReplay,
-                                    MenuEntry::Active(state.loc.t("menus.challenge_menu.delete_replay").to_owned()),
-                                );
-                            } else {
-                                self.confirm_menu
-                                    .set_entry(ConfirmMenuEntry::Replay(ReplayKind::Best), MenuEntry::Hidden);
-                                self.confirm_menu.set_entry(ConfirmMenuEntry::DeleteReplay, MenuEntry::Hidden);
-                            }
-
-                            if state.has_replay_data(ctx, ReplayKind::Last) {
-                                self.confirm_menu.set_entry(
-                                    ConfirmMenuEntry::Replay(ReplayKind::Last),
-                                    MenuEntry::Active(state.loc.t("menus.challenge_menu.replay_last").to_owned()),
-                                );
-                            } else {
-                                self.confirm_menu
-                                    .set_entry(ConfirmMenuEntry::Replay(ReplayKind::Last), MenuEntry::Hidden);
-                            }
-
-                            self.nikumaru_rec.load_counter(state, ctx)?;
-                            self.current_menu = CurrentMenu::ChallengeConfirmMenu;
-                        }
-                    }
+                    self.mod_return_menu = CurrentMenu::ChallengesMenu;
+                    self.select_mod(idx, state, ctx)?;
                 }
                 MenuSelectionResult::Selected(ChallengesMenuEntry::Back, _) | MenuSelectionResult::Canceled => {
                     state.mod_path = None;
@@ -452,10 +479,10 @@ impl Scene for TitleScene {
                 }
                 MenuSelectionResult::Selected(ConfirmMenuEntry::DeleteReplay, _) => {
                     state.delete_replay_data(ctx, ReplayKind::Best)?;
-                    self.current_menu = CurrentMenu::ChallengesMenu;
+                    self.current_menu = self.mod_return_menu;
                 }
                 MenuSelectionResult::Selected(ConfirmMenuEntry::Back, _) | MenuSelectionResult::Canceled => {
-                    self.current_menu = CurrentMenu::ChallengesMenu;
+                    self.current_menu = self.mod_return_menu;
                 }
                 _ => (),
             },
@@ -471,6 +498,25 @@ impl Scene for TitleScene {
                     ctx,
                 )?;
             }
+            CurrentMenu::ModBrowserMenu => {
+                let cm = &mut self.current_menu;
+                let selected = self.mod_browser_menu.tick(
+                    &mut || {
+                        *cm = CurrentMenu::MainMenu;
+                    },
+                    &mut self.controller,
+                    state,
+                    ctx,
+                )?;
+
+                if let Some(idx) = selected {
+                    self.mod_return_menu = CurrentMenu::ModBrowserMenu;
+                    self.select_mod(idx, state, ctx)?;
+                } else if self.current_menu == CurrentMenu::MainMenu && state.mod_path.is_some() {
+                    state.mod_path = None;
+                    self.nikumaru_rec.load_counter(state, ctx)?;
+                }
+            }
         }
 
         self.confirm_menu.update_width(state);
@@ -509,6 +555,7 @@ impl Scene for TitleScene {
                 CurrentMenu::OptionMenu => state.loc.t("menus.main_menu.options"),
                 CurrentMenu::MainMenu => unreachable!(),
                 CurrentMenu::PlayerCountMenu => state.loc.t("menus.main_menu.start"),
+                CurrentMenu::ModBrowserMenu => state.loc.t("menus.main_menu.mods"),
             };
             state
                 .font
@@ -540,6 +587,7 @@ impl Scene for TitleScene {
             CurrentMenu::OptionMenu => self.settings_menu.draw(state, ctx)?,
             CurrentMenu::SaveSelectMenu => self.save_select_menu.draw(state, ctx)?,
             CurrentMenu::PlayerCountMenu => self.coop_menu.draw(state, ctx)?,
+            CurrentMenu::ModBrowserMenu => self.mod_browser_menu.draw(state, ctx)?,
         }
 
         Ok(())